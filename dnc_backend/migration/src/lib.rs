@@ -63,6 +63,27 @@ mod m20260624_064339_create_dentist_applications_table;
 mod m20260624_125338_create_contact_us_messages_table;
 mod m20260701_035605_alter_table_dentist_applications_table;
 mod m20260713_161522_alter_contact_us_messages;
+mod m20261018_090000_add_website_dataobject_and_permissions;
+mod m20261018_100000_create_user_session_table;
+mod m20261018_110000_create_audit_log_table;
+mod m20261018_120000_create_verification_status_history_table;
+mod m20261018_130000_create_approval_code_rule_config_table;
+mod m20261018_140000_create_master_list_mapping_and_preview_tables;
+mod m20261018_150000_alter_master_list_upload_preview_add_mode;
+mod m20261018_160000_alter_hmo_billing_data_add_amounts;
+mod m20261018_170000_create_hmo_claims_billing_item_table;
+mod m20261018_180000_create_billing_run_table;
+mod m20261018_190000_create_report_schedule_state_table;
+mod m20261018_200000_create_job_table;
+mod m20261018_210000_create_hmo_receivables_tables;
+mod m20261018_220000_create_dentist_payout_tables;
+mod m20261018_230000_add_dentist_payout_withholding_tax;
+mod m20261019_000000_create_bank_disbursement_tables;
+mod m20261019_010000_add_dentist_contract_rate_effective_dates;
+mod m20261019_020000_add_verification_void;
+mod m20261019_030000_create_approval_code_validity_table;
+mod m20261019_040000_create_holiday_table;
+mod m20261019_050000_add_approval_code_rule_config_scope_index;
 
 pub struct Migrator;
 
@@ -133,6 +154,27 @@ impl MigratorTrait for Migrator {
             Box::new(m20260624_125338_create_contact_us_messages_table::Migration),
             Box::new(m20260701_035605_alter_table_dentist_applications_table::Migration),
             Box::new(m20260713_161522_alter_contact_us_messages::Migration),
+            Box::new(m20261018_090000_add_website_dataobject_and_permissions::Migration),
+            Box::new(m20261018_100000_create_user_session_table::Migration),
+            Box::new(m20261018_110000_create_audit_log_table::Migration),
+            Box::new(m20261018_120000_create_verification_status_history_table::Migration),
+            Box::new(m20261018_130000_create_approval_code_rule_config_table::Migration),
+            Box::new(m20261018_140000_create_master_list_mapping_and_preview_tables::Migration),
+            Box::new(m20261018_150000_alter_master_list_upload_preview_add_mode::Migration),
+            Box::new(m20261018_160000_alter_hmo_billing_data_add_amounts::Migration),
+            Box::new(m20261018_170000_create_hmo_claims_billing_item_table::Migration),
+            Box::new(m20261018_180000_create_billing_run_table::Migration),
+            Box::new(m20261018_190000_create_report_schedule_state_table::Migration),
+            Box::new(m20261018_200000_create_job_table::Migration),
+            Box::new(m20261018_210000_create_hmo_receivables_tables::Migration),
+            Box::new(m20261018_220000_create_dentist_payout_tables::Migration),
+            Box::new(m20261018_230000_add_dentist_payout_withholding_tax::Migration),
+            Box::new(m20261019_000000_create_bank_disbursement_tables::Migration),
+            Box::new(m20261019_010000_add_dentist_contract_rate_effective_dates::Migration),
+            Box::new(m20261019_020000_add_verification_void::Migration),
+            Box::new(m20261019_030000_create_approval_code_validity_table::Migration),
+            Box::new(m20261019_040000_create_holiday_table::Migration),
+            Box::new(m20261019_050000_add_approval_code_rule_config_scope_index::Migration),
         ]
     }
 }
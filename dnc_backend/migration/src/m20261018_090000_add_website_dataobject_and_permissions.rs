@@ -0,0 +1,26 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20251205_063628_create_table_dataobject::Migration as DataObjectMigration;
+use  crate::m20251205_075427_create_table_permission::Migration as PermissionMigration;
+use  crate::m20251205_075445_create_table_role_permission::Migration as RolePermissionMigration;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Dentist applications and contact-us messages submitted through the public website.
+        DataObjectMigration::add_dataobject(manager, "website", "Website Submissions Object").await?;
+        PermissionMigration::add_all_permissions(manager, "website").await?;
+        RolePermissionMigration::insert_role_all_permissions(manager, "Administrator", "website").await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        RolePermissionMigration::del_role_all_permissions(manager, "Administrator", "website").await?;
+        PermissionMigration::del_all_permissions(manager, "website").await?;
+        DataObjectMigration::delete_dataobject(manager, "website").await?;
+        Ok(())
+    }
+}
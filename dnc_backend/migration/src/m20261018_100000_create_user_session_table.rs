@@ -0,0 +1,112 @@
+use sea_orm_migration::{prelude::*};
+use crate::m20251205_075454_create_table_user::User;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserSession::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(UserSession::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(UserSession::SessionId)
+                        .string()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .col(ColumnDef::new(UserSession::UserId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_user_session_user_id")
+                        .from(UserSession::Table, UserSession::UserId)
+                        .to(User::Table, User::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(UserSession::RefreshTokenHash)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(UserSession::PreviousRefreshTokenHash)
+                        .string()
+                    )
+                    .col(ColumnDef::new(UserSession::CreatedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .col(ColumnDef::new(UserSession::LastRefreshedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(UserSession::ExpiresOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(UserSession::RevokedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(UserSession::RevokedBy)
+                        .string()
+                    )
+                    .col(ColumnDef::new(UserSession::RevokeReason)
+                        .string()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_user_session_refresh_token_hash")
+                    .table(UserSession::Table)
+                    .col(UserSession::RefreshTokenHash)
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_user_session_user_id")
+                    .table(UserSession::Table)
+                    .col(UserSession::UserId)
+                    .to_owned()
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(UserSession::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+/*
+  One row per login. The access token (JWT) carries the session_id as `sid`, so revoking
+  the row logs the user out everywhere that token is used. Only a SHA-256 hash of the
+  refresh token is stored; the previous hash is kept to detect reuse of a rotated token.
+ */
+#[derive(DeriveIden)]
+pub enum UserSession{
+    Table,
+    Id,
+    SessionId,
+    UserId,
+    RefreshTokenHash,
+    PreviousRefreshTokenHash,
+    CreatedOn,
+    LastRefreshedOn,
+    ExpiresOn,
+    RevokedOn,
+    RevokedBy,
+    RevokeReason,
+}
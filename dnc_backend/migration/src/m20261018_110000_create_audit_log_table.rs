@@ -0,0 +1,95 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20251205_063628_create_table_dataobject::Migration as DataObjectMigration;
+use  crate::m20251205_075427_create_table_permission::Migration as PermissionMigration;
+use  crate::m20251205_075445_create_table_role_permission::Migration as RolePermissionMigration;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditLog::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(AuditLog::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(AuditLog::OccurredOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .col(ColumnDef::new(AuditLog::Actor)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(AuditLog::Entity)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(AuditLog::EntityId)
+                        .integer()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(AuditLog::Action)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(AuditLog::BeforeJson)
+                        .json_binary()
+                    )
+                    .col(ColumnDef::new(AuditLog::AfterJson)
+                        .json_binary()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_audit_log_entity_entity_id")
+                    .table(AuditLog::Table)
+                    .col(AuditLog::Entity)
+                    .col(AuditLog::EntityId)
+                    .to_owned()
+            ).await?;
+
+        DataObjectMigration::add_dataobject(manager, "audit_log", "Audit Trail Object").await?;
+        PermissionMigration::add_all_permissions(manager, "audit_log").await?;
+        RolePermissionMigration::insert_role_all_permissions(manager, "Administrator", "audit_log").await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        RolePermissionMigration::del_role_all_permissions(manager, "Administrator", "audit_log").await?;
+        PermissionMigration::del_all_permissions(manager, "audit_log").await?;
+        DataObjectMigration::delete_dataobject(manager, "audit_log").await?;
+        manager.drop_table(Table::drop().table(AuditLog::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+/*
+  One row per create/update/delete of an audited entity (verification, endorsement, dentist,
+  dentist_payments, acc_reconciliation). `entity` is the table name; before_json/after_json
+  hold the whole row as it was before and after the change.
+ */
+#[derive(DeriveIden)]
+pub enum AuditLog{
+    Table,
+    Id,
+    OccurredOn,
+    Actor,
+    Entity,
+    EntityId,
+    Action,     // "create", "update" or "delete"
+    BeforeJson,
+    AfterJson,
+}
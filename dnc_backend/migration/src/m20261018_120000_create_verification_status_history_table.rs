@@ -0,0 +1,122 @@
+use sea_orm_migration::{prelude::*};
+use crate::m20260319_052702_add_verification_tables::{Verification, VerificationStatus};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(VerificationStatusHistory::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(VerificationStatusHistory::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(VerificationStatusHistory::VerificationId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_verification_status_history_verification_id")
+                        .from(VerificationStatusHistory::Table, VerificationStatusHistory::VerificationId)
+                        .to(Verification::Table, Verification::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(VerificationStatusHistory::FromStatusId)
+                        .integer()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_verification_status_history_from_status_id")
+                        .from(VerificationStatusHistory::Table, VerificationStatusHistory::FromStatusId)
+                        .to(VerificationStatus::Table, VerificationStatus::IntCode)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(VerificationStatusHistory::ToStatusId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_verification_status_history_to_status_id")
+                        .from(VerificationStatusHistory::Table, VerificationStatusHistory::ToStatusId)
+                        .to(VerificationStatus::Table, VerificationStatus::IntCode)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(VerificationStatusHistory::ChangedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .col(ColumnDef::new(VerificationStatusHistory::ChangedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(VerificationStatusHistory::Note)
+                        .string()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_verification_status_history_verification_id")
+                    .table(VerificationStatusHistory::Table)
+                    .col(VerificationStatusHistory::VerificationId)
+                    .to_owned()
+            ).await?;
+
+        Self::backfill_current_statuses(manager).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(VerificationStatusHistory::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+impl Migration {
+    // Existing verifications get a single row for the status they are in now,
+    // so every timeline starts somewhere.
+    async fn backfill_current_statuses(manager: &SchemaManager<'_>) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                INSERT INTO verification_status_history
+                (verification_id, from_status_id, to_status_id, changed_on, changed_by, note)
+                SELECT
+                    id,
+                    NULL,
+                    status_id,
+                    COALESCE(approval_date, date_created),
+                    COALESCE(approved_by, created_by),
+                    'status at time of migration'
+                FROM verification;
+                "#
+            ).await?;
+        Ok(())
+    }
+}
+
+/*
+  One row per status change of a verification. from_status_id is NULL for the row written
+  when the verification is created. Both status columns hold verification_status.int_code.
+ */
+#[derive(DeriveIden)]
+pub enum VerificationStatusHistory{
+    Table,
+    Id,
+    VerificationId,
+    FromStatusId,
+    ToStatusId,
+    ChangedOn,
+    ChangedBy,
+    Note,
+}
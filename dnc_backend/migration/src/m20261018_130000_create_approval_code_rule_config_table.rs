@@ -0,0 +1,109 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20251205_063628_create_table_dataobject::Migration as DataObjectMigration;
+use  crate::m20251205_075427_create_table_permission::Migration as PermissionMigration;
+use  crate::m20251205_075445_create_table_role_permission::Migration as RolePermissionMigration;
+use crate::m20260108_051749_create_table_hmo::HMO;
+use crate::m20260220_082933_create_endorsement_tables::Endorsement;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApprovalCodeRuleConfig::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ApprovalCodeRuleConfig::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(ApprovalCodeRuleConfig::RuleKey)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(ApprovalCodeRuleConfig::HmoId)
+                        .integer()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_approval_code_rule_config_hmo_id")
+                        .from(ApprovalCodeRuleConfig::Table, ApprovalCodeRuleConfig::HmoId)
+                        .to(HMO::Table, HMO::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(ApprovalCodeRuleConfig::EndorsementId)
+                        .integer()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_approval_code_rule_config_endorsement_id")
+                        .from(ApprovalCodeRuleConfig::Table, ApprovalCodeRuleConfig::EndorsementId)
+                        .to(Endorsement::Table, Endorsement::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(ApprovalCodeRuleConfig::IsEnabled)
+                        .boolean()
+                        .not_null()
+                        .default(true)
+                    )
+                    .col(ColumnDef::new(ApprovalCodeRuleConfig::Params)
+                        .json_binary()
+                    )
+                    .col(ColumnDef::new(ApprovalCodeRuleConfig::LastModifiedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(ApprovalCodeRuleConfig::LastModifiedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_approval_code_rule_config_rule_key")
+                    .table(ApprovalCodeRuleConfig::Table)
+                    .col(ApprovalCodeRuleConfig::RuleKey)
+                    .to_owned()
+            ).await?;
+
+        DataObjectMigration::add_dataobject(manager, "approval_code_rules", "Approval Code Release Rules Object").await?;
+        PermissionMigration::add_all_permissions(manager, "approval_code_rules").await?;
+        RolePermissionMigration::insert_role_all_permissions(manager, "Administrator", "approval_code_rules").await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        RolePermissionMigration::del_role_all_permissions(manager, "Administrator", "approval_code_rules").await?;
+        PermissionMigration::del_all_permissions(manager, "approval_code_rules").await?;
+        DataObjectMigration::delete_dataobject(manager, "approval_code_rules").await?;
+        manager.drop_table(Table::drop().table(ApprovalCodeRuleConfig::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+/*
+  Overrides for the approval code release rules. A row with neither hmo_id nor endorsement_id
+  applies to everyone; a row with hmo_id applies to that HMO's endorsements; a row with
+  endorsement_id applies to that endorsement only. The most specific row wins. Rules with no
+  row at all use their built-in defaults.
+ */
+#[derive(DeriveIden)]
+pub enum ApprovalCodeRuleConfig{
+    Table,
+    Id,
+    RuleKey,
+    HmoId,
+    EndorsementId,
+    IsEnabled,
+    Params,
+    LastModifiedBy,
+    LastModifiedOn,
+}
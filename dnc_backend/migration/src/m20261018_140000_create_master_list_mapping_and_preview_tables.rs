@@ -0,0 +1,195 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20260108_051749_create_table_hmo::HMO;
+use crate::m20260220_082933_create_endorsement_tables::Endorsement;
+use crate::m20260307_083354_add_endorsement_rates_masterlists::MasterList;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(MasterListColumnMapping::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(MasterListColumnMapping::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(MasterListColumnMapping::HmoId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_master_list_column_mapping_hmo_id")
+                        .from(MasterListColumnMapping::Table, MasterListColumnMapping::HmoId)
+                        .to(HMO::Table, HMO::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(MasterListColumnMapping::Name)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(MasterListColumnMapping::HeaderRow)
+                        .integer()
+                        .not_null()
+                        .default(1)
+                    )
+                    .col(ColumnDef::new(MasterListColumnMapping::Columns)
+                        .json_binary()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(MasterListColumnMapping::IsDefault)
+                        .boolean()
+                        .not_null()
+                        .default(false)
+                    )
+                    .col(ColumnDef::new(MasterListColumnMapping::LastModifiedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(MasterListColumnMapping::LastModifiedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_master_list_column_mapping_hmo_id_name")
+                    .table(MasterListColumnMapping::Table)
+                    .col(MasterListColumnMapping::HmoId)
+                    .col(MasterListColumnMapping::Name)
+                    .unique()
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(MasterListUploadPreview::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(MasterListUploadPreview::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::Token)
+                        .string()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::EndorsementId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_master_list_upload_preview_endorsement_id")
+                        .from(MasterListUploadPreview::Table, MasterListUploadPreview::EndorsementId)
+                        .to(Endorsement::Table, Endorsement::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::FileName)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::ColumnMappingId)
+                        .integer()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_master_list_upload_preview_column_mapping_id")
+                        .from(MasterListUploadPreview::Table, MasterListUploadPreview::ColumnMappingId)
+                        .to(MasterListColumnMapping::Table, MasterListColumnMapping::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::Rows)
+                        .json_binary()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::CreatedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::CreatedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::ExpiresOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::CommittedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::CommittedBy)
+                        .string()
+                    )
+                    .col(ColumnDef::new(MasterListUploadPreview::MasterListId)
+                        .integer()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_master_list_upload_preview_master_list_id")
+                        .from(MasterListUploadPreview::Table, MasterListUploadPreview::MasterListId)
+                        .to(MasterList::Table, MasterList::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                    )
+                    .to_owned()
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.drop_table(Table::drop().table(MasterListUploadPreview::Table).to_owned()).await?;
+        manager.drop_table(Table::drop().table(MasterListColumnMapping::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+/*
+  Per-HMO layouts of master list files. `columns` maps each master list field
+  (account_number, last_name, birth_date, ...) to either a header text or a zero-based
+  column index. header_row is 1-based.
+ */
+#[derive(DeriveIden)]
+pub enum MasterListColumnMapping{
+    Table,
+    Id,
+    HmoId,
+    Name,
+    HeaderRow,
+    Columns,
+    IsDefault,
+    LastModifiedBy,
+    LastModifiedOn,
+}
+
+/*
+  A parsed master list waiting to be committed. `rows` holds the valid mapped rows; the
+  commit endpoint re-checks them against the members in the database at commit time.
+ */
+#[derive(DeriveIden)]
+pub enum MasterListUploadPreview{
+    Table,
+    Id,
+    Token,
+    EndorsementId,
+    FileName,
+    ColumnMappingId,
+    Rows,
+    CreatedBy,
+    CreatedOn,
+    ExpiresOn,
+    CommittedOn,
+    CommittedBy,
+    MasterListId,
+}
@@ -0,0 +1,44 @@
+use sea_orm_migration::{prelude::* };
+
+use crate::m20261018_140000_create_master_list_mapping_and_preview_tables::MasterListUploadPreview;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MasterListUploadPreview::Table)
+                    .add_column( ColumnDef::new(MasterListUploadPreviewMode::Mode)
+                        .string()
+                        .not_null()
+                        .default("append")
+                    ).to_owned(),
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(MasterListUploadPreview::Table)
+                    .drop_column(MasterListUploadPreviewMode::Mode)
+                    .to_owned(),
+            ).await?;
+        Ok(())
+
+    }
+}
+/*
+  "append" previews only add members; "replace" previews treat the file as the endorsement's
+  complete new list and also update changed members and deactivate the ones that are missing.
+ */
+#[derive(Iden)]
+pub enum MasterListUploadPreviewMode{
+    Mode
+}
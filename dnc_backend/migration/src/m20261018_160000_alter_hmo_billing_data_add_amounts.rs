@@ -0,0 +1,114 @@
+use sea_orm_migration::{prelude::*};
+use crate::m20260507_045752_create_app_config_table::Migration as AppConfig;
+use crate::m20260507_075843_create_hmo_billing_data_table::HMOBillingData;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(HMOBillingData::Table)
+                    .add_column(ColumnDef::new(HMOBillingDataAmounts::BillableCount)
+                        .integer()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(HMOBillingDataAmounts::RateSource)
+                        .string()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(HMOBillingDataAmounts::BillingRuleId)
+                        .integer()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(HMOBillingDataAmounts::Rate)
+                        .decimal_len(14, 2)
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(HMOBillingDataAmounts::VatRate)
+                        .decimal_len(5, 4)
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(HMOBillingDataAmounts::VatPerMember)
+                        .decimal_len(14, 2)
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(HMOBillingDataAmounts::RateWithVat)
+                        .decimal_len(14, 2)
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(HMOBillingDataAmounts::AmountBeforeVat)
+                        .decimal_len(14, 2)
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(HMOBillingDataAmounts::VatAmount)
+                        .decimal_len(14, 2)
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(HMOBillingDataAmounts::TotalAmount)
+                        .decimal_len(14, 2)
+                        .null()
+                    )
+                    .to_owned(),
+            ).await?;
+
+        AppConfig::insert_key_value_pair(
+            manager,
+            "hmo_billing_vat_rate",
+            "0.12",
+            "decimal",
+            "VAT rate added to HMO billing amounts"
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        AppConfig::delete_key_value_pair(manager, "hmo_billing_vat_rate").await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(HMOBillingData::Table)
+                    .drop_column(HMOBillingDataAmounts::BillableCount)
+                    .drop_column(HMOBillingDataAmounts::RateSource)
+                    .drop_column(HMOBillingDataAmounts::BillingRuleId)
+                    .drop_column(HMOBillingDataAmounts::Rate)
+                    .drop_column(HMOBillingDataAmounts::VatRate)
+                    .drop_column(HMOBillingDataAmounts::VatPerMember)
+                    .drop_column(HMOBillingDataAmounts::RateWithVat)
+                    .drop_column(HMOBillingDataAmounts::AmountBeforeVat)
+                    .drop_column(HMOBillingDataAmounts::VatAmount)
+                    .drop_column(HMOBillingDataAmounts::TotalAmount)
+                    .to_owned(),
+            ).await?;
+        Ok(())
+    }
+}
+
+/*
+The amounts billed for each endorsement, computed by the billing job instead of by formulas in
+the spreadsheet. They match the columns of the HMO billing summary:
+  F billable_count
+  J rate            per member, from the endorsement_billing_rule tier the count falls in,
+                    else the endorsement's retainer_fee (rate_source says which)
+  K vat_per_member  rate * vat_rate
+  L rate_with_vat   J + K
+  M total_amount    L * F
+billing_rule_id is the tier that was used; the rate itself is copied so later edits to the
+tiers do not change bills that were already sent.
+ */
+#[derive(DeriveIden)]
+pub enum HMOBillingDataAmounts{
+    BillableCount,
+    RateSource,
+    BillingRuleId,
+    Rate,
+    VatRate,
+    VatPerMember,
+    RateWithVat,
+    AmountBeforeVat,
+    VatAmount,
+    TotalAmount,
+}
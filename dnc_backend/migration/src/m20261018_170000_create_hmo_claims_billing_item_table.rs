@@ -0,0 +1,180 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20251221_124454_create_table_dental_services::DentalService;
+use crate::m20260108_051749_create_table_hmo::HMO;
+use crate::m20260220_082933_create_endorsement_tables::Endorsement;
+use crate::m20260319_052702_add_verification_tables::Verification;
+use crate::m20260507_063127_create_reports_and_reports_type_tables::{GeneratedReport, ReportType};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const HMO_CLAIMS_REPORT_TYPE: &str = "HMO Claims";
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(HmoClaimsBillingItem::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(HmoClaimsBillingItem::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    // unique: a verification is billed to its HMO at most once
+                    .col(ColumnDef::new(HmoClaimsBillingItem::VerificationId)
+                        .integer()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_hmo_claims_billing_item_verification_id")
+                        .from(HmoClaimsBillingItem::Table, HmoClaimsBillingItem::VerificationId)
+                        .to(Verification::Table, Verification::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::HmoId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_hmo_claims_billing_item_hmo_id")
+                        .from(HmoClaimsBillingItem::Table, HmoClaimsBillingItem::HmoId)
+                        .to(HMO::Table, HMO::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::EndorsementId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_hmo_claims_billing_item_endorsement_id")
+                        .from(HmoClaimsBillingItem::Table, HmoClaimsBillingItem::EndorsementId)
+                        .to(Endorsement::Table, Endorsement::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::DentalServiceId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_hmo_claims_billing_item_dental_service_id")
+                        .from(HmoClaimsBillingItem::Table, HmoClaimsBillingItem::DentalServiceId)
+                        .to(DentalService::Table, DentalService::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::DentistId)
+                        .integer()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::DateServicePerformed)
+                        .date()
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::PeriodStart)
+                        .date()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::PeriodEnd)
+                        .date()
+                        .not_null()
+                    )
+                    // endorsement_rates.rate for the service at the time of billing
+                    .col(ColumnDef::new(HmoClaimsBillingItem::Rate)
+                        .decimal_len(14, 2)
+                        .not_null()
+                    )
+                    // high_end_verification_information.approved_cost, for high-end services
+                    .col(ColumnDef::new(HmoClaimsBillingItem::ApprovedCost)
+                        .decimal_len(14, 2)
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::Amount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::RequestKey)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::GeneratedReportId)
+                        .integer()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_hmo_claims_billing_item_generated_report_id")
+                        .from(HmoClaimsBillingItem::Table, HmoClaimsBillingItem::GeneratedReportId)
+                        .to(GeneratedReport::Table, GeneratedReport::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::BilledBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoClaimsBillingItem::BilledOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_hmo_claims_billing_item_request_key")
+                    .table(HmoClaimsBillingItem::Table)
+                    .col(HmoClaimsBillingItem::RequestKey)
+                    .to_owned()
+            ).await?;
+
+        let insert = Query::insert()
+            .into_table(ReportType::Table)
+            .columns([ReportType::Name])
+            .values_panic([Expr::val(HMO_CLAIMS_REPORT_TYPE)])
+            .to_owned();
+        manager.exec_stmt(insert).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(HmoClaimsBillingItem::Table).to_owned())
+            .await?;
+
+        let delete = Query::delete()
+            .from_table(ReportType::Table)
+            .and_where(Expr::col(ReportType::Name).eq(HMO_CLAIMS_REPORT_TYPE))
+            .to_owned();
+        manager.exec_stmt(delete).await?;
+
+        Ok(())
+    }
+}
+
+/*
+One row per verification billed to an HMO under fee-per-service. The unique verification_id
+is what keeps a verification from being billed twice.
+ */
+#[derive(DeriveIden)]
+pub enum HmoClaimsBillingItem {
+    Table,
+    Id,
+    VerificationId,
+    HmoId,
+    EndorsementId,
+    DentalServiceId,
+    DentistId,
+    DateServicePerformed,
+    PeriodStart,
+    PeriodEnd,
+    Rate,
+    ApprovedCost,
+    Amount,
+    RequestKey,
+    GeneratedReportId,
+    BilledBy,
+    BilledOn,
+}
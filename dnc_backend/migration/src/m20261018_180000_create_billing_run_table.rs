@@ -0,0 +1,227 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20260507_063127_create_reports_and_reports_type_tables::GeneratedReport;
+use crate::m20260507_075843_create_hmo_billing_data_table::HMOBillingData;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(BillingRun::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(BillingRun::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BillingRun::PeriodStart)
+                        .date()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BillingRun::PeriodEnd)
+                        .date()
+                        .not_null()
+                    )
+                    // draft, finalized or void
+                    .col(ColumnDef::new(BillingRun::Status)
+                        .string()
+                        .not_null()
+                        .default("draft")
+                    )
+                    // stored in hmo_billing_data.request_key
+                    .col(ColumnDef::new(BillingRun::RequestKey)
+                        .string()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .col(ColumnDef::new(BillingRun::EndorsementCount)
+                        .integer()
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(BillingRun::ReportCount)
+                        .integer()
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(BillingRun::BillableCount)
+                        .integer()
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(BillingRun::AmountBeforeVat)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(BillingRun::VatAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(BillingRun::TotalAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(BillingRun::CreatedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BillingRun::CreatedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .col(ColumnDef::new(BillingRun::LastGeneratedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(BillingRun::FinalizedBy)
+                        .string()
+                    )
+                    .col(ColumnDef::new(BillingRun::FinalizedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(BillingRun::VoidedBy)
+                        .string()
+                    )
+                    .col(ColumnDef::new(BillingRun::VoidedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(BillingRun::VoidReason)
+                        .text()
+                    )
+                    .col(ColumnDef::new(BillingRun::SupersedesRunId)
+                        .integer()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_billing_run_supersedes_run_id")
+                        .from(BillingRun::Table, BillingRun::SupersedesRunId)
+                        .to(BillingRun::Table, BillingRun::Id)
+                        .on_delete(ForeignKeyAction::SetNull)
+                    )
+                    .to_owned()
+            ).await?;
+
+        // Only one run per period that is not void, so the same period cannot be billed twice.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE UNIQUE INDEX IF NOT EXISTS idx_billing_run_open_period \
+                 ON billing_run (period_start, period_end) WHERE status <> 'void'",
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(HMOBillingData::Table)
+                    .add_column(ColumnDef::new(BillingRunLinks::BillingRunId)
+                        .integer()
+                        .null()
+                    )
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_hmo_billing_data_billing_run_id")
+                            .from_tbl(HMOBillingData::Table)
+                            .from_col(BillingRunLinks::BillingRunId)
+                            .to_tbl(BillingRun::Table)
+                            .to_col(BillingRun::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GeneratedReport::Table)
+                    .add_column(ColumnDef::new(BillingRunLinks::BillingRunId)
+                        .integer()
+                        .null()
+                    )
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_generated_report_billing_run_id")
+                            .from_tbl(GeneratedReport::Table)
+                            .from_col(BillingRunLinks::BillingRunId)
+                            .to_tbl(BillingRun::Table)
+                            .to_col(BillingRun::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                    )
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GeneratedReport::Table)
+                    .drop_foreign_key(Alias::new("fk_generated_report_billing_run_id"))
+                    .drop_column(BillingRunLinks::BillingRunId)
+                    .to_owned()
+            ).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(HMOBillingData::Table)
+                    .drop_foreign_key(Alias::new("fk_hmo_billing_data_billing_run_id"))
+                    .drop_column(BillingRunLinks::BillingRunId)
+                    .to_owned()
+            ).await?;
+
+        manager
+            .drop_table(Table::drop().table(BillingRun::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+/*
+A billing run is one generation of the HMO billing for a period. A draft can be generated
+again in place; a finalized run is locked; a void run is kept for the record and superseded
+by a new run for the same period.
+ */
+#[derive(DeriveIden)]
+pub enum BillingRun {
+    Table,
+    Id,
+    PeriodStart,
+    PeriodEnd,
+    Status,
+    RequestKey,
+    EndorsementCount,
+    ReportCount,
+    BillableCount,
+    AmountBeforeVat,
+    VatAmount,
+    TotalAmount,
+    CreatedBy,
+    CreatedOn,
+    LastGeneratedOn,
+    FinalizedBy,
+    FinalizedOn,
+    VoidedBy,
+    VoidedOn,
+    VoidReason,
+    SupersedesRunId,
+}
+
+/*
+hmo_billing_data and generated_report rows belong to the run that produced them.
+ */
+#[derive(DeriveIden)]
+pub enum BillingRunLinks {
+    BillingRunId,
+}
@@ -0,0 +1,160 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20260507_045752_create_app_config_table::Migration as AppConfig;
+use crate::m20260507_063127_create_reports_and_reports_type_tables::ReportType;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// report_type.name and the prefix of its schedule keys in app_config
+/// (<prefix>_day already exists; <prefix>_time and <prefix>_period_months are added here).
+const SCHEDULED_REPORT_TYPES: [(&str, &str); 3] = [
+    ("HMO Billing", "hmo_billing"),
+    ("Dentist Claims", "dentist_claims"),
+    ("Dentist Retainers", "dentist_retainers"),
+];
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // ---1 link report types to their app_config schedule keys
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ReportType::Table)
+                    .add_column(ColumnDef::new(ReportTypeSchedule::ConfigKeyPrefix)
+                        .string()
+                        .null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        for (name, prefix) in SCHEDULED_REPORT_TYPES {
+            let update = Query::update()
+                .table(ReportType::Table)
+                .value(ReportTypeSchedule::ConfigKeyPrefix, prefix)
+                .and_where(Expr::col(ReportType::Name).eq(name))
+                .to_owned();
+            manager.exec_stmt(update).await?;
+
+            AppConfig::insert_key_value_pair(
+                manager,
+                &format!("{prefix}_time"),
+                "06:00",
+                "time",
+                &format!("Manila time of day (HH:MM) the {name} report is generated"),
+            ).await?;
+            AppConfig::insert_key_value_pair(
+                manager,
+                &format!("{prefix}_period_months"),
+                "1",
+                "integer",
+                &format!("Number of calendar months before the run date that the {name} report covers"),
+            ).await?;
+        }
+
+        // ---2 scheduler state, one row per report type
+        manager
+            .create_table(
+                Table::create()
+                    .table(ReportScheduleState::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ReportScheduleState::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(ReportScheduleState::ReportTypeId)
+                        .integer()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_report_schedule_state_report_type_id")
+                        .from(ReportScheduleState::Table, ReportScheduleState::ReportTypeId)
+                        .to(ReportType::Table, ReportType::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    // the scheduled time of the last run that was started; runs are claimed by
+                    // moving this forward, so a slot is never run twice
+                    .col(ColumnDef::new(ReportScheduleState::LastScheduledFor)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(ReportScheduleState::LastRunStartedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(ReportScheduleState::LastRunFinishedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    // running, succeeded or failed
+                    .col(ColumnDef::new(ReportScheduleState::LastRunStatus)
+                        .string()
+                    )
+                    .col(ColumnDef::new(ReportScheduleState::LastRunError)
+                        .text()
+                    )
+                    .col(ColumnDef::new(ReportScheduleState::LastPeriodStart)
+                        .date()
+                    )
+                    .col(ColumnDef::new(ReportScheduleState::LastPeriodEnd)
+                        .date()
+                    )
+                    .col(ColumnDef::new(ReportScheduleState::NextRunOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(ReportScheduleState::CreatedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ReportScheduleState::Table).to_owned())
+            .await?;
+
+        for (_, prefix) in SCHEDULED_REPORT_TYPES {
+            AppConfig::delete_key_value_pair(manager, &format!("{prefix}_time")).await?;
+            AppConfig::delete_key_value_pair(manager, &format!("{prefix}_period_months")).await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(ReportType::Table)
+                    .drop_column(ReportTypeSchedule::ConfigKeyPrefix)
+                    .to_owned()
+            ).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum ReportTypeSchedule {
+    ConfigKeyPrefix,
+}
+
+/*
+Where each monthly report worker is up to, so a restart neither skips nor repeats a run.
+ */
+#[derive(DeriveIden)]
+pub enum ReportScheduleState {
+    Table,
+    Id,
+    ReportTypeId,
+    LastScheduledFor,
+    LastRunStartedOn,
+    LastRunFinishedOn,
+    LastRunStatus,
+    LastRunError,
+    LastPeriodStart,
+    LastPeriodEnd,
+    NextRunOn,
+    CreatedOn,
+}
@@ -0,0 +1,127 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20251205_063628_create_table_dataobject::Migration as DataObjectMigration;
+use  crate::m20251205_075427_create_table_permission::Migration as PermissionMigration;
+use  crate::m20251205_075445_create_table_role_permission::Migration as RolePermissionMigration;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Job::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Job::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Job::JobType)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Job::Payload)
+                        .json_binary()
+                    )
+                    .col(ColumnDef::new(Job::Status)
+                        .string()
+                        .not_null()
+                        .default("queued")
+                    )
+                    .col(ColumnDef::new(Job::Attempts)
+                        .integer()
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(Job::MaxAttempts)
+                        .integer()
+                        .not_null()
+                        .default(5)
+                    )
+                    .col(ColumnDef::new(Job::RunAfter)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .col(ColumnDef::new(Job::DedupeKey)
+                        .string()
+                        .unique_key()
+                    )
+                    .col(ColumnDef::new(Job::LastError)
+                        .text()
+                    )
+                    .col(ColumnDef::new(Job::LockedBy)
+                        .string()
+                    )
+                    .col(ColumnDef::new(Job::StartedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(Job::FinishedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(Job::CreatedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Job::CreatedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_job_status_run_after")
+                    .table(Job::Table)
+                    .col(Job::Status)
+                    .col(Job::RunAfter)
+                    .to_owned()
+            ).await?;
+
+        DataObjectMigration::add_dataobject(manager, "jobs", "Background Jobs Object").await?;
+        PermissionMigration::add_all_permissions(manager, "jobs").await?;
+        RolePermissionMigration::insert_role_all_permissions(manager, "Administrator", "jobs").await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        RolePermissionMigration::del_role_all_permissions(manager, "Administrator", "jobs").await?;
+        PermissionMigration::del_all_permissions(manager, "jobs").await?;
+        DataObjectMigration::delete_dataobject(manager, "jobs").await?;
+        manager.drop_table(Table::drop().table(Job::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+/*
+  One row per background job. A worker claims a queued job whose run_after has passed with
+  SELECT ... FOR UPDATE SKIP LOCKED, so each job runs in one process only. A failed attempt
+  is queued again with run_after pushed back until max_attempts is reached.
+  dedupe_key stops two processes from queueing the same scheduled run twice.
+ */
+#[derive(DeriveIden)]
+pub enum Job {
+    Table,
+    Id,
+    JobType,    // e.g. "expire_verifications", "hmo_billing"
+    Payload,
+    Status,     // "queued", "running", "succeeded" or "failed"
+    Attempts,
+    MaxAttempts,
+    RunAfter,
+    DedupeKey,
+    LastError,
+    LockedBy,
+    StartedOn,
+    FinishedOn,
+    CreatedBy,
+    CreatedOn,
+}
@@ -0,0 +1,274 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20260108_051749_create_table_hmo::HMO;
+use crate::m20260507_063127_create_reports_and_reports_type_tables::GeneratedReport;
+use crate::m20261018_180000_create_billing_run_table::BillingRun;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // ---1 the HMO each billing statement was made for
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GeneratedReport::Table)
+                    .add_column(ColumnDef::new(GeneratedReportHmo::HmoId)
+                        .integer()
+                        .null()
+                    )
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_generated_report_hmo_id")
+                            .from_tbl(GeneratedReport::Table)
+                            .from_col(GeneratedReportHmo::HmoId)
+                            .to_tbl(HMO::Table)
+                            .to_col(HMO::Id)
+                            .on_delete(ForeignKeyAction::SetNull)
+                    )
+                    .to_owned()
+            ).await?;
+
+        // claims statements from their items, HMO billing statements from the file name
+        // ("{short_name}_HMO_Billing_...")
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "UPDATE generated_report gr SET hmo_id = i.hmo_id \
+             FROM hmo_claims_billing_item i \
+             WHERE i.generated_report_id = gr.id AND gr.hmo_id IS NULL",
+        ).await?;
+        db.execute_unprepared(
+            "UPDATE generated_report gr SET hmo_id = h.id \
+             FROM hmo h \
+             WHERE gr.hmo_id IS NULL AND gr.file_name LIKE h.short_name || '\\_HMO\\_Billing\\_%'",
+        ).await?;
+
+        // ---2 one invoice per billing statement
+        manager
+            .create_table(
+                Table::create()
+                    .table(HmoInvoice::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(HmoInvoice::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoInvoice::InvoiceNumber)
+                        .string()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .col(ColumnDef::new(HmoInvoice::HmoId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_hmo_invoice_hmo_id")
+                        .from(HmoInvoice::Table, HmoInvoice::HmoId)
+                        .to(HMO::Table, HMO::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(HmoInvoice::GeneratedReportId)
+                        .integer()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_hmo_invoice_generated_report_id")
+                        .from(HmoInvoice::Table, HmoInvoice::GeneratedReportId)
+                        .to(GeneratedReport::Table, GeneratedReport::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(HmoInvoice::BillingRunId)
+                        .integer()
+                        .null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_hmo_invoice_billing_run_id")
+                        .from(HmoInvoice::Table, HmoInvoice::BillingRunId)
+                        .to(BillingRun::Table, BillingRun::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(HmoInvoice::PeriodStart)
+                        .date()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoInvoice::PeriodEnd)
+                        .date()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoInvoice::InvoiceDate)
+                        .date()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoInvoice::TotalAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoInvoice::AmountPaid)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    // open, paid or void
+                    .col(ColumnDef::new(HmoInvoice::Status)
+                        .string()
+                        .not_null()
+                        .default("open")
+                    )
+                    .col(ColumnDef::new(HmoInvoice::CreatedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoInvoice::CreatedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .col(ColumnDef::new(HmoInvoice::VoidedBy)
+                        .string()
+                    )
+                    .col(ColumnDef::new(HmoInvoice::VoidedOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_hmo_invoice_hmo_id_status")
+                    .table(HmoInvoice::Table)
+                    .col(HmoInvoice::HmoId)
+                    .col(HmoInvoice::Status)
+                    .to_owned()
+            ).await?;
+
+        // ---3 payments received against an invoice
+        manager
+            .create_table(
+                Table::create()
+                    .table(HmoPayment::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(HmoPayment::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoPayment::HmoInvoiceId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_hmo_payment_hmo_invoice_id")
+                        .from(HmoPayment::Table, HmoPayment::HmoInvoiceId)
+                        .to(HmoInvoice::Table, HmoInvoice::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(HmoPayment::PaymentDate)
+                        .date()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoPayment::Amount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoPayment::OrNumber)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoPayment::Remarks)
+                        .text()
+                    )
+                    .col(ColumnDef::new(HmoPayment::RecordedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(HmoPayment::RecordedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_hmo_payment_hmo_invoice_id")
+                    .table(HmoPayment::Table)
+                    .col(HmoPayment::HmoInvoiceId)
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(HmoPayment::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(HmoInvoice::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GeneratedReport::Table)
+                    .drop_foreign_key(Alias::new("fk_generated_report_hmo_id"))
+                    .drop_column(GeneratedReportHmo::HmoId)
+                    .to_owned()
+            ).await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+pub enum GeneratedReportHmo {
+    HmoId,
+}
+
+/*
+An invoice is issued for each HMO billing statement when its billing run is finalized, and
+for each HMO claims statement when it is generated. amount_paid is the sum of its payments.
+ */
+#[derive(DeriveIden)]
+pub enum HmoInvoice {
+    Table,
+    Id,
+    InvoiceNumber,
+    HmoId,
+    GeneratedReportId,
+    BillingRunId,
+    PeriodStart,
+    PeriodEnd,
+    InvoiceDate,
+    TotalAmount,
+    AmountPaid,
+    Status,
+    CreatedBy,
+    CreatedOn,
+    VoidedBy,
+    VoidedOn,
+}
+
+/*
+A payment (full or partial) an HMO made against an invoice, with its official receipt number.
+ */
+#[derive(DeriveIden)]
+pub enum HmoPayment {
+    Table,
+    Id,
+    HmoInvoiceId,
+    PaymentDate,
+    Amount,
+    OrNumber,
+    Remarks,
+    RecordedBy,
+    RecordedOn,
+}
@@ -0,0 +1,295 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20251221_124454_create_table_dental_services::DentalService;
+use crate::m20260126_063012_create_tables_dental_clinic::DentalClinic;
+use crate::m20260126_161604_create_table_dentists::Dentist;
+use crate::m20260319_052702_add_verification_tables::Verification;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // ---1 batches
+        manager
+            .create_table(
+                Table::create()
+                    .table(DentistPayoutBatch::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(DentistPayoutBatch::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(DentistPayoutBatch::PeriodStart)
+                        .date()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(DentistPayoutBatch::PeriodEnd)
+                        .date()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(DentistPayoutBatch::RequestKey)
+                        .string()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .col(ColumnDef::new(DentistPayoutBatch::ItemCount)
+                        .integer()
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(DentistPayoutBatch::GrossAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(DentistPayoutBatch::CreatedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(DentistPayoutBatch::CreatedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        // ---2 one remittance (and spreadsheet) per dentist and clinic in a batch
+        manager
+            .create_table(
+                Table::create()
+                    .table(DentistPayoutRemittance::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(DentistPayoutRemittance::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(DentistPayoutRemittance::BatchId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_dentist_payout_remittance_batch_id")
+                        .from(DentistPayoutRemittance::Table, DentistPayoutRemittance::BatchId)
+                        .to(DentistPayoutBatch::Table, DentistPayoutBatch::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(DentistPayoutRemittance::DentistId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_dentist_payout_remittance_dentist_id")
+                        .from(DentistPayoutRemittance::Table, DentistPayoutRemittance::DentistId)
+                        .to(Dentist::Table, Dentist::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(DentistPayoutRemittance::DentalClinicId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_dentist_payout_remittance_dental_clinic_id")
+                        .from(DentistPayoutRemittance::Table, DentistPayoutRemittance::DentalClinicId)
+                        .to(DentalClinic::Table, DentalClinic::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(DentistPayoutRemittance::ItemCount)
+                        .integer()
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(DentistPayoutRemittance::GrossAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .col(ColumnDef::new(DentistPayoutRemittance::FileName)
+                        .string()
+                        .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_dentist_payout_remittance_batch_dentist_clinic")
+                    .table(DentistPayoutRemittance::Table)
+                    .col(DentistPayoutRemittance::BatchId)
+                    .col(DentistPayoutRemittance::DentistId)
+                    .col(DentistPayoutRemittance::DentalClinicId)
+                    .unique()
+                    .to_owned()
+            ).await?;
+
+        // ---3 the verifications paid out; a verification is in one batch at most
+        manager
+            .create_table(
+                Table::create()
+                    .table(DentistPayoutItem::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(DentistPayoutItem::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::BatchId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_dentist_payout_item_batch_id")
+                        .from(DentistPayoutItem::Table, DentistPayoutItem::BatchId)
+                        .to(DentistPayoutBatch::Table, DentistPayoutBatch::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::RemittanceId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_dentist_payout_item_remittance_id")
+                        .from(DentistPayoutItem::Table, DentistPayoutItem::RemittanceId)
+                        .to(DentistPayoutRemittance::Table, DentistPayoutRemittance::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::VerificationId)
+                        .integer()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_dentist_payout_item_verification_id")
+                        .from(DentistPayoutItem::Table, DentistPayoutItem::VerificationId)
+                        .to(Verification::Table, Verification::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::DentistId)
+                        .integer()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::DentalClinicId)
+                        .integer()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::DentalServiceId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_dentist_payout_item_dental_service_id")
+                        .from(DentistPayoutItem::Table, DentistPayoutItem::DentalServiceId)
+                        .to(DentalService::Table, DentalService::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::DateServicePerformed)
+                        .date()
+                        .not_null()
+                    )
+                    // the contract rate, or for a high-end service the approved cost
+                    .col(ColumnDef::new(DentistPayoutItem::Rate)
+                        .decimal_len(14, 2)
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::ApprovedCost)
+                        .decimal_len(14, 2)
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::Amount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::PaidOn)
+                        .timestamp_with_time_zone()
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::PaidBy)
+                        .string()
+                    )
+                    .col(ColumnDef::new(DentistPayoutItem::PaymentReference)
+                        .string()
+                    )
+                    .to_owned()
+            ).await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_dentist_payout_item_remittance_id")
+                    .table(DentistPayoutItem::Table)
+                    .col(DentistPayoutItem::RemittanceId)
+                    .to_owned()
+            ).await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(DentistPayoutItem::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(DentistPayoutRemittance::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(DentistPayoutBatch::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+/*
+A payout batch collects the unpaid reconciled verifications of fee-for-service dentists for
+a period, priced at their contract's service rate or, for high-end services, the approved cost.
+ */
+#[derive(DeriveIden)]
+pub enum DentistPayoutBatch {
+    Table,
+    Id,
+    PeriodStart,
+    PeriodEnd,
+    RequestKey,
+    ItemCount,
+    GrossAmount,
+    CreatedBy,
+    CreatedOn,
+}
+
+/*
+What a batch pays one dentist for work at one clinic, with its remittance spreadsheet.
+ */
+#[derive(DeriveIden)]
+pub enum DentistPayoutRemittance {
+    Table,
+    Id,
+    BatchId,
+    DentistId,
+    DentalClinicId,
+    ItemCount,
+    GrossAmount,
+    FileName,
+}
+
+#[derive(DeriveIden)]
+pub enum DentistPayoutItem {
+    Table,
+    Id,
+    BatchId,
+    RemittanceId,
+    VerificationId,
+    DentistId,
+    DentalClinicId,
+    DentalServiceId,
+    DateServicePerformed,
+    Rate,
+    ApprovedCost,
+    Amount,
+    PaidOn,
+    PaidBy,
+    PaymentReference,
+}
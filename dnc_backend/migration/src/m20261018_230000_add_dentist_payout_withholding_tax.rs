@@ -0,0 +1,329 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20260126_063012_create_tables_dental_clinic::{DentalClinic, TaxClassification, TaxType};
+use crate::m20260507_045752_create_app_config_table::Migration as AppConfig;
+use crate::m20261018_220000_create_dentist_payout_tables::{
+    DentistPayoutBatch, DentistPayoutItem, DentistPayoutRemittance,
+};
+
+/// (key, value, value_type, description)
+const TAX_CONFIG: [(&str, &str, &str, &str); 11] = [
+    ("payout_vat_rate", "0.12", "decimal", "VAT included in the fees paid to VAT-registered dentist clinics"),
+    ("payout_percentage_tax_rate", "0.03", "decimal", "Percentage tax on the fees paid to non-VAT-registered dentist clinics"),
+    ("payout_ewt_rate_individual", "0.05", "decimal", "Expanded withholding tax on professional fees paid to individuals"),
+    ("payout_ewt_atc_individual", "WI010", "string", "ATC of the withholding tax on professional fees paid to individuals"),
+    ("payout_ewt_rate_corporation", "0.10", "decimal", "Expanded withholding tax on professional fees paid to corporations"),
+    ("payout_ewt_atc_corporation", "WC010", "string", "ATC of the withholding tax on professional fees paid to corporations"),
+    ("payout_ewt_rate_gpp", "0.10", "decimal", "Expanded withholding tax on professional fees paid to general professional partnerships"),
+    ("payout_ewt_atc_gpp", "WC010", "string", "ATC of the withholding tax on professional fees paid to general professional partnerships"),
+    ("bir_2307_payor_tin", "", "string", "Payor TIN printed on BIR Form 2307"),
+    ("bir_2307_payor_name", "", "string", "Payor name printed on BIR Form 2307"),
+    ("bir_2307_payor_address", "", "string", "Payor registered address printed on BIR Form 2307"),
+];
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // ---1 the tax on each verification's payout
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DentistPayoutItem::Table)
+                    .add_column(ColumnDef::new(DentistPayoutItemTax::TaxBase)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutItemTax::BusinessTaxAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutItemTax::WithheldAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutItemTax::NetAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .to_owned(),
+            ).await?;
+
+        // ---2 the clinic's tax profile and rates at the time of the batch, and the totals
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DentistPayoutRemittance::Table)
+                    .add_column(ColumnDef::new(DentistPayoutRemittanceTax::TaxTypeId)
+                        .integer()
+                        .null()
+                    )
+                    .add_foreign_key(TableForeignKey::new()
+                        .name("dentist_payout_remittance_tax_type_foreign_key")
+                        .from_tbl(DentistPayoutRemittance::Table)
+                        .from_col(DentistPayoutRemittanceTax::TaxTypeId)
+                        .to_tbl(TaxType::Table)
+                        .to_col(TaxType::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutRemittanceTax::TaxClassificationId)
+                        .integer()
+                        .null()
+                    )
+                    .add_foreign_key(TableForeignKey::new()
+                        .name("dentist_payout_remittance_tax_classification_foreign_key")
+                        .from_tbl(DentistPayoutRemittance::Table)
+                        .from_col(DentistPayoutRemittanceTax::TaxClassificationId)
+                        .to_tbl(TaxClassification::Table)
+                        .to_col(TaxClassification::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutRemittanceTax::VatRegistered)
+                        .boolean()
+                        .not_null()
+                        .default(false)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutRemittanceTax::BusinessTaxRate)
+                        .decimal_len(5, 4)
+                        .not_null()
+                        .default(0)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutRemittanceTax::EwtRate)
+                        .decimal_len(5, 4)
+                        .not_null()
+                        .default(0)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutRemittanceTax::AtcCode)
+                        .string()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutRemittanceTax::TaxBase)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutRemittanceTax::BusinessTaxAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutRemittanceTax::WithheldAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutRemittanceTax::NetAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .to_owned(),
+            ).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DentistPayoutBatch::Table)
+                    .add_column(ColumnDef::new(DentistPayoutBatchTax::WithheldAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .add_column(ColumnDef::new(DentistPayoutBatchTax::NetAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                        .default(0)
+                    )
+                    .to_owned(),
+            ).await?;
+
+        // ---3 batches made before withholding was computed paid the gross
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE dentist_payout_item SET tax_base = amount, net_amount = amount;
+                 UPDATE dentist_payout_remittance SET tax_base = gross_amount, net_amount = gross_amount;
+                 UPDATE dentist_payout_batch SET net_amount = gross_amount;"
+            )
+            .await?;
+
+        // ---4 quarterly certificates
+        manager
+            .create_table(
+                Table::create()
+                    .table(Bir2307Certificate::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Bir2307Certificate::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Bir2307Certificate::DentalClinicId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("bir_2307_certificate_dental_clinic_foreign_key")
+                        .from(Bir2307Certificate::Table, Bir2307Certificate::DentalClinicId)
+                        .to(DentalClinic::Table, DentalClinic::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(Bir2307Certificate::Year)
+                        .integer()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Bir2307Certificate::Quarter)
+                        .integer()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Bir2307Certificate::IncomeTotal)
+                        .decimal_len(14, 2)
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Bir2307Certificate::WithheldTotal)
+                        .decimal_len(14, 2)
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Bir2307Certificate::FileName)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Bir2307Certificate::GeneratedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Bir2307Certificate::GeneratedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_bir_2307_certificate_clinic_quarter")
+                    .table(Bir2307Certificate::Table)
+                    .col(Bir2307Certificate::DentalClinicId)
+                    .col(Bir2307Certificate::Year)
+                    .col(Bir2307Certificate::Quarter)
+                    .unique()
+                    .to_owned()
+            ).await?;
+
+        for (key, value, value_type, description) in TAX_CONFIG {
+            AppConfig::insert_key_value_pair(manager, key, value, value_type, description).await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for (key, ..) in TAX_CONFIG {
+            AppConfig::delete_key_value_pair(manager, key).await?;
+        }
+        manager
+            .drop_table(Table::drop().table(Bir2307Certificate::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DentistPayoutBatch::Table)
+                    .drop_column(DentistPayoutBatchTax::WithheldAmount)
+                    .drop_column(DentistPayoutBatchTax::NetAmount)
+                    .to_owned(),
+            ).await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DentistPayoutRemittance::Table)
+                    .drop_foreign_key("dentist_payout_remittance_tax_type_foreign_key")
+                    .drop_foreign_key("dentist_payout_remittance_tax_classification_foreign_key")
+                    .drop_column(DentistPayoutRemittanceTax::TaxTypeId)
+                    .drop_column(DentistPayoutRemittanceTax::TaxClassificationId)
+                    .drop_column(DentistPayoutRemittanceTax::VatRegistered)
+                    .drop_column(DentistPayoutRemittanceTax::BusinessTaxRate)
+                    .drop_column(DentistPayoutRemittanceTax::EwtRate)
+                    .drop_column(DentistPayoutRemittanceTax::AtcCode)
+                    .drop_column(DentistPayoutRemittanceTax::TaxBase)
+                    .drop_column(DentistPayoutRemittanceTax::BusinessTaxAmount)
+                    .drop_column(DentistPayoutRemittanceTax::WithheldAmount)
+                    .drop_column(DentistPayoutRemittanceTax::NetAmount)
+                    .to_owned(),
+            ).await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DentistPayoutItem::Table)
+                    .drop_column(DentistPayoutItemTax::TaxBase)
+                    .drop_column(DentistPayoutItemTax::BusinessTaxAmount)
+                    .drop_column(DentistPayoutItemTax::WithheldAmount)
+                    .drop_column(DentistPayoutItemTax::NetAmount)
+                    .to_owned(),
+            ).await?;
+        Ok(())
+    }
+}
+
+/*
+tax_base is the income payment subject to withholding: the amount less VAT for VAT-registered
+clinics, the whole amount otherwise. net_amount = amount - withheld_amount.
+ */
+#[derive(DeriveIden)]
+enum DentistPayoutItemTax {
+    TaxBase,
+    BusinessTaxAmount,
+    WithheldAmount,
+    NetAmount,
+}
+
+/*
+Copied from the clinic's tax profile and app_config when the batch was made, so later changes
+do not alter what was paid.
+ */
+#[derive(DeriveIden)]
+enum DentistPayoutRemittanceTax {
+    TaxTypeId,
+    TaxClassificationId,
+    VatRegistered,
+    BusinessTaxRate,
+    EwtRate,
+    AtcCode,
+    TaxBase,
+    BusinessTaxAmount,
+    WithheldAmount,
+    NetAmount,
+}
+
+/*
+Batch totals of the remittances.
+ */
+#[derive(DeriveIden)]
+enum DentistPayoutBatchTax {
+    WithheldAmount,
+    NetAmount,
+}
+
+/*
+A BIR Form 2307 issued to a dentist clinic for the tax withheld from its payouts in a quarter.
+Regenerating it replaces the file.
+ */
+#[derive(DeriveIden)]
+pub enum Bir2307Certificate {
+    Table,
+    Id,
+    DentalClinicId,
+    Year,
+    Quarter,
+    IncomeTotal,
+    WithheldTotal,
+    FileName,
+    GeneratedBy,
+    GeneratedOn,
+}
@@ -0,0 +1,273 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20260126_063012_create_tables_dental_clinic::DentalClinic;
+use crate::m20260126_161604_create_table_dentists::Dentist;
+use crate::m20261018_220000_create_dentist_payout_tables::DentistPayoutRemittance;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // ---1 layouts
+        manager
+            .create_table(
+                Table::create()
+                    .table(BankDisbursementLayout::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(BankDisbursementLayout::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementLayout::BankName)
+                        .string()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .col(ColumnDef::new(BankDisbursementLayout::Spec)
+                        .json_binary()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementLayout::Active)
+                        .boolean()
+                        .not_null()
+                        .default(true)
+                    )
+                    .col(ColumnDef::new(BankDisbursementLayout::LastModifiedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementLayout::LastModifiedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        // ---2 files
+        manager
+            .create_table(
+                Table::create()
+                    .table(BankDisbursementFile::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(BankDisbursementFile::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementFile::LayoutId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("bank_disbursement_file_layout_foreign_key")
+                        .from(BankDisbursementFile::Table, BankDisbursementFile::LayoutId)
+                        .to(BankDisbursementLayout::Table, BankDisbursementLayout::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(BankDisbursementFile::BankName)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementFile::FileName)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementFile::ValueDate)
+                        .date()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementFile::PaymentCount)
+                        .integer()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementFile::TotalAmount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementFile::CreatedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementFile::CreatedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        // ---3 the payments in each file
+        manager
+            .create_table(
+                Table::create()
+                    .table(BankDisbursementPayment::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(BankDisbursementPayment::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementPayment::FileId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("bank_disbursement_payment_file_foreign_key")
+                        .from(BankDisbursementPayment::Table, BankDisbursementPayment::FileId)
+                        .to(BankDisbursementFile::Table, BankDisbursementFile::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(BankDisbursementPayment::Source)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementPayment::DentistId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("bank_disbursement_payment_dentist_foreign_key")
+                        .from(BankDisbursementPayment::Table, BankDisbursementPayment::DentistId)
+                        .to(Dentist::Table, Dentist::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(BankDisbursementPayment::DentalClinicId)
+                        .integer()
+                        .not_null()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("bank_disbursement_payment_dental_clinic_foreign_key")
+                        .from(BankDisbursementPayment::Table, BankDisbursementPayment::DentalClinicId)
+                        .to(DentalClinic::Table, DentalClinic::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(BankDisbursementPayment::RetainerYear)
+                        .integer()
+                        .null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementPayment::RetainerMonth)
+                        .integer()
+                        .null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementPayment::DentistPayoutRemittanceId)
+                        .integer()
+                        .null()
+                        .unique_key()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("bank_disbursement_payment_remittance_foreign_key")
+                        .from(BankDisbursementPayment::Table, BankDisbursementPayment::DentistPayoutRemittanceId)
+                        .to(DentistPayoutRemittance::Table, DentistPayoutRemittance::Id)
+                        .on_delete(ForeignKeyAction::Restrict)
+                    )
+                    .col(ColumnDef::new(BankDisbursementPayment::Amount)
+                        .decimal_len(14, 2)
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementPayment::AccountNumber)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(BankDisbursementPayment::AccountName)
+                        .string()
+                        .not_null()
+                    )
+                    .to_owned()
+            ).await?;
+
+        // a month's retainer goes into one file only
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_bank_disbursement_payment_retainer")
+                    .table(BankDisbursementPayment::Table)
+                    .col(BankDisbursementPayment::DentistId)
+                    .col(BankDisbursementPayment::DentalClinicId)
+                    .col(BankDisbursementPayment::RetainerYear)
+                    .col(BankDisbursementPayment::RetainerMonth)
+                    .unique()
+                    .to_owned()
+            ).await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_bank_disbursement_payment_file_id")
+                    .table(BankDisbursementPayment::Table)
+                    .col(BankDisbursementPayment::FileId)
+                    .to_owned()
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(BankDisbursementPayment::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(BankDisbursementFile::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(BankDisbursementLayout::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+/*
+The bulk-credit upload layout of a bank, matched to dental_clinic.acct_bank_name. spec holds
+the file format (csv or fixed_width) and its fields; see jobs::bank_disbursement::LayoutSpec.
+ */
+#[derive(DeriveIden)]
+pub enum BankDisbursementLayout {
+    Table,
+    Id,
+    BankName,
+    Spec,
+    Active,
+    LastModifiedBy,
+    LastModifiedOn,
+}
+
+/*
+A generated bulk-credit upload file.
+ */
+#[derive(DeriveIden)]
+pub enum BankDisbursementFile {
+    Table,
+    Id,
+    LayoutId,
+    BankName,
+    FileName,
+    ValueDate,
+    PaymentCount,
+    TotalAmount,
+    CreatedBy,
+    CreatedOn,
+}
+
+/*
+A payment in an upload file: a month's retainer (retainer_year, retainer_month) or a payout
+remittance (dentist_payout_remittance_id). The account is copied as it was written to the file.
+ */
+#[derive(DeriveIden)]
+pub enum BankDisbursementPayment {
+    Table,
+    Id,
+    FileId,
+    Source,
+    DentistId,
+    DentalClinicId,
+    RetainerYear,
+    RetainerMonth,
+    DentistPayoutRemittanceId,
+    Amount,
+    AccountNumber,
+    AccountName,
+}
@@ -0,0 +1,131 @@
+use sea_orm_migration::{prelude::*};
+
+/// The effective_from given to the rates that existed before rates were dated.
+const UNDATED_RATES_EFFECTIVE_FROM: &str = "2000-01-01";
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // ---1 rates are money: float -> numeric(14,2)
+        db.execute_unprepared(
+            r#"
+            ALTER TABLE dentist_contract_service_rates
+                ALTER COLUMN rate DROP DEFAULT,
+                ALTER COLUMN rate TYPE numeric(14,2) USING round(rate::numeric, 2),
+                ALTER COLUMN rate SET DEFAULT 0
+            "#,
+        ).await?;
+
+        // ---2 each row is a version of a service's rate, in force from effective_from through
+        // effective_to (open-ended when null)
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DentistContractServiceRates::Table)
+                    .add_column(ColumnDef::new(DentistContractServiceRates::EffectiveFrom)
+                        .date()
+                        .not_null()
+                        .default(UNDATED_RATES_EFFECTIVE_FROM)
+                    )
+                    .add_column(ColumnDef::new(DentistContractServiceRates::EffectiveTo)
+                        .date()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(DentistContractServiceRates::CreatedBy)
+                        .string()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(DentistContractServiceRates::CreatedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+        // new versions are always given a date
+        db.execute_unprepared(
+            "ALTER TABLE dentist_contract_service_rates ALTER COLUMN effective_from DROP DEFAULT",
+        ).await?;
+
+        // ---3 one version per contract, service and start date. Rates used to be replaced
+        // without checking for a service listed twice; the last one inserted was the rate.
+        db.execute_unprepared(
+            r#"
+            DELETE FROM dentist_contract_service_rates older
+            USING dentist_contract_service_rates newer
+            WHERE older.dentist_contract_id = newer.dentist_contract_id
+              AND older.service_id = newer.service_id
+              AND older.id < newer.id
+            "#,
+        ).await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_dentist_contract_service_rates_effective_from")
+                    .table(DentistContractServiceRates::Table)
+                    .col(DentistContractServiceRates::DentistContractId)
+                    .col(DentistContractServiceRates::ServiceId)
+                    .col(DentistContractServiceRates::EffectiveFrom)
+                    .unique()
+                    .to_owned()
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_dentist_contract_service_rates_effective_from")
+                    .table(DentistContractServiceRates::Table)
+                    .to_owned()
+            ).await?;
+
+        // keep only the versions in force today
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            r#"
+            DELETE FROM dentist_contract_service_rates
+            WHERE effective_from > CURRENT_DATE
+               OR (effective_to IS NOT NULL AND effective_to < CURRENT_DATE)
+            "#,
+        ).await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(DentistContractServiceRates::Table)
+                    .drop_column(DentistContractServiceRates::EffectiveFrom)
+                    .drop_column(DentistContractServiceRates::EffectiveTo)
+                    .drop_column(DentistContractServiceRates::CreatedBy)
+                    .drop_column(DentistContractServiceRates::CreatedOn)
+                    .to_owned()
+            ).await?;
+        db.execute_unprepared(
+            r#"
+            ALTER TABLE dentist_contract_service_rates
+                ALTER COLUMN rate DROP DEFAULT,
+                ALTER COLUMN rate TYPE real USING rate::real,
+                ALTER COLUMN rate SET DEFAULT 0.0
+            "#,
+        ).await?;
+        Ok(())
+    }
+}
+
+/*
+dentist_contract_service_rates, now dated: a contract's rate for a service is the row whose
+effective_from..effective_to covers the date the service was performed.
+ */
+#[derive(DeriveIden)]
+pub enum DentistContractServiceRates {
+    Table,
+    EffectiveFrom,
+    EffectiveTo,
+    CreatedBy,
+    CreatedOn,
+}
@@ -0,0 +1,82 @@
+use sea_orm_migration::{prelude::*};
+use crate::m20260319_052702_add_verification_tables::VerificationStatus;
+
+/// verification_status.int_code of a released approval code that was voided
+const VOIDED: i32 = 98;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let insert = Query::insert()
+            .into_table(VerificationStatus::Table)
+            .columns(vec![VerificationStatus::IntCode, VerificationStatus::Name])
+            .values_panic([Expr::val(VOIDED), Expr::val("Voided")])
+            .to_owned();
+        manager.exec_stmt(insert).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Verification::Table)
+                    .add_column(ColumnDef::new(Verification::VoidReasonCode)
+                        .string()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(Verification::VoidNote)
+                        .text()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(Verification::VoidedBy)
+                        .string()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(Verification::VoidedOn)
+                        .timestamp_with_time_zone()
+                        .null()
+                    )
+                    .to_owned()
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // voided codes go back to released
+        manager.get_connection().execute_unprepared(&format!(
+            "UPDATE verification SET status_id = 99 WHERE status_id = {VOIDED}"
+        )).await?;
+        manager.get_connection().execute_unprepared(&format!(
+            "DELETE FROM verification_status_history WHERE from_status_id = {VOIDED} OR to_status_id = {VOIDED}"
+        )).await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Verification::Table)
+                    .drop_column(Verification::VoidReasonCode)
+                    .drop_column(Verification::VoidNote)
+                    .drop_column(Verification::VoidedBy)
+                    .drop_column(Verification::VoidedOn)
+                    .to_owned()
+            ).await?;
+        let delete = Query::delete()
+            .from_table(VerificationStatus::Table)
+            .and_where(Expr::col(VerificationStatus::IntCode).eq(VOIDED))
+            .to_owned();
+        manager.exec_stmt(delete).await?;
+        Ok(())
+    }
+}
+
+/*
+the void columns of verification: why, by whom and when a released approval code was voided
+ */
+#[derive(DeriveIden)]
+pub enum Verification {
+    Table,
+    VoidReasonCode,
+    VoidNote,
+    VoidedBy,
+    VoidedOn,
+}
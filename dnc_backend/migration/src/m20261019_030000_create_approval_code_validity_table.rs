@@ -0,0 +1,134 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20251221_124454_create_table_dental_services::DentalServiceType;
+use crate::m20260108_051749_create_table_hmo::HMO;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApprovalCodeValidity::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ApprovalCodeValidity::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(ApprovalCodeValidity::HmoId)
+                        .integer()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_approval_code_validity_hmo_id")
+                        .from(ApprovalCodeValidity::Table, ApprovalCodeValidity::HmoId)
+                        .to(HMO::Table, HMO::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(ApprovalCodeValidity::DentalServiceTypeId)
+                        .integer()
+                    )
+                    .foreign_key(ForeignKey::create()
+                        .name("fk_approval_code_validity_dental_service_type_id")
+                        .from(ApprovalCodeValidity::Table, ApprovalCodeValidity::DentalServiceTypeId)
+                        .to(DentalServiceType::Table, DentalServiceType::Id)
+                        .on_delete(ForeignKeyAction::Cascade)
+                    )
+                    .col(ColumnDef::new(ApprovalCodeValidity::ValidityDays)
+                        .integer()
+                        .not_null()
+                        .check(Expr::col(ApprovalCodeValidity::ValidityDays).gt(0))
+                    )
+                    .col(ColumnDef::new(ApprovalCodeValidity::LastModifiedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(ApprovalCodeValidity::LastModifiedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        // one window per HMO and service type, the nulls included
+        manager.get_connection().execute_unprepared(
+            r#"
+            CREATE UNIQUE INDEX idx_approval_code_validity_scope
+                ON approval_code_validity (COALESCE(hmo_id, 0), COALESCE(dental_service_type_id, 0))
+            "#,
+        ).await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Verification::Table)
+                    .add_column(ColumnDef::new(Verification::LapsedOn)
+                        .timestamp_with_time_zone()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(Verification::LapseOverrideReason)
+                        .text()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(Verification::LapseOverrideBy)
+                        .string()
+                        .null()
+                    )
+                    .add_column(ColumnDef::new(Verification::LapseOverrideOn)
+                        .timestamp_with_time_zone()
+                        .null()
+                    )
+                    .to_owned()
+            ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Verification::Table)
+                    .drop_column(Verification::LapsedOn)
+                    .drop_column(Verification::LapseOverrideReason)
+                    .drop_column(Verification::LapseOverrideBy)
+                    .drop_column(Verification::LapseOverrideOn)
+                    .to_owned()
+            ).await?;
+        manager.drop_table(Table::drop().table(ApprovalCodeValidity::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+/*
+  How many days a released approval code stays valid for reconciliation. A row with neither
+  hmo_id nor dental_service_type_id applies to everyone; the most specific row wins, HMO over
+  service type. Without any row, codes do not lapse.
+ */
+#[derive(DeriveIden)]
+pub enum ApprovalCodeValidity {
+    Table,
+    Id,
+    HmoId,
+    DentalServiceTypeId,
+    ValidityDays,
+    LastModifiedBy,
+    LastModifiedOn,
+}
+
+/*
+the lapse columns of verification: when the daily job found the code past its window, and who
+allowed it to be reconciled anyway, and why
+ */
+#[derive(DeriveIden)]
+pub enum Verification {
+    Table,
+    LapsedOn,
+    LapseOverrideReason,
+    LapseOverrideBy,
+    LapseOverrideOn,
+}
@@ -0,0 +1,91 @@
+use sea_orm_migration::{prelude::*};
+
+use crate::m20260507_045752_create_app_config_table::Migration as AppConfig;
+use crate::m20251205_063628_create_table_dataobject::Migration as DataObjectMigration;
+use  crate::m20251205_075427_create_table_permission::Migration as PermissionMigration;
+use  crate::m20251205_075445_create_table_role_permission::Migration as RolePermissionMigration;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Holiday::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Holiday::Id)
+                        .integer()
+                        .primary_key()
+                        .auto_increment()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Holiday::HolidayDate)
+                        .date()
+                        .not_null()
+                        .unique_key()
+                    )
+                    .col(ColumnDef::new(Holiday::Name)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Holiday::HolidayType)
+                        .string()
+                        .not_null()
+                        .default("regular")
+                    )
+                    .col(ColumnDef::new(Holiday::LastModifiedBy)
+                        .string()
+                        .not_null()
+                    )
+                    .col(ColumnDef::new(Holiday::LastModifiedOn)
+                        .timestamp_with_time_zone()
+                        .not_null()
+                        .default(Expr::current_timestamp())
+                    )
+                    .to_owned()
+            ).await?;
+
+        // Sundays stay the only rest day, as before
+        AppConfig::insert_key_value_pair(
+            manager,
+            "non_working_weekdays",
+            "Sun",
+            "string",
+            "Weekdays the business is closed, comma separated (e.g. Sat,Sun)",
+        ).await?;
+
+        DataObjectMigration::add_dataobject(manager, "business_calendar", "Business Calendar Object").await?;
+        PermissionMigration::add_all_permissions(manager, "business_calendar").await?;
+        RolePermissionMigration::insert_role_all_permissions(manager, "Administrator", "business_calendar").await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        RolePermissionMigration::del_role_all_permissions(manager, "Administrator", "business_calendar").await?;
+        PermissionMigration::del_all_permissions(manager, "business_calendar").await?;
+        DataObjectMigration::delete_dataobject(manager, "business_calendar").await?;
+        AppConfig::delete_key_value_pair(manager, "non_working_weekdays").await?;
+        manager.drop_table(Table::drop().table(Holiday::Table).to_owned()).await?;
+        Ok(())
+    }
+}
+
+/*
+  Days the business is closed besides the weekdays in app_config non_working_weekdays, one row
+  per date. Regular holidays and special non-working days both close the business;
+  holiday_type only records which one it is.
+ */
+#[derive(DeriveIden)]
+pub enum Holiday {
+    Table,
+    Id,
+    HolidayDate,
+    Name,
+    HolidayType,    // "regular" or "special_non_working"
+    LastModifiedBy,
+    LastModifiedOn,
+}
+
@@ -0,0 +1,39 @@
+use sea_orm_migration::{prelude::* };
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+
+        // the engine already used the newest of duplicate rows; keep only that one
+        db.execute_unprepared(
+            r#"
+            DELETE FROM approval_code_rule_config older
+                USING approval_code_rule_config newer
+                WHERE older.rule_key = newer.rule_key
+                  AND COALESCE(older.hmo_id, 0) = COALESCE(newer.hmo_id, 0)
+                  AND COALESCE(older.endorsement_id, 0) = COALESCE(newer.endorsement_id, 0)
+                  AND older.id < newer.id
+            "#,
+        ).await?;
+
+        // one config per rule and scope, the nulls included
+        db.execute_unprepared(
+            r#"
+            CREATE UNIQUE INDEX idx_approval_code_rule_config_scope
+                ON approval_code_rule_config (rule_key, COALESCE(hmo_id, 0), COALESCE(endorsement_id, 0))
+            "#,
+        ).await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager.get_connection().execute_unprepared(
+            "DROP INDEX IF EXISTS idx_approval_code_rule_config_scope",
+        ).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+//! Audit trail hooks.
+//!
+//! The audited entities (verification, endorsement, dentist, dentist_payments and
+//! acc_reconciliation) get their `ActiveModelBehavior` from `audited_active_model_behavior!`,
+//! which calls these helpers, so every insert, update and delete that goes through an
+//! ActiveModel writes one complete `audit_log` row on the same connection (or transaction) as
+//! the change itself.
+//!
+//! Bulk `update_many`/`delete_many` calls do NOT run the hooks; audited entities must be
+//! changed through their ActiveModel.
+//!
+//! The actor is taken from the task-local set by `require_jwt` for API requests; anything
+//! else (background jobs, migrations) is recorded as "system".
+use std::future::Future;
+
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ConnectionTrait, DbErr, EntityName, EntityTrait, IntoActiveModel,
+    Iterable, PrimaryKeyTrait, Set, TryIntoModel,
+};
+use serde::Serialize;
+
+use crate::entities::audit_log;
+
+tokio::task_local! {
+    static AUDIT_ACTOR: String;
+}
+
+/// Runs `f` with `actor` recorded as the user behind any audited change it makes.
+pub async fn with_actor<F: Future>(actor: String, f: F) -> F::Output {
+    AUDIT_ACTOR.scope(actor, f).await
+}
+
+/// The email of the logged-in user making the current request, or "system".
+pub fn current_actor() -> String {
+    AUDIT_ACTOR
+        .try_with(|actor| actor.clone())
+        .unwrap_or_else(|_| "system".to_string())
+}
+
+/// Primary key of an ActiveModel, if it has one yet.
+pub fn active_id(id: &ActiveValue<i32>) -> Option<i32> {
+    match id {
+        ActiveValue::Set(id) | ActiveValue::Unchanged(id) => Some(*id),
+        ActiveValue::NotSet => None,
+    }
+}
+
+async fn insert_row<C: ConnectionTrait>(
+    db: &C,
+    entity: &str,
+    entity_id: i32,
+    action: &str,
+    before_json: Option<serde_json::Value>,
+    after_json: Option<serde_json::Value>,
+) -> Result<(), DbErr> {
+    audit_log::ActiveModel {
+        occurred_on: Set(Utc::now().fixed_offset()),
+        actor: Set(current_actor()),
+        entity: Set(entity.to_string()),
+        entity_id: Set(entity_id),
+        action: Set(action.to_string()),
+        before_json: Set(before_json),
+        after_json: Set(after_json),
+        ..Default::default()
+    }
+        .insert(db)
+        .await?;
+    Ok(())
+}
+
+async fn load_json<E, C>(db: &C, entity_id: i32) -> Result<Option<serde_json::Value>, DbErr>
+where
+    E: EntityTrait,
+    E::Model: Serialize,
+    <E::PrimaryKey as PrimaryKeyTrait>::ValueType: From<i32>,
+    C: ConnectionTrait,
+{
+    let model = E::find_by_id(entity_id).one(db).await?;
+    Ok(model.and_then(|m| serde_json::to_value(m).ok()))
+}
+
+/// Call from `before_save` when `insert` is false.
+/// Writes the whole "update" row at once: the row as it is now, and the same row with the
+/// ActiveModel's `Set` fields applied, which is what the UPDATE will write.
+pub async fn before_update<A, C>(db: &C, am: &A, entity_id: Option<i32>) -> Result<(), DbErr>
+where
+    A: ActiveModelTrait + TryIntoModel<<A::Entity as EntityTrait>::Model>,
+    <A::Entity as EntityTrait>::Model: Serialize + IntoActiveModel<A>,
+    <<A::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType: From<i32>,
+    C: ConnectionTrait,
+{
+    let Some(entity_id) = entity_id else { return Ok(()) };
+    let changes: Vec<_> = <A::Entity as EntityTrait>::Column::iter()
+        .filter_map(|column| match am.get(column) {
+            ActiveValue::Set(value) => Some((column, value)),
+            _ => None,
+        })
+        .collect();
+
+    // nothing to record if the row is gone; the UPDATE fails too
+    let Some(current) = <A::Entity as EntityTrait>::find_by_id(entity_id).one(db).await? else {
+        return Ok(());
+    };
+    let before_json = serde_json::to_value(&current).ok();
+    let mut after = current.into_active_model();
+    for (column, value) in changes {
+        after.set(column, value);
+    }
+    let after_json = after
+        .try_into_model()
+        .ok()
+        .and_then(|model| serde_json::to_value(model).ok());
+
+    insert_row(db, A::Entity::default().table_name(), entity_id, "update", before_json, after_json).await
+}
+
+/// Call from `after_save` when `insert` is true.
+pub async fn after_insert<E, C>(db: &C, model: &E::Model, entity_id: i32) -> Result<(), DbErr>
+where
+    E: EntityTrait,
+    E::Model: Serialize,
+    C: ConnectionTrait,
+{
+    let after_json = serde_json::to_value(model).ok();
+    insert_row(db, E::default().table_name(), entity_id, "create", None, after_json).await
+}
+
+/// Call from `before_delete`.
+pub async fn before_delete<E, C>(db: &C, entity_id: Option<i32>) -> Result<(), DbErr>
+where
+    E: EntityTrait,
+    E::Model: Serialize,
+    <E::PrimaryKey as PrimaryKeyTrait>::ValueType: From<i32>,
+    C: ConnectionTrait,
+{
+    let Some(entity_id) = entity_id else { return Ok(()) };
+    let before_json = load_json::<E, C>(db, entity_id).await?;
+    insert_row(db, E::default().table_name(), entity_id, "delete", before_json, None).await
+}
+
+/// Implements `ActiveModelBehavior` for an audited entity whose primary key is an i32 `id`.
+/// Invoke in the entity's module instead of writing the impl.
+macro_rules! audited_active_model_behavior {
+    () => {
+        #[async_trait]
+        impl ActiveModelBehavior for ActiveModel {
+            async fn before_save<C>(self, db: &C, insert: bool) -> Result<Self, DbErr>
+            where
+                C: ConnectionTrait,
+            {
+                if !insert {
+                    $crate::audit::before_update(db, &self, $crate::audit::active_id(&self.id)).await?;
+                }
+                Ok(self)
+            }
+
+            async fn after_save<C>(model: Model, db: &C, insert: bool) -> Result<Model, DbErr>
+            where
+                C: ConnectionTrait,
+            {
+                if insert {
+                    $crate::audit::after_insert::<Entity, C>(db, &model, model.id).await?;
+                }
+                Ok(model)
+            }
+
+            async fn before_delete<C>(self, db: &C) -> Result<Self, DbErr>
+            where
+                C: ConnectionTrait,
+            {
+                $crate::audit::before_delete::<Entity, C>(db, $crate::audit::active_id(&self.id)).await?;
+                Ok(self)
+            }
+        }
+    };
+}
+pub(crate) use audited_active_model_behavior;
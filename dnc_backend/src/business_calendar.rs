@@ -0,0 +1,305 @@
+//! The business calendar: Manila time, and which days the business is open.
+//!
+//! The business runs on Manila dates. "Today", the date of a timestamp, and the start of a day
+//! should come from here rather than from a hand-made UTC+8 offset.
+//!
+//! A business day is any day that is not a non-working weekday and not a holiday:
+//! - the non-working weekdays are in app_config non_working_weekdays, comma separated
+//!   (e.g. "Sat,Sun"); Sunday unless changed;
+//! - the holidays are in the holiday table, maintained through /api/business_calendar/holidays.
+//!
+//! Waiting periods counted in business days, like the 7 days a verification may wait for its
+//! approval code, go through `BusinessCalendar`.
+use std::collections::BTreeSet;
+
+use axum::http::StatusCode;
+use chrono::{DateTime, Datelike, Days, FixedOffset, LocalResult, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter};
+use serde::{Deserialize, Serialize};
+
+use crate::entities::{app_config, holiday};
+
+/// The business's time zone.
+pub const TIMEZONE: Tz = chrono_tz::Asia::Manila;
+
+/// app_config key of the non-working weekdays.
+pub const NON_WORKING_WEEKDAYS_KEY: &str = "non_working_weekdays";
+
+// region: Manila Time
+pub fn now() -> DateTime<Tz> {
+    Utc::now().with_timezone(&TIMEZONE)
+}
+
+/// Today's date in Manila.
+pub fn today() -> NaiveDate {
+    now().date_naive()
+}
+
+/// The Manila date of a timestamp.
+pub fn date_of<T: TimeZone>(at: &DateTime<T>) -> NaiveDate {
+    at.with_timezone(&TIMEZONE).date_naive()
+}
+
+/// A Manila date and time. Manila has not had daylight saving time since 1978, so every local
+/// time exists exactly once.
+pub fn local_datetime(date: NaiveDate, time: NaiveTime) -> DateTime<Tz> {
+    match TIMEZONE.from_local_datetime(&date.and_time(time)) {
+        LocalResult::Single(dt) | LocalResult::Ambiguous(dt, _) => dt,
+        LocalResult::None => panic!("{} {} does not exist in Manila", date, time),
+    }
+}
+
+/// Midnight in Manila at the start of `date`, e.g. to filter timestamps by Manila day with
+/// `>= start_of_day(from)` and `< start_of_day(to + 1)`.
+pub fn start_of_day(date: NaiveDate) -> DateTime<FixedOffset> {
+    local_datetime(date, NaiveTime::MIN).fixed_offset()
+}
+
+/// The next midnight in Manila, in UTC.
+pub fn next_midnight_utc(now_utc: DateTime<Utc>) -> DateTime<Utc> {
+    let next_date = date_of(&now_utc)
+        .checked_add_days(Days::new(1))
+        .expect("date overflow while computing next Manila midnight");
+    local_datetime(next_date, NaiveTime::MIN).with_timezone(&Utc)
+}
+// endregion: Manila Time
+
+
+// region: Business Days
+#[derive(Debug, thiserror::Error)]
+pub enum CalendarError {
+    #[error("App config '{key}' must list weekdays separated by commas (e.g. Sat,Sun), not '{value}'")]
+    InvalidWeekdays { key: &'static str, value: String },
+
+    #[error("App config '{0}' leaves no working weekday")]
+    NoWorkingWeekday(&'static str),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+impl From<CalendarError> for (StatusCode, String) {
+    fn from(e: CalendarError) -> Self {
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    }
+}
+
+/// For callers that only report database errors.
+impl From<CalendarError> for DbErr {
+    fn from(e: CalendarError) -> Self {
+        match e {
+            CalendarError::Database(err) => err,
+            other => DbErr::Custom(other.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusinessCalendar {
+    non_working_weekdays: Vec<Weekday>,
+    holidays: BTreeSet<NaiveDate>,
+}
+
+/// Sundays off and no holidays: the calendar before holidays were kept.
+impl Default for BusinessCalendar {
+    fn default() -> Self {
+        Self {
+            non_working_weekdays: vec![Weekday::Sun],
+            holidays: BTreeSet::new(),
+        }
+    }
+}
+
+impl BusinessCalendar {
+    /// At least one weekday must be a working day.
+    pub fn new(
+        non_working_weekdays: impl IntoIterator<Item = Weekday>,
+        holidays: impl IntoIterator<Item = NaiveDate>,
+    ) -> Result<Self, CalendarError> {
+        let mut non_working_weekdays: Vec<Weekday> = non_working_weekdays.into_iter().collect();
+        non_working_weekdays.sort_by_key(Weekday::num_days_from_monday);
+        non_working_weekdays.dedup();
+        if non_working_weekdays.len() >= 7 {
+            return Err(CalendarError::NoWorkingWeekday(NON_WORKING_WEEKDAYS_KEY));
+        }
+        Ok(Self {
+            non_working_weekdays,
+            holidays: holidays.into_iter().collect(),
+        })
+    }
+
+    /// Loads the non-working weekdays from app_config and the holidays. Without the app_config
+    /// key, Sunday is the only non-working weekday.
+    pub async fn load<C: ConnectionTrait>(db: &C) -> Result<Self, CalendarError> {
+        let non_working_weekdays = match app_config::Entity::find()
+            .filter(app_config::Column::Key.eq(NON_WORKING_WEEKDAYS_KEY))
+            .one(db)
+            .await?
+        {
+            Some(row) => parse_weekdays(&row.value)?,
+            None => vec![Weekday::Sun],
+        };
+        let holidays = holiday::Entity::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|h| h.holiday_date);
+        Self::new(non_working_weekdays, holidays)
+    }
+
+    pub fn non_working_weekdays(&self) -> &[Weekday] {
+        &self.non_working_weekdays
+    }
+
+    pub fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.holidays.contains(&date)
+    }
+
+    pub fn is_business_day(&self, date: NaiveDate) -> bool {
+        !self.non_working_weekdays.contains(&date.weekday()) && !self.is_holiday(date)
+    }
+
+    /// The business days strictly after `start` and strictly before `end`; 0 if `end` is not
+    /// after `start`.
+    pub fn business_days_between(&self, start: NaiveDate, end: NaiveDate) -> i64 {
+        start
+            .iter_days()
+            .skip(1)
+            .take_while(|day| *day < end)
+            .filter(|day| self.is_business_day(*day))
+            .count() as i64
+    }
+
+    /// The earliest day of the last `n` business days up to and including `until`, e.g. with
+    /// Sundays off and n = 7, the same weekday a week before a business day `until`. `until`
+    /// itself when n is 0.
+    pub fn start_of_last_business_days(&self, until: NaiveDate, n: u32) -> NaiveDate {
+        if n == 0 {
+            return until;
+        }
+        until
+            .iter_days()
+            .rev()
+            .filter(|day| self.is_business_day(*day))
+            .nth(n as usize - 1)
+            .expect("date overflow while counting business days back")
+    }
+}
+
+/// Parses a comma-separated list of weekday names, e.g. "Sat,Sun" or "saturday, sunday".
+pub fn parse_weekdays(value: &str) -> Result<Vec<Weekday>, CalendarError> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| name.parse::<Weekday>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| CalendarError::InvalidWeekdays {
+            key: NON_WORKING_WEEKDAYS_KEY,
+            value: value.to_string(),
+        })
+}
+// endregion: Business Days
+
+
+// region: Holidays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HolidayType {
+    Regular,
+    SpecialNonWorking,
+}
+
+impl HolidayType {
+    /// Stored in holiday.holiday_type.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            HolidayType::Regular => "regular",
+            HolidayType::SpecialNonWorking => "special_non_working",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "regular" => Some(HolidayType::Regular),
+            "special_non_working" => Some(HolidayType::SpecialNonWorking),
+            _ => None,
+        }
+    }
+}
+
+/// A holiday read from an import file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HolidayEntry {
+    pub holiday_date: NaiveDate,
+    pub name: String,
+    pub holiday_type: HolidayType,
+}
+
+/// Reads a holiday CSV file: a header row, then one holiday per row with the columns
+/// holiday_date (YYYY-MM-DD), name, and optionally holiday_type (regular when blank). Every bad
+/// row is reported, by its line number.
+pub fn parse_holiday_csv(data: &[u8]) -> Result<Vec<HolidayEntry>, Vec<String>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    let mut entries: Vec<HolidayEntry> = Vec::new();
+    let mut errors = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        // line 1 is the header
+        let line = index + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                errors.push(format!("line {}: {}", line, err));
+                continue;
+            }
+        };
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+
+        let holiday_date = match NaiveDate::parse_from_str(record.get(0).unwrap_or_default(), "%Y-%m-%d") {
+            Ok(date) => date,
+            Err(_) => {
+                errors.push(format!("line {}: holiday_date must be a date as YYYY-MM-DD", line));
+                continue;
+            }
+        };
+        let name = record.get(1).unwrap_or_default();
+        if name.is_empty() {
+            errors.push(format!("line {}: name is required", line));
+            continue;
+        }
+        let holiday_type = match record.get(2).filter(|t| !t.is_empty()) {
+            None => HolidayType::Regular,
+            Some(value) => match HolidayType::parse(&value.to_lowercase()) {
+                Some(holiday_type) => holiday_type,
+                None => {
+                    errors.push(format!(
+                        "line {}: holiday_type must be regular or special_non_working, not '{}'",
+                        line, value
+                    ));
+                    continue;
+                }
+            },
+        };
+        if entries.iter().any(|e| e.holiday_date == holiday_date) {
+            errors.push(format!("line {}: {} is listed more than once", line, holiday_date));
+            continue;
+        }
+
+        entries.push(HolidayEntry {
+            holiday_date,
+            name: name.to_string(),
+            holiday_type,
+        });
+    }
+
+    if errors.is_empty() { Ok(entries) } else { Err(errors) }
+}
+// endregion: Holidays
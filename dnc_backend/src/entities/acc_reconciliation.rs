@@ -114,4 +114,4 @@ impl Related<super::tooth_service_type::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+crate::audit::audited_active_model_behavior!();
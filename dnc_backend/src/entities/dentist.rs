@@ -128,4 +128,4 @@ impl Related<super::verification::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+crate::audit::audited_active_model_behavior!();
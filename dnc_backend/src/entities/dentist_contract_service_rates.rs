@@ -3,15 +3,22 @@
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
 #[sea_orm(table_name = "dentist_contract_service_rates")]
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
+    #[sea_orm(unique_key = "idx_dentist_contract_service_rates_effective_from")]
     pub dentist_contract_id: i32,
+    #[sea_orm(unique_key = "idx_dentist_contract_service_rates_effective_from")]
     pub service_id: i32,
-    #[sea_orm(column_type = "Float")]
-    pub rate: f32,
+    #[sea_orm(column_type = "Decimal(Some((14, 2)))")]
+    pub rate: Decimal,
+    #[sea_orm(unique_key = "idx_dentist_contract_service_rates_effective_from")]
+    pub effective_from: Date,
+    pub effective_to: Option<Date>,
+    pub created_by: Option<String>,
+    pub created_on: DateTimeWithTimeZone,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
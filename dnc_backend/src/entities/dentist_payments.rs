@@ -53,4 +53,4 @@ impl Related<super::dentist::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+crate::audit::audited_active_model_behavior!();
@@ -130,4 +130,4 @@ impl Related<super::master_list_member::Entity> for Entity {
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+crate::audit::audited_active_model_behavior!();
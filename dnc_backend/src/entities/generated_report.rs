@@ -11,10 +11,30 @@ pub struct Model {
     pub report_type_id: i32,
     pub file_name: String,
     pub date_generated: Option<DateTimeWithTimeZone>,
+    pub billing_run_id: Option<i32>,
+    pub hmo_id: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::billing_run::Entity",
+        from = "Column::BillingRunId",
+        to = "super::billing_run::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    BillingRun,
+    #[sea_orm(
+        belongs_to = "super::hmo::Entity",
+        from = "Column::HmoId",
+        to = "super::hmo::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    Hmo,
+    #[sea_orm(has_one = "super::hmo_invoice::Entity")]
+    HmoInvoice,
     #[sea_orm(
         belongs_to = "super::report_type::Entity",
         from = "Column::ReportTypeId",
@@ -25,6 +45,24 @@ pub enum Relation {
     ReportType,
 }
 
+impl Related<super::billing_run::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BillingRun.def()
+    }
+}
+
+impl Related<super::hmo::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Hmo.def()
+    }
+}
+
+impl Related<super::hmo_invoice::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::HmoInvoice.def()
+    }
+}
+
 impl Related<super::report_type::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::ReportType.def()
@@ -13,10 +13,36 @@ pub struct Model {
     pub request_key: Option<String>,
     pub master_list_count: Option<i32>,
     pub added_list_count: Option<i32>,
+    pub billable_count: Option<i32>,
+    pub rate_source: Option<String>,
+    pub billing_rule_id: Option<i32>,
+    #[sea_orm(column_type = "Decimal(Some((14, 2)))", nullable)]
+    pub rate: Option<Decimal>,
+    #[sea_orm(column_type = "Decimal(Some((5, 4)))", nullable)]
+    pub vat_rate: Option<Decimal>,
+    #[sea_orm(column_type = "Decimal(Some((14, 2)))", nullable)]
+    pub vat_per_member: Option<Decimal>,
+    #[sea_orm(column_type = "Decimal(Some((14, 2)))", nullable)]
+    pub rate_with_vat: Option<Decimal>,
+    #[sea_orm(column_type = "Decimal(Some((14, 2)))", nullable)]
+    pub amount_before_vat: Option<Decimal>,
+    #[sea_orm(column_type = "Decimal(Some((14, 2)))", nullable)]
+    pub vat_amount: Option<Decimal>,
+    #[sea_orm(column_type = "Decimal(Some((14, 2)))", nullable)]
+    pub total_amount: Option<Decimal>,
+    pub billing_run_id: Option<i32>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::billing_run::Entity",
+        from = "Column::BillingRunId",
+        to = "super::billing_run::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    BillingRun,
     #[sea_orm(
         belongs_to = "super::endorsement::Entity",
         from = "Column::EndorsementId",
@@ -27,6 +53,12 @@ pub enum Relation {
     Endorsement,
 }
 
+impl Related<super::billing_run::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::BillingRun.def()
+    }
+}
+
 impl Related<super::endorsement::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Endorsement.def()
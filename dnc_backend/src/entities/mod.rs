@@ -5,6 +5,14 @@ pub mod prelude;
 pub mod acc_reconciliation;
 pub mod account_type;
 pub mod app_config;
+pub mod approval_code_rule_config;
+pub mod approval_code_validity;
+pub mod audit_log;
+pub mod bank_disbursement_file;
+pub mod bank_disbursement_layout;
+pub mod bank_disbursement_payment;
+pub mod billing_run;
+pub mod bir_2307_certificate;
 pub mod city;
 pub mod clinic_capabilities_list;
 pub mod clinic_capability;
@@ -21,6 +29,9 @@ pub mod dentist_contract;
 pub mod dentist_contract_service_rates;
 pub mod dentist_history;
 pub mod dentist_hmo_relations;
+pub mod dentist_payout_batch;
+pub mod dentist_payout_item;
+pub mod dentist_payout_remittance;
 pub mod dentist_payments;
 pub mod dentist_status;
 pub mod endorsement;
@@ -35,12 +46,20 @@ pub mod high_end_files;
 pub mod high_end_verification_information;
 pub mod hmo;
 pub mod hmo_billing_data;
+pub mod hmo_claims_billing_item;
+pub mod hmo_invoice;
+pub mod hmo_payment;
+pub mod holiday;
+pub mod job;
 pub mod master_list;
+pub mod master_list_column_mapping;
 pub mod master_list_member;
+pub mod master_list_upload_preview;
 pub mod permission;
 pub mod position;
 pub mod province;
 pub mod region;
+pub mod report_schedule_state;
 pub mod report_type;
 pub mod role;
 pub mod role_permission;
@@ -50,6 +69,8 @@ pub mod tax_type;
 pub mod tooth_service_type;
 pub mod tooth_surface;
 pub mod user;
+pub mod user_session;
 pub mod verification;
 pub mod verification_status;
+pub mod verification_status_history;
 pub mod verification_tooth_surfaces;
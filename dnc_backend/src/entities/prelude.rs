@@ -3,6 +3,14 @@
 pub use super::acc_reconciliation::Entity as AccReconciliation;
 pub use super::account_type::Entity as AccountType;
 pub use super::app_config::Entity as AppConfig;
+pub use super::approval_code_rule_config::Entity as ApprovalCodeRuleConfig;
+pub use super::approval_code_validity::Entity as ApprovalCodeValidity;
+pub use super::audit_log::Entity as AuditLog;
+pub use super::bank_disbursement_file::Entity as BankDisbursementFile;
+pub use super::bank_disbursement_layout::Entity as BankDisbursementLayout;
+pub use super::bank_disbursement_payment::Entity as BankDisbursementPayment;
+pub use super::billing_run::Entity as BillingRun;
+pub use super::bir_2307_certificate::Entity as Bir2307Certificate;
 pub use super::city::Entity as City;
 pub use super::clinic_capabilities_list::Entity as ClinicCapabilitiesList;
 pub use super::clinic_capability::Entity as ClinicCapability;
@@ -19,6 +27,9 @@ pub use super::dentist_contract::Entity as DentistContract;
 pub use super::dentist_contract_service_rates::Entity as DentistContractServiceRates;
 pub use super::dentist_history::Entity as DentistHistory;
 pub use super::dentist_hmo_relations::Entity as DentistHmoRelations;
+pub use super::dentist_payout_batch::Entity as DentistPayoutBatch;
+pub use super::dentist_payout_item::Entity as DentistPayoutItem;
+pub use super::dentist_payout_remittance::Entity as DentistPayoutRemittance;
 pub use super::dentist_payments::Entity as DentistPayments;
 pub use super::dentist_status::Entity as DentistStatus;
 pub use super::endorsement::Entity as Endorsement;
@@ -33,12 +44,20 @@ pub use super::high_end_files::Entity as HighEndFiles;
 pub use super::high_end_verification_information::Entity as HighEndVerificationInformation;
 pub use super::hmo::Entity as Hmo;
 pub use super::hmo_billing_data::Entity as HmoBillingData;
+pub use super::hmo_claims_billing_item::Entity as HmoClaimsBillingItem;
+pub use super::hmo_invoice::Entity as HmoInvoice;
+pub use super::hmo_payment::Entity as HmoPayment;
+pub use super::holiday::Entity as Holiday;
+pub use super::job::Entity as Job;
 pub use super::master_list::Entity as MasterList;
+pub use super::master_list_column_mapping::Entity as MasterListColumnMapping;
 pub use super::master_list_member::Entity as MasterListMember;
+pub use super::master_list_upload_preview::Entity as MasterListUploadPreview;
 pub use super::permission::Entity as Permission;
 pub use super::position::Entity as Position;
 pub use super::province::Entity as Province;
 pub use super::region::Entity as Region;
+pub use super::report_schedule_state::Entity as ReportScheduleState;
 pub use super::report_type::Entity as ReportType;
 pub use super::role::Entity as Role;
 pub use super::role_permission::Entity as RolePermission;
@@ -47,6 +66,8 @@ pub use super::tax_type::Entity as TaxType;
 pub use super::tooth_service_type::Entity as ToothServiceType;
 pub use super::tooth_surface::Entity as ToothSurface;
 pub use super::user::Entity as User;
+pub use super::user_session::Entity as UserSession;
 pub use super::verification::Entity as Verification;
 pub use super::verification_status::Entity as VerificationStatus;
+pub use super::verification_status_history::Entity as VerificationStatusHistory;
 pub use super::verification_tooth_surfaces::Entity as VerificationToothSurfaces;
@@ -10,12 +10,15 @@ pub struct Model {
     pub id: i32,
     #[sea_orm(unique)]
     pub name: String,
+    pub config_key_prefix: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
     #[sea_orm(has_many = "super::generated_report::Entity")]
     GeneratedReport,
+    #[sea_orm(has_one = "super::report_schedule_state::Entity")]
+    ReportScheduleState,
 }
 
 impl Related<super::generated_report::Entity> for Entity {
@@ -24,4 +27,10 @@ impl Related<super::generated_report::Entity> for Entity {
     }
 }
 
+impl Related<super::report_schedule_state::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ReportScheduleState.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
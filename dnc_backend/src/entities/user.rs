@@ -28,6 +28,8 @@ pub enum Relation {
         on_delete = "Restrict"
     )]
     Role,
+    #[sea_orm(has_many = "super::user_session::Entity")]
+    UserSession,
 }
 
 impl Related<super::role::Entity> for Entity {
@@ -36,4 +38,10 @@ impl Related<super::role::Entity> for Entity {
     }
 }
 
+impl Related<super::user_session::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserSession.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -24,6 +24,16 @@ pub struct Model {
     pub reconciled_by: Option<String>,
     pub reconciliation_date: Option<DateTimeWithTimeZone>,
     pub dental_clinic_id: i32,
+    pub void_reason_code: Option<String>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub void_note: Option<String>,
+    pub voided_by: Option<String>,
+    pub voided_on: Option<DateTimeWithTimeZone>,
+    pub lapsed_on: Option<DateTimeWithTimeZone>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub lapse_override_reason: Option<String>,
+    pub lapse_override_by: Option<String>,
+    pub lapse_override_on: Option<DateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -83,6 +93,8 @@ pub enum Relation {
         on_delete = "Restrict"
     )]
     VerificationStatus,
+    #[sea_orm(has_many = "super::verification_status_history::Entity")]
+    VerificationStatusHistory,
     #[sea_orm(has_many = "super::verification_tooth_surfaces::Entity")]
     VerificationToothSurfaces,
 }
@@ -135,10 +147,16 @@ impl Related<super::verification_status::Entity> for Entity {
     }
 }
 
+impl Related<super::verification_status_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::VerificationStatusHistory.def()
+    }
+}
+
 impl Related<super::verification_tooth_surfaces::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::VerificationToothSurfaces.def()
     }
 }
 
-impl ActiveModelBehavior for ActiveModel {}
+crate::audit::audited_active_model_behavior!();
@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use axum::{extract::{Path, State}, http::StatusCode, Json};
 use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait,
-              JoinType, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set,
+              JoinType, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set, TransactionTrait,
 };
 
 use crate::entities::{
@@ -14,6 +14,8 @@ use crate::entities::{
     tooth_surface, verification, verification_tooth_surfaces,
 };
 use chrono::{Utc};
+use crate::business_calendar;
+use crate::jobs::approval_code_validity::lapse_status_of;
 
 // region: Get Done Verifications
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,6 +41,7 @@ pub struct DoneVerificationResponse {
 use sea_orm::FromQueryResult;
 use crate::AppState;
 use crate::handlers::AuthUser;
+use crate::verification_state::VerificationStatus;
 
 #[derive(Debug, FromQueryResult)]
 struct DoneVerificationRow {
@@ -76,7 +79,7 @@ pub async fn get_done_verifications(
 ) -> Result<Json<Vec<DoneVerificationResponse>>, (StatusCode, String)> {
     let db = &state.db;
     let rows: Vec<DoneVerificationRow> = verification::Entity::find()
-        .filter(verification::Column::StatusId.eq(99))
+        .filter(verification::Column::StatusId.eq(VerificationStatus::Done.int_code()))
         .join(JoinType::InnerJoin, verification::Relation::Dentist.def())
         .join(JoinType::InnerJoin, verification::Relation::MasterListMember.def())
         .join(JoinType::InnerJoin, verification::Relation::DentalService.def())
@@ -224,14 +227,42 @@ pub async fn reconcile_verification(
     let db = &state.db;
     tracing::info!("in reconcile_verification(): reconciling verification_id: {}", verification_id);
 
+    // locked like void_approval_code, so a void and a reconcile cannot both pass their checks
+    let txn = db.begin().await.map_err(internal_error)?;
     let model = verification::Entity::find_by_id(verification_id)
-        .one(db)
+        .lock_exclusive()
+        .one(&txn)
         .await
         .map_err(internal_error)?
         .ok_or((
             StatusCode::NOT_FOUND,
             format!("Verification {} not found", verification_id),
         ))?;
+    if model.status_id == VerificationStatus::Voided.int_code() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("The approval code of verification {} was voided", verification_id),
+        ));
+    }
+    if model.status_id != VerificationStatus::Done.int_code() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Verification {} is not done", verification_id),
+        ));
+    }
+    let today_manila = business_calendar::today();
+    if let Some(lapse) = lapse_status_of(&txn, &model, today_manila).await.map_err(internal_error)?
+        && lapse.is_lapsed
+    {
+        let valid_until = lapse.valid_until.map(|d| format!(" (valid until {d})")).unwrap_or_default();
+        return Err((
+            StatusCode::CONFLICT,
+            format!(
+                "The approval code of verification {}{} has lapsed; it needs a lapse override to be reconciled",
+                verification_id, valid_until
+            ),
+        ));
+    }
 
     let mut active_model: verification::ActiveModel = model.into();
 
@@ -239,7 +270,8 @@ pub async fn reconcile_verification(
     active_model.reconciled_by = Set(Some(user.claims.email));
     active_model.reconciliation_date = Set(Some(Utc::now().fixed_offset()));
 
-    let updated = active_model.update(db).await.map_err(internal_error)?;
+    let updated = active_model.update(&txn).await.map_err(internal_error)?;
+    txn.commit().await.map_err(internal_error)?;
 
     let row: DoneVerificationRow = verification::Entity::find()
         .filter(verification::Column::Id.eq(updated.id))
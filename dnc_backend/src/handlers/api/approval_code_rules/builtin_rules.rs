@@ -0,0 +1,425 @@
+use chrono::{Datelike, Duration, NaiveDate};
+use sea_orm::entity::prelude::async_trait;
+use sea_orm::{
+    ColumnTrait, Condition, DatabaseTransaction, DbErr, EntityTrait, JoinType, PaginatorTrait,
+    QueryFilter, QuerySelect, RelationTrait, Select,
+};
+use serde_json::{json, Value as JsonValue};
+
+use crate::business_calendar;
+use crate::entities::{endorsement_counts, verification, verification_tooth_surfaces};
+use crate::verification_state::VerificationStatus;
+use super::engine::*;
+
+/// Every release rule, in the order they are checked.
+pub fn all() -> Vec<Box<dyn ApprovalCodeRule>> {
+    vec![
+        Box::new(EndorsementLimitRule),
+        Box::new(DailyDentistMemberLimitRule),
+        Box::new(SameToothSurfaceRule),
+        Box::new(MinimumIntervalRule),
+        Box::new(MemberAgeRule),
+        Box::new(EnrollmentWaitingPeriodRule),
+    ]
+}
+
+/// Other verifications of the same member that already got an approval code.
+fn released_for_same_member(ctx: &ReleaseContext) -> Condition {
+    Condition::all()
+        .add(verification::Column::Id.ne(ctx.verification.id))
+        .add(verification::Column::MemberId.eq(ctx.verification.member_id))
+        .add(verification::Column::ApprovalCode.is_not_null())
+        .add(verification::Column::StatusId.eq(VerificationStatus::Done.int_code()))
+}
+
+// region: endorsement_limit
+/// The number of service availments must be below the endorsement limit.
+pub struct EndorsementLimitRule;
+
+#[async_trait]
+impl ApprovalCodeRule for EndorsementLimitRule {
+    fn key(&self) -> &'static str {
+        "endorsement_limit"
+    }
+
+    fn description(&self) -> &'static str {
+        "The member has not used up the endorsement's count for this dental service."
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    async fn check(
+        &self,
+        db: &DatabaseTransaction,
+        ctx: &ReleaseContext,
+        _params: &RuleParams,
+    ) -> Result<Option<RuleRejection>, DbErr> {
+        // ---1 if unlimited, no need to check further
+        if ctx.dental_service.is_unlimited == Some(true) {
+            return Ok(None);
+        }
+
+        // ---2 get the limit from endorsement counts. If the record doesn't exist, assume 0.
+        let allowed_count = endorsement_counts::Entity::find()
+            .filter(endorsement_counts::Column::EndorsementId.eq(ctx.member.endorsement_id))
+            .filter(endorsement_counts::Column::DentalServicesId.eq(ctx.verification.dental_service_id))
+            .one(db)
+            .await?
+            .map(|row| row.count)
+            .unwrap_or(0);
+
+        // ---3 count the already released approval codes for this service for this member.
+        let released_count = verification::Entity::find()
+            .filter(released_for_same_member(ctx))
+            .filter(verification::Column::DentalServiceId.eq(ctx.verification.dental_service_id))
+            .count(db)
+            .await? as i32;
+
+        if released_count >= allowed_count {
+            return Ok(Some(RuleRejection::new(
+                REJECT_ENDORSEMENT_LIMIT,
+                "endorsement service limit exceeded for this member",
+            )));
+        }
+
+        Ok(None)
+    }
+}
+// endregion: endorsement_limit
+
+
+// region: daily_dentist_member_limit
+/// Only up to `max_per_day` approval codes can be given to a dentist for a member in one day.
+pub struct DailyDentistMemberLimitRule;
+
+#[async_trait]
+impl ApprovalCodeRule for DailyDentistMemberLimitRule {
+    fn key(&self) -> &'static str {
+        "daily_dentist_member_limit"
+    }
+
+    fn description(&self) -> &'static str {
+        "At most max_per_day approval codes per dentist, member and service date."
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    fn default_params(&self) -> JsonValue {
+        json!({ "max_per_day": 3 })
+    }
+
+    async fn check(
+        &self,
+        db: &DatabaseTransaction,
+        ctx: &ReleaseContext,
+        params: &RuleParams,
+    ) -> Result<Option<RuleRejection>, DbErr> {
+        let max_per_day = params.int("max_per_day").unwrap_or(3);
+
+        let count = verification::Entity::find()
+            .filter(released_for_same_member(ctx))
+            .filter(verification::Column::DentistId.eq(ctx.verification.dentist_id))
+            .filter(verification::Column::DateServicePerformed.eq(ctx.date_service_performed))
+            .count(db)
+            .await?;
+        tracing::info!("DentistId:{} MemberID:{} Date:{} have {} occurrences.",
+            ctx.verification.dentist_id, ctx.verification.member_id, ctx.date_service_performed, count);
+
+        if count as i64 >= max_per_day {
+            return Ok(Some(RuleRejection::new(
+                REJECT_DAILY_LIMIT,
+                "approval code release limit exceeded for this dentist, member, and service date",
+            )));
+        }
+
+        Ok(None)
+    }
+}
+// endregion: daily_dentist_member_limit
+
+
+// region: same_tooth_surface
+/// Disallows the same service on the same day, tooth and surface if dentists are different.
+/// The same dentist is allowed if the tooth service types are different.
+pub struct SameToothSurfaceRule;
+
+impl SameToothSurfaceRule {
+    fn with_same_surfaces(query: Select<verification::Entity>, tooth_surface_ids: &[i32]) -> Select<verification::Entity> {
+        if tooth_surface_ids.is_empty() {
+            query
+                .join(JoinType::LeftJoin, verification::Relation::VerificationToothSurfaces.def())
+                .filter(verification_tooth_surfaces::Column::Id.is_null())
+        } else {
+            query
+                .join(JoinType::InnerJoin, verification::Relation::VerificationToothSurfaces.def())
+                .filter(verification_tooth_surfaces::Column::ToothSurfaceId.is_in(tooth_surface_ids.to_vec()))
+        }
+    }
+}
+
+#[async_trait]
+impl ApprovalCodeRule for SameToothSurfaceRule {
+    fn key(&self) -> &'static str {
+        "same_tooth_surface"
+    }
+
+    fn description(&self) -> &'static str {
+        "No repeat of the same service on the same day, tooth and surface."
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        true
+    }
+
+    async fn check(
+        &self,
+        db: &DatabaseTransaction,
+        ctx: &ReleaseContext,
+        _params: &RuleParams,
+    ) -> Result<Option<RuleRejection>, DbErr> {
+        // ---1 same member, same service, SAME DATE service performed, and an approval code is present.
+        let mut base_condition = released_for_same_member(ctx)
+            .add(verification::Column::DentalServiceId.eq(ctx.verification.dental_service_id))
+            .add(verification::Column::DateServicePerformed.eq(ctx.date_service_performed));
+
+        // ---1a if a tooth_id is present, add the condition of same tooth-id,
+        base_condition = match ctx.tooth_id {
+            Some(ref v) => base_condition.add(verification::Column::ToothId.eq(v.clone())),
+            None => base_condition.add(verification::Column::ToothId.is_null()),
+        };
+
+        // ---2 Find if another dentist did the other verifications.
+        let other_dentist_query = verification::Entity::find()
+            .filter(base_condition.clone())
+            .filter(verification::Column::DentistId.ne(ctx.verification.dentist_id));
+        let other_dentist_conflict = Self::with_same_surfaces(other_dentist_query, &ctx.tooth_surface_ids)
+            .one(db)
+            .await?;
+
+        if other_dentist_conflict.is_some() {
+            return Ok(Some(RuleRejection::new(
+                REJECT_SAME_TOOTH_OTHER_DENTIST,
+                "same dental service on same day already has an approved verification on this tooth and surface by another dentist",
+            )));
+        }
+
+        // ---3 Narrow to same dentist and same service type.
+        let mut same_dentist_condition = base_condition
+            .add(verification::Column::DentistId.eq(ctx.verification.dentist_id));
+        same_dentist_condition = match ctx.tooth_service_type_id {
+            Some(v) => same_dentist_condition.add(verification::Column::ToothServiceTypeId.eq(v)),
+            None => same_dentist_condition.add(verification::Column::ToothServiceTypeId.is_null()),
+        };
+        let same_dentist_query = verification::Entity::find().filter(same_dentist_condition);
+        let same_dentist_conflict = Self::with_same_surfaces(same_dentist_query, &ctx.tooth_surface_ids)
+            .one(db)
+            .await?;
+
+        if same_dentist_conflict.is_some() {
+            return Ok(Some(RuleRejection::new(
+                REJECT_SAME_TOOTH_SAME_DENTIST,
+                "same dental service already has an approved verification on this tooth, surface, and service type for this dentist",
+            )));
+        }
+
+        Ok(None)
+    }
+}
+// endregion: same_tooth_surface
+
+
+// region: minimum_interval
+/// A member must wait `days` between two availments of the same service (e.g. cleanings).
+pub struct MinimumIntervalRule;
+
+#[async_trait]
+impl ApprovalCodeRule for MinimumIntervalRule {
+    fn key(&self) -> &'static str {
+        "minimum_interval"
+    }
+
+    fn description(&self) -> &'static str {
+        "At least `days` days between two availments of the same service by the member."
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    fn default_params(&self) -> JsonValue {
+        json!({ "days": 180, "dental_service_ids": [], "services": [] })
+    }
+
+    async fn check(
+        &self,
+        db: &DatabaseTransaction,
+        ctx: &ReleaseContext,
+        params: &RuleParams,
+    ) -> Result<Option<RuleRejection>, DbErr> {
+        let Some(params) = params.for_dental_service(ctx.verification.dental_service_id) else {
+            return Ok(None);
+        };
+        let days = params.int("days").unwrap_or(0);
+        if days <= 0 {
+            return Ok(None);
+        }
+
+        // Exclusive window around the service date, so day `days` itself is allowed.
+        let window_start = ctx.date_service_performed - Duration::days(days - 1);
+        let window_end = ctx.date_service_performed + Duration::days(days - 1);
+
+        let conflict = verification::Entity::find()
+            .filter(released_for_same_member(ctx))
+            .filter(verification::Column::DentalServiceId.eq(ctx.verification.dental_service_id))
+            .filter(verification::Column::DateServicePerformed.between(window_start, window_end))
+            .one(db)
+            .await?;
+
+        match conflict.and_then(|v| v.date_service_performed) {
+            Some(previous) => Ok(Some(RuleRejection::new(
+                REJECT_MIN_INTERVAL,
+                format!(
+                    "member already availed of this service on {previous}; at least {days} days must pass between availments"
+                ),
+            ))),
+            None => Ok(None),
+        }
+    }
+}
+// endregion: minimum_interval
+
+
+// region: member_age
+/// Limits a service to members within `min_age`..=`max_age` on the service date. Services with
+/// their own limits are listed in `services` (see `RuleParams::for_dental_service`).
+pub struct MemberAgeRule;
+
+#[async_trait]
+impl ApprovalCodeRule for MemberAgeRule {
+    fn key(&self) -> &'static str {
+        "member_age"
+    }
+
+    fn description(&self) -> &'static str {
+        "The member's age on the service date is within min_age and max_age."
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    fn default_params(&self) -> JsonValue {
+        json!({ "min_age": null, "max_age": null, "dental_service_ids": [], "services": [] })
+    }
+
+    async fn check(
+        &self,
+        _db: &DatabaseTransaction,
+        ctx: &ReleaseContext,
+        params: &RuleParams,
+    ) -> Result<Option<RuleRejection>, DbErr> {
+        let Some(params) = params.for_dental_service(ctx.verification.dental_service_id) else {
+            return Ok(None);
+        };
+        let min_age = params.int("min_age");
+        let max_age = params.int("max_age");
+        if min_age.is_none() && max_age.is_none() {
+            return Ok(None);
+        }
+
+        let Some(birth_date) = ctx.member.birth_date else {
+            return Ok(Some(RuleRejection::new(
+                REJECT_MEMBER_AGE,
+                "member has no birth date on file; this service has an age limit",
+            )));
+        };
+
+        let age = age_on(birth_date, ctx.date_service_performed);
+        let too_young = min_age.is_some_and(|min| age < min);
+        let too_old = max_age.is_some_and(|max| age > max);
+        if too_young || too_old {
+            return Ok(Some(RuleRejection::new(
+                REJECT_MEMBER_AGE,
+                format!("member is {age} years old on the service date, outside the allowed age for this service"),
+            )));
+        }
+
+        Ok(None)
+    }
+}
+/// Age in whole years on `on`; the year goes up on the birthday itself, and on 1 March for
+/// someone born on 29 February when the year has none.
+pub fn age_on(birth_date: NaiveDate, on: NaiveDate) -> i64 {
+    let mut age = (on.year() - birth_date.year()) as i64;
+    if (on.month(), on.day()) < (birth_date.month(), birth_date.day()) {
+        age -= 1;
+    }
+    age
+}
+// endregion: member_age
+
+
+// region: enrollment_waiting_period
+/// A service becomes available `days` days after the member was enrolled: the upload date
+/// of the member's master list, or the endorsement start date if there is none.
+pub struct EnrollmentWaitingPeriodRule;
+
+#[async_trait]
+impl ApprovalCodeRule for EnrollmentWaitingPeriodRule {
+    fn key(&self) -> &'static str {
+        "enrollment_waiting_period"
+    }
+
+    fn description(&self) -> &'static str {
+        "The service date is at least `days` days after the member's enrollment."
+    }
+
+    fn enabled_by_default(&self) -> bool {
+        false
+    }
+
+    fn default_params(&self) -> JsonValue {
+        json!({ "days": 30, "dental_service_ids": [], "services": [] })
+    }
+
+    async fn check(
+        &self,
+        _db: &DatabaseTransaction,
+        ctx: &ReleaseContext,
+        params: &RuleParams,
+    ) -> Result<Option<RuleRejection>, DbErr> {
+        let Some(params) = params.for_dental_service(ctx.verification.dental_service_id) else {
+            return Ok(None);
+        };
+        let days = params.int("days").unwrap_or(0);
+        if days <= 0 {
+            return Ok(None);
+        }
+
+        let enrolled_on = ctx
+            .master_list
+            .as_ref()
+            .and_then(|ml| ml.upload_date)
+            .map(|d| business_calendar::date_of(&d))
+            .unwrap_or(ctx.endorsement.date_start);
+        let available_on = available_on(enrolled_on, days);
+
+        if ctx.date_service_performed < available_on {
+            return Ok(Some(RuleRejection::new(
+                REJECT_ENROLLMENT_WAITING_PERIOD,
+                format!("member was enrolled on {enrolled_on}; this service is available from {available_on}"),
+            )));
+        }
+
+        Ok(None)
+    }
+}
+/// The first service date allowed for a member enrolled on `enrolled_on`.
+pub fn available_on(enrolled_on: NaiveDate, days: i64) -> NaiveDate {
+    enrolled_on + Duration::days(days)
+}
+// endregion: enrollment_waiting_period
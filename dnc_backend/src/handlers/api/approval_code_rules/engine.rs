@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+
+use sea_orm::entity::prelude::async_trait;
+use sea_orm::{ColumnTrait, Condition, ConnectionTrait, DatabaseTransaction, DbErr, EntityTrait, QueryFilter, Statement};
+use sea_orm::prelude::Date;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use crate::entities::{
+    approval_code_rule_config, dental_service, endorsement, master_list, master_list_member,
+    verification,
+};
+use super::builtin_rules;
+
+// region: Reject Codes
+// reject_code values returned by POST /api/verifications/{id}/approval_code.
+// 0 means the code was released.
+pub const REJECT_DAILY_LIMIT: i32 = 3;
+pub const REJECT_SAME_TOOTH_OTHER_DENTIST: i32 = 4;
+pub const REJECT_SAME_TOOTH_SAME_DENTIST: i32 = 5;
+pub const REJECT_ENDORSEMENT_LIMIT: i32 = 6;
+pub const REJECT_ALREADY_RELEASED: i32 = 7;
+pub const REJECT_MIN_INTERVAL: i32 = 8;
+pub const REJECT_MEMBER_AGE: i32 = 9;
+pub const REJECT_ENROLLMENT_WAITING_PERIOD: i32 = 10;
+// endregion: Reject Codes
+
+
+// region: Release Context
+
+/// What the rules look at: the verification being released, its member, endorsement and
+/// dental service, and the details submitted with the release request.
+#[derive(Debug, Clone)]
+pub struct ReleaseContext {
+    pub verification: verification::Model,
+    pub member: master_list_member::Model,
+    pub endorsement: endorsement::Model,
+    pub dental_service: dental_service::Model,
+    pub master_list: Option<master_list::Model>,
+    pub date_service_performed: Date,
+    pub tooth_id: Option<String>,
+    /// sorted and deduplicated
+    pub tooth_surface_ids: Vec<i32>,
+    pub tooth_service_type_id: Option<i32>,
+}
+
+impl ReleaseContext {
+    pub async fn load<C: ConnectionTrait>(
+        db: &C,
+        verification_id: i32,
+        date_service_performed: Date,
+        tooth_id: Option<String>,
+        tooth_surface_ids: Vec<i32>,
+        tooth_service_type_id: Option<i32>,
+    ) -> Result<Self, DbErr> {
+        let verification = verification::Entity::find_by_id(verification_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| DbErr::Custom(format!("Verification {} not found", verification_id)))?;
+
+        let dental_service = dental_service::Entity::find_by_id(verification.dental_service_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                DbErr::Custom(format!("Dental service {} not found", verification.dental_service_id))
+            })?;
+
+        let member = master_list_member::Entity::find_by_id(verification.member_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| {
+                DbErr::Custom(format!("Master list member {} not found", verification.member_id))
+            })?;
+
+        let endorsement = endorsement::Entity::find_by_id(member.endorsement_id)
+            .one(db)
+            .await?
+            .ok_or_else(|| DbErr::Custom(format!("Endorsement {} not found", member.endorsement_id)))?;
+
+        let master_list = match member.master_list_id {
+            Some(id) => master_list::Entity::find_by_id(id).one(db).await?,
+            None => None,
+        };
+
+        let mut tooth_surface_ids = tooth_surface_ids;
+        tooth_surface_ids.sort();
+        tooth_surface_ids.dedup();
+
+        Ok(Self {
+            verification,
+            member,
+            endorsement,
+            dental_service,
+            master_list,
+            date_service_performed,
+            tooth_id,
+            tooth_surface_ids,
+            tooth_service_type_id,
+        })
+    }
+}
+// endregion: Release Context
+
+
+// region: Release Lock
+
+/// First key of the advisory lock taken while releasing a member's approval codes; the second
+/// key is the member id.
+const RELEASE_LOCK_NAMESPACE: i32 = 7001;
+
+/// Serializes approval code releases for a member until `txn` ends.
+///
+/// The rules count the member's released codes, so two releases checked at the same time could
+/// both pass and together go over a limit. The lock is per member rather than per member and
+/// service because the daily limit counts codes across services. Take it before loading the
+/// `ReleaseContext`, and run the rules and save the code in the same transaction.
+pub async fn lock_member_for_release(txn: &DatabaseTransaction, member_id: i32) -> Result<(), DbErr> {
+    txn.execute_raw(Statement::from_sql_and_values(
+        txn.get_database_backend(),
+        "SELECT pg_advisory_xact_lock($1, $2)",
+        [RELEASE_LOCK_NAMESPACE.into(), member_id.into()],
+    ))
+    .await?;
+    Ok(())
+}
+// endregion: Release Lock
+
+
+// region: Rule Trait
+
+/// Why a rule refused to release the code.
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleRejection {
+    pub code: i32,
+    pub message: String,
+}
+
+impl RuleRejection {
+    pub fn new(code: i32, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+/// Rule settings from approval_code_rule_config.params, merged over the rule's defaults.
+#[derive(Debug, Clone, Default)]
+pub struct RuleParams(pub JsonValue);
+
+impl RuleParams {
+    pub fn int(&self, name: &str) -> Option<i64> {
+        self.0.get(name).and_then(JsonValue::as_i64)
+    }
+
+    pub fn int_list(&self, name: &str) -> Vec<i32> {
+        self.0
+            .get(name)
+            .and_then(JsonValue::as_array)
+            .map(|values| {
+                values
+                    .iter()
+                    .filter_map(JsonValue::as_i64)
+                    .map(|v| v as i32)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The settings for a dental service; `None` if the rule does not cover it.
+    ///
+    /// An entry of `services` that lists the service in its `dental_service_ids` is merged over
+    /// the top-level settings, e.g. `{"services": [{"dental_service_ids": [5], "max_age": 12}]}`
+    /// gives service 5 its own age limit. A service no entry lists gets the top-level settings,
+    /// if the top-level `dental_service_ids` is empty (every service) or lists it.
+    pub fn for_dental_service(&self, id: i32) -> Option<RuleParams> {
+        let entry = self
+            .0
+            .get("services")
+            .and_then(JsonValue::as_array)
+            .and_then(|entries| entries.iter().find(|entry| service_ids(entry).contains(&id)));
+
+        match entry {
+            Some(entry) => {
+                let mut merged = Self::merged_over(self.0.clone(), Some(entry));
+                if let JsonValue::Object(map) = &mut merged.0 {
+                    map.remove("services");
+                }
+                Some(merged)
+            }
+            None => {
+                let ids = self.int_list("dental_service_ids");
+                (ids.is_empty() || ids.contains(&id)).then(|| self.clone())
+            }
+        }
+    }
+
+    /// `overrides`' keys replace the ones in `defaults`; params that are not objects are ignored.
+    pub fn merged_over(defaults: JsonValue, overrides: Option<&JsonValue>) -> Self {
+        let mut merged = defaults;
+        if let (JsonValue::Object(target), Some(JsonValue::Object(source))) = (&mut merged, overrides) {
+            for (k, v) in source {
+                target.insert(k.clone(), v.clone());
+            }
+        }
+        Self(merged)
+    }
+}
+
+fn service_ids(params: &JsonValue) -> Vec<i32> {
+    RuleParams(params.clone()).int_list("dental_service_ids")
+}
+
+/// One approval code release rule. To add a rule, implement this trait and list it in
+/// `builtin_rules::all()`; it can then be switched on and tuned per HMO or endorsement
+/// through approval_code_rule_config without a code change.
+#[async_trait]
+pub trait ApprovalCodeRule: Send + Sync {
+    /// Stored in approval_code_rule_config.rule_key.
+    fn key(&self) -> &'static str;
+
+    fn description(&self) -> &'static str;
+
+    /// Whether the rule runs when no config row applies.
+    fn enabled_by_default(&self) -> bool;
+
+    fn default_params(&self) -> JsonValue {
+        JsonValue::Object(Default::default())
+    }
+
+    /// Returns `Some` if the approval code must not be released. Runs inside the release
+    /// transaction, after `lock_member_for_release`.
+    async fn check(
+        &self,
+        db: &DatabaseTransaction,
+        ctx: &ReleaseContext,
+        params: &RuleParams,
+    ) -> Result<Option<RuleRejection>, DbErr>;
+}
+// endregion: Rule Trait
+
+
+// region: Evaluation
+
+/// Where a rule's effective settings came from, from least to most specific.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleConfigSource {
+    Default,
+    Global,
+    Hmo,
+    Endorsement,
+}
+
+impl RuleConfigSource {
+    /// The scope of a config row.
+    pub fn of_scope(hmo_id: Option<i32>, endorsement_id: Option<i32>) -> Self {
+        match (hmo_id, endorsement_id) {
+            (_, Some(_)) => RuleConfigSource::Endorsement,
+            (Some(_), None) => RuleConfigSource::Hmo,
+            (None, None) => RuleConfigSource::Global,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleOutcome {
+    pub rule_key: &'static str,
+    pub description: &'static str,
+    pub enabled: bool,
+    pub config_source: RuleConfigSource,
+    pub params: JsonValue,
+    /// None if the rule is disabled or was not reached.
+    pub passed: Option<bool>,
+    pub reject_code: Option<i32>,
+    pub message: Option<String>,
+}
+
+impl RuleOutcome {
+    pub fn rejection(&self) -> Option<RuleRejection> {
+        match (self.passed, self.reject_code, &self.message) {
+            (Some(false), Some(code), Some(message)) => Some(RuleRejection::new(code, message.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// Picks, per rule key, the most specific config row that applies to the endorsement.
+async fn load_effective_configs(
+    db: &DatabaseTransaction,
+    endorsement: &endorsement::Model,
+) -> Result<HashMap<String, (RuleConfigSource, approval_code_rule_config::Model)>, DbErr> {
+    let rows = approval_code_rule_config::Entity::find()
+        .filter(
+            Condition::any()
+                .add(approval_code_rule_config::Column::EndorsementId.eq(endorsement.id))
+                .add(
+                    Condition::all()
+                        .add(approval_code_rule_config::Column::HmoId.eq(endorsement.hmo_id))
+                        .add(approval_code_rule_config::Column::EndorsementId.is_null()),
+                )
+                .add(
+                    Condition::all()
+                        .add(approval_code_rule_config::Column::HmoId.is_null())
+                        .add(approval_code_rule_config::Column::EndorsementId.is_null()),
+                ),
+        )
+        .all(db)
+        .await?;
+
+    let mut effective: HashMap<String, (RuleConfigSource, approval_code_rule_config::Model)> = HashMap::new();
+    for row in rows {
+        let source = RuleConfigSource::of_scope(row.hmo_id, row.endorsement_id);
+        let replace = match effective.get(&row.rule_key) {
+            Some((existing, _)) => source > *existing,
+            None => true,
+        };
+        if replace {
+            effective.insert(row.rule_key.clone(), (source, row));
+        }
+    }
+
+    Ok(effective)
+}
+
+/// Runs the release rules in order. With `stop_at_first_rejection`, rules after the first
+/// rejection are reported as not reached (used when actually releasing a code); without it
+/// every enabled rule runs (used by the dry run).
+pub async fn evaluate_release_rules(
+    db: &DatabaseTransaction,
+    ctx: &ReleaseContext,
+    stop_at_first_rejection: bool,
+) -> Result<Vec<RuleOutcome>, DbErr> {
+    let configs = load_effective_configs(db, &ctx.endorsement).await?;
+    let mut outcomes = Vec::new();
+    let mut rejected = false;
+
+    for rule in builtin_rules::all() {
+        let (config_source, enabled, params) = match configs.get(rule.key()) {
+            Some((source, row)) => (
+                *source,
+                row.is_enabled,
+                RuleParams::merged_over(rule.default_params(), row.params.as_ref()),
+            ),
+            None => (
+                RuleConfigSource::Default,
+                rule.enabled_by_default(),
+                RuleParams(rule.default_params()),
+            ),
+        };
+
+        let mut outcome = RuleOutcome {
+            rule_key: rule.key(),
+            description: rule.description(),
+            enabled,
+            config_source,
+            params: params.0.clone(),
+            passed: None,
+            reject_code: None,
+            message: None,
+        };
+
+        if enabled && !(rejected && stop_at_first_rejection) {
+            match rule.check(db, ctx, &params).await? {
+                Some(rejection) => {
+                    rejected = true;
+                    outcome.passed = Some(false);
+                    outcome.reject_code = Some(rejection.code);
+                    outcome.message = Some(rejection.message);
+                }
+                None => outcome.passed = Some(true),
+            }
+        }
+
+        outcomes.push(outcome);
+    }
+
+    Ok(outcomes)
+}
+
+/// The first rejection, if any rule refused.
+pub fn first_rejection(outcomes: &[RuleOutcome]) -> Option<RuleRejection> {
+    outcomes.iter().find_map(RuleOutcome::rejection)
+}
+
+/// Rule keys accepted in approval_code_rule_config.rule_key.
+pub fn known_rule_keys() -> Vec<&'static str> {
+    builtin_rules::all().iter().map(|r| r.key()).collect()
+}
+// endregion: Evaluation
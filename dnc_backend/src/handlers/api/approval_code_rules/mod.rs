@@ -0,0 +1,4 @@
+pub mod engine;
+pub mod builtin_rules;
+pub mod rule_configs;
+pub mod validity;
@@ -0,0 +1,254 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, ModelTrait, QueryFilter, QueryOrder, Set, SqlErr};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tracing::instrument;
+
+use crate::AppState;
+use crate::entities::approval_code_rule_config;
+use crate::handlers::AuthUser;
+use super::builtin_rules;
+use super::engine::known_rule_keys;
+
+fn internal_error(err: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+const DUPLICATE_SCOPE: &str = "A config for this rule and scope already exists; PATCH it instead";
+
+// region: get_approval_code_rules
+#[derive(Debug, Serialize)]
+pub struct ApprovalCodeRuleInfo {
+    pub rule_key: &'static str,
+    pub description: &'static str,
+    pub enabled_by_default: bool,
+    pub default_params: JsonValue,
+}
+
+/// GET /api/approval_code_rules
+/// The release rules this build knows about, in the order they are checked.
+pub async fn get_approval_code_rules() -> Json<Vec<ApprovalCodeRuleInfo>> {
+    Json(
+        builtin_rules::all()
+            .iter()
+            .map(|rule| ApprovalCodeRuleInfo {
+                rule_key: rule.key(),
+                description: rule.description(),
+                enabled_by_default: rule.enabled_by_default(),
+                default_params: rule.default_params(),
+            })
+            .collect(),
+    )
+}
+// endregion: get_approval_code_rules
+
+
+// region: get_approval_code_rule_configs
+#[derive(Debug, Deserialize)]
+pub struct ApprovalCodeRuleConfigQuery {
+    pub rule_key: Option<String>,
+    pub hmo_id: Option<i32>,
+    pub endorsement_id: Option<i32>,
+}
+
+/// GET /api/approval_code_rules/configs?rule_key=&hmo_id=&endorsement_id=
+#[instrument(skip(state), err(Debug))]
+pub async fn get_approval_code_rule_configs(
+    State(state): State<AppState>,
+    Query(params): Query<ApprovalCodeRuleConfigQuery>,
+) -> Result<Json<Vec<approval_code_rule_config::Model>>, (StatusCode, String)> {
+    let mut query = approval_code_rule_config::Entity::find();
+    if let Some(rule_key) = params.rule_key {
+        query = query.filter(approval_code_rule_config::Column::RuleKey.eq(rule_key));
+    }
+    if let Some(hmo_id) = params.hmo_id {
+        query = query.filter(approval_code_rule_config::Column::HmoId.eq(hmo_id));
+    }
+    if let Some(endorsement_id) = params.endorsement_id {
+        query = query.filter(approval_code_rule_config::Column::EndorsementId.eq(endorsement_id));
+    }
+
+    let rows = query
+        .order_by_asc(approval_code_rule_config::Column::RuleKey)
+        .order_by_asc(approval_code_rule_config::Column::Id)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(rows))
+}
+// endregion: get_approval_code_rule_configs
+
+
+// region: post_approval_code_rule_config
+#[derive(Debug, Deserialize)]
+pub struct CreateApprovalCodeRuleConfigRequest {
+    pub rule_key: String,
+    pub hmo_id: Option<i32>,
+    pub endorsement_id: Option<i32>,
+    pub is_enabled: bool,
+    pub params: Option<JsonValue>,
+}
+
+/// POST /api/approval_code_rules/configs
+/// Leave out hmo_id and endorsement_id for a global setting; give one of them to override
+/// the rule for that HMO or endorsement only. There is one config per rule and scope; settings
+/// for particular services go in the params' `services` list.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_approval_code_rule_config(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateApprovalCodeRuleConfigRequest>,
+) -> Result<(StatusCode, Json<approval_code_rule_config::Model>), (StatusCode, String)> {
+    // ---1 the rule must exist
+    if !known_rule_keys().contains(&payload.rule_key.as_str()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unknown rule_key '{}'", payload.rule_key)));
+    }
+
+    // ---2 one scope at a time
+    if payload.hmo_id.is_some() && payload.endorsement_id.is_some() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Give either hmo_id or endorsement_id, not both".to_string(),
+        ));
+    }
+    validate_params(payload.params.as_ref())?;
+
+    // ---3 only one row per rule and scope
+    let mut existing = approval_code_rule_config::Entity::find()
+        .filter(approval_code_rule_config::Column::RuleKey.eq(payload.rule_key.clone()));
+    existing = match payload.hmo_id {
+        Some(v) => existing.filter(approval_code_rule_config::Column::HmoId.eq(v)),
+        None => existing.filter(approval_code_rule_config::Column::HmoId.is_null()),
+    };
+    existing = match payload.endorsement_id {
+        Some(v) => existing.filter(approval_code_rule_config::Column::EndorsementId.eq(v)),
+        None => existing.filter(approval_code_rule_config::Column::EndorsementId.is_null()),
+    };
+    if existing.one(&state.db).await.map_err(internal_error)?.is_some() {
+        return Err((StatusCode::CONFLICT, DUPLICATE_SCOPE.to_string()));
+    }
+
+    // ---4 save
+    let inserted = approval_code_rule_config::ActiveModel {
+        rule_key: Set(payload.rule_key),
+        hmo_id: Set(payload.hmo_id),
+        endorsement_id: Set(payload.endorsement_id),
+        is_enabled: Set(payload.is_enabled),
+        params: Set(payload.params),
+        last_modified_by: Set(auth_user.claims.email),
+        last_modified_on: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    }
+        .insert(&state.db)
+        .await
+        // a config saved for the same scope since the check above
+        .map_err(|err| match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => (StatusCode::CONFLICT, DUPLICATE_SCOPE.to_string()),
+            _ => internal_error(err),
+        })?;
+
+    Ok((StatusCode::CREATED, Json(inserted)))
+}
+// endregion: post_approval_code_rule_config
+
+
+// region: patch_approval_code_rule_config
+#[derive(Debug, Deserialize)]
+pub struct PatchApprovalCodeRuleConfigRequest {
+    pub is_enabled: Option<bool>,
+    #[serde(default, with = "serde_with::rust::double_option")]
+    pub params: Option<Option<JsonValue>>,
+}
+
+/// PATCH /api/approval_code_rules/configs/{id}
+#[instrument(skip(state), err(Debug))]
+pub async fn patch_approval_code_rule_config(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<PatchApprovalCodeRuleConfigRequest>,
+) -> Result<Json<approval_code_rule_config::Model>, (StatusCode, String)> {
+    let row = approval_code_rule_config::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Approval code rule config not found".to_string()))?;
+
+    let mut am: approval_code_rule_config::ActiveModel = row.into();
+    if let Some(v) = payload.is_enabled {
+        am.is_enabled = Set(v);
+    }
+    if let Some(v) = payload.params {
+        validate_params(v.as_ref())?;
+        am.params = Set(v);
+    }
+    am.last_modified_by = Set(auth_user.claims.email);
+    am.last_modified_on = Set(Utc::now().fixed_offset());
+
+    let updated = am.update(&state.db).await.map_err(internal_error)?;
+    Ok(Json(updated))
+}
+// endregion: patch_approval_code_rule_config
+
+
+// region: delete_approval_code_rule_config
+/// DELETE /api/approval_code_rules/configs/{id}
+/// The rule falls back to the next less specific config, or its defaults.
+#[instrument(skip(state), err(Debug))]
+pub async fn delete_approval_code_rule_config(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let row = approval_code_rule_config::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Approval code rule config not found".to_string()))?;
+
+    row.delete(&state.db).await.map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: delete_approval_code_rule_config
+
+
+/// params must be an object. Its optional `services` must be a list of objects that each name
+/// their services in `dental_service_ids`, with no service in two entries.
+fn validate_params(params: Option<&JsonValue>) -> Result<(), (StatusCode, String)> {
+    let bad_request = |message: &str| Err((StatusCode::BAD_REQUEST, message.to_string()));
+    let services = match params {
+        None => return Ok(()),
+        Some(JsonValue::Object(map)) => match map.get("services") {
+            None | Some(JsonValue::Null) => return Ok(()),
+            Some(JsonValue::Array(entries)) => entries,
+            Some(_) => return bad_request("params.services must be a list"),
+        },
+        Some(_) => return bad_request("params must be a JSON object"),
+    };
+
+    let mut seen: Vec<i64> = Vec::new();
+    for entry in services {
+        let ids = match entry.get("dental_service_ids").and_then(JsonValue::as_array) {
+            Some(ids) if entry.is_object() && !ids.is_empty() => ids,
+            _ => return bad_request("each entry of params.services must list its dental_service_ids"),
+        };
+        for id in ids {
+            let Some(id) = id.as_i64() else {
+                return bad_request("dental_service_ids must be numbers");
+            };
+            if seen.contains(&id) {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("dental service {} is in more than one entry of params.services", id),
+                ));
+            }
+            seen.push(id);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, ModelTrait, QueryFilter, QueryOrder, QuerySelect, Set, SqlErr,
+    TransactionTrait,
+};
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::AppState;
+use crate::business_calendar;
+use crate::entities::{approval_code_validity, verification};
+use crate::handlers::AuthUser;
+use crate::jobs::approval_code_validity::lapse_status_of;
+
+fn internal_error(err: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+/// A window for the same scope saved by a concurrent PUT is a conflict rather than a server
+/// error.
+fn save_error(err: DbErr) -> (StatusCode, String) {
+    match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => (
+            StatusCode::CONFLICT,
+            "A validity window for this scope was saved at the same time; try again".to_string(),
+        ),
+        _ => internal_error(err),
+    }
+}
+
+// region: get_approval_code_validity
+/// GET /api/approval_code_rules/validity
+/// The validity windows of released approval codes, global ones first.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_approval_code_validity(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<approval_code_validity::Model>>, (StatusCode, String)> {
+    let rows = approval_code_validity::Entity::find()
+        .order_by_asc(approval_code_validity::Column::HmoId)
+        .order_by_asc(approval_code_validity::Column::DentalServiceTypeId)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(rows))
+}
+// endregion: get_approval_code_validity
+
+
+// region: put_approval_code_validity
+#[derive(Debug, Deserialize)]
+pub struct PutApprovalCodeValidityRequest {
+    pub hmo_id: Option<i32>,
+    pub dental_service_type_id: Option<i32>,
+    pub validity_days: i32,
+}
+
+/// PUT /api/approval_code_rules/validity
+/// Sets the window for an HMO, a dental service type, both, or (neither) everyone. Codes already
+/// marked lapsed stay lapsed.
+#[instrument(skip(state), err(Debug))]
+pub async fn put_approval_code_validity(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<PutApprovalCodeValidityRequest>,
+) -> Result<Json<approval_code_validity::Model>, (StatusCode, String)> {
+    if payload.validity_days <= 0 {
+        return Err((StatusCode::BAD_REQUEST, "validity_days must be at least 1".to_string()));
+    }
+
+    let mut existing = approval_code_validity::Entity::find();
+    existing = match payload.hmo_id {
+        Some(v) => existing.filter(approval_code_validity::Column::HmoId.eq(v)),
+        None => existing.filter(approval_code_validity::Column::HmoId.is_null()),
+    };
+    existing = match payload.dental_service_type_id {
+        Some(v) => existing.filter(approval_code_validity::Column::DentalServiceTypeId.eq(v)),
+        None => existing.filter(approval_code_validity::Column::DentalServiceTypeId.is_null()),
+    };
+
+    let saved = match existing.one(&state.db).await.map_err(internal_error)? {
+        Some(row) => {
+            let mut am: approval_code_validity::ActiveModel = row.into();
+            am.validity_days = Set(payload.validity_days);
+            am.last_modified_by = Set(auth_user.claims.email);
+            am.last_modified_on = Set(Utc::now().fixed_offset());
+            am.update(&state.db).await
+        }
+        None => {
+            approval_code_validity::ActiveModel {
+                hmo_id: Set(payload.hmo_id),
+                dental_service_type_id: Set(payload.dental_service_type_id),
+                validity_days: Set(payload.validity_days),
+                last_modified_by: Set(auth_user.claims.email),
+                last_modified_on: Set(Utc::now().fixed_offset()),
+                ..Default::default()
+            }
+                .insert(&state.db)
+                .await
+        }
+    }
+        .map_err(save_error)?;
+
+    Ok(Json(saved))
+}
+// endregion: put_approval_code_validity
+
+
+// region: delete_approval_code_validity
+/// DELETE /api/approval_code_rules/validity/{id}
+/// Codes fall back to the next less specific window, or do not lapse.
+#[instrument(skip(state), err(Debug))]
+pub async fn delete_approval_code_validity(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let row = approval_code_validity::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Approval code validity window not found".to_string()))?;
+
+    row.delete(&state.db).await.map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: delete_approval_code_validity
+
+
+// region: post_lapse_override
+#[derive(Debug, Deserialize)]
+pub struct LapseOverrideRequest {
+    pub reason: String,
+}
+
+/// POST /api/verifications/{verification_id}/lapse_override
+/// Allows a lapsed approval code to be reconciled anyway.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_lapse_override(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(verification_id): Path<i32>,
+    Json(payload): Json<LapseOverrideRequest>,
+) -> Result<Json<verification::Model>, (StatusCode, String)> {
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "A reason is required".to_string()));
+    }
+
+    let txn = state.db.begin().await.map_err(internal_error)?;
+    let model = verification::Entity::find_by_id(verification_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, format!("Verification {} not found", verification_id)))?;
+
+    let today_manila = business_calendar::today();
+    let lapse = lapse_status_of(&txn, &model, today_manila)
+        .await
+        .map_err(internal_error)?
+        .ok_or((
+            StatusCode::CONFLICT,
+            format!("Verification {} has no released approval code", verification_id),
+        ))?;
+    if !lapse.is_lapsed {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("The approval code of verification {} has not lapsed", verification_id),
+        ));
+    }
+
+    let mut am: verification::ActiveModel = model.into();
+    am.lapse_override_reason = Set(Some(reason.to_string()));
+    am.lapse_override_by = Set(Some(auth_user.claims.email));
+    am.lapse_override_on = Set(Some(Utc::now().fixed_offset()));
+    let updated = am.update(&txn).await.map_err(internal_error)?;
+    txn.commit().await.map_err(internal_error)?;
+
+    Ok(Json(updated))
+}
+// endregion: post_lapse_override
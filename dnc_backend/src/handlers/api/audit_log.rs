@@ -0,0 +1,81 @@
+use axum::{extract::{Query, State}, http::StatusCode, Json};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::AppState;
+use crate::entities::audit_log;
+use crate::handlers::structs::AuthUser;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditLogQuery {
+    /// Table name of the audited entity, e.g. "verification" or "dentist_payments".
+    pub entity: String,
+    /// Row id; omit to get the latest changes to any row of `entity`.
+    pub id: Option<i32>,
+    pub limit: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditLogRow {
+    pub id: i32,
+    pub occurred_on: sea_orm::prelude::DateTimeWithTimeZone,
+    pub actor: String,
+    pub entity: String,
+    pub entity_id: i32,
+    pub action: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+}
+
+impl From<audit_log::Model> for AuditLogRow {
+    fn from(m: audit_log::Model) -> Self {
+        Self {
+            id: m.id,
+            occurred_on: m.occurred_on,
+            actor: m.actor,
+            entity: m.entity,
+            entity_id: m.entity_id,
+            action: m.action,
+            before: m.before_json,
+            after: m.after_json,
+        }
+    }
+}
+
+/// GET /api/audit?entity=verification&id=123
+/// Returns the change history of one row (or of a whole entity), newest first.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_audit_log(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Query(params): Query<AuditLogQuery>,
+) -> Result<Json<Vec<AuditLogRow>>, StatusCode> {
+    let entity = params.entity.trim();
+    if entity.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let limit = params.limit.unwrap_or(200).clamp(1, 1000);
+
+    let mut query = audit_log::Entity::find()
+        .filter(audit_log::Column::Entity.eq(entity));
+
+    if let Some(id) = params.id {
+        query = query.filter(audit_log::Column::EntityId.eq(id));
+    }
+
+    let rows = query
+        .order_by_desc(audit_log::Column::OccurredOn)
+        .order_by_desc(audit_log::Column::Id)
+        .limit(limit)
+        .all(&state.db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to fetch audit log: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    tracing::info!("user {} fetched {} audit rows for {} {:?}", user.claims.email, rows.len(), entity, params.id);
+
+    Ok(Json(rows.into_iter().map(AuditLogRow::from).collect()))
+}
@@ -0,0 +1,275 @@
+use std::collections::HashSet;
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::Response,
+    Json,
+};
+use chrono::{NaiveDate, Utc};
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::instrument;
+
+use crate::AppState;
+use crate::entities::{bank_disbursement_file, bank_disbursement_layout, bank_disbursement_payment, dentist_payments};
+use crate::handlers::api::billing_payments::dentist_retainer_report::{
+    get_dentist_retainer_payables, map_dentist_retainer_payables_error, DentistRetainerPayablesQuery,
+};
+use crate::handlers::AuthUser;
+use crate::jobs::bank_disbursement::{
+    create_disbursement_files, DisbursementError, DisbursementSummary, LayoutSpec, PayableRetainer,
+};
+
+fn internal_error(err: impl ToString) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+// region: layouts
+#[derive(Debug, Deserialize)]
+pub struct BankLayoutRequest {
+    pub bank_name: String,
+    /// see jobs::bank_disbursement::LayoutSpec
+    pub spec: serde_json::Value,
+    pub active: Option<bool>,
+}
+
+/// GET /api/disbursements/layouts
+#[instrument(skip(state), err(Debug))]
+pub async fn get_bank_disbursement_layouts(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<bank_disbursement_layout::Model>>, (StatusCode, String)> {
+    let layouts = bank_disbursement_layout::Entity::find()
+        .order_by_asc(bank_disbursement_layout::Column::BankName)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(layouts))
+}
+
+/// POST /api/disbursements/layouts
+/// The bank name must be written as it is in the clinics' acct_bank_name (case and extra
+/// spaces do not matter).
+#[instrument(skip(state), err(Debug))]
+pub async fn post_bank_disbursement_layout(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<BankLayoutRequest>,
+) -> Result<(StatusCode, Json<bank_disbursement_layout::Model>), (StatusCode, String)> {
+    let bank_name = checked_bank_name(&payload)?;
+    let layout = bank_disbursement_layout::ActiveModel {
+        bank_name: Set(bank_name),
+        spec: Set(payload.spec),
+        active: Set(payload.active.unwrap_or(true)),
+        last_modified_by: Set(auth_user.claims.email.clone()),
+        last_modified_on: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    }
+        .insert(&state.db)
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, format!("Could not save the layout: {e}")))?;
+    Ok((StatusCode::CREATED, Json(layout)))
+}
+
+/// PUT /api/disbursements/layouts/{id}
+#[instrument(skip(state), err(Debug))]
+pub async fn put_bank_disbursement_layout(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<BankLayoutRequest>,
+) -> Result<Json<bank_disbursement_layout::Model>, (StatusCode, String)> {
+    let bank_name = checked_bank_name(&payload)?;
+    let existing = bank_disbursement_layout::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or(DisbursementError::LayoutNotFound(id))?;
+
+    let mut layout: bank_disbursement_layout::ActiveModel = existing.into();
+    layout.bank_name = Set(bank_name);
+    layout.spec = Set(payload.spec);
+    if let Some(active) = payload.active {
+        layout.active = Set(active);
+    }
+    layout.last_modified_by = Set(auth_user.claims.email.clone());
+    layout.last_modified_on = Set(Utc::now().fixed_offset());
+    let layout = layout
+        .update(&state.db)
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, format!("Could not save the layout: {e}")))?;
+    Ok(Json(layout))
+}
+
+fn checked_bank_name(payload: &BankLayoutRequest) -> Result<String, (StatusCode, String)> {
+    let bank_name = payload.bank_name.trim();
+    if bank_name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "bank_name is required".to_string()));
+    }
+    LayoutSpec::parse(&payload.spec)?;
+    Ok(bank_name.to_string())
+}
+// endregion: layouts
+
+
+// region: post_bank_disbursement
+#[derive(Debug, Deserialize)]
+pub struct CreateDisbursementRequest {
+    pub value_date: NaiveDate,
+    /// the month whose unpaid retainers are disbursed
+    pub retainer_year: Option<i32>,
+    pub retainer_month: Option<u32>,
+    /// the payout batch whose unpaid remittances are disbursed
+    pub payout_batch_id: Option<i32>,
+}
+
+/// POST /api/disbursements
+/// Writes a bulk-credit upload file per bank for a month's unpaid retainers and/or a payout
+/// batch's unpaid remittances. Payees without a usable bank account are returned as exceptions.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_bank_disbursement(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateDisbursementRequest>,
+) -> Result<(StatusCode, Json<DisbursementSummary>), (StatusCode, String)> {
+    let retainers = match (payload.retainer_year, payload.retainer_month) {
+        (Some(year), Some(month)) => payable_retainers(&state, year, month).await?,
+        (None, None) => Vec::new(),
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "retainer_year and retainer_month go together".to_string(),
+            ))
+        }
+    };
+    if payload.retainer_year.is_none() && payload.payout_batch_id.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Give a retainer month, a payout batch, or both".to_string(),
+        ));
+    }
+
+    let summary = create_disbursement_files(
+        &state.db,
+        retainers,
+        payload.payout_batch_id,
+        payload.value_date,
+        &auth_user.claims.email,
+    )
+        .await?;
+    let status = if summary.files.is_empty() { StatusCode::OK } else { StatusCode::CREATED };
+    Ok((status, Json(summary)))
+}
+
+/// The month's retainers from the payables report, less those already marked paid.
+async fn payable_retainers(
+    state: &AppState,
+    year: i32,
+    month: u32,
+) -> Result<Vec<PayableRetainer>, (StatusCode, String)> {
+    let payables = get_dentist_retainer_payables(&state.db, DentistRetainerPayablesQuery { year, month })
+        .await
+        .map_err(map_dentist_retainer_payables_error)?;
+    let paid: HashSet<(i32, i32)> = dentist_payments::Entity::find()
+        .filter(dentist_payments::Column::Year.eq(year))
+        .filter(dentist_payments::Column::Month.eq(month as i32))
+        .filter(dentist_payments::Column::DatePaid.is_not_null())
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|p| (p.dentist_id, p.clinic_id))
+        .collect();
+
+    Ok(payables
+        .rows
+        .into_iter()
+        .filter(|row| !paid.contains(&(row.dentist_id, row.clinic_id)))
+        .filter_map(|row| {
+            Some(PayableRetainer {
+                dentist_id: row.dentist_id,
+                dental_clinic_id: row.clinic_id,
+                year,
+                month,
+                amount: Decimal::from_f64(row.rate)?,
+            })
+        })
+        .collect())
+}
+// endregion: post_bank_disbursement
+
+
+// region: get_bank_disbursements
+/// GET /api/disbursements
+/// The upload files, newest first.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_bank_disbursements(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<bank_disbursement_file::Model>>, (StatusCode, String)> {
+    let files = bank_disbursement_file::Entity::find()
+        .order_by_desc(bank_disbursement_file::Column::CreatedOn)
+        .order_by_desc(bank_disbursement_file::Column::Id)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(files))
+}
+
+#[derive(Debug, Serialize)]
+pub struct DisbursementFileDetail {
+    #[serde(flatten)]
+    pub file: bank_disbursement_file::Model,
+    pub payments: Vec<bank_disbursement_payment::Model>,
+}
+
+/// GET /api/disbursements/{id}
+/// The file with the payments written to it.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_bank_disbursement(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<DisbursementFileDetail>, (StatusCode, String)> {
+    let file = bank_disbursement_file::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or(DisbursementError::FileNotFound(id))?;
+    let payments = bank_disbursement_payment::Entity::find()
+        .filter(bank_disbursement_payment::Column::FileId.eq(id))
+        .order_by_asc(bank_disbursement_payment::Column::Id)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(DisbursementFileDetail { file, payments }))
+}
+
+/// GET /api/disbursements/{id}/download
+#[instrument(skip(state), err(Debug))]
+pub async fn download_bank_disbursement(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Response, (StatusCode, String)> {
+    let file = bank_disbursement_file::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or(DisbursementError::FileNotFound(id))?;
+    let bytes = fs::read(format!("generated_reports/{}", file.file_name))
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, format!("File not found: {}", file.file_name)))?;
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file.file_name),
+        )
+        .body(Body::from(bytes))
+        .map_err(|err| internal_error(format!("Failed to build the file response: {err}")))
+}
+// endregion: get_bank_disbursements
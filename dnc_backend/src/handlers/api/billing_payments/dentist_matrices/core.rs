@@ -21,6 +21,8 @@ use sea_orm::{
      FromQueryResult, Statement,
 };
 use sea_orm::entity::prelude::Date;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
 use std::collections::BTreeMap;
 use axum::extract::{Query, State};
 use axum::Json;
@@ -131,7 +133,8 @@ pub struct DentistHmoServiceAuditSourceRow {
     pub dental_service_type_id: i32,
     pub dental_service_name: String,
 
-    pub service_fee: f64,
+    /// the contract rate in force on date_service_performed
+    pub service_fee: Decimal,
 }
 
 pub async fn get_dentist_hmo_service_audit_source_rows(
@@ -162,7 +165,7 @@ pub async fn get_dentist_hmo_service_audit_source_rows(
             ds.name AS dental_service_name,
             ds.type_id AS dental_service_type_id,
 
-            COALESCE(dcsr.rate, 0)::double precision AS service_fee
+            COALESCE(dcsr.rate, 0) AS service_fee
 
         FROM verification v
 
@@ -187,8 +190,11 @@ pub async fn get_dentist_hmo_service_audit_source_rows(
         LEFT JOIN dentist_contract_service_rates dcsr
             ON dcsr.dentist_contract_id = d.accre_dentist_contract_id
            AND dcsr.service_id = v.dental_service_id
+           AND dcsr.effective_from <= v.date_service_performed
+           AND (dcsr.effective_to IS NULL OR dcsr.effective_to >= v.date_service_performed)
 
         WHERE v.is_reconciled IS TRUE
+          AND v.status_id <> 98 -- voided
           AND v.date_service_performed IS NOT NULL
           AND v.date_service_performed BETWEEN $1::date AND $2::date
           AND d.accre_dentist_contract_id IS NOT NULL
@@ -263,7 +269,8 @@ struct DentistAccumulator {
 #[derive(Debug)]
 struct CellAccumulator {
     hmo_id: i32,
-    services: BTreeMap<i32, ServiceAccumulator>,
+    /// by service and fee: a rate change within the period gives the service a line per rate
+    services: BTreeMap<(i32, Decimal), ServiceAccumulator>,
 }
 
 #[derive(Debug)]
@@ -272,7 +279,7 @@ struct ServiceAccumulator {
     dental_service_name: String,
     dental_service_type_id: i32,
     qty: i64,
-    service_fee: f64,
+    service_fee: Decimal,
 }
 
 #[derive(Debug)]
@@ -281,7 +288,12 @@ struct HmoTotalAccumulator {
     hmo_short_name: String,
     hmo_long_name: String,
     total_qty: i64,
-    total_fee: f64,
+    total_fee: Decimal,
+}
+
+/// Fees are added up as Decimal and reported as numbers.
+fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
 }
 
 pub fn map_report_error(err: DbErr) -> (StatusCode, String) {
@@ -323,7 +335,7 @@ pub fn build_dentist_hmo_service_audit_matrix(
                 hmo_short_name: hmo_row.hmo_short_name.clone(),
                 hmo_long_name: hmo_row.hmo_long_name.clone(),
                 total_qty: 0,
-                total_fee: 0.0,
+                total_fee: Decimal::ZERO,
             },
         );
     }
@@ -366,7 +378,7 @@ pub fn build_dentist_hmo_service_audit_matrix(
                 hmo_short_name: row.hmo_short_name.clone(),
                 hmo_long_name: row.hmo_long_name.clone(),
                 total_qty: 0,
-                total_fee: 0.0,
+                total_fee: Decimal::ZERO,
             });
 
         // 2c. insert the dentist into the dentist_map if it doesn't exist;
@@ -398,7 +410,7 @@ pub fn build_dentist_hmo_service_audit_matrix(
         // 2c-2-1. insert the serviceAccumulator into the cellAccumulator's services map if it doesn't exist;
         let service_acc = cell_acc
             .services
-            .entry(row.dental_service_id)
+            .entry((row.dental_service_id, row.service_fee))
             .or_insert_with(|| ServiceAccumulator {
                 dental_service_id: row.dental_service_id,
                 dental_service_name: row.dental_service_name.clone(),
@@ -430,9 +442,9 @@ pub fn build_dentist_hmo_service_audit_matrix(
 
     // Declare variables to hold overall grand totals
     let mut grand_total_qty: i64 = 0;
-    let mut grand_total_fee: f64 = 0.0;
-    let mut grand_total_basic_fee: f64 = 0.0;
-    let mut grand_total_nonbasic_fee: f64 = 0.0;
+    let mut grand_total_fee = Decimal::ZERO;
+    let mut grand_total_basic_fee = Decimal::ZERO;
+    let mut grand_total_nonbasic_fee = Decimal::ZERO;
 
     // Iterate through the dentist_map, one DentistAccumulator at a time.
     for dentist_acc in dentist_map.into_values() {
@@ -440,22 +452,22 @@ pub fn build_dentist_hmo_service_audit_matrix(
 
         // For each dentist, we want to know totals across all cells.
         let mut row_total_qty: i64 = 0;
-        let mut row_total_fee: f64 = 0.0;
-        let mut total_basic_fee: f64 = 0.0;
-        let mut total_nonbasic_fee: f64 = 0.0;
+        let mut row_total_fee = Decimal::ZERO;
+        let mut total_basic_fee = Decimal::ZERO;
+        let mut total_nonbasic_fee = Decimal::ZERO;
 
         // Iterate through the dentist's cells.
         for cell_acc in dentist_acc.cells.into_values() {
             let mut services: Vec<DentistHmoAuditServiceLine> = Vec::new();
 
             let mut cell_total_qty: i64 = 0;
-            let mut cell_total_fee: f64 = 0.0;
+            let mut cell_total_fee = Decimal::ZERO;
 
             // Iterate through the cell's services.
             for service_acc in cell_acc.services.into_values() {
 
                 // Compute the total fee for this service as qty * service_fee.
-                let total_fee = service_acc.qty as f64 * service_acc.service_fee;
+                let total_fee = Decimal::from(service_acc.qty) * service_acc.service_fee;
 
                 // Add the total fee to the cell's total_fee.
                 cell_total_qty += service_acc.qty;
@@ -471,8 +483,8 @@ pub fn build_dentist_hmo_service_audit_matrix(
                     dental_service_id: service_acc.dental_service_id,
                     dental_service_name: service_acc.dental_service_name,
                     qty: service_acc.qty,
-                    service_fee: service_acc.service_fee,
-                    total_fee,
+                    service_fee: to_f64(service_acc.service_fee),
+                    total_fee: to_f64(total_fee),
                 });
             }
 
@@ -481,6 +493,7 @@ pub fn build_dentist_hmo_service_audit_matrix(
                     .to_lowercase()
                     .cmp(&b.dental_service_name.to_lowercase())
                     .then(a.dental_service_id.cmp(&b.dental_service_id))
+                    .then(a.service_fee.total_cmp(&b.service_fee))
             });
 
             row_total_qty += cell_total_qty;
@@ -495,7 +508,7 @@ pub fn build_dentist_hmo_service_audit_matrix(
                 hmo_id: cell_acc.hmo_id,
                 services,
                 cell_total_qty,
-                cell_total_fee,
+                cell_total_fee: to_f64(cell_total_fee),
             });
         }
 
@@ -516,10 +529,10 @@ pub fn build_dentist_hmo_service_audit_matrix(
             period,
             cells,
             row_total_qty,
-            row_total_fee,
-            total_basic_fee,
-            total_nonbasic_fee,
-            subtotal_fee,
+            row_total_fee: to_f64(row_total_fee),
+            total_basic_fee: to_f64(total_basic_fee),
+            total_nonbasic_fee: to_f64(total_nonbasic_fee),
+            subtotal_fee: to_f64(subtotal_fee),
         });
     }
 
@@ -541,7 +554,7 @@ pub fn build_dentist_hmo_service_audit_matrix(
             hmo_short_name: acc.hmo_short_name,
             hmo_long_name: acc.hmo_long_name,
             total_qty: acc.total_qty,
-            total_fee: acc.total_fee,
+            total_fee: to_f64(acc.total_fee),
         })
         .collect();
 
@@ -560,9 +573,9 @@ pub fn build_dentist_hmo_service_audit_matrix(
         hmo_totals,
         rows,
         grand_total_qty,
-        grand_total_fee,
-        grand_total_basic_fee,
-        grand_total_nonbasic_fee,
+        grand_total_fee: to_f64(grand_total_fee),
+        grand_total_basic_fee: to_f64(grand_total_basic_fee),
+        grand_total_nonbasic_fee: to_f64(grand_total_nonbasic_fee),
     }
 }
 // endregion: build_dentist_hmo_service_audit_matrix()
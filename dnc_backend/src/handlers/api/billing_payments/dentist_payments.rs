@@ -4,11 +4,12 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use axum::extract::{Path, State};
 use axum::Json;
-use chrono::{Datelike, FixedOffset, Utc};
+use chrono::Datelike;
 use http::StatusCode;
 use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, FromQueryResult, JoinType, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set};
 use tracing::instrument;
 use crate::AppState;
+use crate::business_calendar;
 use crate::entities::{
     dental_clinic, dentist, dentist_clinic, dentist_payments,
 };
@@ -73,10 +74,7 @@ fn format_dentist_name(
 
 fn build_last_12_months_ending_current_month() -> Vec<DentistPaymentMatrixMonth> {
     // Use Manila time so "current month" follows the business timezone.
-    let manila_offset = FixedOffset::east_opt(8 * 60 * 60)
-        .expect("valid Manila UTC offset");
-
-    let now = Utc::now().with_timezone(&manila_offset);
+    let now = business_calendar::now();
 
     let current_year = now.year();
     let current_month = now.month() as i32;
@@ -374,10 +372,7 @@ pub async fn make_dentist_payment(
         });
     }
 
-    let manila_offset = FixedOffset::east_opt(8 * 60 * 60)
-        .expect("valid Manila UTC offset");
-
-    let now_manila = Utc::now().with_timezone(&manila_offset);
+    let now_manila = business_calendar::now().fixed_offset();
 
     let active_model = dentist_payments::ActiveModel {
         dentist_id: Set(request.dentist_id),
@@ -444,9 +439,19 @@ pub async fn delete_dentist_payment(
     db: &DatabaseConnection,
     payment_id: i32,
 ) -> Result<DeleteDentistPaymentResponse, DbErr> {
-    let delete_result = dentist_payments::Entity::delete_by_id(payment_id)
-        .exec(db)
-        .await?;
+    // Delete through the model so the audit hook records the removed payment.
+    let Some(payment) = dentist_payments::Entity::find_by_id(payment_id)
+        .one(db)
+        .await?
+    else {
+        return Ok(DeleteDentistPaymentResponse {
+            deleted: false,
+            payment_id,
+        });
+    };
+
+    let am: dentist_payments::ActiveModel = payment.into();
+    let delete_result = am.delete(db).await?;
 
     Ok(DeleteDentistPaymentResponse {
         deleted: delete_result.rows_affected > 0,
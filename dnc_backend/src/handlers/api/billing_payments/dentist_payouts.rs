@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::Response,
+    Json,
+};
+use chrono::NaiveDate;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::instrument;
+
+use crate::AppState;
+use crate::entities::{bir_2307_certificate, dentist_payout_batch, dentist_payout_item, dentist_payout_remittance};
+use crate::handlers::AuthUser;
+use crate::jobs::bir_2307::{generate_bir_2307_certificates, CertificateError};
+use crate::jobs::dentist_payouts::{
+    create_payout_batch, mark_item_paid, mark_remittance_paid, PayoutBatchSummary, PayoutError,
+};
+
+fn internal_error(err: impl ToString) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+// region: get_dentist_payout_batches
+#[derive(Debug, Deserialize)]
+pub struct PayoutBatchQuery {
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+}
+
+/// GET /api/dentist_payouts/batches?start_date=&end_date=
+/// Batches whose period overlaps the dates, newest first.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_dentist_payout_batches(
+    State(state): State<AppState>,
+    Query(params): Query<PayoutBatchQuery>,
+) -> Result<Json<Vec<dentist_payout_batch::Model>>, (StatusCode, String)> {
+    let mut query = dentist_payout_batch::Entity::find();
+    if let Some(start_date) = params.start_date {
+        query = query.filter(dentist_payout_batch::Column::PeriodEnd.gte(start_date));
+    }
+    if let Some(end_date) = params.end_date {
+        query = query.filter(dentist_payout_batch::Column::PeriodStart.lte(end_date));
+    }
+
+    let batches = query
+        .order_by_desc(dentist_payout_batch::Column::CreatedOn)
+        .order_by_desc(dentist_payout_batch::Column::Id)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(batches))
+}
+// endregion: get_dentist_payout_batches
+
+
+// region: post_dentist_payout_batch
+#[derive(Debug, Deserialize)]
+pub struct CreatePayoutBatchRequest {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    /// only this dentist's verifications; every fee-per-service dentist when absent
+    pub dentist_id: Option<i32>,
+}
+
+/// POST /api/dentist_payouts/batches
+/// Collects the unpaid reconciled verifications of fee-per-service dentists for the period into
+/// a batch, with a remittance spreadsheet per dentist and clinic.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_dentist_payout_batch(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreatePayoutBatchRequest>,
+) -> Result<(StatusCode, Json<PayoutBatchSummary>), (StatusCode, String)> {
+    if payload.start_date > payload.end_date {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "start_date must be earlier than or equal to end_date".to_string(),
+        ));
+    }
+
+    let summary = create_payout_batch(
+        &state.db,
+        payload.start_date,
+        payload.end_date,
+        payload.dentist_id,
+        &auth_user.claims.email,
+    )
+        .await?;
+    let status = if summary.batch_id.is_some() { StatusCode::CREATED } else { StatusCode::OK };
+    Ok((status, Json(summary)))
+}
+// endregion: post_dentist_payout_batch
+
+
+// region: get_dentist_payout_batch
+#[derive(Debug, Serialize)]
+pub struct RemittanceDetail {
+    #[serde(flatten)]
+    pub remittance: dentist_payout_remittance::Model,
+    pub items: Vec<dentist_payout_item::Model>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PayoutBatchDetailResponse {
+    #[serde(flatten)]
+    pub batch: dentist_payout_batch::Model,
+    pub remittances: Vec<RemittanceDetail>,
+}
+
+/// GET /api/dentist_payouts/batches/{id}
+/// The batch with its remittances and their items.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_dentist_payout_batch(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<PayoutBatchDetailResponse>, (StatusCode, String)> {
+    let batch = dentist_payout_batch::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| PayoutError::BatchNotFound(id))?;
+    let remittances = dentist_payout_remittance::Entity::find()
+        .filter(dentist_payout_remittance::Column::BatchId.eq(id))
+        .order_by_asc(dentist_payout_remittance::Column::Id)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+    let mut items_by_remittance: HashMap<i32, Vec<dentist_payout_item::Model>> = HashMap::new();
+    for item in dentist_payout_item::Entity::find()
+        .filter(dentist_payout_item::Column::BatchId.eq(id))
+        .order_by_asc(dentist_payout_item::Column::DateServicePerformed)
+        .order_by_asc(dentist_payout_item::Column::Id)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?
+    {
+        items_by_remittance.entry(item.remittance_id).or_default().push(item);
+    }
+
+    let remittances = remittances
+        .into_iter()
+        .map(|remittance| RemittanceDetail {
+            items: items_by_remittance.remove(&remittance.id).unwrap_or_default(),
+            remittance,
+        })
+        .collect();
+
+    Ok(Json(PayoutBatchDetailResponse { batch, remittances }))
+}
+// endregion: get_dentist_payout_batch
+
+
+// region: download_dentist_payout_remittance
+/// GET /api/dentist_payouts/remittances/{id}/download
+#[instrument(skip(state))]
+pub async fn download_dentist_payout_remittance(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Response, (StatusCode, String)> {
+    let remittance = dentist_payout_remittance::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| PayoutError::RemittanceNotFound(id))?;
+
+    xlsx_file_response(&remittance.file_name).await
+}
+
+/// Responds with a spreadsheet from generated_reports/.
+async fn xlsx_file_response(file_name: &str) -> Result<Response, (StatusCode, String)> {
+    let bytes = fs::read(format!("generated_reports/{}", file_name))
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, format!("File not found: {}", file_name)))?;
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(
+            header::CONTENT_TYPE,
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        )
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", file_name),
+        )
+        .body(Body::from(bytes))
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to build xlsx response: {err}"),
+            )
+        })
+}
+// endregion: download_dentist_payout_remittance
+
+
+// region: post_dentist_payout_paid
+#[derive(Debug, Deserialize)]
+pub struct MarkPaidRequest {
+    /// e.g. the check or bank transfer reference
+    pub payment_reference: Option<String>,
+}
+
+/// POST /api/dentist_payouts/items/{id}/pay
+/// Marks one verification's payout paid. Refused if it already is.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_dentist_payout_item_paid(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<MarkPaidRequest>,
+) -> Result<Json<dentist_payout_item::Model>, (StatusCode, String)> {
+    let item = mark_item_paid(&state.db, id, payload.payment_reference, &auth_user.claims.email).await?;
+    Ok(Json(item))
+}
+
+#[derive(Debug, Serialize)]
+pub struct RemittancePaidResponse {
+    pub remittance_id: i32,
+    pub items_marked_paid: u64,
+}
+
+/// POST /api/dentist_payouts/remittances/{id}/pay
+/// Marks every unpaid item of the remittance paid.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_dentist_payout_remittance_paid(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<MarkPaidRequest>,
+) -> Result<Json<RemittancePaidResponse>, (StatusCode, String)> {
+    let items_marked_paid =
+        mark_remittance_paid(&state.db, id, payload.payment_reference, &auth_user.claims.email).await?;
+    Ok(Json(RemittancePaidResponse {
+        remittance_id: id,
+        items_marked_paid,
+    }))
+}
+// endregion: post_dentist_payout_paid
+
+
+// region: bir_2307
+#[derive(Debug, Deserialize)]
+pub struct Bir2307Query {
+    pub year: Option<i32>,
+    pub quarter: Option<i32>,
+    pub dental_clinic_id: Option<i32>,
+}
+
+/// GET /api/dentist_payouts/bir_2307?year=&quarter=&dental_clinic_id=
+#[instrument(skip(state), err(Debug))]
+pub async fn get_bir_2307_certificates(
+    State(state): State<AppState>,
+    Query(params): Query<Bir2307Query>,
+) -> Result<Json<Vec<bir_2307_certificate::Model>>, (StatusCode, String)> {
+    let mut query = bir_2307_certificate::Entity::find();
+    if let Some(year) = params.year {
+        query = query.filter(bir_2307_certificate::Column::Year.eq(year));
+    }
+    if let Some(quarter) = params.quarter {
+        query = query.filter(bir_2307_certificate::Column::Quarter.eq(quarter));
+    }
+    if let Some(dental_clinic_id) = params.dental_clinic_id {
+        query = query.filter(bir_2307_certificate::Column::DentalClinicId.eq(dental_clinic_id));
+    }
+
+    let certificates = query
+        .order_by_desc(bir_2307_certificate::Column::Year)
+        .order_by_desc(bir_2307_certificate::Column::Quarter)
+        .order_by_asc(bir_2307_certificate::Column::DentalClinicId)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(certificates))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GenerateBir2307Request {
+    pub year: i32,
+    /// 1 to 4
+    pub quarter: u32,
+    /// only this clinic; every clinic with tax withheld in the quarter when absent
+    pub dental_clinic_id: Option<i32>,
+}
+
+/// POST /api/dentist_payouts/bir_2307
+/// Generates (or regenerates) the quarter's BIR Form 2307 certificates from the payouts paid
+/// in it.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_bir_2307_certificates(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<GenerateBir2307Request>,
+) -> Result<Json<Vec<bir_2307_certificate::Model>>, (StatusCode, String)> {
+    let certificates = generate_bir_2307_certificates(
+        &state.db,
+        payload.year,
+        payload.quarter,
+        payload.dental_clinic_id,
+        &auth_user.claims.email,
+    )
+        .await?;
+    Ok(Json(certificates))
+}
+
+/// GET /api/dentist_payouts/bir_2307/{id}/download
+#[instrument(skip(state))]
+pub async fn download_bir_2307_certificate(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Response, (StatusCode, String)> {
+    let certificate = bir_2307_certificate::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| CertificateError::NotFound(id))?;
+    xlsx_file_response(&certificate.file_name).await
+}
+// endregion: bir_2307
@@ -332,7 +332,7 @@ pub async fn get_dentist_retainer_payables_handler(
 
     Ok(Json(response))
 }
-fn map_dentist_retainer_payables_error(
+pub(crate) fn map_dentist_retainer_payables_error(
     error: DentistRetainerPayablesError,
 ) -> (StatusCode, String) {
     match error {
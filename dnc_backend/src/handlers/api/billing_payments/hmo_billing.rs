@@ -1,29 +1,186 @@
 use tokio::fs;
+use std::collections::HashMap;
 use std::path::{ Path as FsPath,PathBuf};
 use axum::{
-    extract::{Path,State},
+    extract::{Path, Query, State},
     Json,
 };
 use axum::body::Body;
 use axum::response::Response;
+use chrono::NaiveDate;
 use http::{header, HeaderMap, HeaderValue, StatusCode};
-use tracing::info;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use tracing::{info, instrument};
 use crate::{
     AppState,
 };
-use crate::handlers::reports::{get_bill_reports, GeneratedBillingReportResponse};
+use crate::entities::{billing_run, generated_report};
+use crate::handlers::AuthUser;
+use crate::handlers::reports::GeneratedBillingReportResponse;
+use crate::jobs::billing_runs::{self, BillingRunStatus};
+use crate::jobs::hmo_billing::generate_hmo_billing_reports;
 
-// get_generated_hmo_billing_reports returns a list of generated billing reports based on
-// the table
+// region: get_generated_hmo_billing_reports
+#[derive(Debug, Deserialize)]
+pub struct BillingRunQuery {
+    /// draft, finalized or void
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BillingRunResponse {
+    #[serde(flatten)]
+    pub run: billing_run::Model,
+    /// the run that replaced this one, if it was voided
+    pub superseded_by_run_id: Option<i32>,
+    pub reports: Vec<GeneratedBillingReportResponse>,
+}
+
+// get_generated_hmo_billing_reports returns the billing runs, newest period first, each with
+// the spreadsheets it generated.
+#[instrument(skip(state), err(Debug))]
 pub async fn get_generated_hmo_billing_reports(
     State(state): State<AppState>,
-) -> Result<Json<Vec<GeneratedBillingReportResponse>>, StatusCode> {
-    let reports = get_bill_reports(&state.db, 1)
+    Query(params): Query<BillingRunQuery>,
+) -> Result<Json<Vec<BillingRunResponse>>, (StatusCode, String)> {
+    let mut query = billing_run::Entity::find();
+    if let Some(status) = params.status {
+        let status = BillingRunStatus::parse(&status)
+            .ok_or((StatusCode::BAD_REQUEST, format!("Unknown billing run status '{}'", status)))?;
+        query = query.filter(billing_run::Column::Status.eq(status.as_str()));
+    }
+    let runs = query
+        .order_by_desc(billing_run::Column::PeriodEnd)
+        .order_by_desc(billing_run::Column::Id)
+        .all(&state.db)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let run_ids: Vec<i32> = runs.iter().map(|r| r.id).collect();
+    let mut reports_by_run: HashMap<i32, Vec<GeneratedBillingReportResponse>> = HashMap::new();
+    let reports = generated_report::Entity::find()
+        .filter(generated_report::Column::BillingRunId.is_in(run_ids))
+        .order_by_asc(generated_report::Column::FileName)
+        .all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    for row in reports {
+        let Some(run_id) = row.billing_run_id else { continue };
+        reports_by_run.entry(run_id).or_default().push(GeneratedBillingReportResponse {
+            id: row.id,
+            report_type_id: row.report_type_id,
+            file_name: row.file_name,
+            date_generated: row
+                .date_generated
+                .map(|dt| dt.format("%B %-d, %Y").to_string()),
+        });
+    }
+    let superseded_by: HashMap<i32, i32> = runs
+        .iter()
+        .filter_map(|r| r.supersedes_run_id.map(|old| (old, r.id)))
+        .collect();
+
+    Ok(Json(
+        runs.into_iter()
+            .map(|run| BillingRunResponse {
+                superseded_by_run_id: superseded_by.get(&run.id).copied(),
+                reports: reports_by_run.remove(&run.id).unwrap_or_default(),
+                run,
+            })
+            .collect(),
+    ))
+}
+// endregion: get_generated_hmo_billing_reports
+
+
+// region: post_hmo_billing_run
+#[derive(Debug, Deserialize)]
+pub struct HmoBillingRunRequest {
+    /// defaults to a month before end_date
+    pub start_date: Option<NaiveDate>,
+    pub end_date: NaiveDate,
+}
+
+/// POST /api/hmo_billing/runs
+/// Generates the billing for the period. If the period already has a draft run it is
+/// regenerated in place; if its run is finalized this returns 409.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_hmo_billing_run(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<HmoBillingRunRequest>,
+) -> Result<Json<billing_run::Model>, (StatusCode, String)> {
+    if payload.start_date.is_some_and(|start| start > payload.end_date) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "start_date must be earlier than or equal to end_date".to_string(),
+        ));
+    }
+    let run = generate_hmo_billing_reports(state, payload.start_date, payload.end_date, &auth_user.claims.email)
+        .await
+        .map_err(|e| match e.downcast::<billing_runs::BillingRunError>() {
+            Ok(run_error) => run_error.into(),
+            Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        })?;
+    Ok(Json(run))
+}
+// endregion: post_hmo_billing_run
+
+
+// region: post_regenerate_hmo_billing_run
+/// POST /api/hmo_billing/runs/{id}/regenerate
+#[instrument(skip(state), err(Debug))]
+pub async fn post_regenerate_hmo_billing_run(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<billing_run::Model>, (StatusCode, String)> {
+    let run = billing_runs::regenerate_run(&state.db, id).await?;
+    Ok(Json(run))
+}
+// endregion: post_regenerate_hmo_billing_run
+
 
-    Ok(Json(reports))
+// region: post_finalize_hmo_billing_run
+/// POST /api/hmo_billing/runs/{id}/finalize
+#[instrument(skip(state), err(Debug))]
+pub async fn post_finalize_hmo_billing_run(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> Result<Json<billing_run::Model>, (StatusCode, String)> {
+    let run = billing_runs::finalize_run(&state.db, id, &auth_user.claims.email).await?;
+    Ok(Json(run))
+}
+// endregion: post_finalize_hmo_billing_run
+
+
+// region: post_void_hmo_billing_run
+#[derive(Debug, Deserialize)]
+pub struct VoidBillingRunRequest {
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VoidBillingRunResponse {
+    pub voided: billing_run::Model,
+    pub replacement: billing_run::Model,
+}
+
+/// POST /api/hmo_billing/runs/{id}/void
+/// Voids the run and generates a new draft for the same period that supersedes it.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_void_hmo_billing_run(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<VoidBillingRunRequest>,
+) -> Result<Json<VoidBillingRunResponse>, (StatusCode, String)> {
+    let (voided, replacement) =
+        billing_runs::void_run(&state.db, id, &payload.reason, &auth_user.claims.email).await?;
+    Ok(Json(VoidBillingRunResponse { voided, replacement }))
 }
+// endregion: post_void_hmo_billing_run
 
 
 pub async fn download_generated_report(
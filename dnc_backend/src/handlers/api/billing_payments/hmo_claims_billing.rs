@@ -0,0 +1,76 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Json,
+};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::AppState;
+use crate::handlers::AuthUser;
+use crate::handlers::reports::{get_bill_reports, GeneratedBillingReportResponse};
+use crate::jobs::hmo_claims_billing::{generate_hmo_claims_billing, hmo_claims_report_type_id, HmoClaimsStatement};
+
+// region: get_generated_hmo_claims_reports
+/// GET /api/hmo_claims_billing/
+/// The claims statements generated so far. Download them with /api/hmo_billing/download/{file_name}.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_generated_hmo_claims_reports(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<GeneratedBillingReportResponse>>, (StatusCode, String)> {
+    let report_type_id = hmo_claims_report_type_id(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let reports = get_bill_reports(&state.db, report_type_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(reports))
+}
+// endregion: get_generated_hmo_claims_reports
+
+
+// region: post_hmo_claims_billing
+#[derive(Debug, Deserialize)]
+pub struct HmoClaimsBillingRequest {
+    /// leave out to bill every HMO
+    pub hmo_id: Option<i32>,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+/// POST /api/hmo_claims_billing
+/// Bills the reconciled verifications whose service was performed in the period and that
+/// have not been billed before, one claims statement per HMO.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_hmo_claims_billing(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<HmoClaimsBillingRequest>,
+) -> Result<Json<Vec<HmoClaimsStatement>>, (StatusCode, String)> {
+    if payload.start_date > payload.end_date {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "start_date must be earlier than or equal to end_date".to_string(),
+        ));
+    }
+
+    let statements = generate_hmo_claims_billing(
+        state,
+        payload.hmo_id,
+        payload.start_date,
+        payload.end_date,
+        &auth_user.claims.email,
+    )
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to generate HMO claims billing: {}", e),
+            )
+        })?;
+
+    Ok(Json(statements))
+}
+// endregion: post_hmo_claims_billing
@@ -1,5 +1,9 @@
 pub mod hmo_billing;
+pub mod hmo_claims_billing;
+pub mod receivables;
 pub mod dentist_retainers;
 pub mod dentist_matrices;
 pub mod dentist_payments;
-pub mod dentist_retainer_report;
\ No newline at end of file
+pub mod dentist_payouts;
+pub mod dentist_retainer_report;
+pub mod bank_disbursements;
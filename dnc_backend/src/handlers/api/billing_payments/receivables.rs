@@ -0,0 +1,227 @@
+use std::io::Cursor;
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::Response,
+    Json,
+};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use umya_spreadsheet::writer;
+
+use crate::AppState;
+use crate::business_calendar;
+use crate::entities::{hmo_invoice, hmo_payment};
+use crate::handlers::AuthUser;
+use crate::jobs::receivables::{
+    aging_report, record_payment, write_aging_to_workbook, HmoAging, InvoiceStatus, ReceivableError,
+};
+
+fn internal_error(err: impl ToString) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvoiceResponse {
+    #[serde(flatten)]
+    pub invoice: hmo_invoice::Model,
+    pub balance: Decimal,
+}
+
+impl From<hmo_invoice::Model> for InvoiceResponse {
+    fn from(invoice: hmo_invoice::Model) -> Self {
+        Self {
+            balance: invoice.total_amount - invoice.amount_paid,
+            invoice,
+        }
+    }
+}
+
+// region: get_hmo_invoices
+#[derive(Debug, Deserialize)]
+pub struct HmoInvoiceQuery {
+    pub hmo_id: Option<i32>,
+    pub status: Option<String>,
+}
+
+/// GET /api/receivables/invoices?hmo_id=&status=
+/// Invoices, newest first, with their outstanding balances.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_hmo_invoices(
+    State(state): State<AppState>,
+    Query(params): Query<HmoInvoiceQuery>,
+) -> Result<Json<Vec<InvoiceResponse>>, (StatusCode, String)> {
+    let mut query = hmo_invoice::Entity::find();
+    if let Some(hmo_id) = params.hmo_id {
+        query = query.filter(hmo_invoice::Column::HmoId.eq(hmo_id));
+    }
+    if let Some(status) = params.status {
+        let status = InvoiceStatus::parse(&status)
+            .ok_or((StatusCode::BAD_REQUEST, format!("Unknown invoice status '{}'", status)))?;
+        query = query.filter(hmo_invoice::Column::Status.eq(status.as_str()));
+    }
+
+    let invoices = query
+        .order_by_desc(hmo_invoice::Column::InvoiceDate)
+        .order_by_desc(hmo_invoice::Column::Id)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(invoices.into_iter().map(InvoiceResponse::from).collect()))
+}
+// endregion: get_hmo_invoices
+
+
+// region: get_hmo_invoice
+#[derive(Debug, Serialize)]
+pub struct InvoiceDetailResponse {
+    #[serde(flatten)]
+    pub invoice: InvoiceResponse,
+    pub payments: Vec<hmo_payment::Model>,
+}
+
+/// GET /api/receivables/invoices/{id}
+#[instrument(skip(state), err(Debug))]
+pub async fn get_hmo_invoice(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<InvoiceDetailResponse>, (StatusCode, String)> {
+    let invoice = hmo_invoice::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| ReceivableError::NotFound(id))?;
+    let payments = hmo_payment::Entity::find()
+        .filter(hmo_payment::Column::HmoInvoiceId.eq(id))
+        .order_by_asc(hmo_payment::Column::PaymentDate)
+        .order_by_asc(hmo_payment::Column::Id)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(InvoiceDetailResponse {
+        invoice: invoice.into(),
+        payments,
+    }))
+}
+// endregion: get_hmo_invoice
+
+
+// region: post_hmo_payment
+#[derive(Debug, Deserialize)]
+pub struct HmoPaymentRequest {
+    pub payment_date: NaiveDate,
+    pub amount: Decimal,
+    pub or_number: String,
+    pub remarks: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HmoPaymentResponse {
+    pub invoice: InvoiceResponse,
+    pub payment: hmo_payment::Model,
+}
+
+/// POST /api/receivables/invoices/{id}/payments
+/// Records a full or partial payment. The invoice is marked paid when its balance reaches zero.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_hmo_payment(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<HmoPaymentRequest>,
+) -> Result<(StatusCode, Json<HmoPaymentResponse>), (StatusCode, String)> {
+    let (invoice, payment) = record_payment(
+        &state.db,
+        id,
+        payload.payment_date,
+        payload.amount,
+        &payload.or_number,
+        payload.remarks,
+        &auth_user.claims.email,
+    )
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(HmoPaymentResponse {
+            invoice: invoice.into(),
+            payment,
+        }),
+    ))
+}
+// endregion: post_hmo_payment
+
+
+// region: get_receivables_aging
+#[derive(Debug, Deserialize)]
+pub struct AgingQuery {
+    /// defaults to today in Manila
+    pub as_of: Option<NaiveDate>,
+}
+
+fn as_of_or_today(params: &AgingQuery) -> NaiveDate {
+    params.as_of.unwrap_or_else(business_calendar::today)
+}
+
+/// GET /api/receivables/aging?as_of=
+/// Outstanding balances per HMO, by age of invoice: current (up to 30 days), 31-60, 61-90
+/// and over 90 days.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_receivables_aging(
+    State(state): State<AppState>,
+    Query(params): Query<AgingQuery>,
+) -> Result<Json<Vec<HmoAging>>, (StatusCode, String)> {
+    let rows = aging_report(&state.db, as_of_or_today(&params))
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(rows))
+}
+
+/// GET /api/receivables/aging/download?as_of=
+/// The aging report as a spreadsheet.
+#[instrument(skip(state))]
+pub async fn download_receivables_aging(
+    State(state): State<AppState>,
+    Query(params): Query<AgingQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let as_of = as_of_or_today(&params);
+    let rows = aging_report(&state.db, as_of)
+        .await
+        .map_err(internal_error)?;
+
+    let workbook = write_aging_to_workbook(&rows, as_of);
+    let mut buffer = Cursor::new(Vec::<u8>::new());
+    writer::xlsx::write_writer(&workbook, &mut buffer).map_err(|err| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to generate aging xlsx: {err}"),
+        )
+    })?;
+
+    let filename = format!("receivables_aging_{}.xlsx", as_of);
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(
+            header::CONTENT_TYPE,
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        )
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{filename}\""),
+        )
+        .body(Body::from(buffer.into_inner()))
+        .map_err(|err| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to build xlsx response: {err}"),
+            )
+        })
+}
+// endregion: get_receivables_aging
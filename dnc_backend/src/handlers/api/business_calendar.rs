@@ -0,0 +1,250 @@
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Datelike, NaiveDate, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, ModelTrait, QueryFilter, QueryOrder, Set, SqlErr,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::AppState;
+use crate::business_calendar::{self, BusinessCalendar, HolidayType};
+use crate::entities::holiday;
+use crate::handlers::AuthUser;
+
+fn internal_error(err: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+/// A second holiday on the same date is a conflict rather than a server error.
+fn save_error(err: DbErr, holiday_date: NaiveDate) -> (StatusCode, String) {
+    match err.sql_err() {
+        Some(SqlErr::UniqueConstraintViolation(_)) => (
+            StatusCode::CONFLICT,
+            format!("There is already a holiday on {}", holiday_date),
+        ),
+        _ => internal_error(err),
+    }
+}
+
+// region: get_business_calendar
+#[derive(Debug, Deserialize)]
+pub struct BusinessCalendarQuery {
+    /// defaults to this year in Manila
+    pub year: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BusinessCalendarResponse {
+    pub today: NaiveDate,
+    /// e.g. ["Sun"]
+    pub non_working_weekdays: Vec<String>,
+    pub holidays: Vec<holiday::Model>,
+}
+
+/// GET /api/business_calendar?year=
+/// The non-working weekdays and the year's holidays.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_business_calendar(
+    State(state): State<AppState>,
+    Query(params): Query<BusinessCalendarQuery>,
+) -> Result<Json<BusinessCalendarResponse>, (StatusCode, String)> {
+    let today = business_calendar::today();
+    let year = params.year.unwrap_or(today.year());
+    let (Some(first), Some(last)) = (NaiveDate::from_ymd_opt(year, 1, 1), NaiveDate::from_ymd_opt(year, 12, 31)) else {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid year {}", year)));
+    };
+
+    let calendar = BusinessCalendar::load(&state.db).await?;
+    let holidays = holiday::Entity::find()
+        .filter(holiday::Column::HolidayDate.between(first, last))
+        .order_by_asc(holiday::Column::HolidayDate)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(BusinessCalendarResponse {
+        today,
+        non_working_weekdays: calendar.non_working_weekdays().iter().map(|d| d.to_string()).collect(),
+        holidays,
+    }))
+}
+// endregion: get_business_calendar
+
+
+// region: post_holiday
+#[derive(Debug, Deserialize)]
+pub struct PostHolidayRequest {
+    pub holiday_date: NaiveDate,
+    pub name: String,
+    /// regular when left out
+    pub holiday_type: Option<HolidayType>,
+}
+
+/// POST /api/business_calendar/holidays
+#[instrument(skip(state), err(Debug))]
+pub async fn post_holiday(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<PostHolidayRequest>,
+) -> Result<(StatusCode, Json<holiday::Model>), (StatusCode, String)> {
+    let name = payload.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "name is required".to_string()));
+    }
+
+    let inserted = holiday::ActiveModel {
+        holiday_date: Set(payload.holiday_date),
+        name: Set(name.to_string()),
+        holiday_type: Set(payload.holiday_type.unwrap_or(HolidayType::Regular).as_str().to_string()),
+        last_modified_by: Set(auth_user.claims.email),
+        last_modified_on: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    }
+        .insert(&state.db)
+        .await
+        .map_err(|err| save_error(err, payload.holiday_date))?;
+
+    Ok((StatusCode::CREATED, Json(inserted)))
+}
+// endregion: post_holiday
+
+
+// region: patch_holiday
+#[derive(Debug, Deserialize)]
+pub struct PatchHolidayRequest {
+    pub holiday_date: Option<NaiveDate>,
+    pub name: Option<String>,
+    pub holiday_type: Option<HolidayType>,
+}
+
+/// PATCH /api/business_calendar/holidays/{id}
+#[instrument(skip(state), err(Debug))]
+pub async fn patch_holiday(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<PatchHolidayRequest>,
+) -> Result<Json<holiday::Model>, (StatusCode, String)> {
+    let row = holiday::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Holiday not found".to_string()))?;
+    let holiday_date = payload.holiday_date.unwrap_or(row.holiday_date);
+
+    let mut am: holiday::ActiveModel = row.into();
+    if let Some(date) = payload.holiday_date {
+        am.holiday_date = Set(date);
+    }
+    if let Some(name) = payload.name {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "name cannot be empty".to_string()));
+        }
+        am.name = Set(name.to_string());
+    }
+    if let Some(holiday_type) = payload.holiday_type {
+        am.holiday_type = Set(holiday_type.as_str().to_string());
+    }
+    am.last_modified_by = Set(auth_user.claims.email);
+    am.last_modified_on = Set(Utc::now().fixed_offset());
+
+    let updated = am.update(&state.db).await.map_err(|err| save_error(err, holiday_date))?;
+    Ok(Json(updated))
+}
+// endregion: patch_holiday
+
+
+// region: delete_holiday
+/// DELETE /api/business_calendar/holidays/{id}
+#[instrument(skip(state), err(Debug))]
+pub async fn delete_holiday(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let row = holiday::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Holiday not found".to_string()))?;
+
+    row.delete(&state.db).await.map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: delete_holiday
+
+
+// region: import_holidays
+#[derive(Debug, Serialize)]
+pub struct ImportHolidaysResponse {
+    pub inserted: usize,
+    pub updated: usize,
+}
+
+/// POST /api/business_calendar/holidays/import
+/// Multipart field "file": a CSV file with a header row and the columns holiday_date
+/// (YYYY-MM-DD), name and, optionally, holiday_type (regular or special_non_working). A holiday
+/// already on one of the dates is replaced. Nothing is saved if any row is bad.
+#[instrument(skip(state, multipart), err(Debug))]
+pub async fn import_holidays(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    mut multipart: Multipart,
+) -> Result<Json<ImportHolidaysResponse>, (StatusCode, String)> {
+    let mut file_bytes: Option<Vec<u8>> = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+    {
+        if field.name() == Some("file") {
+            let data = field.bytes().await.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+            file_bytes = Some(data.to_vec());
+        }
+    }
+    let file_bytes = file_bytes.ok_or((StatusCode::BAD_REQUEST, "Missing 'file' field".to_string()))?;
+
+    let entries = business_calendar::parse_holiday_csv(&file_bytes)
+        .map_err(|errors| (StatusCode::BAD_REQUEST, errors.join("\n")))?;
+    if entries.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "The file lists no holidays".to_string()));
+    }
+
+    let txn = state.db.begin().await.map_err(internal_error)?;
+    let now = Utc::now().fixed_offset();
+    let mut response = ImportHolidaysResponse { inserted: 0, updated: 0 };
+    for entry in entries {
+        let existing = holiday::Entity::find()
+            .filter(holiday::Column::HolidayDate.eq(entry.holiday_date))
+            .one(&txn)
+            .await
+            .map_err(internal_error)?;
+        let mut am: holiday::ActiveModel = match existing {
+            Some(row) => {
+                response.updated += 1;
+                row.into()
+            }
+            None => {
+                response.inserted += 1;
+                holiday::ActiveModel {
+                    holiday_date: Set(entry.holiday_date),
+                    ..Default::default()
+                }
+            }
+        };
+        am.name = Set(entry.name);
+        am.holiday_type = Set(entry.holiday_type.as_str().to_string());
+        am.last_modified_by = Set(auth_user.claims.email.clone());
+        am.last_modified_on = Set(now);
+        am.save(&txn).await.map_err(internal_error)?;
+    }
+    txn.commit().await.map_err(internal_error)?;
+
+    Ok(Json(response))
+}
+// endregion: import_holidays
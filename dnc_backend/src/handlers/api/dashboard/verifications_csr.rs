@@ -3,7 +3,7 @@ use axum::{
     http::StatusCode,
     Json,
 };
-use chrono::{DateTime, Datelike, Days, FixedOffset, NaiveDate, TimeZone};
+use chrono::{DateTime, Datelike, Days, FixedOffset, NaiveDate};
 use sea_orm::{
     ColumnTrait, DatabaseConnection, DbBackend, DbErr, EntityTrait, FromQueryResult,
     QueryFilter, QueryOrder, QuerySelect, Statement
@@ -11,6 +11,7 @@ use sea_orm::{
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use crate::{
+    business_calendar,
     entities::{role, user, verification},
     AppState,
 };
@@ -55,24 +56,6 @@ pub async fn get_csr_verification_activity_counts(
 
     // Treat date interval as Manila calendar days:
     // start_date 00:00:00 up to end_date + 1 day 00:00:00
-    let manila_offset = FixedOffset::east_opt(8 * 60 * 60)
-        .ok_or_else(|| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Could not create Manila timezone offset".to_string(),
-            )
-        })?;
-
-    let start_naive = params
-        .start_date
-        .and_hms_opt(0, 0, 0)
-        .ok_or_else(|| {
-            (
-                StatusCode::BAD_REQUEST,
-                "Invalid start_date".to_string(),
-            )
-        })?;
-
     let end_plus_one = params
         .end_date
         .checked_add_days(Days::new(1))
@@ -83,34 +66,8 @@ pub async fn get_csr_verification_activity_counts(
             )
         })?;
 
-    let end_naive = end_plus_one
-        .and_hms_opt(0, 0, 0)
-        .ok_or_else(|| {
-            (
-                StatusCode::BAD_REQUEST,
-                "Invalid end_date".to_string(),
-            )
-        })?;
-
-    let start_dt = manila_offset
-        .from_local_datetime(&start_naive)
-        .single()
-        .ok_or_else(|| {
-            (
-                StatusCode::BAD_REQUEST,
-                "Invalid start datetime".to_string(),
-            )
-        })?;
-
-    let end_dt = manila_offset
-        .from_local_datetime(&end_naive)
-        .single()
-        .ok_or_else(|| {
-            (
-                StatusCode::BAD_REQUEST,
-                "Invalid end datetime".to_string(),
-            )
-        })?;
+    let start_dt = business_calendar::start_of_day(params.start_date);
+    let end_dt = business_calendar::start_of_day(end_plus_one);
 
     // 1. Get active CSR users
     let csr_users = user::Entity::find()
@@ -371,24 +328,6 @@ pub async fn get_csr_verification_activity_unit_counts(
         ));
     }
 
-    let manila_offset = FixedOffset::east_opt(8 * 60 * 60)
-        .ok_or_else(|| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "Could not create Manila timezone offset".to_string(),
-            )
-        })?;
-
-    let start_naive = params
-        .start_date
-        .and_hms_opt(0, 0, 0)
-        .ok_or_else(|| {
-            (
-                StatusCode::BAD_REQUEST,
-                "Invalid start_date".to_string(),
-            )
-        })?;
-
     let end_plus_one = params
         .end_date
         .checked_add_days(Days::new(1))
@@ -399,34 +338,8 @@ pub async fn get_csr_verification_activity_unit_counts(
             )
         })?;
 
-    let end_naive = end_plus_one
-        .and_hms_opt(0, 0, 0)
-        .ok_or_else(|| {
-            (
-                StatusCode::BAD_REQUEST,
-                "Invalid end_date".to_string(),
-            )
-        })?;
-
-    let start_dt = manila_offset
-        .from_local_datetime(&start_naive)
-        .single()
-        .ok_or_else(|| {
-            (
-                StatusCode::BAD_REQUEST,
-                "Invalid start datetime".to_string(),
-            )
-        })?;
-
-    let end_dt = manila_offset
-        .from_local_datetime(&end_naive)
-        .single()
-        .ok_or_else(|| {
-            (
-                StatusCode::BAD_REQUEST,
-                "Invalid end datetime".to_string(),
-            )
-        })?;
+    let start_dt = business_calendar::start_of_day(params.start_date);
+    let end_dt = business_calendar::start_of_day(end_plus_one);
 
     // 1. Get active CSR users
     let csr_users = user::Entity::find()
@@ -554,12 +467,13 @@ async fn count_verification_activity_by_period(
     dental_service_id: Option<i32>,
 ) -> Result<Vec<CountByPeriodEmail>, DbErr> {
     let sql_unit = unit.sql_date_trunc_unit();
+    let time_zone = business_calendar::TIMEZONE.name();
 
     let mut sql = format!(
         r#"
         SELECT
             {user_column} AS email,
-            date_trunc('{sql_unit}', {timestamp_column} AT TIME ZONE 'Asia/Manila')::date AS period_start,
+            date_trunc('{sql_unit}', {timestamp_column} AT TIME ZONE '{time_zone}')::date AS period_start,
             COUNT(id)::bigint AS count
         FROM verification
         WHERE {user_column} IS NOT NULL
@@ -1,15 +1,19 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseTransaction, DbErr, EntityTrait,
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use sea_orm::{ActiveModelTrait, ColumnTrait, Condition, DatabaseTransaction, EntityTrait,
               ActiveValue::NotSet, QueryFilter, QueryOrder, Set, TransactionTrait};
 use serde::{Serialize, Deserialize};
 use tracing::instrument;
 
 use crate::AppState;
+use crate::business_calendar;
 use crate::handlers::structs::AuthUser;
+use crate::jobs::contract_rates::{cancel_scheduled_rate, schedule_contract_rates, RateScheduleError};
 
 // CHANGE THIS: import your permission helper + enum if you want these endpoints permission-gated
 use crate::handlers::helpers::role_has_permission_by_data_object_name;
@@ -38,14 +42,36 @@ pub struct DentistContractServiceRateRow {
     pub dentist_contract_id: i32,
     pub service_id: i32,
     pub service_name: Option<String>, // derived from related dental_service
-    pub rate: f32,
+    pub rate: Decimal,
+    pub effective_from: NaiveDate,
+    pub effective_to: Option<NaiveDate>,
+    pub created_by: Option<String>,
+}
+
+impl DentistContractServiceRateRow {
+    fn new(rate_model: dentist_contract_service_rates::Model, svc_opt: Option<dental_service::Model>) -> Self {
+        DentistContractServiceRateRow {
+            id: rate_model.id,
+            dentist_contract_id: rate_model.dentist_contract_id,
+            service_id: rate_model.service_id,
+            service_name: svc_opt.map(|s| s.name),
+            rate: rate_model.rate,
+            effective_from: rate_model.effective_from,
+            effective_to: rate_model.effective_to,
+            created_by: rate_model.created_by,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
 pub struct DentistContractWithRates {
     pub contract: DentistContractRow,
+    // the rates in force today
     pub rates: Vec<DentistContractServiceRateRow>,
+    // rate changes that have not started yet
+    pub scheduled_rates: Vec<DentistContractServiceRateRow>,
 }
+
 //--------------------------
 // GET /api/get_all_dentist_contracts()
 // returns a list of {id, name, description, active }
@@ -103,10 +129,11 @@ pub async fn get_all_dentist_contracts(
 
 //--------------------------
 // GET /api/get_dentist_contract/{:id}
-// returns a single contract with rates:
+// returns a single contract with its current and scheduled rates:
 // {
 //   contract: {id, name, description, active },
-//   rates: [{id, dentist_contract_id, service_id, service_name, rate }]
+//   rates: [{id, dentist_contract_id, service_id, service_name, rate, effective_from, effective_to }],
+//   scheduled_rates: [...]
 // }
 //
 //--------------------------
@@ -153,14 +180,21 @@ pub async fn get_dentist_contract(
         last_modified_on: contract.last_modified_on,
     };
 
-    // 3) Fetch service rates + join dental_service to get service_name
+    // 3) Fetch service rates in force today or later + join dental_service to get service_name
     //
     // We do a left join via `find_also_related`, so even if service_id is NULL
     // or the related dental_service row is missing, you still get the rate row.
+    let today = business_calendar::today();
     let rate_pairs: Vec<(dentist_contract_service_rates::Model, Option<dental_service::Model>)> =
         dentist_contract_service_rates::Entity::find()
             .filter(dentist_contract_service_rates::Column::DentistContractId.eq(id))
+            .filter(
+                Condition::any()
+                    .add(dentist_contract_service_rates::Column::EffectiveTo.is_null())
+                    .add(dentist_contract_service_rates::Column::EffectiveTo.gte(today)),
+            )
             .find_also_related(dental_service::Entity)
+            .order_by_asc(dentist_contract_service_rates::Column::EffectiveFrom)
             .order_by_asc(dentist_contract_service_rates::Column::Id)
             .all(&state.db)
             .await
@@ -169,20 +203,15 @@ pub async fn get_dentist_contract(
                 StatusCode::INTERNAL_SERVER_ERROR
             })?;
 
-    let rates = rate_pairs
+    let (scheduled_rates, rates): (Vec<_>, Vec<_>) = rate_pairs
         .into_iter()
-        .map(|(rate_model, svc_opt)| DentistContractServiceRateRow {
-            id: rate_model.id,
-            dentist_contract_id: rate_model.dentist_contract_id,
-            service_id: rate_model.service_id,
-            service_name: svc_opt.map(|s| s.name),
-            rate: rate_model.rate,
-        })
-        .collect();
+        .map(|(rate_model, svc_opt)| DentistContractServiceRateRow::new(rate_model, svc_opt))
+        .partition(|row| row.effective_from > today);
 
     Ok(Json(DentistContractWithRates {
         contract: contract_row,
         rates,
+        scheduled_rates,
     }))
 }
 
@@ -198,6 +227,8 @@ pub struct CreateDentistContractRequest {
 
     // Optional: create initial rates in the same request
     pub rates: Option<Vec<UpsertDentistContractRateRequest>>,
+    // When the rates take effect; defaults to today
+    pub effective_from: Option<NaiveDate>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -206,59 +237,58 @@ pub struct PatchDentistContractRequest {
     pub description: Option<String>,
     pub active: Option<bool>,
 
-    // Optional: if present, we REPLACE ALL rates
+    // Optional: if present, these become ALL the rates from effective_from
     // If absent, we leave rates unchanged.
     pub rates: Option<Vec<UpsertDentistContractRateRequest>>,
+    // When the rates take effect; defaults to today
+    pub effective_from: Option<NaiveDate>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct UpsertDentistContractRateRequest {
     pub service_id: i32,
-    pub rate: f32,
+    pub rate: Decimal,
 }
 
 /*
  * POST and PATCH rate handlers.
  */
 
+// Makes `rates` the contract's rates from `effective_from` (today when absent): listed
+// services get a new rate version, the others' rates end the day before. Rates in force
+// earlier are kept, so older verifications stay priced at the rates of the time.
 async fn replace_all_rates(
     tx: &DatabaseTransaction,
     dentist_contract_id: i32,
     rates: Vec<UpsertDentistContractRateRequest>,
-) -> Result<(), DbErr> {
-    use sea_orm::DbErr;
-
-    // 1) Delete existing rates for this contract
-    dentist_contract_service_rates::Entity::delete_many()
-        .filter(dentist_contract_service_rates::Column::DentistContractId.eq(dentist_contract_id))
-        .exec(tx)
+    effective_from: Option<NaiveDate>,
+    modified_by: &str,
+) -> Result<(), RateScheduleError> {
+    let today = business_calendar::today();
+    let rates: Vec<(i32, Decimal)> = rates.into_iter().map(|r| (r.service_id, r.rate)).collect();
+    schedule_contract_rates(
+        tx,
+        dentist_contract_id,
+        effective_from.unwrap_or(today),
+        &rates,
+        true,
+        modified_by,
+        today,
+    )
         .await?;
-
-    // 2) Insert new rates
-    for r in rates {
-        // Optional: validate (avoid negatives)
-        if r.rate.is_nan() || r.rate < 0.0 {
-            return Err(DbErr::Custom(format!(
-                "Invalid rate for service_id {}: {}",
-                r.service_id, r.rate
-            )));
-        }
-
-        let am = dentist_contract_service_rates::ActiveModel {
-            id: NotSet, // ignored by auto-increment; safe with SeaORM
-            dentist_contract_id: Set(dentist_contract_id),
-            service_id: Set(r.service_id),
-            rate: Set(r.rate),
-        };
-
-        am.insert(tx).await?;
-    }
-
     Ok(())
 }
+
+// The StatusCode a failed rate change is reported with.
+fn rate_error_status(e: RateScheduleError) -> StatusCode {
+    let (status, message) = e.into();
+    tracing::error!("Failed to change contract rates: {message}");
+    status
+}
+
 /*
  * POST /api/post_dentist_contract/
- * data: {name, description, active, rates: [{service_id, rate}]}
+ * data: {name, description, active, rates: [{service_id, rate}], effective_from}
  *
 */
 
@@ -317,12 +347,9 @@ pub async fn post_dentist_contract(
     // 2) Optional rates
     if let Some(rates) = payload.rates {
         // Replace-all on create means "insert these"
-        replace_all_rates(&tx, contract_model.id, rates)
+        replace_all_rates(&tx, contract_model.id, rates, payload.effective_from, &user.claims.email)
             .await
-            .map_err(|e| {
-                tracing::error!("Failed to insert contract rates: {e:?}");
-                StatusCode::INTERNAL_SERVER_ERROR
-            })?;
+            .map_err(rate_error_status)?;
     }
 
     tx.commit().await.map_err(|e| {
@@ -337,7 +364,7 @@ pub async fn post_dentist_contract(
 
 /*
  * PATCH /api/patch_dentist_contract/{:id}
- * data: {name, description, active, rates: [{service_id, rate}]}
+ * data: {name, description, active, rates: [{service_id, rate}], effective_from}
  */
 
 
@@ -407,10 +434,9 @@ pub async fn patch_dentist_contract(
 
     // 3) Optional: replace rates
     if let Some(rates) = payload.rates {
-        replace_all_rates(&tx, id, rates).await.map_err(|e| {
-            tracing::error!("Failed to replace rates for contract {id}: {e:?}");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        replace_all_rates(&tx, id, rates, payload.effective_from, &user.claims.email)
+            .await
+            .map_err(rate_error_status)?;
     }
 
     tx.commit().await.map_err(|e| {
@@ -425,11 +451,13 @@ pub async fn patch_dentist_contract(
 #[derive(Debug, Deserialize)]
 pub struct PatchDentistContractRatesRequest {
     pub rates: Vec<UpsertDentistContractRateRequest>,
+    // When the rates take effect; defaults to today
+    pub effective_from: Option<NaiveDate>,
 }
 
 /*
  * PATCH /api/patch_dentist_contract_rates/{:id}
- * data: {rates: [{service_id, rate}]}
+ * data: {rates: [{service_id, rate}], effective_from}
  * 
  */
 #[instrument(skip(state), err(Debug))]
@@ -474,10 +502,9 @@ pub async fn patch_dentist_contract_rates(
         return Err(StatusCode::NOT_FOUND);
     }
 
-    replace_all_rates(&tx, id, payload.rates).await.map_err(|e| {
-        tracing::error!("Failed to replace rates for contract {id}: {e:?}");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    replace_all_rates(&tx, id, payload.rates, payload.effective_from, &user.claims.email)
+        .await
+        .map_err(rate_error_status)?;
 
     // Optional: also update audit fields on the contract when rates change
     // (recommended so UI shows last_modified_on changes when rates are edited)
@@ -509,3 +536,97 @@ pub async fn patch_dentist_contract_rates(
     get_dentist_contract(State(state), user, Path(id)).await
 }
 
+
+
+/*
+ * Rate history and scheduled rate changes.
+ */
+#[derive(Debug, Deserialize)]
+pub struct DentistContractRateHistoryQuery {
+    pub service_id: Option<i32>,
+}
+
+/*
+ * GET /api/dentist_contracts/{:id}/rate_history?service_id=
+ * returns every rate version of the contract, past, current and scheduled, by service and date.
+ */
+#[instrument(skip(state), err(Debug))]
+pub async fn get_dentist_contract_rate_history(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+    Query(params): Query<DentistContractRateHistoryQuery>,
+) -> Result<Json<Vec<DentistContractServiceRateRow>>, (StatusCode, String)> {
+    dentist_contract::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or(RateScheduleError::ContractNotFound(id))?;
+
+    let mut query = dentist_contract_service_rates::Entity::find()
+        .filter(dentist_contract_service_rates::Column::DentistContractId.eq(id));
+    if let Some(service_id) = params.service_id {
+        query = query.filter(dentist_contract_service_rates::Column::ServiceId.eq(service_id));
+    }
+    let mut rows: Vec<DentistContractServiceRateRow> = query
+        .find_also_related(dental_service::Entity)
+        .all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .into_iter()
+        .map(|(rate_model, svc_opt)| DentistContractServiceRateRow::new(rate_model, svc_opt))
+        .collect();
+    rows.sort_by(|a, b| {
+        a.service_name
+            .cmp(&b.service_name)
+            .then(a.service_id.cmp(&b.service_id))
+            .then(a.effective_from.cmp(&b.effective_from))
+    });
+    Ok(Json(rows))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleDentistContractRatesRequest {
+    pub effective_from: NaiveDate,
+    pub rates: Vec<UpsertDentistContractRateRequest>,
+}
+
+/*
+ * POST /api/dentist_contracts/{:id}/rate_schedules
+ * data: {effective_from, rates: [{service_id, rate}]}
+ * puts the listed services' rates in force from effective_from (today or later); the other
+ * services keep their rates.
+ */
+#[instrument(skip(state), err(Debug))]
+pub async fn post_dentist_contract_rate_schedule(
+    State(state): State<AppState>,
+    user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<ScheduleDentistContractRatesRequest>,
+) -> Result<Json<Vec<DentistContractServiceRateRow>>, (StatusCode, String)> {
+    if payload.rates.is_empty() {
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, "rates must not be empty".to_string()));
+    }
+    let tx = state.db.begin().await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let rates: Vec<(i32, Decimal)> = payload.rates.iter().map(|r| (r.service_id, r.rate)).collect();
+    schedule_contract_rates(&tx, id, payload.effective_from, &rates, false, &user.claims.email, business_calendar::today())
+        .await?;
+    tx.commit().await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    get_dentist_contract_rate_history(State(state), Path(id), Query(DentistContractRateHistoryQuery { service_id: None }))
+        .await
+}
+
+/*
+ * DELETE /api/dentist_contracts/{:id}/rate_schedules/{:rate_id}
+ * cancels a rate version that has not started yet.
+ */
+#[instrument(skip(state), err(Debug))]
+pub async fn delete_dentist_contract_rate_schedule(
+    State(state): State<AppState>,
+    Path((id, rate_id)): Path<(i32, i32)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let tx = state.db.begin().await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    cancel_scheduled_rate(&tx, id, rate_id, business_calendar::today()).await?;
+    tx.commit().await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -36,6 +36,7 @@ use axum::{
     Json,
 };
 use crate::AppState;
+use crate::business_calendar;
 use sea_orm::{ColumnTrait, Condition, ConnectionTrait, EntityTrait, JoinType, QueryFilter, QuerySelect, RelationTrait,};
 use serde::Serialize;
 use tracing::instrument;
@@ -47,7 +48,6 @@ use crate::entities::{
     hmo,
 };
 
-use chrono::Utc;
 
 pub async fn get_endorsement_ids_for_dentist_id<C>(
     db: &C,
@@ -173,7 +173,7 @@ pub async fn get_endorsements_for_dentist_id<C>(
 where
     C: ConnectionTrait,
 {
-    let today = Utc::now().date_naive();
+    let today = business_calendar::today();
 
     // Get all company relations for this dentist
     let company_relations = dentist_company_relations::Entity::find()
@@ -1,24 +1,23 @@
-use std::collections::HashSet;
-use std::io::Cursor;
-
 use axum::{
     extract::{Extension, Multipart, Path, State},
     http::StatusCode,
     Json,
 };
-use calamine::{Data, Reader, Xlsx};
 use chrono::Utc;
-use sea_orm::{
-    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, QueryFilter, Set, TransactionTrait,
-};
+use sea_orm::{ActiveModelTrait, EntityTrait, Set, TransactionTrait};
 use serde::Serialize;
 use tracing::instrument;
 
 use crate::{
     AppState,
-    entities::{endorsement, master_list, master_list_member},
+    entities::{endorsement, master_list},
     handlers::structs::AuthUser,
 };
+use super::master_list_import::classify::classify_rows;
+use super::master_list_import::delta::insert_members;
+use super::master_list_import::parsing::{
+    map_rows, read_table, ColumnMapping, CsvOptions, FileFormat, MappedRow, RowError,
+};
 
 /*
 UploadMasterListMemberRow is a row from the spreadsheet
@@ -76,22 +75,32 @@ pub struct UploadEndorsementMasterListResponse {
     pub skipped_corporate_number_mismatch_count: usize,
     pub duplicate_count: usize,
     pub duplicates: Vec<DuplicateRowResponse>,
+    /// Problems with rows that were left out: a blank required field, or a bad birth date,
+    /// email address or mobile number
+    pub error_count: usize,
+    pub errors: Vec<RowError>,
 }
 
-/*
-Collection of XL row information that will be saved
-*/
-#[derive(Debug, Clone)]
-struct PendingMasterListMemberRow {
-    account_number: String,
-    last_name: String,
-    first_name: String,
-    middle_name: String,
+impl From<MappedRow> for UploadedMasterListMemberRow {
+    fn from(row: MappedRow) -> Self {
+        Self {
+            row_number: row.row_number,
+            corporate_number: row.corporate_number.unwrap_or_default(),
+            account_number: row.account_number,
+            last_name: row.last_name,
+            first_name: row.first_name,
+            middle_name: row.middle_name,
+        }
+    }
 }
 
 /// POST /api/endorsements/:endorsement_id/master-list/upload
 ///
-/// Expects multipart/form-data with a file field named "file".
+/// Expects multipart/form-data with a file field named "file": XLSX, XLS, ODS, CSV or a
+/// JSON array, told apart by content and extension. Columns are found from the header row,
+/// or else read from the fixed positions F to J. Rows go through the same validation and
+/// duplicate checks as the master list preview; rows that fail validation are not inserted and
+/// their problems are listed in errors.
 #[instrument(skip(state, multipart), err(Debug))]
 pub async fn upload_endorsement_master_list(
     State(state): State<AppState>,
@@ -108,13 +117,14 @@ pub async fn upload_endorsement_master_list(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .ok_or(StatusCode::NOT_FOUND)?;
 
-    // 2) Extract agreement_corp_number from endorsement
-    let agreement_corp_number = endorsement_row
+    // 2) The endorsement must have an agreement_corp_number to check rows against
+    if endorsement_row
         .agreement_corp_number
-        .clone()
-        .ok_or(StatusCode::BAD_REQUEST)?
-        .trim()
-        .to_string();
+        .as_deref()
+        .is_none_or(|s| s.trim().is_empty())
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
 
     // 3) Read uploaded file from multipart
     let mut uploaded_file_name: Option<String> = None;
@@ -138,14 +148,14 @@ pub async fn upload_endorsement_master_list(
     let file_name = uploaded_file_name.unwrap_or_else(|| "uploaded_master_list.xlsx".to_string());
     let file_bytes = uploaded_file_bytes.ok_or(StatusCode::BAD_REQUEST)?;
 
-    // 4) Parse XLSX as an Excel worksbook, reading the first sheet.
-    let cursor = Cursor::new(file_bytes);
-    let mut workbook: Xlsx<_> = Xlsx::new(cursor).map_err(|_| StatusCode::BAD_REQUEST)?;
-
-    let range = workbook
-        .worksheet_range_at(0)
-        .ok_or(StatusCode::BAD_REQUEST)?
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    // 4) Parse the file and map its columns
+    let format = FileFormat::detect(&file_name, &file_bytes).ok_or(StatusCode::BAD_REQUEST)?;
+    let table = read_table(file_bytes, format, &CsvOptions::default()).map_err(|e| {
+        tracing::warn!(error = %e, "could not read uploaded master list");
+        StatusCode::BAD_REQUEST
+    })?;
+    let mapping = ColumnMapping::detect(&table, 1).unwrap_or_else(ColumnMapping::legacy);
+    let sheet = map_rows(&table, &mapping).map_err(|_| StatusCode::BAD_REQUEST)?;
 
     // 5) Start transaction
     let txn = state
@@ -154,206 +164,69 @@ pub async fn upload_endorsement_master_list(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let uploaded_by = Some(auth_user.claims.email.clone());
-    // ------5a) Prepare variable vectors for duplicates, pending rows, and inserted rows
-    let mut duplicates: Vec<DuplicateRowResponse> = Vec::new();
-    let mut pending_rows: Vec<PendingMasterListMemberRow> = Vec::new();
-    let mut pending_account_numbers: HashSet<String> = HashSet::new();
-    let mut inserted_rows: Vec<InsertedMasterListMemberRow> = Vec::new();
-    let mut inserted_count = 0usize;
-    let mut skipped_corporate_number_mismatch_count = 0usize;
-
-    // Skip header row
-    for (zero_based_idx, row) in range.rows().enumerate().skip(1) {
-        let row_number = zero_based_idx + 1;
-
-        // zero-based indexes:
-        // 5 = CorporateNumber
-        // 6 = AccountNumber
-        // 7 = LastName
-        // 8 = FirstName
-        // 9 = MiddleName
-        let corporate_number = get_cell_string(row, 5);
-        let account_number = get_cell_string(row, 6);
-        let last_name = get_cell_string(row, 7);
-        let first_name = get_cell_string(row, 8);
-        let middle_name = get_cell_string(row, 9);
-
-        tracing::info!(
-            "row_number:{} corporate_number:{} account_number:{} last_name:{} first_name:{} middle_name:{}",
-            row_number,
-            corporate_number,
-            account_number,
-            last_name,
-            first_name,
-            middle_name
-        );
-
-        if account_number.is_empty() || corporate_number.is_empty() {
-            continue;
-        }
-
-        // Skip rows whose corporate number does not match the endorsement's agreement_corp_number
-        if corporate_number != agreement_corp_number {
-            skipped_corporate_number_mismatch_count += 1;
-            continue;
-        }
-
-        let uploaded_row = UploadedMasterListMemberRow {
-            row_number,
-            corporate_number,
-            account_number: account_number.clone(),
-            last_name: last_name.clone(),
-            first_name: first_name.clone(),
-            middle_name: middle_name.clone(),
-        };
-
-        // Ignore duplicate account numbers within the same uploaded file
-        if pending_account_numbers.contains(&account_number) {
-            continue;
-        }
-
-        // Exact same account + exact same names already in DB for this endorsement => ignore
-        let exact_existing = match master_list_member::Entity::find()
-            .filter(master_list_member::Column::EndorsementId.eq(endorsement_id))
-            .filter(master_list_member::Column::AccountNumber.eq(account_number.clone()))
-            .filter(master_list_member::Column::LastName.eq(last_name.clone()))
-            .filter(master_list_member::Column::FirstName.eq(first_name.clone()))
-            .filter(master_list_member::Column::MiddleName.eq(middle_name.clone()))
-            .one(&txn)
-            .await
-        {
-            Ok(existing) => existing,
-            Err(_) => {
-                let _ = txn.rollback().await;
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
-        };
-
-        if exact_existing.is_some() {
-            continue;
-        }
-
-        // Same account number but different name fields for this endorsement => duplicate
-        let existing = match master_list_member::Entity::find()
-            .filter(master_list_member::Column::EndorsementId.eq(endorsement_id))
-            .filter(master_list_member::Column::AccountNumber.eq(account_number.clone()))
-            .filter(
-                Condition::any()
-                    .add(master_list_member::Column::LastName.ne(last_name.clone()))
-                    .add(master_list_member::Column::FirstName.ne(first_name.clone()))
-                    .add(master_list_member::Column::MiddleName.ne(middle_name.clone())),
-            )
-            .one(&txn)
-            .await
-        {
-            Ok(existing) => existing,
-            Err(_) => {
-                let _ = txn.rollback().await;
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
-        };
+    let uploaded_by = auth_user.claims.email.clone();
 
-        if let Some(existing) = existing {
-            duplicates.push(DuplicateRowResponse {
-                uploaded_row,
-                existing_row: ExistingDuplicateRow {
-                    id: existing.id,
-                    endorsement_id: existing.endorsement_id,
-                    master_list_id: existing.master_list_id,
-                    account_number: existing.account_number,
-                    last_name: existing.last_name,
-                    first_name: existing.first_name,
-                    middle_name: existing.middle_name,
-                    email_address: existing.email_address,
-                    mobile_number: existing.mobile_number,
-                    birth_date: existing.birth_date.map(|d| d.to_string()),
-                    is_active: existing.is_active,
-                },
-            });
-            continue;
-        }
-
-        pending_account_numbers.insert(account_number.clone());
-        pending_rows.push(PendingMasterListMemberRow {
-            account_number,
-            last_name,
-            first_name,
-            middle_name,
-        });
-    }
+    // ------5a) Sort rows into new members, duplicates and corporate number mismatches
+    let classification = classify_rows(&txn, &endorsement_row, sheet.rows)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    tracing::info!(pending_rows_count = pending_rows.len(),
-    duplicates_count = duplicates.len(),
-    skipped_corporate_number_mismatch_count = skipped_corporate_number_mismatch_count,
+    tracing::info!(pending_rows_count = classification.to_insert.len(),
+    duplicates_count = classification.duplicates_existing.len(),
+    skipped_corporate_number_mismatch_count = classification.corporate_number_mismatches.len(),
+    invalid_row_count = sheet.errors.len(),
     "finished parsing uploaded file");
 
     // 6) Create master_list
-    let new_master_list = master_list::ActiveModel {
+    let now = Utc::now().fixed_offset();
+    let master_list_row = master_list::ActiveModel {
         file_name: Set(file_name.clone()),
         endorsement_id: Set(Some(endorsement_id)),
-        uploaded_by: Set(uploaded_by.clone()),
-        upload_date: Set(Some(Utc::now().fixed_offset())),
+        uploaded_by: Set(Some(uploaded_by.clone())),
+        upload_date: Set(Some(now)),
         ..Default::default()
-    };
-
-    let master_list_row = match new_master_list.insert(&txn).await {
-        Ok(row) => row,
-        Err(_) => {
-            let _ = txn.rollback().await;
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
+    }
+        .insert(&txn)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // 7) Insert master_list_member rows
-    for pending in pending_rows {
-        let inserted_row_for_response = InsertedMasterListMemberRow {
-            account_number: pending.account_number.clone(),
-            last_name: pending.last_name.clone(),
-            first_name: pending.first_name.clone(),
-            middle_name: pending.middle_name.clone(),
-        };
-
-        let insert_result = master_list_member::ActiveModel {
-            endorsement_id: Set(endorsement_id),
-            master_list_id: Set(Some(master_list_row.id)),
-            account_number: Set(pending.account_number),
-            last_name: Set(pending.last_name),
-            first_name: Set(pending.first_name),
-            middle_name: Set(pending.middle_name),
-            email_address: Set(None),
-            mobile_number: Set(None),
-            birth_date: Set(None),
-            is_active: Set(true),
-            last_edited_by: Set(uploaded_by.clone()),
-            last_edited_date: Set(Utc::now().fixed_offset()),
-            ..Default::default()
-        }
-            .insert(&txn)
-            .await;
-
-        match insert_result {
-            Ok(_) => {
-                inserted_count += 1;
-                inserted_rows.push(inserted_row_for_response);
-            }
-            Err(_) => {
-                let _ = txn.rollback().await;
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
-        }
-    }
+    insert_members(&txn, endorsement_id, master_list_row.id, &classification.to_insert, &uploaded_by, now)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     txn.commit()
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let inserted_count = classification.to_insert.len();
     let status = if inserted_count >0 {
         StatusCode::CREATED
     } else{
         StatusCode::OK
     };
 
+    let duplicates: Vec<DuplicateRowResponse> = classification
+        .duplicates_existing
+        .into_iter()
+        .map(|d| DuplicateRowResponse {
+            uploaded_row: d.uploaded_row.into(),
+            existing_row: ExistingDuplicateRow {
+                id: d.existing_row.id,
+                endorsement_id: d.existing_row.endorsement_id,
+                master_list_id: d.existing_row.master_list_id,
+                account_number: d.existing_row.account_number,
+                last_name: d.existing_row.last_name,
+                first_name: d.existing_row.first_name,
+                middle_name: d.existing_row.middle_name,
+                email_address: d.existing_row.email_address,
+                mobile_number: d.existing_row.mobile_number,
+                birth_date: d.existing_row.birth_date.map(|d| d.to_string()),
+                is_active: d.existing_row.is_active,
+            },
+        })
+        .collect();
+
     Ok((
         status,
         Json(UploadEndorsementMasterListResponse {
@@ -361,31 +234,21 @@ pub async fn upload_endorsement_master_list(
             file_name,
             endorsement_id,
             inserted_count,
-            inserted_rows,
-            skipped_corporate_number_mismatch_count,
+            inserted_rows: classification
+                .to_insert
+                .into_iter()
+                .map(|row| InsertedMasterListMemberRow {
+                    account_number: row.account_number,
+                    last_name: row.last_name,
+                    first_name: row.first_name,
+                    middle_name: row.middle_name,
+                })
+                .collect(),
+            skipped_corporate_number_mismatch_count: classification.corporate_number_mismatches.len(),
             duplicate_count: duplicates.len(),
             duplicates,
+            error_count: sheet.errors.len(),
+            errors: sheet.errors,
         }),
     ))
 }
-
-fn get_cell_string(row: &[Data], idx: usize) -> String {
-    match row.get(idx) {
-        Some(Data::String(s)) => s.trim().to_string(),
-        Some(Data::Float(f)) => {
-            if f.fract() == 0.0 {
-                (*f as i64).to_string()
-            } else {
-                f.to_string()
-            }
-        }
-        Some(Data::Int(i)) => i.to_string(),
-        Some(Data::Bool(b)) => b.to_string(),
-        Some(Data::DateTime(dt)) => dt.to_string(),
-        Some(Data::DateTimeIso(s)) => s.trim().to_string(),
-        Some(Data::DurationIso(s)) => s.trim().to_string(),
-        Some(Data::Error(_)) => String::new(),
-        Some(Data::Empty) => String::new(),
-        None => String::new(),
-    }
-}
\ No newline at end of file
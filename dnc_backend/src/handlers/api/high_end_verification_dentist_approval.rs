@@ -16,7 +16,8 @@ use crate::{
     hmo, master_list_member,
     verification, verification_status,
 },
-    handlers::AuthUser
+    handlers::AuthUser,
+    verification_state::{self, VerificationStatus},
 };
 
 #[derive(Debug, Serialize)]
@@ -237,14 +238,15 @@ pub async fn post_high_end_verification_approval(
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // ✅ Update verification.status_id to 3:"dentist quoted; waiting for approval code".
-    let mut verification_am: verification::ActiveModel = verification_model.into();
-    verification_am.status_id = Set(3);
-
-    verification_am
-        .update(&txn)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // ✅ Move the verification to "Dentist-quoted; waiting for approval code".
+    let updated_verification = verification_state::transition(
+        &txn,
+        verification_model.into(),
+        VerificationStatus::DentistQuoted,
+        &auth.claims.email,
+        None,
+    )
+        .await?;
 
     txn.commit()
         .await
@@ -257,7 +259,7 @@ pub async fn post_high_end_verification_approval(
         approved_cost: inserted.approved_cost,
         approval_date: inserted.approval_date,
         dentist_notes: inserted.dentist_notes,
-        verification_status_id: 1,
+        verification_status_id: updated_verification.status_id,
     }))
 }
 // endregion: post_high_end_verification_approval
@@ -18,6 +18,8 @@ use tokio::fs;
 use uuid::Uuid;
 use crate::AppState;
 use crate::entities::{high_end_files, verification};
+use crate::handlers::AuthUser;
+use crate::verification_state::{self, VerificationStatus};
 
 use serde::Serialize;
 use tokio::io::AsyncWriteExt;
@@ -99,6 +101,7 @@ fn internal_error<E: std::fmt::Display>(err: E) -> (StatusCode, String) {
 // region: upload_high_end_file()
 pub async fn upload_high_end_file(
     State(state): State<AppState>,
+    auth_user: AuthUser,
     Path(verification_id): Path<i32>,
     mut multipart: Multipart,
 ) -> Result<Json<UploadedHighEndFileResponse>, (StatusCode, String)> {
@@ -202,20 +205,24 @@ pub async fn upload_high_end_file(
         .await
         .map_err(internal_error)?;
 
-    // ---- 10. Update the verification model.
+    // ---- 10. The first upload moves the verification to "Waiting for Dentist Approval";
+    // later uploads leave the status alone.
     let verification_model = verification::Entity::find_by_id(verification_id)
         .one(db)
         .await
         .map_err(internal_error)?
         .ok_or((StatusCode::NOT_FOUND, "Verification not found".to_string()))?;
 
-    let mut verification_active_model : verification::ActiveModel = verification_model.into();
-    verification_active_model.status_id= Set(21);
-
-    verification_active_model
-        .update(db)
-        .await
-        .map_err(internal_error)?;
+    if verification_state::status_of(&verification_model)? == VerificationStatus::WaitingForFiles {
+        verification_state::transition(
+            db,
+            verification_model.into(),
+            VerificationStatus::WaitingForDentistApproval,
+            &auth_user.claims.email,
+            None,
+        )
+            .await?;
+    }
 
     // ---- 11. Return the result.
     Ok(Json(UploadedHighEndFileResponse {
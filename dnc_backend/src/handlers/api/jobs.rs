@@ -0,0 +1,117 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tracing::instrument;
+
+use crate::AppState;
+use crate::entities::job;
+use crate::handlers::AuthUser;
+use crate::jobs::queue::{enqueue, retry_job, Job, JobError, JobStatus};
+
+// region: get_jobs
+#[derive(Debug, Deserialize)]
+pub struct JobQuery {
+    pub status: Option<String>,
+    pub job_type: Option<String>,
+    pub limit: Option<u64>,
+}
+
+/// GET /api/jobs?status=&job_type=&limit=
+/// Job history, newest first.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_jobs(
+    State(state): State<AppState>,
+    Query(params): Query<JobQuery>,
+) -> Result<Json<Vec<job::Model>>, (StatusCode, String)> {
+    let mut query = job::Entity::find();
+    if let Some(status) = params.status {
+        let status = JobStatus::parse(&status)
+            .ok_or((StatusCode::BAD_REQUEST, format!("Unknown job status '{}'", status)))?;
+        query = query.filter(job::Column::Status.eq(status.as_str()));
+    }
+    if let Some(job_type) = params.job_type {
+        query = query.filter(job::Column::JobType.eq(job_type));
+    }
+
+    let rows = query
+        .order_by_desc(job::Column::CreatedOn)
+        .order_by_desc(job::Column::Id)
+        .limit(params.limit.unwrap_or(200).clamp(1, 1000))
+        .all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(rows))
+}
+// endregion: get_jobs
+
+
+// region: get_job
+/// GET /api/jobs/{id}
+#[instrument(skip(state), err(Debug))]
+pub async fn get_job(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<Json<job::Model>, (StatusCode, String)> {
+    let row = job::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| JobError::NotFound(id))?;
+
+    Ok(Json(row))
+}
+// endregion: get_job
+
+
+// region: post_job
+#[derive(Debug, Deserialize)]
+pub struct CreateJobRequest {
+    /// one of jobs::queue::JOB_TYPES
+    pub job_type: String,
+    pub payload: Option<JsonValue>,
+}
+
+/// POST /api/jobs
+/// Queues a job to run now, e.g. {"job_type": "hmo_billing", "payload": {"period_start":
+/// "2026-09-01", "period_end": "2026-09-30"}}.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_job(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Json(payload): Json<CreateJobRequest>,
+) -> Result<(StatusCode, Json<job::Model>), (StatusCode, String)> {
+    let job = Job::parse(&payload.job_type, payload.payload.as_ref())?;
+    if let Job::HmoBilling(p) = &job {
+        if p.period_start > p.period_end {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "period_start must be earlier than or equal to period_end".to_string(),
+            ));
+        }
+    }
+
+    let queued = enqueue(&state.db, &job, None, &auth_user.claims.email).await?;
+    Ok((StatusCode::CREATED, Json(queued)))
+}
+// endregion: post_job
+
+
+// region: post_retry_job
+/// POST /api/jobs/{id}/retry
+/// Queues a failed job again with a fresh set of attempts.
+#[instrument(skip(state), err(Debug))]
+pub async fn post_retry_job(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+) -> Result<Json<job::Model>, (StatusCode, String)> {
+    let row = retry_job(&state.db, id, &auth_user.claims.email).await?;
+    Ok(Json(row))
+}
+// endregion: post_retry_job
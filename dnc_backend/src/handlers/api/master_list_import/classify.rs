@@ -0,0 +1,96 @@
+use std::collections::{HashMap, HashSet};
+
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter};
+use serde::Serialize;
+
+use crate::entities::{endorsement, master_list_member};
+use super::parsing::MappedRow;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExistingMemberDuplicate {
+    pub uploaded_row: MappedRow,
+    pub existing_row: master_list_member::Model,
+}
+
+/// What would happen to each valid row of an upload.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UploadClassification {
+    /// New members
+    pub to_insert: Vec<MappedRow>,
+    /// Same account number and names as a member already in the endorsement; ignored
+    pub already_present: Vec<MappedRow>,
+    /// Account number already used by an earlier row of the same file; ignored
+    pub duplicates_in_file: Vec<MappedRow>,
+    /// Same account number as an existing member but different names; not inserted
+    pub duplicates_existing: Vec<ExistingMemberDuplicate>,
+    /// Corporate number differs from the endorsement's agreement_corp_number; not inserted
+    pub corporate_number_mismatches: Vec<MappedRow>,
+}
+
+/// Sorts `rows` the way the master list upload always has: rows for another corporate
+/// number and repeated account numbers are dropped, exact matches of existing members are
+/// ignored, and same-account/different-name matches are reported as duplicates.
+///
+/// Rows without a corporate number (the mapping has no such column) are not checked against
+/// the endorsement's agreement_corp_number.
+pub async fn classify_rows<C: ConnectionTrait>(
+    db: &C,
+    endorsement: &endorsement::Model,
+    rows: Vec<MappedRow>,
+) -> Result<UploadClassification, DbErr> {
+    let agreement_corp_number = endorsement
+        .agreement_corp_number
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default();
+
+    let mut existing_by_account: HashMap<String, Vec<master_list_member::Model>> = HashMap::new();
+    for member in master_list_member::Entity::find()
+        .filter(master_list_member::Column::EndorsementId.eq(endorsement.id))
+        .all(db)
+        .await?
+    {
+        existing_by_account
+            .entry(member.account_number.clone())
+            .or_default()
+            .push(member);
+    }
+
+    let mut result = UploadClassification::default();
+    let mut seen_account_numbers: HashSet<String> = HashSet::new();
+
+    for row in rows {
+        if let Some(corporate_number) = &row.corporate_number {
+            if corporate_number != agreement_corp_number {
+                result.corporate_number_mismatches.push(row);
+                continue;
+            }
+        }
+
+        if !seen_account_numbers.insert(row.account_number.clone()) {
+            result.duplicates_in_file.push(row);
+            continue;
+        }
+
+        match existing_by_account.get(&row.account_number) {
+            Some(existing) if existing.iter().any(|m| same_names(m, &row)) => {
+                result.already_present.push(row);
+            }
+            Some(existing) => {
+                result.duplicates_existing.push(ExistingMemberDuplicate {
+                    uploaded_row: row,
+                    existing_row: existing[0].clone(),
+                });
+            }
+            None => result.to_insert.push(row),
+        }
+    }
+
+    Ok(result)
+}
+
+fn same_names(member: &master_list_member::Model, row: &MappedRow) -> bool {
+    member.last_name == row.last_name
+        && member.first_name == row.first_name
+        && member.middle_name == row.middle_name
+}
@@ -0,0 +1,224 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, ModelTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use tracing::instrument;
+
+use crate::AppState;
+use crate::entities::{hmo, master_list_column_mapping};
+use crate::handlers::AuthUser;
+use super::parsing::ColumnMapping;
+
+fn internal_error(err: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+/// The HMO's default mapping profile, if it has one.
+pub async fn default_mapping_for_hmo<C: ConnectionTrait>(
+    db: &C,
+    hmo_id: i32,
+) -> Result<Option<master_list_column_mapping::Model>, DbErr> {
+    master_list_column_mapping::Entity::find()
+        .filter(master_list_column_mapping::Column::HmoId.eq(hmo_id))
+        .filter(master_list_column_mapping::Column::IsDefault.eq(true))
+        .one(db)
+        .await
+}
+
+/// Only one default profile per HMO: clears the flag on the others.
+async fn clear_other_defaults<C: ConnectionTrait>(db: &C, hmo_id: i32, keep_id: i32) -> Result<(), DbErr> {
+    let others = master_list_column_mapping::Entity::find()
+        .filter(master_list_column_mapping::Column::HmoId.eq(hmo_id))
+        .filter(master_list_column_mapping::Column::IsDefault.eq(true))
+        .filter(master_list_column_mapping::Column::Id.ne(keep_id))
+        .all(db)
+        .await?;
+    for other in others {
+        let mut am: master_list_column_mapping::ActiveModel = other.into();
+        am.is_default = Set(false);
+        am.update(db).await?;
+    }
+    Ok(())
+}
+
+fn validate_columns(header_row: i32, columns: &JsonValue) -> Result<(), (StatusCode, String)> {
+    ColumnMapping::from_profile(header_row, columns)
+        .map(|_| ())
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))
+}
+
+// region: get_master_list_column_mappings
+/// GET /api/hmos/{hmo_id}/master_list_mappings
+#[instrument(skip(state), err(Debug))]
+pub async fn get_master_list_column_mappings(
+    State(state): State<AppState>,
+    Path(hmo_id): Path<i32>,
+) -> Result<Json<Vec<master_list_column_mapping::Model>>, (StatusCode, String)> {
+    let rows = master_list_column_mapping::Entity::find()
+        .filter(master_list_column_mapping::Column::HmoId.eq(hmo_id))
+        .order_by_desc(master_list_column_mapping::Column::IsDefault)
+        .order_by_asc(master_list_column_mapping::Column::Name)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(rows))
+}
+// endregion: get_master_list_column_mappings
+
+
+// region: post_master_list_column_mapping
+#[derive(Debug, Deserialize)]
+pub struct CreateMasterListColumnMappingRequest {
+    pub name: String,
+    pub header_row: Option<i32>,
+    /// e.g. {"account_number": "Member ID", "last_name": 2, "birth_date": "DOB"}
+    pub columns: JsonValue,
+    #[serde(default)]
+    pub is_default: bool,
+}
+
+/// POST /api/hmos/{hmo_id}/master_list_mappings
+#[instrument(skip(state), err(Debug))]
+pub async fn post_master_list_column_mapping(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(hmo_id): Path<i32>,
+    Json(payload): Json<CreateMasterListColumnMappingRequest>,
+) -> Result<(StatusCode, Json<master_list_column_mapping::Model>), (StatusCode, String)> {
+    // ---1 validate
+    hmo::Entity::find_by_id(hmo_id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "HMO not found".to_string()))?;
+
+    let name = payload.name.trim().to_string();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "name is required".to_string()));
+    }
+    let header_row = payload.header_row.unwrap_or(1);
+    validate_columns(header_row, &payload.columns)?;
+
+    let existing = master_list_column_mapping::Entity::find()
+        .filter(master_list_column_mapping::Column::HmoId.eq(hmo_id))
+        .filter(master_list_column_mapping::Column::Name.eq(name.clone()))
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?;
+    if existing.is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("This HMO already has a mapping named '{}'", name),
+        ));
+    }
+
+    // ---2 save
+    let txn = state.db.begin().await.map_err(internal_error)?;
+
+    let inserted = master_list_column_mapping::ActiveModel {
+        hmo_id: Set(hmo_id),
+        name: Set(name),
+        header_row: Set(header_row),
+        columns: Set(payload.columns),
+        is_default: Set(payload.is_default),
+        last_modified_by: Set(auth_user.claims.email),
+        last_modified_on: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    }
+        .insert(&txn)
+        .await
+        .map_err(internal_error)?;
+
+    if inserted.is_default {
+        clear_other_defaults(&txn, hmo_id, inserted.id).await.map_err(internal_error)?;
+    }
+
+    txn.commit().await.map_err(internal_error)?;
+
+    Ok((StatusCode::CREATED, Json(inserted)))
+}
+// endregion: post_master_list_column_mapping
+
+
+// region: patch_master_list_column_mapping
+#[derive(Debug, Deserialize)]
+pub struct PatchMasterListColumnMappingRequest {
+    pub name: Option<String>,
+    pub header_row: Option<i32>,
+    pub columns: Option<JsonValue>,
+    pub is_default: Option<bool>,
+}
+
+/// PATCH /api/master_list_mappings/{id}
+#[instrument(skip(state), err(Debug))]
+pub async fn patch_master_list_column_mapping(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(id): Path<i32>,
+    Json(payload): Json<PatchMasterListColumnMappingRequest>,
+) -> Result<Json<master_list_column_mapping::Model>, (StatusCode, String)> {
+    let row = master_list_column_mapping::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Master list column mapping not found".to_string()))?;
+
+    let header_row = payload.header_row.unwrap_or(row.header_row);
+    let columns = payload.columns.clone().unwrap_or_else(|| row.columns.clone());
+    validate_columns(header_row, &columns)?;
+
+    let hmo_id = row.hmo_id;
+    let mut am: master_list_column_mapping::ActiveModel = row.into();
+    if let Some(name) = payload.name {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "name cannot be blank".to_string()));
+        }
+        am.name = Set(name);
+    }
+    am.header_row = Set(header_row);
+    am.columns = Set(columns);
+    if let Some(v) = payload.is_default {
+        am.is_default = Set(v);
+    }
+    am.last_modified_by = Set(auth_user.claims.email);
+    am.last_modified_on = Set(Utc::now().fixed_offset());
+
+    let txn = state.db.begin().await.map_err(internal_error)?;
+    let updated = am.update(&txn).await.map_err(internal_error)?;
+    if updated.is_default {
+        clear_other_defaults(&txn, hmo_id, updated.id).await.map_err(internal_error)?;
+    }
+    txn.commit().await.map_err(internal_error)?;
+
+    Ok(Json(updated))
+}
+// endregion: patch_master_list_column_mapping
+
+
+// region: delete_master_list_column_mapping
+/// DELETE /api/master_list_mappings/{id}
+#[instrument(skip(state), err(Debug))]
+pub async fn delete_master_list_column_mapping(
+    State(state): State<AppState>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let row = master_list_column_mapping::Entity::find_by_id(id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Master list column mapping not found".to_string()))?;
+
+    row.delete(&state.db).await.map_err(internal_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+// endregion: delete_master_list_column_mapping
@@ -0,0 +1,246 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, FixedOffset};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, Set,
+};
+use serde::Serialize;
+
+use crate::entities::{endorsement, master_list_member};
+use super::parsing::MappedRow;
+
+/// A field of an existing member that the new list changes.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberChange {
+    pub member_id: i32,
+    pub account_number: String,
+    pub uploaded_row: MappedRow,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MemberDrop {
+    pub member_id: i32,
+    pub account_number: String,
+    pub last_name: String,
+    pub first_name: String,
+    pub middle_name: String,
+}
+
+/// The difference between a replacement list and the endorsement's members, keyed by
+/// account number.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MasterListDelta {
+    /// Account numbers the endorsement has never had
+    pub added: Vec<MappedRow>,
+    /// Inactive members that are back on the list; reactivated (and updated if changed)
+    pub reactivated: Vec<MemberChange>,
+    /// Active members whose names, birth date or contact details differ
+    pub changed: Vec<MemberChange>,
+    /// Active members missing from the new list; deactivated, never deleted
+    pub dropped: Vec<MemberDrop>,
+    pub unchanged_count: usize,
+    /// Account number already used by an earlier row of the same file; ignored
+    pub duplicates_in_file: Vec<MappedRow>,
+    /// Corporate number differs from the endorsement's agreement_corp_number; ignored
+    pub corporate_number_mismatches: Vec<MappedRow>,
+}
+
+/// Compares `rows` (the endorsement's complete new list) with its current members.
+///
+/// A birth date, email address or mobile number that is blank in the file leaves the stored
+/// value alone, since many HMO layouts do not carry those columns.
+pub async fn compute_delta<C: ConnectionTrait>(
+    db: &C,
+    endorsement: &endorsement::Model,
+    rows: Vec<MappedRow>,
+) -> Result<MasterListDelta, DbErr> {
+    let agreement_corp_number = endorsement
+        .agreement_corp_number
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default();
+
+    // Prefer the active member when an account number appears more than once.
+    let mut members_by_account: HashMap<String, master_list_member::Model> = HashMap::new();
+    for member in master_list_member::Entity::find()
+        .filter(master_list_member::Column::EndorsementId.eq(endorsement.id))
+        .order_by_asc(master_list_member::Column::Id)
+        .all(db)
+        .await?
+    {
+        let keep_existing = members_by_account
+            .get(&member.account_number)
+            .is_some_and(|existing| existing.is_active && !member.is_active);
+        if !keep_existing {
+            members_by_account.insert(member.account_number.clone(), member);
+        }
+    }
+
+    let mut delta = MasterListDelta::default();
+    let mut seen_account_numbers: HashSet<String> = HashSet::new();
+
+    for row in rows {
+        if let Some(corporate_number) = &row.corporate_number {
+            if corporate_number != agreement_corp_number {
+                delta.corporate_number_mismatches.push(row);
+                continue;
+            }
+        }
+
+        if !seen_account_numbers.insert(row.account_number.clone()) {
+            delta.duplicates_in_file.push(row);
+            continue;
+        }
+
+        match members_by_account.get(&row.account_number) {
+            None => delta.added.push(row),
+            Some(member) => {
+                let changes = field_changes(member, &row);
+                let change = MemberChange {
+                    member_id: member.id,
+                    account_number: member.account_number.clone(),
+                    uploaded_row: row,
+                    changes,
+                };
+                if !member.is_active {
+                    delta.reactivated.push(change);
+                } else if !change.changes.is_empty() {
+                    delta.changed.push(change);
+                } else {
+                    delta.unchanged_count += 1;
+                }
+            }
+        }
+    }
+
+    let mut dropped: Vec<MemberDrop> = members_by_account
+        .into_values()
+        .filter(|m| m.is_active && !seen_account_numbers.contains(&m.account_number))
+        .map(|m| MemberDrop {
+            member_id: m.id,
+            account_number: m.account_number,
+            last_name: m.last_name,
+            first_name: m.first_name,
+            middle_name: m.middle_name,
+        })
+        .collect();
+    dropped.sort_by(|a, b| a.account_number.cmp(&b.account_number));
+    delta.dropped = dropped;
+
+    Ok(delta)
+}
+
+fn field_changes(member: &master_list_member::Model, row: &MappedRow) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut compare = |field: &'static str, from: Option<String>, to: Option<String>| {
+        if from != to {
+            changes.push(FieldChange { field, from, to });
+        }
+    };
+
+    compare("last_name", Some(member.last_name.clone()), Some(row.last_name.clone()));
+    compare("first_name", Some(member.first_name.clone()), Some(row.first_name.clone()));
+    compare("middle_name", Some(member.middle_name.clone()), Some(row.middle_name.clone()));
+    if let Some(birth_date) = row.birth_date {
+        compare("birth_date", member.birth_date.map(|d| d.to_string()), Some(birth_date.to_string()));
+    }
+    if row.email_address.is_some() {
+        compare("email_address", member.email_address.clone(), row.email_address.clone());
+    }
+    if row.mobile_number.is_some() {
+        compare("mobile_number", member.mobile_number.clone(), row.mobile_number.clone());
+    }
+
+    changes
+}
+
+/// Applies `delta` on `db` (a transaction): inserts the added members under `master_list_id`,
+/// updates changed and reactivated members, and deactivates the dropped ones.
+pub async fn apply_delta<C: ConnectionTrait>(
+    db: &C,
+    endorsement_id: i32,
+    master_list_id: i32,
+    delta: &MasterListDelta,
+    edited_by: &str,
+    now: DateTime<FixedOffset>,
+) -> Result<(), DbErr> {
+    insert_members(db, endorsement_id, master_list_id, &delta.added, edited_by, now).await?;
+
+    for change in delta.changed.iter().chain(delta.reactivated.iter()) {
+        let row = &change.uploaded_row;
+        let mut am = master_list_member::ActiveModel {
+            id: ActiveValue::Unchanged(change.member_id),
+            last_name: Set(row.last_name.clone()),
+            first_name: Set(row.first_name.clone()),
+            middle_name: Set(row.middle_name.clone()),
+            is_active: Set(true),
+            last_edited_by: Set(Some(edited_by.to_string())),
+            last_edited_date: Set(now),
+            ..Default::default()
+        };
+        if row.birth_date.is_some() {
+            am.birth_date = Set(row.birth_date);
+        }
+        if row.email_address.is_some() {
+            am.email_address = Set(row.email_address.clone());
+        }
+        if row.mobile_number.is_some() {
+            am.mobile_number = Set(row.mobile_number.clone());
+        }
+        am.update(db).await?;
+    }
+
+    for dropped in &delta.dropped {
+        master_list_member::ActiveModel {
+            id: ActiveValue::Unchanged(dropped.member_id),
+            is_active: Set(false),
+            last_edited_by: Set(Some(edited_by.to_string())),
+            last_edited_date: Set(now),
+            ..Default::default()
+        }
+            .update(db)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Inserts `rows` as active members of the endorsement, from `master_list_id`.
+pub async fn insert_members<C: ConnectionTrait>(
+    db: &C,
+    endorsement_id: i32,
+    master_list_id: i32,
+    rows: &[MappedRow],
+    edited_by: &str,
+    now: DateTime<FixedOffset>,
+) -> Result<(), DbErr> {
+    for row in rows {
+        master_list_member::ActiveModel {
+            endorsement_id: Set(endorsement_id),
+            master_list_id: Set(Some(master_list_id)),
+            account_number: Set(row.account_number.clone()),
+            last_name: Set(row.last_name.clone()),
+            first_name: Set(row.first_name.clone()),
+            middle_name: Set(row.middle_name.clone()),
+            email_address: Set(row.email_address.clone()),
+            mobile_number: Set(row.mobile_number.clone()),
+            birth_date: Set(row.birth_date),
+            is_active: Set(true),
+            last_edited_by: Set(Some(edited_by.to_string())),
+            last_edited_date: Set(now),
+            ..Default::default()
+        }
+            .insert(db)
+            .await?;
+    }
+    Ok(())
+}
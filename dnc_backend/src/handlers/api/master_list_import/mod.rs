@@ -0,0 +1,7 @@
+//! Master list uploads in two steps: a preview that parses the file with a column mapping
+//! and reports what would change, and a commit that applies a preview by its token.
+pub mod parsing;
+pub mod classify;
+pub mod delta;
+pub mod column_mappings;
+pub mod preview;
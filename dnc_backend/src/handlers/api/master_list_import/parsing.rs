@@ -0,0 +1,641 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::Cursor;
+
+use calamine::{Data, Ods, Reader, Xls, Xlsx};
+use chrono::{Duration, NaiveDate};
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+// region: Fields and Mappings
+
+/// The master_list_member fields a file column can be mapped to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MasterListField {
+    CorporateNumber,
+    AccountNumber,
+    LastName,
+    FirstName,
+    MiddleName,
+    BirthDate,
+    EmailAddress,
+    MobileNumber,
+}
+
+impl MasterListField {
+    pub const ALL: [MasterListField; 8] = [
+        MasterListField::CorporateNumber,
+        MasterListField::AccountNumber,
+        MasterListField::LastName,
+        MasterListField::FirstName,
+        MasterListField::MiddleName,
+        MasterListField::BirthDate,
+        MasterListField::EmailAddress,
+        MasterListField::MobileNumber,
+    ];
+
+    /// A row is rejected if any of these is blank.
+    pub const fn is_required(self) -> bool {
+        matches!(
+            self,
+            MasterListField::AccountNumber | MasterListField::LastName | MasterListField::FirstName
+        )
+    }
+
+    pub const fn key(self) -> &'static str {
+        match self {
+            MasterListField::CorporateNumber => "corporate_number",
+            MasterListField::AccountNumber => "account_number",
+            MasterListField::LastName => "last_name",
+            MasterListField::FirstName => "first_name",
+            MasterListField::MiddleName => "middle_name",
+            MasterListField::BirthDate => "birth_date",
+            MasterListField::EmailAddress => "email_address",
+            MasterListField::MobileNumber => "mobile_number",
+        }
+    }
+
+    /// Header texts (lowercase, letters and digits only) recognised when no mapping profile is given.
+    fn header_synonyms(self) -> &'static [&'static str] {
+        match self {
+            MasterListField::CorporateNumber => &["corporatenumber", "corpnumber", "corpno", "corporateno", "agreementcorpnumber", "companycode"],
+            MasterListField::AccountNumber => &["accountnumber", "accountno", "acctno", "acctnumber", "memberid", "membernumber", "memberno", "cardnumber", "cardno"],
+            MasterListField::LastName => &["lastname", "surname", "familyname"],
+            MasterListField::FirstName => &["firstname", "givenname"],
+            MasterListField::MiddleName => &["middlename", "middleinitial", "mi"],
+            MasterListField::BirthDate => &["birthdate", "dateofbirth", "dob", "birthday"],
+            MasterListField::EmailAddress => &["emailaddress", "email", "emailadd"],
+            MasterListField::MobileNumber => &["mobilenumber", "mobileno", "mobile", "cellphonenumber", "cellphoneno", "contactnumber", "contactno"],
+        }
+    }
+}
+
+/// Where a field is in the file: a zero-based column index, or the text of its header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColumnRef {
+    Index(usize),
+    Header(String),
+}
+
+/// How the columns of a file map to master list fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    /// 1-based row that holds the headers; data starts on the row after it.
+    pub header_row: usize,
+    pub columns: BTreeMap<MasterListField, ColumnRef>,
+}
+
+impl ColumnMapping {
+    /// The fixed layout the original upload endpoint reads: corporate number, account number,
+    /// last, first and middle name in columns F to J, headers on row 1.
+    pub fn legacy() -> Self {
+        Self {
+            header_row: 1,
+            columns: BTreeMap::from([
+                (MasterListField::CorporateNumber, ColumnRef::Index(5)),
+                (MasterListField::AccountNumber, ColumnRef::Index(6)),
+                (MasterListField::LastName, ColumnRef::Index(7)),
+                (MasterListField::FirstName, ColumnRef::Index(8)),
+                (MasterListField::MiddleName, ColumnRef::Index(9)),
+            ]),
+        }
+    }
+
+    /// Builds a mapping from a master_list_column_mapping row.
+    pub fn from_profile(header_row: i32, columns: &JsonValue) -> Result<Self, String> {
+        if header_row < 1 {
+            return Err("header_row must be 1 or more".to_string());
+        }
+        let columns: BTreeMap<MasterListField, ColumnRef> = serde_json::from_value(columns.clone())
+            .map_err(|e| format!("Invalid column mapping: {}", e))?;
+        let mapping = Self {
+            header_row: header_row as usize,
+            columns,
+        };
+        mapping.validate()?;
+        Ok(mapping)
+    }
+
+    /// Recognises the columns from the header texts on `header_row`. Returns `None` if any
+    /// required field could not be found.
+    pub fn detect(table: &RawTable, header_row: usize) -> Option<Self> {
+        let headers = table.rows.get(header_row.checked_sub(1)?)?;
+        let mut columns = BTreeMap::new();
+        let mut used = HashSet::new();
+
+        for field in MasterListField::ALL {
+            let found = headers.iter().enumerate().find(|(idx, header)| {
+                !used.contains(idx) && field.header_synonyms().contains(&normalize_header(header).as_str())
+            });
+            if let Some((idx, header)) = found {
+                used.insert(idx);
+                columns.insert(field, ColumnRef::Header(header.trim().to_string()));
+            }
+        }
+
+        let mapping = Self { header_row, columns };
+        mapping.validate().ok().map(|_| mapping)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let missing: Vec<&str> = MasterListField::ALL
+            .iter()
+            .filter(|f| f.is_required() && !self.columns.contains_key(f))
+            .map(|f| f.key())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!("Column mapping is missing {}", missing.join(", ")));
+        }
+        Ok(())
+    }
+
+    /// Resolves header references against the file's header row.
+    fn resolve(&self, headers: &[String]) -> Result<BTreeMap<MasterListField, ResolvedColumn>, String> {
+        let mut resolved = BTreeMap::new();
+        for (field, column) in &self.columns {
+            let index = match column {
+                ColumnRef::Index(i) => *i,
+                ColumnRef::Header(name) => {
+                    let wanted = normalize_header(name);
+                    headers
+                        .iter()
+                        .position(|h| normalize_header(h) == wanted)
+                        .ok_or_else(|| format!("Column '{}' for {} is not in the header row", name, field.key()))?
+                }
+            };
+            resolved.insert(
+                *field,
+                ResolvedColumn {
+                    index,
+                    header: headers.get(index).cloned().unwrap_or_default(),
+                },
+            );
+        }
+        Ok(resolved)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedColumn {
+    pub index: usize,
+    pub header: String,
+}
+
+fn normalize_header(header: &str) -> String {
+    header
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+// endregion: Fields and Mappings
+
+
+// region: Reading Files
+
+/// Every row of the uploaded sheet as trimmed text, header rows included.
+#[derive(Debug, Clone, Default)]
+pub struct RawTable {
+    pub rows: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileFormat {
+    Xlsx,
+    Xls,
+    Ods,
+    Csv,
+    Json,
+}
+
+impl FileFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "xlsx" | "xlsm" => Some(FileFormat::Xlsx),
+            "xls" => Some(FileFormat::Xls),
+            "ods" => Some(FileFormat::Ods),
+            "csv" | "txt" | "tsv" => Some(FileFormat::Csv),
+            "json" => Some(FileFormat::Json),
+            _ => None,
+        }
+    }
+
+    /// Works out the format from the file's first bytes, falling back to the extension for
+    /// text files and for zip files that are not recognisably XLSX or ODS.
+    pub fn detect(file_name: &str, bytes: &[u8]) -> Option<Self> {
+        let by_extension = file_name
+            .rsplit_once('.')
+            .and_then(|(_, ext)| Self::from_name(ext));
+
+        const OLE_MAGIC: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+        if bytes.starts_with(&OLE_MAGIC) {
+            return Some(FileFormat::Xls);
+        }
+        if bytes.starts_with(b"PK\x03\x04") {
+            // ODS stores its mimetype uncompressed as the first zip entry.
+            let head = &bytes[..bytes.len().min(128)];
+            if head.windows(11).any(|w| w == b"opendocumen") {
+                return Some(FileFormat::Ods);
+            }
+            return match by_extension {
+                Some(FileFormat::Ods) => Some(FileFormat::Ods),
+                _ => Some(FileFormat::Xlsx),
+            };
+        }
+
+        let text = strip_utf8_bom(bytes);
+        match text.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'[') | Some(b'{') if by_extension != Some(FileFormat::Csv) => Some(FileFormat::Json),
+            Some(_) => match by_extension {
+                Some(FileFormat::Json) => Some(FileFormat::Json),
+                Some(FileFormat::Csv) | None => Some(FileFormat::Csv),
+                // a spreadsheet extension on a text file: most likely a CSV export renamed
+                Some(_) => Some(FileFormat::Csv),
+            },
+            None => by_extension,
+        }
+    }
+}
+
+/// Settings for CSV files. Both are guessed when not given.
+#[derive(Debug, Clone, Default)]
+pub struct CsvOptions {
+    pub delimiter: Option<u8>,
+    /// An encoding label such as "utf-8", "windows-1252" or "utf-16le"
+    pub encoding: Option<String>,
+}
+
+impl CsvOptions {
+    /// Accepts a single character, or "tab".
+    pub fn parse_delimiter(text: &str) -> Result<u8, String> {
+        match text {
+            "tab" | "\\t" | "\t" => Ok(b'\t'),
+            _ if text.len() == 1 && text.is_ascii() => Ok(text.as_bytes()[0]),
+            _ => Err(format!("'{}' is not a delimiter; give a single character or \"tab\"", text)),
+        }
+    }
+}
+
+/// Reads the file into rows of text: the first sheet of a workbook, every record of a CSV,
+/// or every element of a JSON array.
+pub fn read_table(bytes: Vec<u8>, format: FileFormat, csv_options: &CsvOptions) -> Result<RawTable, String> {
+    match format {
+        FileFormat::Xlsx => read_first_sheet(
+            Xlsx::new(Cursor::new(bytes)).map_err(|e| format!("Not a readable XLSX file: {}", e))?,
+        ),
+        FileFormat::Xls => read_first_sheet(
+            Xls::new(Cursor::new(bytes)).map_err(|e| format!("Not a readable XLS file: {}", e))?,
+        ),
+        FileFormat::Ods => read_first_sheet(
+            Ods::new(Cursor::new(bytes)).map_err(|e| format!("Not a readable ODS file: {}", e))?,
+        ),
+        FileFormat::Csv => read_csv(&bytes, csv_options),
+        FileFormat::Json => read_json(&bytes),
+    }
+}
+
+/// Reads the first sheet of a workbook. Date cells become `YYYY-MM-DD`.
+fn read_first_sheet<RS, R>(mut workbook: R) -> Result<RawTable, String>
+where
+    RS: std::io::Read + std::io::Seek,
+    R: Reader<RS>,
+    R::Error: std::fmt::Display,
+{
+    let range = workbook
+        .worksheet_range_at(0)
+        .ok_or_else(|| "The workbook has no sheets".to_string())?
+        .map_err(|e| format!("Could not read the first sheet: {}", e))?;
+
+    Ok(RawTable {
+        rows: range
+            .rows()
+            .map(|row| row.iter().map(cell_to_string).collect())
+            .collect(),
+    })
+}
+
+fn cell_to_string(cell: &Data) -> String {
+    match cell {
+        Data::String(s) => s.trim().to_string(),
+        Data::Float(f) => {
+            if f.fract() == 0.0 {
+                (*f as i64).to_string()
+            } else {
+                f.to_string()
+            }
+        }
+        Data::Int(i) => i.to_string(),
+        Data::Bool(b) => b.to_string(),
+        Data::DateTime(dt) => excel_serial_to_date(dt.as_f64())
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .unwrap_or_default(),
+        Data::DateTimeIso(s) => s.trim().to_string(),
+        Data::DurationIso(s) => s.trim().to_string(),
+        Data::Error(_) | Data::Empty => String::new(),
+    }
+}
+
+fn excel_serial_to_date(serial: f64) -> Option<NaiveDate> {
+    if !(1.0..2_958_466.0).contains(&serial) {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(1899, 12, 30)?.checked_add_signed(Duration::days(serial.floor() as i64))
+}
+
+fn strip_utf8_bom(bytes: &[u8]) -> &[u8] {
+    bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes)
+}
+
+/// Decodes CSV bytes to text: the requested encoding, else the one named by a BOM, else
+/// UTF-8 if the bytes are valid UTF-8, else Windows-1252 (what Excel on Windows writes).
+pub fn decode_text(bytes: &[u8], encoding: Option<&str>) -> Result<String, String> {
+    let encoding = match encoding {
+        Some(label) => Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| format!("Unknown encoding '{}'", label))?,
+        None => match Encoding::for_bom(bytes) {
+            Some((encoding, _)) => encoding,
+            None if std::str::from_utf8(bytes).is_ok() => UTF_8,
+            None => WINDOWS_1252,
+        },
+    };
+    let (text, _, had_errors) = encoding.decode(bytes);
+    if had_errors {
+        return Err(format!("The file is not valid {}", encoding.name()));
+    }
+    Ok(text.into_owned())
+}
+
+/// The most frequent of `,` `;` tab and `|` on the first line.
+pub fn guess_delimiter(text: &str) -> u8 {
+    let first_line = text.lines().next().unwrap_or_default();
+    [b',', b';', b'\t', b'|']
+        .into_iter()
+        .max_by_key(|d| first_line.bytes().filter(|b| b == d).count())
+        .filter(|d| first_line.as_bytes().contains(d))
+        .unwrap_or(b',')
+}
+
+fn read_csv(bytes: &[u8], options: &CsvOptions) -> Result<RawTable, String> {
+    let text = decode_text(bytes, options.encoding.as_deref())?;
+    let delimiter = options.delimiter.unwrap_or_else(|| guess_delimiter(&text));
+
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes());
+
+    let mut rows = Vec::new();
+    for (idx, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| format!("Could not read CSV line {}: {}", idx + 1, e))?;
+        rows.push(record.iter().map(|s| s.trim().to_string()).collect());
+    }
+    Ok(RawTable { rows })
+}
+
+/// Accepts an array of objects (the keys become the header row, in order of first
+/// appearance) or an array of arrays (the first one is the header row). The array may also
+/// be the only array-valued field of a wrapping object, e.g. `{"members": [...]}`.
+pub fn read_json(bytes: &[u8]) -> Result<RawTable, String> {
+    let value: JsonValue = serde_json::from_slice(strip_utf8_bom(bytes))
+        .map_err(|e| format!("Not a readable JSON file: {}", e))?;
+
+    let items = match value {
+        JsonValue::Array(items) => items,
+        JsonValue::Object(map) => {
+            let mut arrays = map.into_iter().filter_map(|(_, v)| match v {
+                JsonValue::Array(items) => Some(items),
+                _ => None,
+            });
+            match (arrays.next(), arrays.next()) {
+                (Some(items), None) => items,
+                _ => return Err("Expected a JSON array of members".to_string()),
+            }
+        }
+        _ => return Err("Expected a JSON array of members".to_string()),
+    };
+
+    if items.iter().all(JsonValue::is_array) {
+        return Ok(RawTable {
+            rows: items
+                .iter()
+                .map(|item| {
+                    item.as_array()
+                        .map(|cells| cells.iter().map(json_to_string).collect())
+                        .unwrap_or_default()
+                })
+                .collect(),
+        });
+    }
+
+    let mut headers: Vec<String> = Vec::new();
+    for item in &items {
+        let object = item
+            .as_object()
+            .ok_or_else(|| "Every element of the JSON array must be an object".to_string())?;
+        for key in object.keys() {
+            if !headers.contains(key) {
+                headers.push(key.clone());
+            }
+        }
+    }
+
+    let mut rows = Vec::with_capacity(items.len() + 1);
+    rows.push(headers.clone());
+    for item in &items {
+        rows.push(
+            headers
+                .iter()
+                .map(|h| item.get(h).map(json_to_string).unwrap_or_default())
+                .collect(),
+        );
+    }
+    Ok(RawTable { rows })
+}
+
+fn json_to_string(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => String::new(),
+        JsonValue::String(s) => s.trim().to_string(),
+        other => other.to_string(),
+    }
+}
+// endregion: Reading Files
+
+
+// region: Mapping Rows
+
+/// One data row of the file after mapping and validation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MappedRow {
+    /// 1-based row number in the file
+    pub row_number: usize,
+    pub corporate_number: Option<String>,
+    pub account_number: String,
+    pub last_name: String,
+    pub first_name: String,
+    pub middle_name: String,
+    pub birth_date: Option<NaiveDate>,
+    pub email_address: Option<String>,
+    pub mobile_number: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RowError {
+    pub row_number: usize,
+    pub field: Option<&'static str>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MappedSheet {
+    pub headers: Vec<String>,
+    pub columns: BTreeMap<MasterListField, ResolvedColumn>,
+    /// Rows that passed validation
+    pub rows: Vec<MappedRow>,
+    pub errors: Vec<RowError>,
+    pub blank_row_count: usize,
+}
+
+/// Applies `mapping` to every row below the header row. Rows where every mapped cell is
+/// blank are skipped; rows that fail validation are reported in `errors` and left out of `rows`.
+pub fn map_rows(table: &RawTable, mapping: &ColumnMapping) -> Result<MappedSheet, String> {
+    mapping.validate()?;
+
+    let header_idx = mapping.header_row - 1;
+    let headers = table.rows.get(header_idx).cloned().unwrap_or_default();
+    let columns = mapping.resolve(&headers)?;
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut blank_row_count = 0usize;
+
+    for (zero_based_idx, row) in table.rows.iter().enumerate().skip(header_idx + 1) {
+        let row_number = zero_based_idx + 1;
+        let cell = |field: MasterListField| -> String {
+            columns
+                .get(&field)
+                .and_then(|c| row.get(c.index))
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        if columns.values().all(|c| row.get(c.index).is_none_or(|s| s.trim().is_empty())) {
+            blank_row_count += 1;
+            continue;
+        }
+
+        let mut row_errors = Vec::new();
+        for field in MasterListField::ALL {
+            if field.is_required() && cell(field).is_empty() {
+                row_errors.push(RowError {
+                    row_number,
+                    field: Some(field.key()),
+                    message: format!("{} is blank", field.key()),
+                });
+            }
+        }
+
+        let birth_date_text = cell(MasterListField::BirthDate);
+        let birth_date = if birth_date_text.is_empty() {
+            None
+        } else {
+            match parse_date(&birth_date_text) {
+                Some(d) => Some(d),
+                None => {
+                    row_errors.push(RowError {
+                        row_number,
+                        field: Some(MasterListField::BirthDate.key()),
+                        message: format!("'{}' is not a date", birth_date_text),
+                    });
+                    None
+                }
+            }
+        };
+
+        let email_address = non_empty(cell(MasterListField::EmailAddress));
+        if let Some(email) = &email_address {
+            if !is_plausible_email(email) {
+                row_errors.push(RowError {
+                    row_number,
+                    field: Some(MasterListField::EmailAddress.key()),
+                    message: format!("'{}' is not an email address", email),
+                });
+            }
+        }
+
+        let mobile_number = non_empty(cell(MasterListField::MobileNumber));
+        if let Some(mobile) = &mobile_number {
+            if !is_plausible_mobile_number(mobile) {
+                row_errors.push(RowError {
+                    row_number,
+                    field: Some(MasterListField::MobileNumber.key()),
+                    message: format!("'{}' is not a mobile number", mobile),
+                });
+            }
+        }
+
+        if !row_errors.is_empty() {
+            errors.extend(row_errors);
+            continue;
+        }
+
+        rows.push(MappedRow {
+            row_number,
+            corporate_number: columns
+                .contains_key(&MasterListField::CorporateNumber)
+                .then(|| cell(MasterListField::CorporateNumber)),
+            account_number: cell(MasterListField::AccountNumber),
+            last_name: cell(MasterListField::LastName),
+            first_name: cell(MasterListField::FirstName),
+            middle_name: cell(MasterListField::MiddleName),
+            birth_date,
+            email_address,
+            mobile_number,
+        });
+    }
+
+    Ok(MappedSheet {
+        headers,
+        columns,
+        rows,
+        errors,
+        blank_row_count,
+    })
+}
+
+fn non_empty(s: String) -> Option<String> {
+    if s.is_empty() { None } else { Some(s) }
+}
+
+/// Accepts ISO dates, month names (18-Oct-2014, Oct 18, 2014), US month-first slashes
+/// (10/18/2014) and Excel serial numbers. Day-first slashes are not accepted: 01/02/2014 would
+/// read as either format, so a day-first file would be read wrong without an error.
+pub fn parse_date(text: &str) -> Option<NaiveDate> {
+    let text = text.trim();
+    let text = text.split_once('T').map(|(d, _)| d).unwrap_or(text);
+    const FORMATS: [&str; 6] = ["%Y-%m-%d", "%m/%d/%Y", "%Y/%m/%d", "%d-%b-%Y", "%b %d, %Y", "%B %d, %Y"];
+    FORMATS
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(text, f).ok())
+        .or_else(|| text.parse::<f64>().ok().and_then(excel_serial_to_date))
+}
+
+fn is_plausible_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => !local.is_empty() && domain.contains('.') && !email.contains(char::is_whitespace),
+        None => false,
+    }
+}
+
+fn is_plausible_mobile_number(mobile: &str) -> bool {
+    let digits: String = mobile
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '(' | ')' | '+'))
+        .collect();
+    (7..=15).contains(&digits.len()) && digits.chars().all(|c| c.is_ascii_digit())
+}
+// endregion: Mapping Rows
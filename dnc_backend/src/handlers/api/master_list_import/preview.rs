@@ -0,0 +1,554 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Multipart, Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+    QuerySelect, Set, TransactionTrait,
+};
+use serde::Serialize;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::AppState;
+use crate::entities::{
+    endorsement, master_list, master_list_column_mapping, master_list_upload_preview,
+};
+use crate::handlers::AuthUser;
+use super::classify::{classify_rows, ExistingMemberDuplicate, UploadClassification};
+use super::delta::{apply_delta, compute_delta, insert_members, MasterListDelta};
+use super::column_mappings::default_mapping_for_hmo;
+use super::parsing::{
+    map_rows, read_table, ColumnMapping, CsvOptions, FileFormat, MappedRow, MappedSheet,
+    MasterListField, ResolvedColumn, RowError,
+};
+
+/// How long a preview can be committed.
+const PREVIEW_TTL_HOURS: i64 = 24;
+
+/// master_list_upload_preview.mode: only add members.
+pub const MODE_APPEND: &str = "append";
+/// master_list_upload_preview.mode: the file replaces the endorsement's list.
+pub const MODE_REPLACE: &str = "replace";
+
+fn internal_error(err: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+/// Where the column mapping of a preview came from.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MappingSource {
+    /// mapping_id sent with the upload
+    Profile,
+    /// the HMO's default profile
+    HmoDefault,
+    /// recognised from the header texts
+    Detected,
+    /// columns F to J, as the original upload endpoint reads them
+    Legacy,
+}
+
+/// Picks the column mapping for an upload: the requested profile, else the HMO's default
+/// profile, else whatever the header row reveals, else the legacy fixed layout.
+async fn choose_mapping(
+    db: &DatabaseConnection,
+    endorsement: &endorsement::Model,
+    mapping_id: Option<i32>,
+    table: &super::parsing::RawTable,
+) -> Result<(MappingSource, Option<i32>, ColumnMapping), (StatusCode, String)> {
+    let profile = match mapping_id {
+        Some(id) => {
+            let profile = master_list_column_mapping::Entity::find_by_id(id)
+                .one(db)
+                .await
+                .map_err(internal_error)?
+                .ok_or((StatusCode::NOT_FOUND, "Master list column mapping not found".to_string()))?;
+            if profile.hmo_id != endorsement.hmo_id {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "The column mapping belongs to a different HMO".to_string(),
+                ));
+            }
+            Some((MappingSource::Profile, profile))
+        }
+        None => default_mapping_for_hmo(db, endorsement.hmo_id)
+            .await
+            .map_err(internal_error)?
+            .map(|p| (MappingSource::HmoDefault, p)),
+    };
+
+    if let Some((source, profile)) = profile {
+        let mapping = ColumnMapping::from_profile(profile.header_row, &profile.columns)
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+        return Ok((source, Some(profile.id), mapping));
+    }
+
+    Ok(match ColumnMapping::detect(table, 1) {
+        Some(mapping) => (MappingSource::Detected, None, mapping),
+        None => (MappingSource::Legacy, None, ColumnMapping::legacy()),
+    })
+}
+
+
+// region: preview_endorsement_master_list
+#[derive(Debug, Serialize)]
+pub struct MasterListPreviewMapping {
+    pub format: FileFormat,
+    pub source: MappingSource,
+    pub column_mapping_id: Option<i32>,
+    pub header_row: usize,
+    pub columns: BTreeMap<MasterListField, ResolvedColumn>,
+}
+
+/// An uploaded file after parsing and mapping, before it is compared with the members.
+struct ParsedUpload {
+    endorsement: endorsement::Model,
+    file_name: String,
+    mapping: MasterListPreviewMapping,
+    sheet: MappedSheet,
+}
+
+/// Reads the multipart upload and maps its rows. Fields: "file", and optionally "mapping_id",
+/// "format" (xlsx, xls, ods, csv or json; detected when left out), and for CSV files
+/// "delimiter" and "encoding".
+async fn parse_upload(
+    db: &DatabaseConnection,
+    endorsement_id: i32,
+    mut multipart: Multipart,
+) -> Result<ParsedUpload, (StatusCode, String)> {
+    // ---1 endorsement
+    let endorsement_row = endorsement::Entity::find_by_id(endorsement_id)
+        .one(db)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Endorsement not found".to_string()))?;
+
+    // ---2 multipart
+    let mut file_name: Option<String> = None;
+    let mut file_bytes: Option<Vec<u8>> = None;
+    let mut mapping_id: Option<i32> = None;
+    let mut format: Option<FileFormat> = None;
+    let mut csv_options = CsvOptions::default();
+
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?
+    {
+        let field_name = field.name().unwrap_or_default().to_string();
+        if field_name == "file" {
+            file_name = field.file_name().map(|s| s.to_string());
+            let data = field.bytes().await.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+            file_bytes = Some(data.to_vec());
+            continue;
+        }
+
+        let text = field.text().await.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match field_name.as_str() {
+            "mapping_id" => {
+                mapping_id = Some(text.parse().map_err(|_| {
+                    (StatusCode::BAD_REQUEST, "mapping_id must be a number".to_string())
+                })?);
+            }
+            "format" => {
+                format = Some(FileFormat::from_name(text).ok_or_else(|| {
+                    (StatusCode::BAD_REQUEST, format!("Unsupported format '{}'", text))
+                })?);
+            }
+            "delimiter" => {
+                csv_options.delimiter =
+                    Some(CsvOptions::parse_delimiter(text).map_err(|e| (StatusCode::BAD_REQUEST, e))?);
+            }
+            "encoding" => csv_options.encoding = Some(text.to_string()),
+            _ => {}
+        }
+    }
+
+    let file_name = file_name.unwrap_or_else(|| "uploaded_master_list.xlsx".to_string());
+    let file_bytes = file_bytes.ok_or((StatusCode::BAD_REQUEST, "file is required".to_string()))?;
+    let format = format
+        .or_else(|| FileFormat::detect(&file_name, &file_bytes))
+        .ok_or((StatusCode::BAD_REQUEST, "Could not tell the file format; send a \"format\" field".to_string()))?;
+
+    // ---3 parse and map
+    let table = read_table(file_bytes, format, &csv_options).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let (source, column_mapping_id, mapping) =
+        choose_mapping(db, &endorsement_row, mapping_id, &table).await?;
+    let sheet = map_rows(&table, &mapping).map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+
+    Ok(ParsedUpload {
+        endorsement: endorsement_row,
+        file_name,
+        mapping: MasterListPreviewMapping {
+            format,
+            source,
+            column_mapping_id,
+            header_row: mapping.header_row,
+            columns: sheet.columns.clone(),
+        },
+        sheet,
+    })
+}
+
+/// Keeps the mapped rows of an upload so they can be committed by token.
+async fn save_preview(
+    db: &DatabaseConnection,
+    upload: &ParsedUpload,
+    mode: &str,
+    created_by: String,
+) -> Result<(String, DateTime<FixedOffset>), (StatusCode, String)> {
+    let token = Uuid::new_v4().simple().to_string();
+    let now = Utc::now().fixed_offset();
+    let expires_on = now + Duration::hours(PREVIEW_TTL_HOURS);
+    let rows_json = serde_json::to_value(&upload.sheet.rows)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    master_list_upload_preview::ActiveModel {
+        token: Set(token.clone()),
+        endorsement_id: Set(upload.endorsement.id),
+        file_name: Set(upload.file_name.clone()),
+        column_mapping_id: Set(upload.mapping.column_mapping_id),
+        rows: Set(rows_json),
+        created_by: Set(created_by),
+        created_on: Set(now),
+        expires_on: Set(expires_on),
+        committed_on: Set(None),
+        committed_by: Set(None),
+        master_list_id: Set(None),
+        mode: Set(mode.to_string()),
+        ..Default::default()
+    }
+        .insert(db)
+        .await
+        .map_err(internal_error)?;
+
+    Ok((token, expires_on))
+}
+
+#[derive(Debug, Serialize)]
+pub struct MasterListPreviewResponse {
+    pub token: String,
+    pub expires_on: DateTime<FixedOffset>,
+    pub endorsement_id: i32,
+    pub file_name: String,
+    pub mapping: MasterListPreviewMapping,
+    pub headers: Vec<String>,
+    pub valid_row_count: usize,
+    pub blank_row_count: usize,
+    pub error_count: usize,
+    pub errors: Vec<RowError>,
+    pub to_insert_count: usize,
+    pub already_present_count: usize,
+    pub duplicate_count: usize,
+    pub corporate_number_mismatch_count: usize,
+    #[serde(flatten)]
+    pub classification: UploadClassification,
+}
+
+/// POST /api/endorsements/{endorsement_id}/master_list/preview
+///
+/// Expects multipart/form-data with a file field named "file" and an optional "mapping_id"
+/// (a master_list_column_mapping of the endorsement's HMO). Parses and checks the file
+/// against the endorsement's members without touching them, and keeps the parsed rows so
+/// they can be committed with the returned token.
+#[instrument(skip(state, multipart), err(Debug))]
+pub async fn preview_endorsement_master_list(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(endorsement_id): Path<i32>,
+    multipart: Multipart,
+) -> Result<Json<MasterListPreviewResponse>, (StatusCode, String)> {
+    let upload = parse_upload(&state.db, endorsement_id, multipart).await?;
+
+    let classification = classify_rows(&state.db, &upload.endorsement, upload.sheet.rows.clone())
+        .await
+        .map_err(internal_error)?;
+
+    let (token, expires_on) = save_preview(&state.db, &upload, MODE_APPEND, auth_user.claims.email).await?;
+
+    Ok(Json(MasterListPreviewResponse {
+        token,
+        expires_on,
+        endorsement_id,
+        file_name: upload.file_name,
+        mapping: upload.mapping,
+        headers: upload.sheet.headers,
+        valid_row_count: upload.sheet.rows.len(),
+        blank_row_count: upload.sheet.blank_row_count,
+        error_count: upload.sheet.errors.len(),
+        errors: upload.sheet.errors,
+        to_insert_count: classification.to_insert.len(),
+        already_present_count: classification.already_present.len(),
+        duplicate_count: classification.duplicates_existing.len(),
+        corporate_number_mismatch_count: classification.corporate_number_mismatches.len(),
+        classification,
+    }))
+}
+// endregion: preview_endorsement_master_list
+
+
+// region: preview_endorsement_master_list_replacement
+#[derive(Debug, Serialize)]
+pub struct MasterListDeltaPreviewResponse {
+    pub token: String,
+    pub expires_on: DateTime<FixedOffset>,
+    pub endorsement_id: i32,
+    pub file_name: String,
+    pub mapping: MasterListPreviewMapping,
+    pub headers: Vec<String>,
+    pub valid_row_count: usize,
+    pub blank_row_count: usize,
+    pub added_count: usize,
+    pub reactivated_count: usize,
+    pub changed_count: usize,
+    pub dropped_count: usize,
+    #[serde(flatten)]
+    pub delta: MasterListDelta,
+}
+
+/// POST /api/endorsements/{endorsement_id}/master_list/replacement_preview
+///
+/// Same upload as the preview, but the file is taken as the endorsement's complete new list.
+/// Returns who would be added, whose details changed, who is back after being deactivated
+/// and which active members are missing and would be deactivated. Nothing is changed until
+/// the token is committed. A file with bad rows, rows of another corporate number, or no
+/// members at all is refused.
+#[instrument(skip(state, multipart), err(Debug))]
+pub async fn preview_endorsement_master_list_replacement(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(endorsement_id): Path<i32>,
+    multipart: Multipart,
+) -> Result<Json<MasterListDeltaPreviewResponse>, (StatusCode, String)> {
+    let upload = parse_upload(&state.db, endorsement_id, multipart).await?;
+
+    // A row that fails validation would look like a dropped member; refuse the file instead.
+    if let Some(first) = upload.sheet.errors.first() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "{} row(s) have errors (first: row {}: {}); use the preview to see them all and fix the file before replacing the master list",
+                upload.sheet.errors.len(),
+                first.row_number,
+                first.message,
+            ),
+        ));
+    }
+
+    let delta = compute_delta(&state.db, &upload.endorsement, upload.sheet.rows.clone())
+        .await
+        .map_err(internal_error)?;
+    check_replacement(&delta)?;
+
+    let (token, expires_on) = save_preview(&state.db, &upload, MODE_REPLACE, auth_user.claims.email).await?;
+
+    Ok(Json(MasterListDeltaPreviewResponse {
+        token,
+        expires_on,
+        endorsement_id,
+        file_name: upload.file_name,
+        mapping: upload.mapping,
+        headers: upload.sheet.headers,
+        valid_row_count: upload.sheet.rows.len(),
+        blank_row_count: upload.sheet.blank_row_count,
+        added_count: delta.added.len(),
+        reactivated_count: delta.reactivated.len(),
+        changed_count: delta.changed.len(),
+        dropped_count: delta.dropped.len(),
+        delta,
+    }))
+}
+/// Refuses a replacement that would deactivate members by mistake: rows left out for their
+/// corporate number would look like dropped members, and a list with no valid rows would drop
+/// everyone.
+fn check_replacement(delta: &MasterListDelta) -> Result<(), (StatusCode, String)> {
+    if let Some(first) = delta.corporate_number_mismatches.first() {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!(
+                "{} row(s) have a corporate number other than the endorsement's (first: row {}: '{}'); fix the file before replacing the master list",
+                delta.corporate_number_mismatches.len(),
+                first.row_number,
+                first.corporate_number.as_deref().unwrap_or_default(),
+            ),
+        ));
+    }
+    let valid_row_count = delta.added.len() + delta.reactivated.len() + delta.changed.len() + delta.unchanged_count;
+    if valid_row_count == 0 {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "The file has no members; replacing the master list with it would deactivate every member".to_string(),
+        ));
+    }
+    Ok(())
+}
+// endregion: preview_endorsement_master_list_replacement
+
+
+// region: commit_endorsement_master_list_preview
+#[derive(Debug, Serialize)]
+pub struct AppendedMasterListResponse {
+    pub master_list_id: i32,
+    pub file_name: String,
+    pub endorsement_id: i32,
+    pub mode: &'static str,
+    pub inserted_count: usize,
+    pub inserted_rows: Vec<MappedRow>,
+    pub already_present_count: usize,
+    pub duplicate_count: usize,
+    pub duplicates: Vec<ExistingMemberDuplicate>,
+    pub skipped_corporate_number_mismatch_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReplacedMasterListResponse {
+    pub master_list_id: i32,
+    pub file_name: String,
+    pub endorsement_id: i32,
+    pub mode: &'static str,
+    pub added_count: usize,
+    pub reactivated_count: usize,
+    pub changed_count: usize,
+    pub dropped_count: usize,
+    #[serde(flatten)]
+    pub delta: MasterListDelta,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum CommitMasterListPreviewResponse {
+    Appended(AppendedMasterListResponse),
+    Replaced(ReplacedMasterListResponse),
+}
+
+/// POST /api/endorsements/{endorsement_id}/master_list/previews/{token}/commit
+///
+/// Applies a preview in one transaction. The rows are checked again against the members as
+/// they are now, so anything changed since the preview is taken into account; the response
+/// says what was actually done.
+#[instrument(skip(state), err(Debug))]
+pub async fn commit_endorsement_master_list_preview(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path((endorsement_id, token)): Path<(i32, String)>,
+) -> Result<(StatusCode, Json<CommitMasterListPreviewResponse>), (StatusCode, String)> {
+    let txn = state.db.begin().await.map_err(internal_error)?;
+
+    // ---1 the preview, locked so it cannot be committed twice
+    let preview = master_list_upload_preview::Entity::find()
+        .filter(master_list_upload_preview::Column::Token.eq(token))
+        .filter(master_list_upload_preview::Column::EndorsementId.eq(endorsement_id))
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Master list preview not found".to_string()))?;
+
+    if preview.committed_on.is_some() {
+        return Err((StatusCode::CONFLICT, "This preview has already been committed".to_string()));
+    }
+    let now = Utc::now().fixed_offset();
+    if preview.expires_on < now {
+        return Err((StatusCode::GONE, "This preview has expired; upload the file again".to_string()));
+    }
+
+    let endorsement_row = endorsement::Entity::find_by_id(endorsement_id)
+        .one(&txn)
+        .await
+        .map_err(internal_error)?
+        .ok_or((StatusCode::NOT_FOUND, "Endorsement not found".to_string()))?;
+
+    let rows: Vec<MappedRow> = serde_json::from_value(preview.rows.clone())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // ---2 the master_list the new members come from
+    let uploaded_by = auth_user.claims.email;
+    let master_list_row = master_list::ActiveModel {
+        file_name: Set(preview.file_name.clone()),
+        endorsement_id: Set(Some(endorsement_id)),
+        uploaded_by: Set(Some(uploaded_by.clone())),
+        upload_date: Set(Some(now)),
+        ..Default::default()
+    }
+        .insert(&txn)
+        .await
+        .map_err(internal_error)?;
+
+    // ---3 apply, checked against the members as they are now
+    let file_name = preview.file_name.clone();
+    let (status, response) = if preview.mode == MODE_REPLACE {
+        let delta = compute_delta(&txn, &endorsement_row, rows)
+            .await
+            .map_err(internal_error)?;
+        // the endorsement's corporate number may have changed since the preview
+        check_replacement(&delta)?;
+        apply_delta(&txn, endorsement_id, master_list_row.id, &delta, &uploaded_by, now)
+            .await
+            .map_err(internal_error)?;
+
+        (
+            StatusCode::OK,
+            CommitMasterListPreviewResponse::Replaced(ReplacedMasterListResponse {
+                master_list_id: master_list_row.id,
+                file_name,
+                endorsement_id,
+                mode: MODE_REPLACE,
+                added_count: delta.added.len(),
+                reactivated_count: delta.reactivated.len(),
+                changed_count: delta.changed.len(),
+                dropped_count: delta.dropped.len(),
+                delta,
+            }),
+        )
+    } else {
+        let classification = classify_rows(&txn, &endorsement_row, rows)
+            .await
+            .map_err(internal_error)?;
+        insert_members(&txn, endorsement_id, master_list_row.id, &classification.to_insert, &uploaded_by, now)
+            .await
+            .map_err(internal_error)?;
+
+        let status = if classification.to_insert.is_empty() {
+            StatusCode::OK
+        } else {
+            StatusCode::CREATED
+        };
+        (
+            status,
+            CommitMasterListPreviewResponse::Appended(AppendedMasterListResponse {
+                master_list_id: master_list_row.id,
+                file_name,
+                endorsement_id,
+                mode: MODE_APPEND,
+                inserted_count: classification.to_insert.len(),
+                inserted_rows: classification.to_insert,
+                already_present_count: classification.already_present.len(),
+                duplicate_count: classification.duplicates_existing.len(),
+                duplicates: classification.duplicates_existing,
+                skipped_corporate_number_mismatch_count: classification.corporate_number_mismatches.len(),
+            }),
+        )
+    };
+
+    // ---4 mark the preview as used
+    let mut preview_am: master_list_upload_preview::ActiveModel = preview.into();
+    preview_am.committed_on = Set(Some(now));
+    preview_am.committed_by = Set(Some(uploaded_by));
+    preview_am.master_list_id = Set(Some(master_list_row.id));
+    preview_am.update(&txn).await.map_err(internal_error)?;
+
+    txn.commit().await.map_err(internal_error)?;
+
+    Ok((status, Json(response)))
+}
+
+// endregion: commit_endorsement_master_list_preview
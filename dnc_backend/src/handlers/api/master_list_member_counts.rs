@@ -1,5 +1,3 @@
-use chrono::{Datelike, Utc};
-
 use axum::{
     Json,
     extract::{Path, State},
@@ -12,7 +10,10 @@ use tracing::instrument;
 
 use crate::{
     AppState,
+    business_calendar::{self, BusinessCalendar},
+    jobs::PENDING_VERIFICATION_BUSINESS_DAYS,
     entities::{dental_service, endorsement, endorsement_counts, master_list_member, verification},
+    verification_state::VerificationStatus,
 };
 
 #[derive(Debug, Serialize, Clone)]
@@ -50,32 +51,18 @@ fn resolve_allowed_count(dental_service_type_id: i32, explicit_count: Option<i32
 
 /// Central place for deciding whether a verification counts as "used".
 ///
-/// Right now, this counts every verification row as used.
+/// Right now, this counts every verification row as used, except voided approval codes,
+/// which give the availment back to the member.
 /// Later, if only some status_id values should count, change only this function.
-fn verification_status_counts_as_used(_status_id: i32) -> bool {
-    true
+fn verification_status_counts_as_used(status_id: i32) -> bool {
+    status_id != VerificationStatus::Voided.int_code()
 }
 /// Central place for deciding whether a verification counts as "pending conflict".
 ///
-/// Right now: status_id == 1
+/// Right now: Waiting for Approval Code
 /// Later, if the rule changes, edit only this function.
 fn verification_status_counts_as_pending(status_id: i32) -> bool {
-    status_id == 1
-}
-fn cutoff_date_last_7_non_sundays() -> Date {
-    let mut current = Utc::now().date_naive();
-    let mut counted_days = 0;
-
-    while counted_days < 7 {
-        if current.weekday().num_days_from_sunday() != 0 {
-            counted_days += 1;
-        }
-
-        if counted_days < 7 {
-            current = current.pred_opt().expect("valid previous date");
-        }
-    }
-    current
+    status_id == VerificationStatus::WaitingForApprovalCode.int_code()
 }
 
 /* =========================================================
@@ -195,7 +182,8 @@ async fn build_pending_conflicts_for_member(
     member_id: i32,
 ) -> Result<HashMap<i32, Date>, DbErr> {
     let verifications = get_verifications_for_member(db, member_id).await?;
-    let cutoff_date = cutoff_date_last_7_non_sundays();
+    let calendar = BusinessCalendar::load(db).await?;
+    let cutoff_date = calendar.start_of_last_business_days(business_calendar::today(), PENDING_VERIFICATION_BUSINESS_DAYS);
 
     let mut pending_map: HashMap<i32, Date> = HashMap::new();
 
@@ -203,7 +191,7 @@ async fn build_pending_conflicts_for_member(
         if !verification_status_counts_as_pending(row.status_id) {
             continue;
         }
-        let created_date: Date = row.date_created.date_naive();
+        let created_date: Date = business_calendar::date_of(&row.date_created);
 
         if created_date < cutoff_date {
             continue;
@@ -235,7 +223,7 @@ pub struct MemberServiceCountSummaryResponse {
     pub conflict_date: Option<Date>,
 }
 
-async fn build_count_summary_for_member(
+pub(crate) async fn build_count_summary_for_member(
     db: &DatabaseConnection,
     member_id: i32,
 ) -> Result<Vec<MemberServiceCountSummaryResponse>, DbErr> {
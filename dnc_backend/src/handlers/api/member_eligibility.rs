@@ -0,0 +1,242 @@
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use sea_orm::{ColumnTrait, Condition, DbErr, EntityTrait, QueryFilter, QueryOrder, prelude::Date};
+use sea_orm::sea_query::{Expr, ExprTrait, Func};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::AppState;
+use crate::business_calendar;
+use crate::entities::{endorsement, endorsement_company, hmo, master_list_member};
+use super::dentist_relations::get_endorsement_ids_for_dentist_id;
+use super::master_list_member_counts::build_count_summary_for_member;
+
+fn internal_error(err: DbErr) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EligibilityQuery {
+    pub account_number: Option<String>,
+    pub last_name: Option<String>,
+    pub first_name: Option<String>,
+    pub birth_date: Option<Date>,
+    /// defaults to today
+    pub service_date: Option<Date>,
+    pub dentist_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EligibilityEndorsement {
+    pub endorsement_id: i32,
+    pub hmo_id: i32,
+    pub hmo_short_name: String,
+    pub endorsement_company_id: i32,
+    pub endorsement_company_name: String,
+    pub agreement_corp_number: Option<String>,
+    pub date_start: Date,
+    pub date_end: Date,
+    pub is_active: bool,
+    pub hmo_is_active: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ServiceEligibility {
+    pub dental_service_id: i32,
+    pub dental_service_name: String,
+    pub dental_service_type_id: i32,
+    pub counts_allowed: i32,
+    pub counts_used: i32,
+    pub counts_remaining: i32,
+    pub has_pending: bool,
+    pub conflict_date: Option<Date>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberEligibility {
+    pub member_id: i32,
+    pub account_number: String,
+    pub last_name: String,
+    pub first_name: String,
+    pub middle_name: String,
+    pub birth_date: Option<Date>,
+    pub member_is_active: bool,
+    pub endorsement: EligibilityEndorsement,
+    pub endorsement_covers_service_date: bool,
+    /// None when no dentist_id was given
+    pub dentist_allowed: Option<bool>,
+    pub is_eligible: bool,
+    /// Why the member is not eligible; empty when eligible
+    pub reasons: Vec<String>,
+    pub services: Vec<ServiceEligibility>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EligibilityResponse {
+    pub service_date: Date,
+    pub dentist_id: Option<i32>,
+    /// True if any of the matched members is eligible
+    pub is_eligible: bool,
+    pub match_count: usize,
+    pub members: Vec<MemberEligibility>,
+}
+
+/// GET /api/eligibility?account_number=&service_date=&dentist_id=
+/// GET /api/eligibility?last_name=&first_name=&birth_date=&service_date=&dentist_id=
+///
+/// Everything a CSR needs to check a walk-in patient in one call. An account number can be
+/// on more than one endorsement, so every matching member is returned, each with its own
+/// verdict; eligible members come first.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_member_eligibility(
+    State(state): State<AppState>,
+    Query(params): Query<EligibilityQuery>,
+) -> Result<Json<EligibilityResponse>, (StatusCode, String)> {
+    let service_date = params.service_date.unwrap_or_else(business_calendar::today);
+
+    // ---1 find the member(s)
+    let mut query = master_list_member::Entity::find();
+    match params.account_number.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        Some(account_number) => {
+            query = query.filter(master_list_member::Column::AccountNumber.eq(account_number));
+        }
+        None => {
+            let last_name = params.last_name.as_deref().map(str::trim).filter(|s| !s.is_empty());
+            let first_name = params.first_name.as_deref().map(str::trim).filter(|s| !s.is_empty());
+            let (Some(last_name), Some(first_name), Some(birth_date)) = (last_name, first_name, params.birth_date) else {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    "Give account_number, or last_name, first_name and birth_date".to_string(),
+                ));
+            };
+            // exact names, ignoring case; not patterns, so "%" or "_" in a name matches only itself
+            query = query.filter(
+                Condition::all()
+                    .add(Func::lower(Expr::col(master_list_member::Column::LastName)).eq(Func::lower(Expr::val(last_name))))
+                    .add(Func::lower(Expr::col(master_list_member::Column::FirstName)).eq(Func::lower(Expr::val(first_name))))
+                    .add(master_list_member::Column::BirthDate.eq(birth_date)),
+            );
+        }
+    }
+    let members = query
+        .order_by_asc(master_list_member::Column::Id)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+
+    // ---2 endorsements the dentist may serve
+    let dentist_endorsement_ids: Option<HashSet<i32>> = match params.dentist_id {
+        Some(dentist_id) => Some(
+            get_endorsement_ids_for_dentist_id(&state.db, dentist_id)
+                .await
+                .map_err(internal_error)?
+                .into_iter()
+                .collect(),
+        ),
+        None => None,
+    };
+
+    // ---3 verdict per member
+    let mut results = Vec::with_capacity(members.len());
+    for member in members {
+        let Some(endorsement_row) = endorsement::Entity::find_by_id(member.endorsement_id)
+            .one(&state.db)
+            .await
+            .map_err(internal_error)?
+        else {
+            continue;
+        };
+        let hmo_row = hmo::Entity::find_by_id(endorsement_row.hmo_id)
+            .one(&state.db)
+            .await
+            .map_err(internal_error)?;
+        let company_row = endorsement_company::Entity::find_by_id(endorsement_row.endorsement_company_id)
+            .one(&state.db)
+            .await
+            .map_err(internal_error)?;
+
+        let covers_date = endorsement_row.date_start <= service_date && service_date <= endorsement_row.date_end;
+        let hmo_is_active = hmo_row.as_ref().is_some_and(|h| h.active);
+        let dentist_allowed = dentist_endorsement_ids
+            .as_ref()
+            .map(|ids| ids.contains(&endorsement_row.id));
+
+        let mut reasons = Vec::new();
+        if !member.is_active {
+            reasons.push("The member is inactive".to_string());
+        }
+        if !endorsement_row.is_active {
+            reasons.push("The endorsement is inactive".to_string());
+        }
+        if !hmo_is_active {
+            reasons.push("The HMO is inactive".to_string());
+        }
+        if !covers_date {
+            reasons.push(format!(
+                "The endorsement runs from {} to {}, which does not include {}",
+                endorsement_row.date_start, endorsement_row.date_end, service_date
+            ));
+        }
+        if dentist_allowed == Some(false) {
+            reasons.push("The dentist may not serve this endorsement".to_string());
+        }
+
+        let services = build_count_summary_for_member(&state.db, member.id)
+            .await
+            .map_err(internal_error)?
+            .into_iter()
+            .map(|s| ServiceEligibility {
+                dental_service_id: s.dental_service_id,
+                dental_service_name: s.dental_service_name,
+                dental_service_type_id: s.dental_service_type_id,
+                counts_allowed: s.counts_allowed,
+                counts_used: s.counts_used,
+                counts_remaining: (s.counts_allowed - s.counts_used).max(0),
+                has_pending: s.has_pending,
+                conflict_date: s.conflict_date,
+            })
+            .collect();
+
+        results.push(MemberEligibility {
+            member_id: member.id,
+            account_number: member.account_number,
+            last_name: member.last_name,
+            first_name: member.first_name,
+            middle_name: member.middle_name,
+            birth_date: member.birth_date,
+            member_is_active: member.is_active,
+            endorsement: EligibilityEndorsement {
+                endorsement_id: endorsement_row.id,
+                hmo_id: endorsement_row.hmo_id,
+                hmo_short_name: hmo_row.map(|h| h.short_name).unwrap_or_default(),
+                endorsement_company_id: endorsement_row.endorsement_company_id,
+                endorsement_company_name: company_row.map(|c| c.name).unwrap_or_default(),
+                agreement_corp_number: endorsement_row.agreement_corp_number,
+                date_start: endorsement_row.date_start,
+                date_end: endorsement_row.date_end,
+                is_active: endorsement_row.is_active,
+                hmo_is_active,
+            },
+            endorsement_covers_service_date: covers_date,
+            dentist_allowed,
+            is_eligible: reasons.is_empty(),
+            reasons,
+            services,
+        });
+    }
+
+    results.sort_by_key(|m| !m.is_eligible);
+
+    Ok(Json(EligibilityResponse {
+        service_date,
+        dentist_id: params.dentist_id,
+        is_eligible: results.iter().any(|m| m.is_eligible),
+        match_count: results.len(),
+        members: results,
+    }))
+}
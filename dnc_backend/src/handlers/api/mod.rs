@@ -35,6 +35,9 @@ pub mod endorsement_master_list_delete;
 pub mod endorsement_master_list_member;
 pub mod hmo_endorsement;
 pub mod verification;
+pub mod verification_validation;
+pub mod verification_void;
+pub mod business_calendar;
 pub mod master_list_member;
 pub mod dentist_company_relations;
 pub mod dentist_relations;
@@ -55,4 +58,9 @@ pub mod endorsements;
 pub mod website;
 pub mod csr_dentists;
 pub mod csr_endorsements;
+pub mod audit_log;
+pub mod approval_code_rules;
 
+pub mod master_list_import;
+pub mod member_eligibility;
+pub mod jobs;
@@ -5,28 +5,32 @@ use axum::{
     extract::{State},
     Json,
 };
-use chrono::{Utc};
 use serde::{Serialize};
 
 
 use crate::AppState;
+use crate::business_calendar;
+use crate::handlers::AuthUser;
 use crate::jobs::hmo_billing::generate_hmo_billing_reports;
 
 #[derive(Debug, Serialize)]
 pub struct GenerateHmoBillingReportsResponse {
     pub success: bool,
     pub message: String,
+    pub billing_run_id: i32,
 }
 // test_generate_hmo_billing_reports is an API endpoint that generates an HMO billing report
-// where today is the end_date.
+// where today is the end_date. Calling it again the same day regenerates the same draft run.
 pub async fn test_generate_hmo_billing_reports(
     State(state): State<AppState>,
+    auth_user: AuthUser,
 ) -> Result<Json<GenerateHmoBillingReportsResponse>, (axum::http::StatusCode, String)> {
-    let today = Utc::now().date_naive();
-    generate_hmo_billing_reports(
+    let today = business_calendar::today();
+    let run = generate_hmo_billing_reports(
         state,
         None,
-        today
+        today,
+        &auth_user.claims.email,
     )
         .await
         .map_err(|e| {
@@ -39,5 +43,6 @@ pub async fn test_generate_hmo_billing_reports(
     Ok(Json(GenerateHmoBillingReportsResponse {
         success: true,
         message: "HMO billing reports generated successfully".to_string(),
+        billing_run_id: run.id,
     }))
 }
\ No newline at end of file
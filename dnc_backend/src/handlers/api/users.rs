@@ -7,6 +7,7 @@ use sea_orm::{ActiveModelTrait, ColumnTrait,  Condition, EntityTrait, FromQueryR
 use sea_orm::sea_query::extension::postgres::PgExpr;
 use serde::{Serialize, Deserialize};
 use crate::handlers::helpers::role_has_permission_by_data_object_name;
+use crate::handlers::sessions::revoke_all_sessions_for_user;
 use crate::entities::{user, role};
 use crate::entities::sea_orm_active_enums::PermissionActionEnum;
 use crate::handlers::{ListQuery, PageResponse};
@@ -371,5 +372,15 @@ pub async fn patch_user(
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
+    // 6) A deactivated user is logged out everywhere.
+    if !updated.active {
+        revoke_all_sessions_for_user(&state.db, updated.id, &auth.claims.email, "user deactivated")
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to revoke sessions of user {id}: {e:?}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    }
+
     Ok(Json(updated.into()))
 }
\ No newline at end of file
@@ -1,13 +1,14 @@
 use axum::{extract::State, http::StatusCode, Json};
 use axum::extract::Path;
-use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, FromQueryResult,
+use sea_orm::{ActiveModelTrait, ColumnTrait, DbErr, EntityTrait, FromQueryResult,
               JoinType, QueryFilter, QueryOrder, QuerySelect, PaginatorTrait, RelationTrait, Set,
-              TransactionTrait,
-              Condition};
+              TransactionTrait};
 use sea_orm::sea_query::{Expr,ExprTrait};
 use serde::{Serialize, Deserialize};
 use tracing::instrument;
 use chrono::{ Utc};
+use crate::business_calendar;
+use crate::jobs::approval_code_validity::lapse_status_of;
 use std::collections::HashMap;
 use crate::{
     AppState,
@@ -19,13 +20,22 @@ use crate::{
         master_list_member,
         verification,
         verification_status,
-        endorsement_counts,
+        verification_status_history,
         high_end_verification_information,
         tooth_surface,
         verification_tooth_surfaces,
     },
 };
 use crate::handlers::AuthUser;
+use crate::handlers::approval_codes::{ApprovalCodeKeyRing, decode_approval_code, generate_approval_code};
+use crate::verification_state::{self, VerificationStatus};
+use crate::handlers::api::approval_code_rules::engine::{
+    evaluate_release_rules, first_rejection, lock_member_for_release, ReleaseContext, RuleOutcome,
+    REJECT_ALREADY_RELEASED,
+};
+use crate::handlers::api::verification_validation::{
+    lock_member_for_create, validate_new_verification, NewVerification, VerificationValidation,
+};
 use sea_orm::prelude::{Date, Decimal};
 
 
@@ -260,7 +270,7 @@ pub async fn get_all_verifications(
     let response = rows
         .into_iter()
         .map(|row| {
-            let (approval_code, approved_by, approval_date) = if row.status_id==99 {
+            let (approval_code, approved_by, approval_date) = if row.status_id==VerificationStatus::Done.int_code() {
                 (row.approval_code, row.approved_by, row.approval_date)
             } else{
                 (None, None, None)
@@ -337,6 +347,29 @@ pub struct CreateVerificationResponse {
     pub approval_code: Option<String>,
 }
 
+impl CreateVerificationRequest {
+    fn as_new_verification(&self) -> NewVerification {
+        NewVerification {
+            dentist_id: self.dentist_id,
+            member_id: self.member_id,
+            dental_service_id: self.dental_service_id,
+            dental_clinic_id: self.dental_clinic_id,
+        }
+    }
+}
+
+/// Runs the checks of create_verification without creating anything.
+#[instrument(skip(state), err(Debug))]
+pub async fn validate_verification(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateVerificationRequest>,
+) -> Result<Json<VerificationValidation>, (StatusCode, String)> {
+    let validation = validate_new_verification(&state.db, payload.as_new_verification())
+        .await
+        .map_err(internal_error)?;
+    Ok(Json(validation))
+}
+
 #[instrument(skip(state), err(Debug))]
 pub async fn create_verification(
     State(state): State<AppState>,
@@ -345,46 +378,45 @@ pub async fn create_verification(
 ) -> Result<(StatusCode, Json<CreateVerificationResponse>), (StatusCode, String)> {
     let now = Utc::now().fixed_offset();
 
-    // find the dental service record
-    let dental_service = dental_service::Entity::find_by_id(payload.dental_service_id)
-        .one(&state.db)
-    .await
-        .map_err(internal_error)?
-        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Dental service not found".to_string()))?;
+    // the checks count the member's open verifications; hold the lock until the insert commits
+    let txn = state.db.begin().await.map_err(internal_error)?;
+    lock_member_for_create(&txn, payload.member_id).await.map_err(internal_error)?;
 
-    let _dental_clinic = dental_clinic::Entity::find_by_id(payload.dental_clinic_id)
-        .one(&state.db)
+    // every check runs; all the reasons go back to the CSR
+    let validation = validate_new_verification(&txn, payload.as_new_verification())
+        .await
+        .map_err(internal_error)?;
+    if !validation.is_valid {
+        let body = serde_json::to_string(&validation).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, body));
+    }
+
+    let dental_service = dental_service::Entity::find_by_id(payload.dental_service_id)
+        .one(&txn)
         .await
         .map_err(internal_error)?
-        .ok_or_else(||{
-            (
-                StatusCode::BAD_REQUEST,
-                "Dental clinic not found".to_string(),
-            )
-        })?;
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Dental service not found".to_string()))?;
 
-    // status_id=2 if dental_service.type_id==3, else 1
-    let status_id = if dental_service.type_id==3 {2} else {1};
+    // high-end services (dental_service.type_id==3) wait for files first
+    let initial_status = VerificationStatus::initial(dental_service.type_id==3);
 
     let new_verification = verification::ActiveModel {
         date_created: Set(now),
-        created_by: Set(auth_user.claims.email),
+        created_by: Set(auth_user.claims.email.clone()),
         dentist_id: Set(payload.dentist_id),
         member_id: Set(payload.member_id),
         dental_service_id: Set(payload.dental_service_id),
         dental_clinic_id: Set(payload.dental_clinic_id),
         date_service_performed: Set(None),
-        status_id: Set(status_id), // temporary
         approved_by: Set(None),
         approval_date: Set(None),
         approval_code: Set(None),
         ..Default::default()
     };
 
-    let inserted = new_verification
-        .insert(&state.db)
-        .await
-        .map_err(internal_error)?;
+    let inserted = verification_state::create(&txn, new_verification, initial_status, &auth_user.claims.email)
+        .await?;
+    txn.commit().await.map_err(internal_error)?;
 
     Ok((
         StatusCode::CREATED,
@@ -412,14 +444,30 @@ fn internal_error(err: DbErr) -> (StatusCode, String) {
 
 
 // region:Cancel Verification
+/// The body is optional; the reason is saved as the status history note.
+#[derive(Debug, Deserialize)]
+pub struct CancelVerificationRequest {
+    pub reason: Option<String>,
+}
+
 #[instrument(skip(state), err(Debug))]
 pub async fn cancel_verification(
     State(state): State<AppState>,
+    auth_user: AuthUser,
     Path(verification_id): Path<i32>,
+    payload: Option<Json<CancelVerificationRequest>>,
 ) -> Result<Json<verification::Model>, (StatusCode, String)> {
     tracing::info!("cancel_verification({})", verification_id);
+    let reason = payload
+        .as_ref()
+        .and_then(|Json(p)| p.reason.as_deref())
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+
+    let txn = state.db.begin().await.map_err(internal_error)?;
     let verification = verification::Entity::find_by_id(verification_id)
-        .one(&state.db)
+        .lock_exclusive()
+        .one(&txn)
         .await
         .map_err(|e| {
             (
@@ -434,22 +482,93 @@ pub async fn cancel_verification(
             )
         })?;
 
-    let mut verification_active: verification::ActiveModel = verification.into();
-    verification_active.status_id = Set(0);
+    // a released code is voided, not cancelled, so the reason and the reconciliation and
+    // payment checks are not skipped
+    if verification.approval_code.is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Verification {verification_id} already has an approval code; void the code instead"),
+        ));
+    }
+
+    let updated = verification_state::transition(
+        &txn,
+        verification.into(),
+        VerificationStatus::Cancelled,
+        &auth_user.claims.email,
+        reason,
+    )
+        .await?;
+    txn.commit().await.map_err(internal_error)?;
+
+    Ok(Json(updated))
+}
+// endregion: Cancel Verification
+
 
-    let updated = verification_active
-        .update(&state.db)
+// region: Verification Status History
+#[derive(Debug, Serialize)]
+pub struct VerificationStatusHistoryResponse {
+    pub id: i32,
+    pub verification_id: i32,
+    pub from_status_id: Option<i32>,
+    pub from_status_name: Option<String>,
+    pub to_status_id: i32,
+    pub to_status_name: String,
+    pub changed_on: sea_orm::prelude::DateTimeWithTimeZone,
+    pub changed_by: String,
+    pub note: Option<String>,
+}
+
+/// GET /api/verifications/{verification_id}/status_history
+/// The status timeline of one verification, oldest first.
+#[instrument(skip(state), err(Debug))]
+pub async fn get_verification_status_history(
+    State(state): State<AppState>,
+    Path(verification_id): Path<i32>,
+) -> Result<Json<Vec<VerificationStatusHistoryResponse>>, (StatusCode, String)> {
+    verification::Entity::find_by_id(verification_id)
+        .one(&state.db)
         .await
-        .map_err(|e| {
+        .map_err(internal_error)?
+        .ok_or_else(|| {
             (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to cancel verification: {e}"),
+                StatusCode::NOT_FOUND,
+                format!("Verification with id {verification_id} not found"),
             )
         })?;
 
-    Ok(Json(updated))
+    let rows = verification_status_history::Entity::find()
+        .filter(verification_status_history::Column::VerificationId.eq(verification_id))
+        .order_by_asc(verification_status_history::Column::ChangedOn)
+        .order_by_asc(verification_status_history::Column::Id)
+        .all(&state.db)
+        .await
+        .map_err(internal_error)?;
+
+    let status_name = |int_code: i32| {
+        VerificationStatus::from_int_code(int_code)
+            .map(|s| s.name().to_string())
+            .unwrap_or_else(|| format!("Unknown ({int_code})"))
+    };
+
+    Ok(Json(
+        rows.into_iter()
+            .map(|row| VerificationStatusHistoryResponse {
+                id: row.id,
+                verification_id: row.verification_id,
+                from_status_id: row.from_status_id,
+                from_status_name: row.from_status_id.map(status_name),
+                to_status_id: row.to_status_id,
+                to_status_name: status_name(row.to_status_id),
+                changed_on: row.changed_on,
+                changed_by: row.changed_by,
+                note: row.note,
+            })
+            .collect(),
+    ))
 }
-// endregion: Cancel Verification
+// endregion: Verification Status History
 
 
 // region: Get Approval Code
@@ -469,303 +588,6 @@ pub struct GetApprovalCodeResponse {
     pub approval_code: Option<String>,
 }
 
-// region: Approval Code Release Check
-/*
-The following checks need to be performed:
-1. The number of service availments must be below the endorsement limit.
-1. If it's the same dentist and the same member, reject if there are already have 3 approval codes for the same service_performed_day.
-2. An approval code was already released for the member but from another dentist on that day.
-3. The same service on the same tooth id and same surface will be denied.
-
- */
-#[derive(Debug)]
-struct ValidationCheckResult {
-    code: i32,
-    message: &'static str,
-}
-impl ValidationCheckResult {
-    fn ok()->Self{
-        Self{
-            code: 0,
-            message: "ok",
-        }
-    }
-}
-// check_approval_code_release() checks if an approval code could be released. The checks are:
-// 1. The endorsement limit has not been reached.
-// 2. Only up to 3 approval codes can be released in the same date_service_performed day.
-// 3. No same tooth_id and surface if dentists are different.
-async fn check_approval_code_release(
-    db: &DatabaseConnection,
-    verification_id: i32,
-    date_service_performed: Date,
-    tooth_id: Option<String>,
-    tooth_surface_ids: Vec<i32>,
-    tooth_service_type_id: Option<i32>,
-) -> Result<ValidationCheckResult, DbErr> {
-    let checks = [
-        check_service_allowed_by_endorsement_limit(db,
-        verification_id
-        ).await?,
-
-        check_other_released_approval_codes_for_same_date(
-            db,
-            verification_id,
-            date_service_performed,
-        ).await?,
-
-        check_no_same_dental_service_on_tooth_id_and_surface_and_service_type(
-            db,
-            verification_id,
-            date_service_performed,
-            tooth_id,
-            tooth_surface_ids,
-            tooth_service_type_id,
-        ) .await?,
-    ];
-
-    for check in checks {
-        if check.code != 0 {
-            return Ok(check);
-        }
-    }
-
-    Ok(ValidationCheckResult::ok())
-}
-
-// check_service_allowed_by_endorsement_limit() checks if the member can still avail of the service
-// given the limits set by the endorsement.
-async fn check_service_allowed_by_endorsement_limit(
-    db: &DatabaseConnection,
-    verification_id: i32
-)->Result<ValidationCheckResult, DbErr> {
-
-    // ---1 retrieve current_verification from verification_id
-    let current_verification = verification::Entity::find_by_id(verification_id)
-        .one(db)
-        .await?
-        .ok_or_else(|| DbErr::Custom(format!("Verification {} not found", verification_id)))?;
-
-    // ---2 retrieve dental_service record from the verification's dental_service_id
-    let dental_service = dental_service::Entity::find_by_id(current_verification.dental_service_id)
-        .one(db)
-        .await?
-        .ok_or_else(|| {
-            DbErr::Custom(format!(
-                "Dental service {} not found",
-                current_verification.dental_service_id
-            ))
-        })?;
-
-    //---3 if unlimited, no need to check further
-    if dental_service.is_unlimited == Some(true) {
-        return Ok(ValidationCheckResult::ok());
-    }
-
-    // ---4 Get endorsement by first getting the master_list_member record.
-    let member = master_list_member::Entity::find_by_id(current_verification.member_id)
-        .one(db)
-        .await?
-        .ok_or_else(|| {
-            DbErr::Custom(format!(
-                "Master list member {} not found",
-                current_verification.member_id
-            ))
-        })?;
-
-    // ---5 Since dental_service is not unlimited, get the limit from endorsement counts.
-    // if the record doesn't exist, assume 0.
-    let allowed_count = endorsement_counts::Entity::find()
-        .filter(endorsement_counts::Column::EndorsementId.eq(member.endorsement_id))
-        .filter(
-            endorsement_counts::Column::DentalServicesId
-                .eq(current_verification.dental_service_id),
-        )
-        .one(db)
-        .await?
-        .map(|row| row.count)
-        .unwrap_or(0);
-
-    // ---6. Count the total number of already released approval codes for this service for this member.
-    let released_count = verification::Entity::find()
-        .filter(verification::Column::Id.ne(verification_id))
-        .filter(verification::Column::MemberId.eq(current_verification.member_id))
-        .filter(
-            verification::Column::DentalServiceId.eq(current_verification.dental_service_id),
-        )
-        .filter(verification::Column::ApprovalCode.is_not_null())
-        .filter(verification::Column::StatusId.eq(99))
-        .count(db)
-        .await? as i32;
-
-    // ----7. Return result
-    if released_count >= allowed_count {
-        return Ok(ValidationCheckResult {
-            code: 6,
-            message: "endorsement service limit exceeded for this member",
-        });
-    }
-
-    Ok(ValidationCheckResult::ok())
-}
-
-// check_other_released_approval_codes_for_same_date() checks that only up to 3 approval codes
-// can be given to a dentist for a patient in one day.
-async fn check_other_released_approval_codes_for_same_date(
-    db: &DatabaseConnection,
-    verification_id: i32,
-    date_service_performed: Date,
-) -> Result<ValidationCheckResult, DbErr> {
-    let current_verification = verification::Entity::find_by_id(verification_id)
-        .one(db)
-        .await?
-        .ok_or_else(|| DbErr::Custom(format!("Verification {} not found", verification_id)))?;
-
-        let count = verification::Entity::find()
-        .filter(verification::Column::Id.ne(verification_id))
-        .filter(verification::Column::DentistId.eq(current_verification.dentist_id))
-        .filter(verification::Column::MemberId.eq(current_verification.member_id))
-        .filter(verification::Column::DateServicePerformed.eq(date_service_performed))
-        .filter(verification::Column::ApprovalCode.is_not_null())
-        .filter(verification::Column::StatusId.eq(99))
-        .count(db)
-        .await?;
-        tracing::info!("DentistId:{} MemberID:{} Date:{} have {} occurrences.",
-            current_verification.dental_service_id, current_verification.member_id, date_service_performed,count);
-
-        if count >= 3 {
-            return Ok(ValidationCheckResult {
-                code: 3,
-                message: "approval code release limit exceeded for this dentist, member, and service date",
-            });
-        }
-
-        Ok(ValidationCheckResult::ok())
-}
-
-// check_no_same_dental_service_on_tooth_id_and_surface_and_service_type()
-// disallows having the same service on the same tooth and surface if dentists are different.
-// But the same dentist is allowed if the service_types are different.
-async fn check_no_same_dental_service_on_tooth_id_and_surface_and_service_type(
-    db: &DatabaseConnection,
-    verification_id: i32,
-    date_service_performed: Date,
-    tooth_id: Option<String>,
-    tooth_surface_ids: Vec<i32>,
-    tooth_service_type_id: Option<i32>,
-)->Result<ValidationCheckResult, DbErr> {
-
-
-    //--- 1. Retrieve the verification record defined by the verification_id.
-    let current_verification = verification::Entity::find_by_id(verification_id)
-        .one(db)
-        .await?
-        .ok_or_else(|| DbErr::Custom(format!("Verification {} not found", verification_id)))?;
-
-    //---2. Create a condition of two verifications different from the one in question,
-    // but the same member, same service, SAME DATE service performed, and an approval code is present.
-    let mut base_condition = Condition::all()
-        .add(verification::Column::Id.ne(verification_id))
-        .add(verification::Column::MemberId.eq(current_verification.member_id))
-        .add(verification::Column::DentalServiceId.eq(current_verification.dental_service_id))
-        .add(verification::Column::DateServicePerformed.eq(date_service_performed))
-        .add(verification::Column::ApprovalCode.is_not_null())
-        .add(verification::Column::StatusId.eq(99));
-
-    // ---2a1 if a tooth_id is present, add the condition of same tooth-id,
-    base_condition = match tooth_id {
-        Some(ref v) => base_condition.add(verification::Column::ToothId.eq(v.clone())),
-        None => base_condition.add(verification::Column::ToothId.is_null()),
-    };
-
-    // Add: Normalize the surface IDs so duplicates do not affect checks/inserts
-    let mut tooth_surface_ids = tooth_surface_ids;
-    tooth_surface_ids.sort();
-    tooth_surface_ids.dedup();
-
-    // ---3a Find if another dentist did the other verifications.
-    let mut other_dentist_query= verification::Entity::find()
-        .filter(base_condition.clone())
-        .filter(verification::Column::DentistId.ne(current_verification.dentist_id));
-
-    if tooth_surface_ids.is_empty() {
-        other_dentist_query = other_dentist_query
-            .join(
-                JoinType::LeftJoin,
-                verification::Relation::VerificationToothSurfaces.def(),
-            )
-            .filter(verification_tooth_surfaces::Column::Id.is_null());
-    } else {
-
-        other_dentist_query = other_dentist_query
-            .join(
-                JoinType::InnerJoin,
-                verification::Relation::VerificationToothSurfaces.def(),
-            )
-            .filter(
-                verification_tooth_surfaces::Column::ToothSurfaceId
-                    .is_in(tooth_surface_ids.clone())
-            );
-    }
-    let other_dentist_conflict = other_dentist_query.one(db).await?;
-
-
-    // ---3b if that other dentist exists, error.
-    if other_dentist_conflict.is_some() {
-        return Ok(ValidationCheckResult {
-            code: 4,
-            message: "same dental service on same day already has an approved verification on this tooth and surface by another dentist",
-        });
-    }
-
-    // --- 4 Narrow to same dentist.
-    let mut same_dentist_same_service_type_condition = base_condition
-        .clone()
-        .add(verification::Column::DentistId.eq(current_verification.dentist_id));
-
-    // ---4a Compare service types
-    same_dentist_same_service_type_condition = match tooth_service_type_id {
-        Some(v) => same_dentist_same_service_type_condition
-            .add(verification::Column::ToothServiceTypeId.eq(v)),
-        None => same_dentist_same_service_type_condition
-            .add(verification::Column::ToothServiceTypeId.is_null()),
-    };
-    // --- 4b find a conflict of same service type.
-    let mut same_dentist_query= verification::Entity::find()
-        .filter(same_dentist_same_service_type_condition);
-
-    if tooth_surface_ids.is_empty() {
-        same_dentist_query = same_dentist_query
-            .join(
-                JoinType::LeftJoin,
-                verification::Relation::VerificationToothSurfaces.def(),
-            )
-            .filter(verification_tooth_surfaces::Column::Id.is_null());
-    } else {
-        same_dentist_query = same_dentist_query
-            .join(
-                JoinType::InnerJoin,
-                verification::Relation::VerificationToothSurfaces.def(),
-            )
-            .filter(
-                verification_tooth_surfaces::Column::ToothSurfaceId
-                    .is_in(tooth_surface_ids.clone())
-            );
-    }
-    let same_dentist_same_service_type_conflict = same_dentist_query.one(db).await?;
-
-    // --- 4c If found, return error
-    if same_dentist_same_service_type_conflict.is_some() {
-        return Ok(ValidationCheckResult {
-            code: 5,
-            message: "same dental service already has an approved verification on this tooth, surface, and service type for this dentist",
-        });
-    }
-
-    // ----5 all good
-    Ok(ValidationCheckResult::ok())
-}
-
 // region: Get Approval Code For Verification ID
 #[instrument(skip(state, auth_user, payload), err(Debug))]
 pub async fn get_approval_code_for_verification_id(
@@ -775,30 +597,54 @@ pub async fn get_approval_code_for_verification_id(
     Json(payload): Json<GetApprovalCodeRequest>,
 ) -> Result<Json<GetApprovalCodeResponse>, (StatusCode, String)> {
 
-    // --- 1. Retrieve the verification row defined by verification_id
-    let verification_model = verification::Entity::find()
-        .filter(verification::Column::Id.eq(verification_id))
-        .one(&state.db)
+    // --- 1. Serialize releases for the member. The lock is held until the transaction ends, so
+    // the checks below and the save all see the codes released before this one.
+    let txn = state.db.begin().await.map_err(internal_error)?;
+    let member_id = verification::Entity::find_by_id(verification_id)
+        .one(&txn)
         .await
         .map_err(internal_error)?
+        .map(|v| v.member_id)
         .ok_or_else(|| {
             (
                 StatusCode::NOT_FOUND,
                 format!("Verification {} not found", verification_id),
             )
         })?;
+    lock_member_for_release(&txn, member_id).await.map_err(internal_error)?;
 
-    if verification_model.approval_code.is_some() || verification_model.status_id == 99 {
+    // --- 1a. Retrieve the verification row defined by verification_id, as of now
+    let verification_model = verification::Entity::find_by_id(verification_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Verification {} not found", verification_id),
+            )
+        })?;
+
+    let current_status = verification_state::status_of(&verification_model)?;
+    if verification_model.approval_code.is_some() || current_status == VerificationStatus::Done {
         return Ok(Json(GetApprovalCodeResponse {
-            reject_code: 7,
+            reject_code: REJECT_ALREADY_RELEASED,
             reject_message: "approval code already released for this verification".to_string(),
             approval_code: verification_model.approval_code.clone(),
         }));
     }
+    // e.g. Expired, Cancelled, or a high-end verification the dentist has not quoted yet
+    if !current_status.can_transition_to(VerificationStatus::Done) {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Cannot release an approval code for a verification that is '{current_status}'"),
+        ));
+    }
 
-    // --- 2. Do checks if the approval code could be released.
-    let validation = check_approval_code_release(
-        &state.db,
+    // --- 2. Run the release rules configured for this member's HMO and endorsement.
+    let ctx = ReleaseContext::load(
+        &txn,
         verification_id,
         payload.date_service_performed,
         payload.tooth_id.clone(),
@@ -807,12 +653,15 @@ pub async fn get_approval_code_for_verification_id(
     )
         .await
         .map_err(internal_error)?;
+    let outcomes = evaluate_release_rules(&txn, &ctx, true)
+        .await
+        .map_err(internal_error)?;
 
     // --- 2a. If there was an issue, return the code and message.
-    if validation.code != 0 {
+    if let Some(rejection) = first_rejection(&outcomes) {
         return Ok(Json(GetApprovalCodeResponse {
-            reject_code: validation.code,
-            reject_message: validation.message.to_string(),
+            reject_code: rejection.code,
+            reject_message: rejection.message,
             approval_code: None,
         }));
     }
@@ -826,7 +675,7 @@ pub async fn get_approval_code_for_verification_id(
     if !tooth_surface_ids.is_empty() {
         let found_count = tooth_surface::Entity::find()
             .filter(tooth_surface::Column::Id.is_in(tooth_surface_ids.clone()))
-            .count(&state.db)
+            .count(&txn)
             .await
             .map_err(internal_error)?;
 
@@ -839,10 +688,12 @@ pub async fn get_approval_code_for_verification_id(
 
     }
 
-    // Use transaction so verification update and surface rows save together
-    let txn = state.db.begin().await.map_err(internal_error)?;
+    let keys = ApprovalCodeKeyRing::from_env()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let approval_code = generate_approval_code(verification_id);
+    // Save in the same transaction, so verification update and surface rows save together
+    // before the lock is released
+    let approval_code = generate_approval_code(&keys, verification_id);
 
     let mut verification_active: verification::ActiveModel = verification_model.into();
     verification_active.date_service_performed = Set(Some(payload.date_service_performed));
@@ -852,12 +703,15 @@ pub async fn get_approval_code_for_verification_id(
     verification_active.approved_by = Set(Some(auth_user.claims.email.clone()));
     verification_active.approval_date = Set(Some(Utc::now().into()));
     verification_active.approval_code = Set(Some(approval_code.clone()));
-    verification_active.status_id = Set(99); // 99 is "Done"
 
-    verification_active
-        .update(&txn)
-        .await
-        .map_err(internal_error)?;
+    verification_state::transition(
+        &txn,
+        verification_active,
+        VerificationStatus::Done,
+        &auth_user.claims.email,
+        None,
+    )
+        .await?;
 
     // Delete any existing tooth surfaces rows for this verification.
     verification_tooth_surfaces::Entity::delete_many()
@@ -896,79 +750,80 @@ pub async fn get_approval_code_for_verification_id(
 // endregion: Get Approval Code for Verification ID
 
 
-// region: generate_approval_code
-
-use hmac::{Hmac, KeyInit, Mac};
-use sha2::Sha256;
-
-type HmacSha256 = Hmac<Sha256>;
-
- const APPROVAL_CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
-const ID_PART_LEN: usize = 5;
-const TAG_PART_LEN: usize = 4;
-
-fn generate_approval_code(verification_id: i32) -> String {
-    assert!(verification_id >= 0, "verification_id must be non-negative");
-
-    let secret = "Dental Network Company's Random Secret";
-    let id_u64 = verification_id as u64;
-
-    let mut mask_mac =
-        <HmacSha256 as KeyInit>::new_from_slice(secret.as_bytes())
-            .expect("invalid HMAC key");
-    mask_mac.update(b"approval-code-mask:v1");
-    let mask_bytes = mask_mac.finalize().into_bytes();
-
-    let mut mask_arr = [0u8; 8];
-    mask_arr.copy_from_slice(&mask_bytes[..8]);
-    let mask_25 = u64::from_be_bytes(mask_arr) & ((1u64 << 25) - 1);
-
-    let obfuscated_id = id_u64 ^ mask_25;
-
-    let mut tag_mac =
-        <HmacSha256 as KeyInit>::new_from_slice(secret.as_bytes())
-            .expect("invalid HMAC key");
-    tag_mac.update(b"approval-code-tag:v1:");
-    tag_mac.update(verification_id.to_string().as_bytes());
-    let tag_bytes = tag_mac.finalize().into_bytes();
-
-    let mut tag_arr = [0u8; 4];
-    tag_arr.copy_from_slice(&tag_bytes[..4]);
-    let tag_20 = (u32::from_be_bytes(tag_arr) >> 12) & ((1u32 << 20) - 1);
-
-    let id_part = encode_base32_fixed(obfuscated_id, ID_PART_LEN);
-    let tag_part = encode_base32_fixed(tag_20 as u64, TAG_PART_LEN);
-
-    let raw = format!("{id_part}{tag_part}");
-    format_approval_code(&raw)
+// region: Dry Run Approval Code Release
+#[derive(Debug, Serialize)]
+pub struct ApprovalCodeDryRunResponse {
+    pub verification_id: i32,
+    pub status_id: i32,
+    pub status_name: String,
+    pub would_release: bool,
+    pub reject_code: i32,
+    pub reject_message: String,
+    pub rules: Vec<RuleOutcome>,
 }
 
-fn encode_base32_fixed(mut value: u64, len: usize) -> String {
-    let mut out = vec!['A'; len];
-    for i in (0..len).rev() {
-        let idx = (value & 0b1_1111) as usize;
-        out[i] = APPROVAL_CODE_ALPHABET[idx] as char;
-        value >>= 5;
-    }
-
-    out.into_iter().collect()
-}
+/// POST /api/verifications/{verification_id}/approval_code/dry_run
+/// Takes the same body as the release endpoint and reports every rule's outcome without
+/// releasing a code or changing anything.
+#[instrument(skip(state, payload), err(Debug))]
+pub async fn dry_run_approval_code_for_verification_id(
+    State(state): State<AppState>,
+    Path(verification_id): Path<i32>,
+    Json(payload): Json<GetApprovalCodeRequest>,
+) -> Result<Json<ApprovalCodeDryRunResponse>, (StatusCode, String)> {
+    let verification_model = verification::Entity::find_by_id(verification_id)
+        .one(&state.db)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Verification {} not found", verification_id),
+            )
+        })?;
+    let current_status = verification_state::status_of(&verification_model)?;
 
-fn format_approval_code(raw: &str) -> String {
-    debug_assert_eq!(raw.len(), 9);
+    // the rules run in a transaction that is dropped, so rolled back, without writing anything
+    let txn = state.db.begin().await.map_err(internal_error)?;
+    let ctx = ReleaseContext::load(
+        &txn,
+        verification_id,
+        payload.date_service_performed,
+        payload.tooth_id.clone(),
+        payload.tooth_surface_ids.clone(),
+        payload.tooth_service_type_id,
+    )
+        .await
+        .map_err(internal_error)?;
+    let rules = evaluate_release_rules(&txn, &ctx, false)
+        .await
+        .map_err(internal_error)?;
 
-    let mut out = String::with_capacity(11);
-    for (i, ch) in raw.chars().enumerate() {
-        if i > 0 && i % 3 == 0 {
-            out.push('-');
-        }
-        out.push(ch);
-    }
-    out
+    // Same order of checks as the release endpoint.
+    let (reject_code, reject_message) =
+        if verification_model.approval_code.is_some() || current_status == VerificationStatus::Done {
+            (REJECT_ALREADY_RELEASED, "approval code already released for this verification".to_string())
+        } else if !current_status.can_transition_to(VerificationStatus::Done) {
+            (-1, format!("Cannot release an approval code for a verification that is '{current_status}'"))
+        } else if let Some(rejection) = first_rejection(&rules) {
+            (rejection.code, rejection.message)
+        } else {
+            (0, "ok".to_string())
+        };
+
+    Ok(Json(ApprovalCodeDryRunResponse {
+        verification_id,
+        status_id: current_status.int_code(),
+        status_name: current_status.name().to_string(),
+        would_release: reject_code == 0,
+        reject_code,
+        reject_message,
+        rules,
+    }))
 }
+// endregion: Dry Run Approval Code Release
 
 
-// endregion: generate_approval_code
 
 
 // region HTTP Check Approval Code
@@ -977,112 +832,54 @@ fn format_approval_code(raw: &str) -> String {
 pub struct CheckApprovalCodeResponse {
     pub is_approval_code: bool,
     pub verification_id: Option<i32>,
+    pub key_id: Option<String>,
+    /// the code was released, then voided; it must not be honored
+    pub is_void: bool,
+    pub void_reason_code: Option<String>,
+    pub voided_on: Option<sea_orm::prelude::DateTimeWithTimeZone>,
+    /// the code was not reconciled within its validity window and was not overridden
+    pub is_lapsed: bool,
+    /// the last day the code can be reconciled; None if it does not lapse
+    pub valid_until: Option<Date>,
 }
 
 pub async fn check_approval_code(
+    State(state): State<AppState>,
     Path(code): Path<String>,
 ) -> Result<Json<CheckApprovalCodeResponse>, (StatusCode, String)> {
     tracing::info!("check_approval_code: {}", code);
-    let verification_id = decode_approval_code(&code);
+    let keys = ApprovalCodeKeyRing::from_env()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let decoded = decode_approval_code(&keys, &code);
+
+    let released = match &decoded {
+        Some(d) => verification::Entity::find_by_id(d.verification_id)
+            .one(&state.db)
+            .await
+            .map_err(internal_error)?,
+        None => None,
+    };
+    let lapse = match &released {
+        Some(v) => {
+            let today_manila = business_calendar::today();
+            lapse_status_of(&state.db, v, today_manila).await.map_err(internal_error)?
+        }
+        None => None,
+    };
+    let voided = released.filter(|v| v.status_id == VerificationStatus::Voided.int_code());
 
     Ok(Json(CheckApprovalCodeResponse {
-        is_approval_code: verification_id.is_some(),
-        verification_id,
+        is_approval_code: decoded.is_some(),
+        verification_id: decoded.as_ref().map(|d| d.verification_id),
+        key_id: decoded.map(|d| d.key_id),
+        is_void: voided.is_some(),
+        void_reason_code: voided.as_ref().and_then(|v| v.void_reason_code.clone()),
+        voided_on: voided.and_then(|v| v.voided_on),
+        is_lapsed: lapse.is_some_and(|l| l.is_lapsed),
+        valid_until: lapse.and_then(|l| l.valid_until),
     }))
 }
 
 // endregion HTTP Check Approval Code
 
 
-// region: Check Approval Code
-pub fn decode_approval_code(code: &str) -> Option<i32> {
-    let secret = "Dental Network Company's Random Secret";
-
-    // 1. Check format: AAA-BBB-CCC
-    if code.len() != 11 {
-        return None;
-    }
-
-    let bytes = code.as_bytes();
-    if bytes[3] != b'-' || bytes[7] != b'-' {
-        return None;
-    }
-
-    // 2. Remove hyphens
-    let raw: String = code.chars().filter(|&c| c != '-').collect();
-    if raw.len() != ID_PART_LEN + TAG_PART_LEN {
-        return None;
-    }
-
-    let id_part = &raw[..ID_PART_LEN];
-    let tag_part = &raw[ID_PART_LEN..];
-
-    // 3. Decode both parts from your custom base32 alphabet
-    let obfuscated_id = decode_base32_fixed(id_part)?;
-    let supplied_tag = decode_base32_fixed(tag_part)? as u32;
-
-    // 4. Rebuild the same 25-bit mask
-    let mut mask_mac =
-        <HmacSha256 as KeyInit>::new_from_slice(secret.as_bytes())
-            .expect("invalid HMAC key");
-    mask_mac.update(b"approval-code-mask:v1");
-    let mask_bytes = mask_mac.finalize().into_bytes();
-
-    let mut mask_arr = [0u8; 8];
-    mask_arr.copy_from_slice(&mask_bytes[..8]);
-    let mask_25 = u64::from_be_bytes(mask_arr) & ((1u64 << 25) - 1);
-
-    // 5. Reverse the obfuscation
-    let verification_id_u64 = obfuscated_id ^ mask_25;
-
-    // Must fit in i32 and be non-negative
-    if verification_id_u64 > i32::MAX as u64 {
-        return None;
-    }
-    let verification_id = verification_id_u64 as i32;
-
-    // 6. Recompute the expected 20-bit tag
-    let mut tag_mac =
-        <HmacSha256 as KeyInit>::new_from_slice(secret.as_bytes())
-            .expect("invalid HMAC key");
-    tag_mac.update(b"approval-code-tag:v1:");
-    tag_mac.update(verification_id.to_string().as_bytes());
-    let tag_bytes = tag_mac.finalize().into_bytes();
-
-    let mut tag_arr = [0u8; 4];
-    tag_arr.copy_from_slice(&tag_bytes[..4]);
-    let expected_tag = (u32::from_be_bytes(tag_arr) >> 12) & ((1u32 << 20) - 1);
-
-    // 7. Compare supplied vs. expected tag
-    if supplied_tag != expected_tag {
-        return None;
-    }
-
-    Some(verification_id)
-}
-
-pub fn is_approval_code(code: &str) -> bool {
-    decode_approval_code(code).is_some()
-}
-
-fn decode_base32_fixed(s: &str) -> Option<u64> {
-    let mut value = 0u64;
-
-    for ch in s.chars() {
-        let idx = approval_code_char_index(ch)?;
-        value = (value << 5) | idx as u64;
-    }
-
-    Some(value)
-}
-
-fn approval_code_char_index(ch: char) -> Option<u8> {
-    let upper = ch.to_ascii_uppercase() as u8;
-    APPROVAL_CODE_ALPHABET
-        .iter()
-        .position(|&c| c == upper)
-        .map(|i| i as u8)
-}
-
-
-// endregion: Check Approval Code
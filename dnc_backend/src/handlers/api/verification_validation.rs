@@ -0,0 +1,219 @@
+//! Checks run before a verification is created.
+//!
+//! A new verification is checked against everything it refers to: the dental service, the
+//! clinic, the dentist and whether they practice there, the member, and the member's
+//! endorsement and whether the dentist may serve it. It is also checked against the member's
+//! open verifications. Every check runs, so the CSR sees all the problems at once; checks that
+//! need a record that was not found are skipped.
+//!
+//! Each problem comes back with a reason code, listed below, and a message.
+//!
+//! The open verification checks count the member's verifications, so creating one must take
+//! `lock_member_for_create`, then validate and insert in the same transaction.
+use std::collections::HashSet;
+
+use sea_orm::prelude::Date;
+use sea_orm::{ColumnTrait, ConnectionTrait, DatabaseTransaction, DbErr, EntityTrait, QueryFilter, Statement};
+use serde::Serialize;
+
+use crate::business_calendar;
+use crate::entities::{dental_clinic, dental_service, dentist, dentist_clinic, endorsement, master_list_member, verification};
+use crate::verification_state::VerificationStatus;
+use super::dentist_relations::get_endorsement_ids_for_dentist_id;
+
+// region: Reason Codes
+// code values of the reasons a verification cannot be created.
+pub const INVALID_DENTAL_SERVICE: i32 = 101;
+pub const INACTIVE_DENTAL_SERVICE: i32 = 102;
+pub const INVALID_DENTAL_CLINIC: i32 = 103;
+pub const INACTIVE_DENTAL_CLINIC: i32 = 104;
+pub const INVALID_DENTIST: i32 = 105;
+pub const DENTIST_NOT_AT_CLINIC: i32 = 106;
+pub const INVALID_MEMBER: i32 = 107;
+pub const INACTIVE_MEMBER: i32 = 108;
+pub const INACTIVE_ENDORSEMENT: i32 = 109;
+pub const ENDORSEMENT_NOT_IN_FORCE: i32 = 110;
+pub const DENTIST_NOT_ALLOWED_FOR_ENDORSEMENT: i32 = 111;
+pub const PENDING_DUPLICATE: i32 = 112;
+pub const VERIFICATION_LIMIT_REACHED: i32 = 113;
+// endregion: Reason Codes
+
+// region: Create Lock
+
+/// First key of the advisory lock taken while creating a verification for a member; the second
+/// key is the member id.
+const CREATE_LOCK_NAMESPACE: i32 = 7002;
+
+/// Serializes verification creates for a member until `txn` ends.
+///
+/// Without it, two creates checked at the same time could both pass PENDING_DUPLICATE or
+/// VERIFICATION_LIMIT_REACHED. Take it before `validate_new_verification`, and validate and
+/// insert in the same transaction.
+pub async fn lock_member_for_create(txn: &DatabaseTransaction, member_id: i32) -> Result<(), DbErr> {
+    txn.execute_raw(Statement::from_sql_and_values(
+        txn.get_database_backend(),
+        "SELECT pg_advisory_xact_lock($1, $2)",
+        [CREATE_LOCK_NAMESPACE.into(), member_id.into()],
+    ))
+    .await?;
+    Ok(())
+}
+// endregion: Create Lock
+
+/// What is being checked: the fields of a new verification.
+#[derive(Debug, Clone, Copy)]
+pub struct NewVerification {
+    pub dentist_id: i32,
+    pub member_id: i32,
+    pub dental_service_id: i32,
+    pub dental_clinic_id: i32,
+}
+
+/// Why a verification cannot be created.
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidationReason {
+    pub code: i32,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VerificationValidation {
+    /// the day the endorsement must be in force
+    pub service_date: Date,
+    pub is_valid: bool,
+    /// empty when valid
+    pub reasons: Vec<ValidationReason>,
+}
+
+/// Runs every check on a new verification, as of today in Manila.
+pub async fn validate_new_verification<C: ConnectionTrait>(
+    db: &C,
+    new: NewVerification,
+) -> Result<VerificationValidation, DbErr> {
+    let service_date = business_calendar::today();
+    let mut reasons = Vec::new();
+    let mut reject = |code: i32, message: String| reasons.push(ValidationReason { code, message });
+
+    // ---1 the dental service
+    let service = dental_service::Entity::find_by_id(new.dental_service_id).one(db).await?;
+    match &service {
+        None => reject(INVALID_DENTAL_SERVICE, format!("Dental service {} not found", new.dental_service_id)),
+        Some(service) if !service.active => {
+            reject(INACTIVE_DENTAL_SERVICE, format!("{} is no longer offered", service.name))
+        }
+        Some(_) => {}
+    }
+
+    // ---2 the clinic, the dentist, and whether the dentist practices at the clinic
+    let clinic = dental_clinic::Entity::find_by_id(new.dental_clinic_id).one(db).await?;
+    match &clinic {
+        None => reject(INVALID_DENTAL_CLINIC, format!("Dental clinic {} not found", new.dental_clinic_id)),
+        Some(clinic) if clinic.active == Some(false) => {
+            reject(INACTIVE_DENTAL_CLINIC, format!("{} is inactive", clinic.name))
+        }
+        Some(_) => {}
+    }
+    let the_dentist = dentist::Entity::find_by_id(new.dentist_id).one(db).await?;
+    if the_dentist.is_none() {
+        reject(INVALID_DENTIST, format!("Dentist {} not found", new.dentist_id));
+    }
+    if let (Some(the_dentist), Some(clinic)) = (&the_dentist, &clinic) {
+        let practices_there = dentist_clinic::Entity::find()
+            .filter(dentist_clinic::Column::DentistId.eq(the_dentist.id))
+            .filter(dentist_clinic::Column::ClinicId.eq(clinic.id))
+            .one(db)
+            .await?
+            .is_some();
+        if !practices_there {
+            reject(
+                DENTIST_NOT_AT_CLINIC,
+                format!("Dr. {} {} does not practice at {}", the_dentist.given_name, the_dentist.last_name, clinic.name),
+            );
+        }
+    }
+
+    // ---3 the member and the endorsement
+    let member = master_list_member::Entity::find_by_id(new.member_id).one(db).await?;
+    let the_endorsement = match &member {
+        None => {
+            reject(INVALID_MEMBER, format!("Member {} not found", new.member_id));
+            None
+        }
+        Some(member) => {
+            if !member.is_active {
+                reject(INACTIVE_MEMBER, format!("Member {} is inactive", member.account_number));
+            }
+            endorsement::Entity::find_by_id(member.endorsement_id).one(db).await?
+        }
+    };
+    if let Some(the_endorsement) = &the_endorsement {
+        if !the_endorsement.is_active {
+            reject(INACTIVE_ENDORSEMENT, "The member's endorsement is inactive".to_string());
+        }
+        if service_date < the_endorsement.date_start || the_endorsement.date_end < service_date {
+            reject(
+                ENDORSEMENT_NOT_IN_FORCE,
+                format!(
+                    "The member's endorsement runs from {} to {}, which does not include {}",
+                    the_endorsement.date_start, the_endorsement.date_end, service_date
+                ),
+            );
+        }
+        // only active endorsements are listed for a dentist; an inactive one is reported above
+        if the_endorsement.is_active && the_dentist.is_some() {
+            let allowed: HashSet<i32> = get_endorsement_ids_for_dentist_id(db, new.dentist_id)
+                .await?
+                .into_iter()
+                .collect();
+            if !allowed.contains(&the_endorsement.id) {
+                reject(
+                    DENTIST_NOT_ALLOWED_FOR_ENDORSEMENT,
+                    "The dentist's HMO and company relations do not allow serving this endorsement".to_string(),
+                );
+            }
+        }
+    }
+
+    // ---4 the member's open verifications for the service
+    if let (Some(member), Some(service)) = (&member, &service) {
+        let open: Vec<verification::Model> = verification::Entity::find()
+            .filter(verification::Column::MemberId.eq(member.id))
+            .filter(verification::Column::DentalServiceId.eq(service.id))
+            .filter(verification::Column::StatusId.is_not_in(
+                VerificationStatus::ALL
+                    .into_iter()
+                    .filter(|s| s.is_final())
+                    .map(VerificationStatus::int_code),
+            ))
+            .all(db)
+            .await?;
+        let duplicate = open
+            .iter()
+            .find(|v| v.dentist_id == new.dentist_id && v.dental_clinic_id == new.dental_clinic_id);
+        if let Some(duplicate) = duplicate {
+            reject(
+                PENDING_DUPLICATE,
+                format!(
+                    "Verification {} for the same member, service, dentist and clinic is still pending",
+                    duplicate.id
+                ),
+            );
+        } else if open.len() as i32 >= service.verification_limit {
+            reject(
+                VERIFICATION_LIMIT_REACHED,
+                format!(
+                    "The member already has {} pending verification(s) for {}; the limit is {}",
+                    open.len(),
+                    service.name,
+                    service.verification_limit
+                ),
+            );
+        }
+    }
+
+    Ok(VerificationValidation {
+        service_date,
+        is_valid: reasons.is_empty(),
+        reasons,
+    })
+}
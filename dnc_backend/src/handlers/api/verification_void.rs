@@ -0,0 +1,214 @@
+//! Voiding a released approval code.
+//!
+//! A verification with a released code (Done) can be voided when the code should not have been
+//! released or the service was not rendered. The code stays on the verification so it can still
+//! be looked up, but it no longer counts toward the member's limits and is reported as void.
+//! Codes that were already reconciled or paid cannot be voided.
+use axum::{extract::{Path, State}, http::StatusCode, Json};
+use chrono::Utc;
+use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter, QuerySelect, Set, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::AppState;
+use crate::entities::{dentist_payout_item, verification};
+use crate::handlers::AuthUser;
+use crate::verification_state::{self, TransitionError, VerificationStatus};
+
+// region: Void Reasons
+/// Why a released code is voided; stored in verification.void_reason_code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoidReason {
+    DuplicateRelease,
+    WrongMember,
+    WrongService,
+    WrongDentistOrClinic,
+    ServiceNotRendered,
+    /// needs a note
+    Other,
+}
+
+impl VoidReason {
+    pub const ALL: [VoidReason; 6] = [
+        VoidReason::DuplicateRelease,
+        VoidReason::WrongMember,
+        VoidReason::WrongService,
+        VoidReason::WrongDentistOrClinic,
+        VoidReason::ServiceNotRendered,
+        VoidReason::Other,
+    ];
+
+    pub const fn code(self) -> &'static str {
+        match self {
+            VoidReason::DuplicateRelease => "duplicate_release",
+            VoidReason::WrongMember => "wrong_member",
+            VoidReason::WrongService => "wrong_service",
+            VoidReason::WrongDentistOrClinic => "wrong_dentist_or_clinic",
+            VoidReason::ServiceNotRendered => "service_not_rendered",
+            VoidReason::Other => "other",
+        }
+    }
+
+    pub const fn description(self) -> &'static str {
+        match self {
+            VoidReason::DuplicateRelease => "The code was released twice for the same service.",
+            VoidReason::WrongMember => "The code was released for the wrong member.",
+            VoidReason::WrongService => "The code was released for the wrong dental service.",
+            VoidReason::WrongDentistOrClinic => "The code was released for the wrong dentist or clinic.",
+            VoidReason::ServiceNotRendered => "The member did not get the service.",
+            VoidReason::Other => "Another reason, given in the note.",
+        }
+    }
+}
+// endregion: Void Reasons
+
+
+// region: Errors
+#[derive(Debug, thiserror::Error)]
+pub enum VoidError {
+    #[error("Verification {0} not found")]
+    NotFound(i32),
+
+    #[error("Verification {0} has no released approval code")]
+    NotReleased(i32),
+
+    #[error("The approval code of verification {0} is already void")]
+    AlreadyVoided(i32),
+
+    #[error("Verification {0} is already reconciled; unreconcile it before voiding its code")]
+    Reconciled(i32),
+
+    #[error("Verification {verification_id} was already paid to the dentist in payout batch {batch_id}")]
+    Paid { verification_id: i32, batch_id: i32 },
+
+    #[error("Verification {verification_id} is on dentist payout batch {batch_id}; remove it before voiding its code")]
+    OnPayoutBatch { verification_id: i32, batch_id: i32 },
+
+    #[error("A note is required when the reason is 'other'")]
+    NoteRequired,
+
+    #[error(transparent)]
+    Transition(#[from] TransitionError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+impl From<VoidError> for (StatusCode, String) {
+    fn from(e: VoidError) -> Self {
+        match e {
+            VoidError::NotFound(_) => (StatusCode::NOT_FOUND, e.to_string()),
+            VoidError::NoteRequired => (StatusCode::BAD_REQUEST, e.to_string()),
+            VoidError::NotReleased(_)
+            | VoidError::AlreadyVoided(_)
+            | VoidError::Reconciled(_)
+            | VoidError::Paid { .. }
+            | VoidError::OnPayoutBatch { .. } => (StatusCode::CONFLICT, e.to_string()),
+            VoidError::Transition(e) => e.into(),
+            VoidError::Database(_) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        }
+    }
+}
+// endregion: Errors
+
+
+// region: Void Approval Code
+#[derive(Debug, Deserialize)]
+pub struct VoidApprovalCodeRequest {
+    pub reason_code: VoidReason,
+    pub note: Option<String>,
+}
+
+/// POST /api/verifications/{verification_id}/void
+/// Voids the released approval code of a verification that is not yet reconciled or paid.
+#[instrument(skip(state), err(Debug))]
+pub async fn void_approval_code(
+    State(state): State<AppState>,
+    auth_user: AuthUser,
+    Path(verification_id): Path<i32>,
+    Json(payload): Json<VoidApprovalCodeRequest>,
+) -> Result<Json<verification::Model>, (StatusCode, String)> {
+    let note = payload.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    if payload.reason_code == VoidReason::Other && note.is_none() {
+        return Err(VoidError::NoteRequired.into());
+    }
+
+    let txn = state.db.begin().await.map_err(VoidError::from)?;
+    let model = verification::Entity::find_by_id(verification_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .map_err(VoidError::from)?
+        .ok_or(VoidError::NotFound(verification_id))?;
+
+    match verification_state::status_of(&model).map_err(VoidError::from)? {
+        VerificationStatus::Voided => return Err(VoidError::AlreadyVoided(verification_id).into()),
+        VerificationStatus::Done if model.approval_code.is_some() => {}
+        _ => return Err(VoidError::NotReleased(verification_id).into()),
+    }
+    if model.is_reconciled == Some(true) {
+        return Err(VoidError::Reconciled(verification_id).into());
+    }
+    let payout_item = dentist_payout_item::Entity::find()
+        .filter(dentist_payout_item::Column::VerificationId.eq(verification_id))
+        .one(&txn)
+        .await
+        .map_err(VoidError::from)?;
+    if let Some(item) = payout_item {
+        let batch_id = item.batch_id;
+        return Err(match item.paid_on {
+            Some(_) => VoidError::Paid { verification_id, batch_id },
+            None => VoidError::OnPayoutBatch { verification_id, batch_id },
+        }
+        .into());
+    }
+
+    let mut active: verification::ActiveModel = model.into();
+    active.void_reason_code = Set(Some(payload.reason_code.code().to_string()));
+    active.void_note = Set(note.map(str::to_string));
+    active.voided_by = Set(Some(auth_user.claims.email.clone()));
+    active.voided_on = Set(Some(Utc::now().fixed_offset()));
+
+    let history_note = match note {
+        Some(note) => format!("{}: {}", payload.reason_code.code(), note),
+        None => payload.reason_code.code().to_string(),
+    };
+    let updated = verification_state::transition(
+        &txn,
+        active,
+        VerificationStatus::Voided,
+        &auth_user.claims.email,
+        Some(&history_note),
+    )
+        .await
+        .map_err(VoidError::from)?;
+    txn.commit().await.map_err(VoidError::from)?;
+
+    Ok(Json(updated))
+}
+// endregion: Void Approval Code
+
+
+// region: Get Void Reasons
+#[derive(Debug, Serialize)]
+pub struct VoidReasonResponse {
+    pub reason_code: &'static str,
+    pub description: &'static str,
+    pub note_required: bool,
+}
+
+/// GET /api/verification_void_reasons
+pub async fn get_void_reasons() -> Json<Vec<VoidReasonResponse>> {
+    Json(
+        VoidReason::ALL
+            .into_iter()
+            .map(|reason| VoidReasonResponse {
+                reason_code: reason.code(),
+                description: reason.description(),
+                note_required: reason == VoidReason::Other,
+            })
+            .collect(),
+    )
+}
+// endregion: Get Void Reasons
@@ -0,0 +1,271 @@
+//! Approval code generation and checking.
+//!
+//! An approval code hides the verification id behind an HMAC mask and carries a 20-bit
+//! HMAC tag, both keyed by a secret that is loaded from the environment:
+//!
+//!   APPROVAL_CODE_KEYS=v1=<old secret>,v2=<new secret>
+//!   APPROVAL_CODE_SIGNING_KEY_ID=v2        (optional; defaults to the highest version)
+//!
+//! Codes issued under "v1" keep the original `AAA-BBB-CCC` format. Codes issued under any
+//! other key carry the key id in front, e.g. `V2-AAA-BBB-CCC`. A code is valid as long as the
+//! key it was issued under is still listed in APPROVAL_CODE_KEYS, so a leaked key is rotated
+//! by adding a new version, making it the signing key, and removing the leaked one once
+//! its codes no longer need to be accepted.
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const APPROVAL_CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const ID_PART_LEN: usize = 5;
+const TAG_PART_LEN: usize = 4;
+
+/// The key id of codes that have no key id prefix.
+pub const LEGACY_KEY_ID: &str = "v1";
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ApprovalCodeKeyError {
+    #[error("APPROVAL_CODE_KEYS is not set")]
+    Missing,
+
+    #[error("Invalid entry '{0}' in APPROVAL_CODE_KEYS; expected v<number>=<secret>")]
+    InvalidEntry(String),
+
+    #[error("Approval code key '{0}' is listed more than once")]
+    DuplicateKeyId(String),
+
+    #[error("Signing key '{0}' is not in APPROVAL_CODE_KEYS")]
+    UnknownSigningKey(String),
+}
+
+struct ApprovalCodeKey {
+    id: String,
+    version: u32,
+    secret: Vec<u8>,
+}
+
+/// The active approval code keys. New codes are issued under the signing key; codes issued
+/// under any key in the ring are accepted.
+pub struct ApprovalCodeKeyRing {
+    keys: Vec<ApprovalCodeKey>,
+    signing_key_id: String,
+}
+
+impl ApprovalCodeKeyRing {
+    pub fn from_env() -> Result<Self, ApprovalCodeKeyError> {
+        let keys = std::env::var("APPROVAL_CODE_KEYS").map_err(|_| ApprovalCodeKeyError::Missing)?;
+        let signing_key_id = std::env::var("APPROVAL_CODE_SIGNING_KEY_ID").ok();
+        Self::parse(&keys, signing_key_id.as_deref())
+    }
+
+    /// Parses `v1=secret,v2=secret`. Without `signing_key_id` the highest version signs.
+    pub fn parse(keys: &str, signing_key_id: Option<&str>) -> Result<Self, ApprovalCodeKeyError> {
+        let mut parsed: Vec<ApprovalCodeKey> = Vec::new();
+
+        for entry in keys.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (id, secret) = entry
+                .split_once('=')
+                .ok_or_else(|| ApprovalCodeKeyError::InvalidEntry(entry.to_string()))?;
+            let id = id.trim().to_ascii_lowercase();
+            let version = parse_key_version(&id)
+                .ok_or_else(|| ApprovalCodeKeyError::InvalidEntry(entry.to_string()))?;
+            if secret.is_empty() {
+                return Err(ApprovalCodeKeyError::InvalidEntry(entry.to_string()));
+            }
+            if parsed.iter().any(|k| k.id == id) {
+                return Err(ApprovalCodeKeyError::DuplicateKeyId(id));
+            }
+            parsed.push(ApprovalCodeKey {
+                id,
+                version,
+                secret: secret.as_bytes().to_vec(),
+            });
+        }
+
+        let signing_key_id = match signing_key_id.map(|s| s.trim().to_ascii_lowercase()) {
+            Some(id) if !id.is_empty() => {
+                if !parsed.iter().any(|k| k.id == id) {
+                    return Err(ApprovalCodeKeyError::UnknownSigningKey(id));
+                }
+                id
+            }
+            _ => parsed
+                .iter()
+                .max_by_key(|k| k.version)
+                .map(|k| k.id.clone())
+                .ok_or(ApprovalCodeKeyError::Missing)?,
+        };
+
+        Ok(Self {
+            keys: parsed,
+            signing_key_id,
+        })
+    }
+
+    pub fn signing_key_id(&self) -> &str {
+        &self.signing_key_id
+    }
+
+    fn get(&self, id: &str) -> Option<&ApprovalCodeKey> {
+        self.keys.iter().find(|k| k.id == id)
+    }
+}
+
+fn parse_key_version(id: &str) -> Option<u32> {
+    id.strip_prefix('v')
+        .filter(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        .and_then(|n| n.parse::<u32>().ok())
+        .filter(|v| *v > 0)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedApprovalCode {
+    pub verification_id: i32,
+    pub key_id: String,
+}
+
+/// Issues the approval code for `verification_id` under the ring's signing key.
+pub fn generate_approval_code(keys: &ApprovalCodeKeyRing, verification_id: i32) -> String {
+    assert!(verification_id >= 0, "verification_id must be non-negative");
+
+    let key = keys
+        .get(&keys.signing_key_id)
+        .expect("signing key is always in the ring");
+
+    let obfuscated_id = verification_id as u64 ^ id_mask(key);
+    let tag_20 = id_tag(key, verification_id);
+
+    let id_part = encode_base32_fixed(obfuscated_id, ID_PART_LEN);
+    let tag_part = encode_base32_fixed(tag_20 as u64, TAG_PART_LEN);
+
+    let code = format_approval_code(&format!("{id_part}{tag_part}"));
+    if key.id == LEGACY_KEY_ID {
+        code
+    } else {
+        format!("{}-{code}", key.id.to_ascii_uppercase())
+    }
+}
+
+/// Returns the verification id and key id of a valid code. Codes issued under keys that
+/// are no longer in the ring are rejected.
+pub fn decode_approval_code(keys: &ApprovalCodeKeyRing, code: &str) -> Option<DecodedApprovalCode> {
+    let code = code.trim();
+
+    // 1. Split off the key id: "V2-AAA-BBB-CCC", or "AAA-BBB-CCC" for v1.
+    let (key_id, code) = match code.len() {
+        11 => (LEGACY_KEY_ID.to_string(), code),
+        _ => {
+            let (prefix, rest) = code.split_once('-')?;
+            let key_id = prefix.to_ascii_lowercase();
+            parse_key_version(&key_id)?;
+            (key_id, rest)
+        }
+    };
+    let key = keys.get(&key_id)?;
+
+    // 2. Check format: AAA-BBB-CCC
+    if code.len() != 11 {
+        return None;
+    }
+    let bytes = code.as_bytes();
+    if bytes[3] != b'-' || bytes[7] != b'-' {
+        return None;
+    }
+
+    // 3. Remove hyphens
+    let raw: String = code.chars().filter(|&c| c != '-').collect();
+    if raw.len() != ID_PART_LEN + TAG_PART_LEN {
+        return None;
+    }
+    let id_part = &raw[..ID_PART_LEN];
+    let tag_part = &raw[ID_PART_LEN..];
+
+    // 4. Decode both parts from the custom base32 alphabet
+    let obfuscated_id = decode_base32_fixed(id_part)?;
+    let supplied_tag = decode_base32_fixed(tag_part)? as u32;
+
+    // 5. Reverse the obfuscation. Must fit in i32 and be non-negative.
+    let verification_id_u64 = obfuscated_id ^ id_mask(key);
+    if verification_id_u64 > i32::MAX as u64 {
+        return None;
+    }
+    let verification_id = verification_id_u64 as i32;
+
+    // 6. Compare supplied vs. expected tag
+    if supplied_tag != id_tag(key, verification_id) {
+        return None;
+    }
+
+    Some(DecodedApprovalCode {
+        verification_id,
+        key_id: key.id.clone(),
+    })
+}
+
+/// 25-bit mask XORed over the verification id.
+fn id_mask(key: &ApprovalCodeKey) -> u64 {
+    let mut mask_mac = <HmacSha256 as KeyInit>::new_from_slice(&key.secret)
+        .expect("invalid HMAC key");
+    mask_mac.update(format!("approval-code-mask:{}", key.id).as_bytes());
+    let mask_bytes = mask_mac.finalize().into_bytes();
+
+    let mut mask_arr = [0u8; 8];
+    mask_arr.copy_from_slice(&mask_bytes[..8]);
+    u64::from_be_bytes(mask_arr) & ((1u64 << 25) - 1)
+}
+
+/// 20-bit tag that makes codes unguessable.
+fn id_tag(key: &ApprovalCodeKey, verification_id: i32) -> u32 {
+    let mut tag_mac = <HmacSha256 as KeyInit>::new_from_slice(&key.secret)
+        .expect("invalid HMAC key");
+    tag_mac.update(format!("approval-code-tag:{}:", key.id).as_bytes());
+    tag_mac.update(verification_id.to_string().as_bytes());
+    let tag_bytes = tag_mac.finalize().into_bytes();
+
+    let mut tag_arr = [0u8; 4];
+    tag_arr.copy_from_slice(&tag_bytes[..4]);
+    (u32::from_be_bytes(tag_arr) >> 12) & ((1u32 << 20) - 1)
+}
+
+fn encode_base32_fixed(mut value: u64, len: usize) -> String {
+    let mut out = vec!['A'; len];
+    for i in (0..len).rev() {
+        let idx = (value & 0b1_1111) as usize;
+        out[i] = APPROVAL_CODE_ALPHABET[idx] as char;
+        value >>= 5;
+    }
+
+    out.into_iter().collect()
+}
+
+fn format_approval_code(raw: &str) -> String {
+    debug_assert_eq!(raw.len(), 9);
+
+    let mut out = String::with_capacity(11);
+    for (i, ch) in raw.chars().enumerate() {
+        if i > 0 && i % 3 == 0 {
+            out.push('-');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+fn decode_base32_fixed(s: &str) -> Option<u64> {
+    let mut value = 0u64;
+
+    for ch in s.chars() {
+        let idx = approval_code_char_index(ch)?;
+        value = (value << 5) | idx as u64;
+    }
+
+    Some(value)
+}
+
+fn approval_code_char_index(ch: char) -> Option<u8> {
+    let upper = ch.to_ascii_uppercase() as u8;
+    APPROVAL_CODE_ALPHABET
+        .iter()
+        .position(|&c| c == upper)
+        .map(|i| i as u8)
+}
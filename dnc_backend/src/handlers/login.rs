@@ -5,8 +5,6 @@ use axum::{
     extract::{Json, State},
     http::StatusCode,
 };
-use jsonwebtoken::{encode, EncodingKey};
-use jsonwebtoken::Header;
 use password_hash::{PasswordHash, PasswordVerifier};
 use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter,};
 use serde::{Deserialize, Serialize};
@@ -14,7 +12,7 @@ use std::collections::HashMap;
 use opentelemetry::trace::TraceContextExt;
 use tracing::instrument;
 use tracing_opentelemetry::OpenTelemetrySpanExt;
-use crate::handlers::structs::{Claims, };
+use crate::handlers::sessions::{create_session, issue_access_token};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
@@ -44,6 +42,8 @@ pub struct LoginResponse{
     role_id:i32,
     role_name:String,
     pub token:String,
+    pub refresh_token:String,
+    pub expires_in:i64,
     menu_activation_map:MenuActivationMap,
 }
 use crate::entities::{user, permission, data_object, role_permission};
@@ -120,44 +120,23 @@ pub async fn login_handler(
         return Err((StatusCode::UNAUTHORIZED, "Invalid email or password".into()));
     }
 
-    // 3. Create JWT
-    let secret = std::env::var("JWT_SECRET")
-        .map_err(|_| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                "JWT_SECRET missing".to_string(),
-            )
-        })?;
-
-
-    let expiration = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::hours(24))
-        .expect("valid timestamp")
-        .timestamp() as usize;
+    // 3. Inactive users may not log in.
+    if !user.active {
+        return Err((StatusCode::FORBIDDEN, "Account is inactive".into()));
+    }
 
-    let claims = Claims{
-        sub: user.id,
-        email:user.email.clone(),
-        role_id: user.role_id,
-        exp:expiration,
-    };
-    let header = Header::new(jsonwebtoken::Algorithm::HS512);
-    let token = encode(
-        &header,
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )
-        .map_err(|e|{
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Token creation error:{e}")
-            )
+    // 4. Open a session and create the JWT bound to it
+    let (session_id, refresh_token) = create_session(&state.db, user.id)
+        .await
+        .map_err(|e| {
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("Session creation error {e}"))
         })?;
+    let (token, expires_in) = issue_access_token(&user, &session_id)?;
 
     let menu_activation_map = build_menu_activation_map(& state.db, role.id).await.unwrap();
     tracing::info!("MENU activation map for role_id:{:?}: is {:?}", role.id, menu_activation_map);
 
-    // 4. Return LoginResponse with JWT included
+    // 5. Return LoginResponse with JWT included
     let response = LoginResponse {
         user_id: user.id,
         name: user.name,
@@ -165,6 +144,8 @@ pub async fn login_handler(
         role_id: user.role_id,
         role_name:role.name,
         token,
+        refresh_token,
+        expires_in,
         menu_activation_map,
     };
     tracing::info!("Login successful");
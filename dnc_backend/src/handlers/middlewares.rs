@@ -21,11 +21,15 @@ pub async fn inject_jwt_config(
 
 
 use jsonwebtoken::{decode};
+use crate::AppState;
 use crate::handlers::structs::AuthUser;
+use crate::handlers::sessions::is_session_active;
+use crate::audit::with_actor;
 
 /// Middleware to check the presence of a JWT token in the Authorization header.
+/// The token's session must not be revoked and its user must still be active.
 pub async fn require_jwt(
-    State(cfg): State<Arc<JwtConfig>>,
+    State((cfg, state)): State<(Arc<JwtConfig>, AppState)>,
     mut req: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
@@ -43,6 +47,66 @@ pub async fn require_jwt(
     let data = decode::<Claims>(token, &cfg.decoding_key, &cfg.validation)
         .map_err(|_| StatusCode::UNAUTHORIZED)?;
 
+    let session_active = is_session_active(&state.db, &data.claims.sid, data.claims.sub)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !session_active {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // Audited entity changes made while handling this request are attributed to this user.
+    let actor = data.claims.email.clone();
     req.extensions_mut().insert(AuthUser { claims: data.claims });
-    Ok(next.run(req).await)
-}
\ No newline at end of file
+    Ok(with_actor(actor, next.run(req)).await)
+}
+
+use axum::extract::MatchedPath;
+use crate::handlers::helpers::role_has_permission_by_data_object_name;
+use crate::handlers::route_permissions::{route_access, RouteAccess};
+
+/// Middleware to check that the caller's role may use the matched route.
+/// Must run after `require_jwt`, which puts the AuthUser in the request extensions.
+pub async fn require_permission(
+    State(state): State<AppState>,
+    req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let role_id = req
+        .extensions()
+        .get::<AuthUser>()
+        .map(|user| user.claims.role_id)
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let matched_path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let required = match route_access(req.method().as_str(), &matched_path) {
+        Some(RouteAccess::Authenticated) => return Ok(next.run(req).await),
+        Some(RouteAccess::Requires(required)) => required,
+        None => {
+            tracing::warn!("No permission mapping for {} {}; denying", req.method(), matched_path);
+            return Err(StatusCode::FORBIDDEN);
+        }
+    };
+
+    for (data_object_name, action) in required {
+        let has_permission = role_has_permission_by_data_object_name(
+            &state.db,
+            role_id,
+            data_object_name,
+            action.clone(),
+        )
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if has_permission {
+            return Ok(next.run(req).await);
+        }
+    }
+
+    tracing::info!("role_id {} denied {} {}", role_id, req.method(), matched_path);
+    Err(StatusCode::FORBIDDEN)
+}
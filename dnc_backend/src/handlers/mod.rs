@@ -2,10 +2,13 @@
 #![allow(dead_code)]
 pub mod boiler;
 pub mod login;
+pub mod sessions;
 mod structs;
 mod request_parts;
 mod middlewares;
 mod helpers;
+pub mod route_permissions;
+pub mod approval_codes;
 mod api;
 mod app_config;
 mod reports;
@@ -22,12 +25,13 @@ pub use structs::{AppError, AuthUser, Claims, JwtConfig, ListQuery, PageResponse
 
 pub use login::{LoginRequest, LoginResponse};
 
-pub use middlewares::{inject_jwt_config, require_jwt};
+pub use middlewares::{inject_jwt_config, require_jwt, require_permission};
 pub use boiler::WhoAmIResponse;
 pub use api::hmo::{get_companies_for_hmo_id, get_hmo_by_id, get_hmos, patch_hmo, post_hmo};
 pub use api::dentist_contracts::{get_all_dentist_contracts, get_dentist_contract,
                                  patch_dentist_contract, patch_dentist_contract_rates,
-                                 post_dentist_contract};
+                                 post_dentist_contract, get_dentist_contract_rate_history,
+                                 post_dentist_contract_rate_schedule, delete_dentist_contract_rate_schedule};
 pub use api::region::{get_region_by_id, get_regions, patch_region, post_region};
 pub use api::province::{get_cities_by_province, get_provinces};
 pub use api::city::get_cities;
@@ -75,7 +79,8 @@ pub use api::endorsement_master_list_meta_data::get_master_list_meta_data_for_en
 pub use api::endorsement_master_list_delete::delete_master_lists_for_endorsement_id;
 pub  use api::endorsement_master_list_member::set_master_list_member_active;
 pub use api::hmo_endorsement::get_endorsements_for_hmo_id;
-pub use api::verification::{cancel_verification, create_verification,
+pub use api::verification::{cancel_verification, get_verification_status_history, create_verification, validate_verification,
+                            dry_run_approval_code_for_verification_id,
                             get_all_verifications,
                             get_approval_code_for_verification_id};
 
@@ -102,9 +107,12 @@ pub use api::high_end_verification_dentist_approval::{get_high_end_verifications
 pub use api::acc_reconciliation::{create_acc_reconciliation, get_acc_recons, get_done_verifications, reconcile_verification, unreconcile_verification};
 pub use api::endorsement_company_master_list_members::{get_all_member_names_from_company, save_member_name_for_company};
 pub use api::verification::check_approval_code;
+pub use api::verification_void::{get_void_reasons, void_approval_code};
 
 pub use api::hmo_utilization::{download_utilization_report, get_utilization_report};
-pub use api::billing_payments::hmo_billing::{download_generated_report, get_generated_hmo_billing_reports};
+pub use api::billing_payments::hmo_billing::{download_generated_report, get_generated_hmo_billing_reports, post_hmo_billing_run,
+                                             post_regenerate_hmo_billing_run, post_finalize_hmo_billing_run,
+                                             post_void_hmo_billing_run};
 
 pub use api::test_reports::test_generate_hmo_billing_reports;
 
@@ -121,4 +129,34 @@ pub use api::website::dentist_applications::{download_dentist_application_docume
 pub use api::website::contact_us_messages::get_contact_us_messages_handler;
 
 pub use api::csr_dentists::get_all_dentists_for_csr;
-pub use api::csr_endorsements::get_endorsements_for_csr;
\ No newline at end of file
+pub use api::csr_endorsements::get_endorsements_for_csr;
+
+pub use api::audit_log::get_audit_log;
+pub use api::approval_code_rules::rule_configs::{get_approval_code_rules, get_approval_code_rule_configs,
+                                                  post_approval_code_rule_config, patch_approval_code_rule_config,
+                                                  delete_approval_code_rule_config};
+pub use api::approval_code_rules::validity::{get_approval_code_validity, put_approval_code_validity,
+                                              delete_approval_code_validity, post_lapse_override};
+pub use api::approval_code_rules::engine::{RuleConfigSource, RuleParams};
+pub use api::approval_code_rules::builtin_rules::{age_on, available_on};
+pub use api::master_list_import::column_mappings::{get_master_list_column_mappings, post_master_list_column_mapping,
+                                                   patch_master_list_column_mapping, delete_master_list_column_mapping};
+pub use api::member_eligibility::get_member_eligibility;
+pub use api::master_list_import::preview::{preview_endorsement_master_list, preview_endorsement_master_list_replacement,
+                                           commit_endorsement_master_list_preview};
+pub use api::master_list_import::parsing::{decode_text, guess_delimiter, map_rows, parse_date, read_json,
+                                           ColumnMapping, ColumnRef, FileFormat, MasterListField, RawTable};
+pub use api::billing_payments::hmo_claims_billing::{get_generated_hmo_claims_reports, post_hmo_claims_billing};
+pub use api::jobs::{get_job, get_jobs, post_job, post_retry_job};
+pub use api::billing_payments::receivables::{download_receivables_aging, get_hmo_invoice, get_hmo_invoices,
+                                             get_receivables_aging, post_hmo_payment};
+pub use api::billing_payments::dentist_payouts::{download_dentist_payout_remittance, get_dentist_payout_batch,
+                                                 get_dentist_payout_batches, post_dentist_payout_batch,
+                                                 post_dentist_payout_item_paid, post_dentist_payout_remittance_paid,
+                                                 get_bir_2307_certificates, post_bir_2307_certificates,
+                                                 download_bir_2307_certificate};
+pub use api::billing_payments::bank_disbursements::{download_bank_disbursement, get_bank_disbursement,
+                                                    get_bank_disbursement_layouts, get_bank_disbursements,
+                                                    post_bank_disbursement, post_bank_disbursement_layout,
+                                                    put_bank_disbursement_layout};
+pub use api::business_calendar::{get_business_calendar, post_holiday, patch_holiday, delete_holiday, import_holidays};
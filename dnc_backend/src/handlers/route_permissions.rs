@@ -0,0 +1,322 @@
+//! Route to permission mapping for everything nested under `/api`.
+//!
+//! `require_permission` (see middlewares.rs) looks up the matched route here and checks the
+//! caller's role against the `role_permission` table. Every route in `protected_routes()` must
+//! have an entry; a route that is not listed is rejected with 403 so that a new handler is
+//! never exposed by accident.
+pub use crate::entities::sea_orm_active_enums::PermissionActionEnum;
+
+/// (data_object name, action) pair as stored in the `permission` table.
+pub type RoutePermission = (&'static str, PermissionActionEnum);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteAccess {
+    /// Any logged-in user may call the route (lookup tables, whoami, ...).
+    Authenticated,
+    /// The caller's role must hold at least ONE of the listed permissions.
+    Requires(&'static [RoutePermission]),
+}
+
+/// Returns the access rule for a route, or None if the route is not mapped.
+///
+/// `method` is the HTTP method (e.g. "GET") and `path` is the route pattern exactly as it was
+/// registered in `protected_routes()` (as returned by axum's `MatchedPath`), with or without
+/// the "/api" prefix.
+pub fn route_access(method: &str, path: &str) -> Option<RouteAccess> {
+    use PermissionActionEnum::{Create, Delete, Read, Update};
+    use RouteAccess::{Authenticated, Requires};
+
+    let path = path.strip_prefix("/api").unwrap_or(path);
+    // axum answers HEAD with the GET handler.
+    let method = if method == "HEAD" { "GET" } else { method };
+
+    let access = match (method, path) {
+        /*
+        Lookups and session
+         */
+        ("POST", "/test_post") => Authenticated,
+        ("GET", "/whoami") => Authenticated,
+        ("POST", "/logout") => Authenticated,
+        ("GET", "/cities") => Authenticated,
+        ("GET", "/provinces") => Authenticated,
+        ("GET", "/provinces/{:province_id}/cities") => Authenticated,
+        ("GET", "/regions") => Authenticated,
+        ("GET", "/regions/{:id}") => Authenticated,
+        ("GET", "/bank_account_types") => Authenticated,
+        ("GET", "/dentist_clinics/positions") => Authenticated,
+        ("GET", "/dentist_histories/") => Authenticated,
+        ("GET", "/dentist_statuses/") => Authenticated,
+        ("GET", "/tax_classifications/") => Authenticated,
+        ("GET", "/tax_types/") => Authenticated,
+        ("GET", "/endorsement_types") => Authenticated,
+        ("GET", "/endorsement_billing_period_types") => Authenticated,
+        ("GET", "/tooth_service_types") => Authenticated,
+        ("GET", "/tooth_surfaces") => Authenticated,
+
+        /*
+        Setup
+         */
+        ("GET", "/dental_services") => Requires(&[("dental_service", Read)]),
+        ("POST", "/dental_services/") => Requires(&[("dental_service", Create)]),
+        ("PATCH", "/dental_services/{:id}") => Requires(&[("dental_service", Update)]),
+        ("GET", "/dental_service_types") => Requires(&[("dental_service", Read)]),
+        ("GET", "/clinic_capabilities") => Requires(&[("clinic_capability", Read)]),
+        ("POST", "/clinic_capabilities/") => Requires(&[("clinic_capability", Create)]),
+        ("PATCH", "/clinic_capabilities/{:id}") => Requires(&[("clinic_capability", Update)]),
+        ("GET", "/users") => Requires(&[("user", Read)]),
+        ("POST", "/users/") => Requires(&[("user", Create)]),
+        ("PATCH", "/users/{:id}") => Requires(&[("user", Update)]),
+        ("POST", "/users/{:id}/revoke_sessions") => Requires(&[("user", Update)]),
+        ("GET", "/roles") => Requires(&[("role", Read)]),
+        ("POST", "/roles/") => Requires(&[("role", Create)]),
+        ("PATCH", "/roles/{:id}") => Requires(&[("role", Update)]),
+        ("GET", "/role_permissions") => Requires(&[("role_permission", Read)]),
+        ("GET", "/data_objects") => Requires(&[("role_permission", Read)]),
+        ("GET", "/hmos") => Requires(&[("hmo", Read)]),
+        ("GET", "/hmos/{:id}") => Requires(&[("hmo", Read)]),
+        ("PATCH", "/hmos/{:id}") => Requires(&[("hmo", Update)]),
+        ("POST", "/hmos/") => Requires(&[("hmo", Create)]),
+        ("GET", "/hmos/{hmo_id}/companies") => Requires(&[("hmo", Read)]),
+        ("GET", "/hmos/{:id}/endorsements") => Requires(&[("endorsements", Read)]),
+        ("GET", "/dentist_contracts") => Requires(&[("dentist_contract", Read)]),
+        ("GET", "/dentist_contracts/{:id}") => Requires(&[("dentist_contract", Read)]),
+        ("POST", "/dentist_contracts/") => Requires(&[("dentist_contract", Create)]),
+        ("PATCH", "/dentist_contracts/{:id}") => Requires(&[("dentist_contract", Update)]),
+        ("PATCH", "/dentist_contracts/{:id}/rates") => Requires(&[("dentist_contract", Update)]),
+        ("GET", "/dentist_contracts/{:id}/rate_history") => Requires(&[("dentist_contract", Read)]),
+        ("POST", "/dentist_contracts/{:id}/rate_schedules") => Requires(&[("dentist_contract", Update)]),
+        ("DELETE", "/dentist_contracts/{:id}/rate_schedules/{:rate_id}") => Requires(&[("dentist_contract", Update)]),
+        ("POST", "/regions/") => Requires(&[("region", Create)]),
+        ("PATCH", "/regions/{:id}") => Requires(&[("region", Update)]),
+
+        /*
+        Dental Clinics
+         */
+        ("GET", "/dental_clinics/") => Requires(&[("dental_clinic", Read)]),
+        ("GET", "/dental_clinics/{id}") => Requires(&[("dental_clinic", Read)]),
+        ("POST", "/dental_clinics/") => Requires(&[("dental_clinic", Create)]),
+        ("PATCH", "/dental_clinics/{id}") => Requires(&[("dental_clinic", Update)]),
+        ("GET", "/dental_clinics/{:clinic_id}/capabilities") => Requires(&[("clinic_capabilities_list", Read)]),
+        ("POST", "/dental_clinics/{:clinic_id}/capabilities/") => Requires(&[("clinic_capabilities_list", Create)]),
+        ("DELETE", "/dental_clinics/{:clinic_id}/capabilities/{:capability_id}") => Requires(&[("clinic_capabilities_list", Delete)]),
+        ("PATCH", "/dental/_clinics/{:clinic_id}/capabilities") => Requires(&[("clinic_capabilities_list", Update)]),
+        ("GET", "/dental_clinics/dentist/{dentist_id}/names") => Requires(&[("dental_clinic", Read), ("verifications", Read)]),
+        ("GET", "/dental_clinics/{:clinic_id}/dentists") => Requires(&[("dental_clinic", Read)]),
+        ("GET", "/extended_clinics") => Requires(&[("dental_clinic", Read)]),
+
+        /*
+        Dentists
+         */
+        ("GET", "/dentists/") => Requires(&[("dentist", Read)]),
+        ("POST", "/dentists/") => Requires(&[("dentist", Create)]),
+        ("GET", "/dentist-names") => Requires(&[("dentist", Read), ("verifications", Read), ("acc_reconciliation", Read)]),
+        ("GET", "/dentists/{:id}") => Requires(&[("dentist", Read)]),
+        ("PATCH", "/dentists/{:id}") => Requires(&[("dentist", Update)]),
+        ("GET", "/dentists/{:id}/endorsements") => Requires(&[("dentist", Read), ("verifications", Read)]),
+        ("GET", "/dentist_clinics/") => Requires(&[("dentist", Read)]),
+        ("GET", "/dentists/{:dentist_id}/clinics") => Requires(&[("dentist", Read), ("verifications", Read)]),
+        ("POST", "/dentists/{:dentist_id}/clinics") => Requires(&[("dentist", Update)]),
+        ("DELETE", "/dentists/{:dentist_id}/clinics/{:clinic_id}") => Requires(&[("dentist", Update)]),
+        ("GET", "/dentists/{:dentist_id}/hmos/exclusive") => Requires(&[("dentist", Read)]),
+        ("POST", "/dentists/{:dentist_id}/hmos/exclusive/{:hmo_id}") => Requires(&[("dentist", Update)]),
+        ("DELETE", "/dentists/{:dentist_id}/hmos/exclusive/{:hmo_id}") => Requires(&[("dentist", Update)]),
+        ("GET", "/dentists/{dentist_id}/companies/exclusive") => Requires(&[("dentist", Read)]),
+        ("POST", "/dentists/{dentist_id}/companies/exclusive/{company_id}") => Requires(&[("dentist", Update)]),
+        ("DELETE", "/dentists/{dentist_id}/companies/exclusive/{company_id}") => Requires(&[("dentist", Update)]),
+        ("GET", "/dentists/{:dentist_id}/hmos/except") => Requires(&[("dentist", Read)]),
+        ("POST", "/dentists/{:dentist_id}/hmos/except/{:hmo_id}") => Requires(&[("dentist", Update)]),
+        ("DELETE", "/dentists/{:dentist_id}/hmos/except/{:hmo_id}") => Requires(&[("dentist", Update)]),
+        ("GET", "/dentists/{dentist_id}/companies/except") => Requires(&[("dentist", Read)]),
+        ("POST", "/dentists/{dentist_id}/companies/except/{company_id}") => Requires(&[("dentist", Update)]),
+        ("DELETE", "/dentists/{dentist_id}/companies/except/{company_id}") => Requires(&[("dentist", Update)]),
+        ("POST", "/dentists/{:dentist_id}/contract-file") => Requires(&[("dentist", Update)]),
+        ("GET", "/dentists/{:dentist_id}/contract-file/{:file_name}") => Requires(&[("dentist", Read)]),
+
+        /*
+        Endorsements and Master Lists
+         */
+        ("GET", "/endorsements") => Requires(&[("endorsements", Read)]),
+        ("POST", "/endorsements") => Requires(&[("endorsements", Create)]),
+        ("GET", "/endorsements/{id}") => Requires(&[("endorsements", Read)]),
+        ("PATCH", "/endorsements/{id}") => Requires(&[("endorsements", Update)]),
+        ("GET", "/endorsements/companies") => Requires(&[("endorsements", Read)]),
+        ("POST", "/endorsements/companies") => Requires(&[("endorsements", Create)]),
+        ("GET", "/endorsements/{endorsement_id}/rates") => Requires(&[("endorsement_rates", Read)]),
+        ("POST", "/endorsements/{endorsement_id}/rates") => Requires(&[("endorsement_rates", Create)]),
+        ("PUT" | "PATCH", "/endorsements/{endorsement_id}/rates/{rate_id}") => Requires(&[("endorsement_rates", Update)]),
+        ("GET", "/endorsements/{endorsement_id}/counts") => Requires(&[("endorsement_counts", Read)]),
+        ("POST", "/endorsements/{endorsement_id}/counts") => Requires(&[("endorsement_counts", Create)]),
+        ("PUT" | "PATCH", "/endorsements/{endorsement_id}/counts/{count_id}") => Requires(&[("endorsement_counts", Update)]),
+        ("POST", "/endorsements/{endorsement_id}/master_list") => Requires(&[("endorsements", Create)]),
+        ("DELETE", "/endorsements/{endorsement_id}/master_list") => Requires(&[("endorsements", Delete)]),
+        ("GET", "/endorsements/{endorsement_id}/master_list_metadata") => Requires(&[("endorsements", Read)]),
+        ("GET", "/endorsements/{endorsement_id}/master_list_members") => Requires(&[("endorsements", Read), ("verifications", Read)]),
+        ("GET", "/endorsements/{endorsement_id}/master_lists_with_members") => Requires(&[("endorsements", Read)]),
+        ("PATCH", "/endorsements/master_list_members/{master_list_member_id}/active") => Requires(&[("endorsements", Update)]),
+        ("GET", "/endorsements/{endorsement_id}/billing_rules") => Requires(&[("endorsements", Read)]),
+        ("POST", "/endorsements/{endorsement_id}/billing_rules") => Requires(&[("endorsements", Create)]),
+        ("PATCH", "/endorsements/{endorsement_id}/billing_rules/id") => Requires(&[("endorsements", Update)]),
+        ("DELETE", "/endorsements/{endorsement_id}/billing_rules/id") => Requires(&[("endorsements", Delete)]),
+        ("GET", "/endorsements/{endorsement_id}/service_counts") => Requires(&[("endorsements", Read), ("verifications", Read)]),
+        ("GET", "/endorsements/companies/{company_id}/members") => Requires(&[("endorsements", Read), ("verifications", Read)]),
+        ("POST", "/endorsements/companies/{company_id}/members") => Requires(&[("endorsements", Update), ("verifications", Create)]),
+        ("GET", "/master_list_members/{master_list_member_id}/used_service_counts") => Requires(&[("endorsements", Read), ("verifications", Read)]),
+        ("GET", "/master_list_members/{master_list_member_id}/service_counts") => Requires(&[("endorsements", Read), ("verifications", Read)]),
+        ("POST", "/master_list_members") => Requires(&[("endorsements", Create), ("verifications", Create)]),
+        ("GET", "/master_list_members/{id}") => Requires(&[("endorsements", Read), ("verifications", Read)]),
+        ("PATCH", "/master_list_members/{id}") => Requires(&[("endorsements", Update), ("verifications", Update)]),
+
+        /*
+        Verifications (CSR)
+         */
+        ("GET", "/verifications") => Requires(&[("verifications", Read)]),
+        ("POST", "/verifications") => Requires(&[("verifications", Create)]),
+        ("POST", "/verifications/validate") => Requires(&[("verifications", Read)]),
+        ("POST", "/verifications/{verification_id}/cancel") => Requires(&[("verifications", Update)]),
+        ("POST", "/verifications/{verification_id}/void") => Requires(&[("verifications", Delete)]),
+        ("GET", "/verification_void_reasons") => Requires(&[("verifications", Read)]),
+        ("GET", "/verifications/{verification_id}/status_history") => Requires(&[("verifications", Read), ("dashboard", Read)]),
+        ("POST", "/verifications/{verification_id}/approval_code") => Requires(&[("verifications", Update)]),
+        ("POST", "/verifications/{verification_id}/approval_code/dry_run") => Requires(&[("verifications", Read), ("approval_code_rules", Read)]),
+        ("GET", "/verifications/{verification_id}/high_end_files") => Requires(&[("verifications", Read), ("high_end_verification_information", Read)]),
+        ("POST", "/verifications/{verification_id}/high_end_files") => Requires(&[("verifications", Update)]),
+        ("GET", "/high_end_files/{high_end_file_id}/download") => Requires(&[("verifications", Read), ("high_end_verification_information", Read)]),
+        ("GET", "/high_end_verifications") => Requires(&[("high_end_verification_information", Read)]),
+        ("POST", "/high_end_verifications/{verification_id}/approval") => Requires(&[("high_end_verification_information", Update)]),
+        ("GET", "/approval_codes/check/{code}") => Requires(&[("verifications", Read), ("acc_reconciliation", Read)]),
+        ("GET", "/csr/dentists") => Requires(&[("verifications", Read)]),
+        ("GET", "/csr/endorsements") => Requires(&[("verifications", Read)]),
+
+        /*
+        Billing and Payments
+         */
+        ("GET", "/acc_recon/verifications") => Requires(&[("acc_reconciliation", Read)]),
+        ("POST", "/acc_recon/{id}/reconcile") => Requires(&[("acc_reconciliation", Update)]),
+        ("POST", "/acc_recon/{id}/unreconcile") => Requires(&[("acc_reconciliation", Update)]),
+        ("GET", "/acc_recon") => Requires(&[("acc_reconciliation", Read)]),
+        ("POST", "/acc_recon") => Requires(&[("acc_reconciliation", Create)]),
+        ("GET", "/utilization_reports/company/{company_id}") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/utilization_reports/company/{company_id}/download") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/hmo_billing/") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/hmo_billing/download/{file_name}") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/generate_hmo_billings") => Requires(&[("acc_reconciliation", Create)]),
+        ("POST", "/hmo_billing/runs") => Requires(&[("acc_reconciliation", Create)]),
+        ("POST", "/hmo_billing/runs/{id}/regenerate") => Requires(&[("acc_reconciliation", Create)]),
+        ("POST", "/hmo_billing/runs/{id}/finalize") => Requires(&[("acc_reconciliation", Update)]),
+        ("POST", "/hmo_billing/runs/{id}/void") => Requires(&[("acc_reconciliation", Update)]),
+        ("GET", "/dentist_clinics/reconciled_jobs") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/dentists/claims_matrix") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/dentists/claims_matrix/download") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/dentists/payments/matrix") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/dentists/payments/make_payment") => Requires(&[("acc_reconciliation", Create)]),
+        ("GET", "/dentists/payments/{payment_id}") => Requires(&[("acc_reconciliation", Delete)]),
+        ("GET", "/dentists/retainer_payables") => Requires(&[("acc_reconciliation", Read)]),
+
+        /*
+        Dashboard
+         */
+        ("GET", "/dashboard/csr_verification_activity") => Requires(&[("dashboard", Read)]),
+        ("GET", "/dashboard/csr_verification_activity_unit_counts") => Requires(&[("dashboard", Read)]),
+
+        /*
+        Website Internal Handlers
+         */
+        ("GET", "/website/dentist_applications") => Requires(&[("website", Read)]),
+        ("PATCH", "/website/dentist_applications/{application_id}/status") => Requires(&[("website", Update)]),
+        ("GET", "/website/dentist_applications/{application_id}/documents/{document_type}") => Requires(&[("website", Read)]),
+        ("GET", "/website/contact_us_messages") => Requires(&[("website", Read)]),
+
+        /*
+        Audit Trail
+         */
+        ("GET", "/audit") => Requires(&[("audit_log", Read)]),
+
+        /*
+        Approval Code Release Rules
+         */
+        ("GET", "/approval_code_rules") => Requires(&[("approval_code_rules", Read), ("verifications", Read)]),
+        ("GET", "/approval_code_rules/configs") => Requires(&[("approval_code_rules", Read)]),
+        ("POST", "/approval_code_rules/configs") => Requires(&[("approval_code_rules", Create)]),
+        ("PATCH", "/approval_code_rules/configs/{id}") => Requires(&[("approval_code_rules", Update)]),
+        ("DELETE", "/approval_code_rules/configs/{id}") => Requires(&[("approval_code_rules", Delete)]),
+        ("GET", "/approval_code_rules/validity") => Requires(&[("approval_code_rules", Read)]),
+        ("PUT", "/approval_code_rules/validity") => Requires(&[("approval_code_rules", Update)]),
+        ("DELETE", "/approval_code_rules/validity/{id}") => Requires(&[("approval_code_rules", Delete)]),
+        ("POST", "/verifications/{verification_id}/lapse_override") => Requires(&[("approval_code_rules", Update), ("acc_reconciliation", Update)]),
+
+        /*
+        Member Eligibility
+         */
+        ("GET", "/eligibility") => Requires(&[("verifications", Read), ("endorsements", Read)]),
+
+        /*
+        Master List Previews and Column Mappings
+         */
+        ("GET", "/hmos/{hmo_id}/master_list_mappings") => Requires(&[("endorsements", Read)]),
+        ("POST", "/hmos/{hmo_id}/master_list_mappings") => Requires(&[("endorsements", Create)]),
+        ("PATCH", "/master_list_mappings/{id}") => Requires(&[("endorsements", Update)]),
+        ("DELETE", "/master_list_mappings/{id}") => Requires(&[("endorsements", Delete)]),
+        ("POST", "/endorsements/{endorsement_id}/master_list/preview") => Requires(&[("endorsements", Read)]),
+        ("POST", "/endorsements/{endorsement_id}/master_list/replacement_preview") => Requires(&[("endorsements", Read)]),
+        ("POST", "/endorsements/{endorsement_id}/master_list/previews/{token}/commit") => Requires(&[("endorsements", Create)]),
+
+        /*
+        HMO Claims Billing
+         */
+        ("POST", "/hmo_claims_billing") => Requires(&[("acc_reconciliation", Create)]),
+        ("GET", "/hmo_claims_billing/") => Requires(&[("acc_reconciliation", Read)]),
+
+        /*
+        Background Jobs
+         */
+        ("GET", "/jobs") => Requires(&[("jobs", Read)]),
+        ("GET", "/jobs/{id}") => Requires(&[("jobs", Read)]),
+        ("POST", "/jobs") => Requires(&[("jobs", Create)]),
+        ("POST", "/jobs/{id}/retry") => Requires(&[("jobs", Update)]),
+
+        /*
+        HMO Receivables
+         */
+        ("GET", "/receivables/invoices") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/receivables/invoices/{id}") => Requires(&[("acc_reconciliation", Read)]),
+        ("POST", "/receivables/invoices/{id}/payments") => Requires(&[("acc_reconciliation", Create)]),
+        ("GET", "/receivables/aging") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/receivables/aging/download") => Requires(&[("acc_reconciliation", Read)]),
+
+        /*
+        Dentist Fee-for-Service Payouts
+         */
+        ("GET", "/dentist_payouts/batches") => Requires(&[("acc_reconciliation", Read)]),
+        ("POST", "/dentist_payouts/batches") => Requires(&[("acc_reconciliation", Create)]),
+        ("GET", "/dentist_payouts/batches/{id}") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/dentist_payouts/remittances/{id}/download") => Requires(&[("acc_reconciliation", Read)]),
+        ("POST", "/dentist_payouts/remittances/{id}/pay") => Requires(&[("acc_reconciliation", Update)]),
+        ("POST", "/dentist_payouts/items/{id}/pay") => Requires(&[("acc_reconciliation", Update)]),
+        ("GET", "/dentist_payouts/bir_2307") => Requires(&[("acc_reconciliation", Read)]),
+        ("POST", "/dentist_payouts/bir_2307") => Requires(&[("acc_reconciliation", Create)]),
+        ("GET", "/dentist_payouts/bir_2307/{id}/download") => Requires(&[("acc_reconciliation", Read)]),
+
+        /*
+        Bank Disbursements
+         */
+        ("GET", "/disbursements/layouts") => Requires(&[("acc_reconciliation", Read)]),
+        ("POST", "/disbursements/layouts") => Requires(&[("acc_reconciliation", Create)]),
+        ("PUT", "/disbursements/layouts/{id}") => Requires(&[("acc_reconciliation", Update)]),
+        ("GET", "/disbursements") => Requires(&[("acc_reconciliation", Read)]),
+        ("POST", "/disbursements") => Requires(&[("acc_reconciliation", Create)]),
+        ("GET", "/disbursements/{id}") => Requires(&[("acc_reconciliation", Read)]),
+        ("GET", "/disbursements/{id}/download") => Requires(&[("acc_reconciliation", Read)]),
+
+        /*
+        Business Calendar
+         */
+        ("GET", "/business_calendar") => Requires(&[("business_calendar", Read)]),
+        ("POST", "/business_calendar/holidays") => Requires(&[("business_calendar", Create)]),
+        ("POST", "/business_calendar/holidays/import") => Requires(&[("business_calendar", Create)]),
+        ("PATCH", "/business_calendar/holidays/{id}") => Requires(&[("business_calendar", Update)]),
+        ("DELETE", "/business_calendar/holidays/{id}") => Requires(&[("business_calendar", Delete)]),
+
+        _ => return None,
+    };
+    Some(access)
+}
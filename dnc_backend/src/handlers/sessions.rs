@@ -0,0 +1,302 @@
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+};
+use chrono::Utc;
+use jsonwebtoken::{encode, EncodingKey, Header};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set};
+use sea_orm::sea_query::Expr;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::AppState;
+use crate::entities::{user, user_session};
+use crate::handlers::structs::{AuthUser, Claims};
+
+/// Access tokens are short-lived; clients use the refresh token to get a new one.
+pub const ACCESS_TOKEN_MINUTES: i64 = 15;
+/// A session that is not refreshed within this window has to log in again.
+pub const REFRESH_TOKEN_DAYS: i64 = 7;
+
+/// Only the SHA-256 of a refresh token is stored in user_session.
+fn hash_refresh_token(refresh_token: &str) -> String {
+    Sha256::digest(refresh_token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn new_refresh_token() -> String {
+    format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Signs a new access token for `user` that is bound to the session `session_id`.
+/// Returns the token and its lifetime in seconds.
+pub(crate) fn issue_access_token(user: &user::Model, session_id: &str) -> Result<(String, i64), (StatusCode, String)> {
+    let secret = std::env::var("JWT_SECRET")
+        .map_err(|_| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "JWT_SECRET missing".to_string(),
+            )
+        })?;
+
+    let expiration = Utc::now()
+        .checked_add_signed(chrono::Duration::minutes(ACCESS_TOKEN_MINUTES))
+        .expect("valid timestamp")
+        .timestamp() as usize;
+
+    let claims = Claims {
+        sub: user.id,
+        email: user.email.clone(),
+        role_id: user.role_id,
+        sid: session_id.to_string(),
+        exp: expiration,
+    };
+    let header = Header::new(jsonwebtoken::Algorithm::HS512);
+    let token = encode(
+        &header,
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Token creation error:{e}")
+            )
+        })?;
+
+    Ok((token, ACCESS_TOKEN_MINUTES * 60))
+}
+
+/// Opens a new session for `user_id`. Returns (session_id, refresh_token).
+pub(crate) async fn create_session(db: &DatabaseConnection, user_id: i32) -> Result<(String, String), DbErr> {
+    let now = Utc::now().fixed_offset();
+    let session_id = Uuid::new_v4().to_string();
+    let refresh_token = new_refresh_token();
+
+    user_session::ActiveModel {
+        session_id: Set(session_id.clone()),
+        user_id: Set(user_id),
+        refresh_token_hash: Set(hash_refresh_token(&refresh_token)),
+        created_on: Set(now),
+        expires_on: Set(now + chrono::Duration::days(REFRESH_TOKEN_DAYS)),
+        ..Default::default()
+    }
+        .insert(db)
+        .await?;
+
+    Ok((session_id, refresh_token))
+}
+
+/// True if the session exists, belongs to `user_id`, is not revoked and has not expired,
+/// and the user is still active. Called by `require_jwt` on every request.
+pub(crate) async fn is_session_active(db: &DatabaseConnection, session_id: &str, user_id: i32) -> Result<bool, DbErr> {
+    let found = user_session::Entity::find()
+        .filter(user_session::Column::SessionId.eq(session_id))
+        .filter(user_session::Column::UserId.eq(user_id))
+        .filter(user_session::Column::RevokedOn.is_null())
+        .filter(user_session::Column::ExpiresOn.gt(Utc::now().fixed_offset()))
+        .find_also_related(user::Entity)
+        .one(db)
+        .await?;
+
+    Ok(matches!(found, Some((_, Some(u))) if u.active))
+}
+
+/// Revokes every open session of `user_id`. Returns the number of sessions revoked.
+pub(crate) async fn revoke_all_sessions_for_user(
+    db: &DatabaseConnection,
+    user_id: i32,
+    revoked_by: &str,
+    reason: &str,
+) -> Result<u64, DbErr> {
+    let result = user_session::Entity::update_many()
+        .col_expr(user_session::Column::RevokedOn, Expr::value(Utc::now().fixed_offset()))
+        .col_expr(user_session::Column::RevokedBy, Expr::value(revoked_by.to_string()))
+        .col_expr(user_session::Column::RevokeReason, Expr::value(reason.to_string()))
+        .filter(user_session::Column::UserId.eq(user_id))
+        .filter(user_session::Column::RevokedOn.is_null())
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected)
+}
+
+async fn revoke_session(
+    db: &DatabaseConnection,
+    session: user_session::Model,
+    revoked_by: &str,
+    reason: &str,
+) -> Result<(), DbErr> {
+    let mut am: user_session::ActiveModel = session.into();
+    am.revoked_on = Set(Some(Utc::now().fixed_offset()));
+    am.revoked_by = Set(Some(revoked_by.to_string()));
+    am.revoke_reason = Set(Some(reason.to_string()));
+    am.update(db).await?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshResponse {
+    pub token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+}
+
+/// POST /refresh
+/// Exchanges a refresh token for a new access token. The refresh token is rotated on every
+/// call; presenting an already-rotated token revokes the whole session.
+#[instrument(skip(state, payload), err(Debug))]
+pub async fn refresh_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Json<RefreshResponse>, (StatusCode, String)> {
+    let db_err = |e: DbErr| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error {e}"));
+    let presented_hash = hash_refresh_token(payload.refresh_token.trim());
+
+    let session = user_session::Entity::find()
+        .filter(user_session::Column::RefreshTokenHash.eq(presented_hash.clone()))
+        .one(&state.db)
+        .await
+        .map_err(db_err)?;
+
+    let session = match session {
+        Some(s) => s,
+        None => {
+            // 1. A rotated token being replayed means it leaked; kill the session it came from.
+            let reused = user_session::Entity::find()
+                .filter(user_session::Column::PreviousRefreshTokenHash.eq(presented_hash))
+                .filter(user_session::Column::RevokedOn.is_null())
+                .one(&state.db)
+                .await
+                .map_err(db_err)?;
+            if let Some(reused) = reused {
+                tracing::warn!("Refresh token reuse detected for session {}", reused.session_id);
+                revoke_session(&state.db, reused, "system", "refresh token reuse")
+                    .await
+                    .map_err(db_err)?;
+            }
+            return Err((StatusCode::UNAUTHORIZED, "Invalid refresh token".to_string()));
+        }
+    };
+
+    // 2. The session must still be open.
+    let now = Utc::now().fixed_offset();
+    if session.revoked_on.is_some() || session.expires_on <= now {
+        return Err((StatusCode::UNAUTHORIZED, "Session expired or revoked".to_string()));
+    }
+
+    // 3. The user must still exist and be active.
+    let user = user::Entity::find_by_id(session.user_id)
+        .one(&state.db)
+        .await
+        .map_err(db_err)?
+        .ok_or((StatusCode::UNAUTHORIZED, "Invalid refresh token".to_string()))?;
+    if !user.active {
+        revoke_session(&state.db, session, "system", "user inactive")
+            .await
+            .map_err(db_err)?;
+        return Err((StatusCode::FORBIDDEN, "Account is inactive".to_string()));
+    }
+
+    // 4. Rotate the refresh token, only if it is still the one presented, and issue a new
+    // access token. Losing the rotation to a concurrent refresh counts as reuse.
+    let refresh_token = new_refresh_token();
+    let session_id = session.session_id.clone();
+    let rotated = user_session::Entity::update_many()
+        .col_expr(user_session::Column::PreviousRefreshTokenHash, Expr::value(presented_hash.clone()))
+        .col_expr(user_session::Column::RefreshTokenHash, Expr::value(hash_refresh_token(&refresh_token)))
+        .col_expr(user_session::Column::LastRefreshedOn, Expr::value(now))
+        .filter(user_session::Column::SessionId.eq(session_id.clone()))
+        .filter(user_session::Column::RefreshTokenHash.eq(presented_hash))
+        .filter(user_session::Column::RevokedOn.is_null())
+        .exec(&state.db)
+        .await
+        .map_err(db_err)?;
+    if rotated.rows_affected == 0 {
+        tracing::warn!("Refresh token reuse detected for session {}", session_id);
+        let current = user_session::Entity::find()
+            .filter(user_session::Column::SessionId.eq(session_id))
+            .filter(user_session::Column::RevokedOn.is_null())
+            .one(&state.db)
+            .await
+            .map_err(db_err)?;
+        if let Some(current) = current {
+            revoke_session(&state.db, current, "system", "refresh token reuse")
+                .await
+                .map_err(db_err)?;
+        }
+        return Err((StatusCode::UNAUTHORIZED, "Invalid refresh token".to_string()));
+    }
+
+    let (token, expires_in) = issue_access_token(&user, &session_id)?;
+
+    Ok(Json(RefreshResponse {
+        token,
+        refresh_token,
+        expires_in,
+    }))
+}
+
+/// POST /api/logout
+/// Revokes the session the caller's access token belongs to.
+#[instrument(skip(state), err(Debug))]
+pub async fn logout_handler(
+    State(state): State<AppState>,
+    user: AuthUser,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let session = user_session::Entity::find()
+        .filter(user_session::Column::SessionId.eq(user.claims.sid.clone()))
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error {e}")))?;
+
+    if let Some(session) = session.filter(|s| s.revoked_on.is_none()) {
+        revoke_session(&state.db, session, &user.claims.email, "logout")
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error {e}")))?;
+    }
+    tracing::info!("user {} logged out", user.claims.email);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Serialize)]
+pub struct RevokeSessionsResponse {
+    pub user_id: i32,
+    pub revoked_sessions: u64,
+}
+
+/// POST /api/users/{id}/revoke_sessions
+/// Admin action: logs a user out of every device.
+#[instrument(skip(state), err(Debug))]
+pub async fn revoke_user_sessions_handler(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(user_id): Path<i32>,
+) -> Result<Json<RevokeSessionsResponse>, (StatusCode, String)> {
+    user::Entity::find_by_id(user_id)
+        .one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error {e}")))?
+        .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+    let revoked_sessions = revoke_all_sessions_for_user(&state.db, user_id, &auth.claims.email, "revoked by admin")
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error {e}")))?;
+
+    tracing::info!("user {} revoked {} sessions of user_id {}", auth.claims.email, revoked_sessions, user_id);
+
+    Ok(Json(RevokeSessionsResponse {
+        user_id,
+        revoked_sessions,
+    }))
+}
@@ -7,6 +7,7 @@ pub struct Claims{
     pub sub:i32, // subject: user id
     pub email:String,
     pub role_id: i32,
+    pub sid: String, // session id: user_session.session_id
     pub exp:usize // expiration timestamp
 }
 
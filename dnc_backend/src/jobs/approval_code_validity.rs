@@ -0,0 +1,222 @@
+//! How long a released approval code stays valid for reconciliation.
+//!
+//! The window is set in approval_code_validity, per HMO, per dental service type, or both; the
+//! most specific row wins, HMO over service type, and a row with neither applies to everyone.
+//! Without any row that applies, the code does not lapse. A code released on day D with a
+//! window of N days can be reconciled through day D + N - 1, Manila time.
+//!
+//! The daily job marks unreconciled codes past their window as lapsed (verification.lapsed_on).
+//! A lapsed code is refused by reconciliation and reported by the approval code check, unless
+//! a user allowed it with a reason (verification.lapse_override_*).
+use chrono::{Days, NaiveDate, Utc};
+use sea_orm::prelude::DateTimeWithTimeZone;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait, FromQueryResult, JoinType,
+    QueryFilter, QuerySelect, RelationTrait, Set, TransactionTrait,
+};
+use serde::Serialize;
+use tracing::info;
+
+use crate::AppState;
+use crate::business_calendar;
+use crate::entities::{approval_code_validity, dental_service, endorsement, master_list_member, verification};
+use crate::verification_state::VerificationStatus;
+
+/// A validity window; `None` scopes apply to every HMO or service type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidityWindow {
+    pub hmo_id: Option<i32>,
+    pub dental_service_type_id: Option<i32>,
+    pub validity_days: i32,
+}
+
+impl From<&approval_code_validity::Model> for ValidityWindow {
+    fn from(row: &approval_code_validity::Model) -> Self {
+        Self {
+            hmo_id: row.hmo_id,
+            dental_service_type_id: row.dental_service_type_id,
+            validity_days: row.validity_days,
+        }
+    }
+}
+
+/// The days a code of the HMO and service type stays valid, from the most specific window
+/// that applies; `None` if no window applies.
+pub fn validity_days_for(windows: &[ValidityWindow], hmo_id: i32, dental_service_type_id: i32) -> Option<i32> {
+    windows
+        .iter()
+        .filter(|w| w.hmo_id.is_none_or(|id| id == hmo_id))
+        .filter(|w| w.dental_service_type_id.is_none_or(|id| id == dental_service_type_id))
+        .max_by_key(|w| (w.hmo_id.is_some(), w.dental_service_type_id.is_some()))
+        .map(|w| w.validity_days)
+}
+
+/// The last day a code released on `released_on` can be reconciled.
+pub fn valid_until(released_on: NaiveDate, validity_days: i32) -> NaiveDate {
+    released_on + Days::new(validity_days.max(1) as u64 - 1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct LapseStatus {
+    /// None if no window applies
+    pub valid_until: Option<NaiveDate>,
+    /// past its window, or marked lapsed by the daily job, and not overridden, reconciled or
+    /// voided
+    pub is_lapsed: bool,
+    pub is_overridden: bool,
+}
+
+/// Whether a code is lapsed on `today`. `marked_lapsed` is a lapsed_on set by the daily job,
+/// which stands even if the window was lengthened afterwards. `settled` is a code that was
+/// reconciled, within its window or after an override, or voided; it no longer lapses.
+pub fn lapse_status(
+    released_on: NaiveDate,
+    validity_days: Option<i32>,
+    marked_lapsed: bool,
+    overridden: bool,
+    settled: bool,
+    today: NaiveDate,
+) -> LapseStatus {
+    let valid_until = validity_days.map(|days| valid_until(released_on, days));
+    let past_window = valid_until.is_some_and(|until| today > until);
+    LapseStatus {
+        valid_until,
+        is_lapsed: (past_window || marked_lapsed) && !overridden && !settled,
+        is_overridden: overridden,
+    }
+}
+
+pub(crate) async fn load_validity_windows<C: ConnectionTrait>(db: &C) -> Result<Vec<ValidityWindow>, DbErr> {
+    Ok(approval_code_validity::Entity::find()
+        .all(db)
+        .await?
+        .iter()
+        .map(ValidityWindow::from)
+        .collect())
+}
+
+/// The lapse status of a verification's code; `None` if no code was released.
+pub(crate) async fn lapse_status_of<C: ConnectionTrait>(
+    db: &C,
+    model: &verification::Model,
+    today: NaiveDate,
+) -> Result<Option<LapseStatus>, DbErr> {
+    let (Some(_), Some(approval_date)) = (&model.approval_code, model.approval_date) else {
+        return Ok(None);
+    };
+    let scope = verification::Entity::find_by_id(model.id)
+        .join(JoinType::InnerJoin, verification::Relation::MasterListMember.def())
+        .join(JoinType::InnerJoin, master_list_member::Relation::Endorsement.def())
+        .join(JoinType::InnerJoin, verification::Relation::DentalService.def())
+        .select_only()
+        .column_as(endorsement::Column::HmoId, "hmo_id")
+        .column_as(dental_service::Column::TypeId, "dental_service_type_id")
+        .into_tuple::<(i32, i32)>()
+        .one(db)
+        .await?;
+    let validity_days = match scope {
+        Some((hmo_id, dental_service_type_id)) => {
+            validity_days_for(&load_validity_windows(db).await?, hmo_id, dental_service_type_id)
+        }
+        None => None,
+    };
+
+    Ok(Some(lapse_status(
+        business_calendar::date_of(&approval_date),
+        validity_days,
+        model.lapsed_on.is_some(),
+        model.lapse_override_on.is_some(),
+        model.is_reconciled == Some(true) || model.status_id == VerificationStatus::Voided.int_code(),
+        today,
+    )))
+}
+
+
+// region: Daily Lapse
+#[derive(Debug, FromQueryResult)]
+struct UnreconciledCodeRow {
+    id: i32,
+    approval_date: DateTimeWithTimeZone,
+    hmo_id: i32,
+    dental_service_type_id: i32,
+}
+
+/// Marks released, unreconciled codes past their validity window as lapsed.
+pub(crate) async fn lapse_unreconciled_approval_codes(state: AppState) -> anyhow::Result<()> {
+    let db = &state.db;
+    let today_manila = business_calendar::today();
+
+    let windows = load_validity_windows(db).await?;
+    if windows.is_empty() {
+        info!(target: "jobs", "lapse_unreconciled_approval_codes(): no validity windows are set");
+        return Ok(());
+    }
+
+    let rows = verification::Entity::find()
+        .join(JoinType::InnerJoin, verification::Relation::MasterListMember.def())
+        .join(JoinType::InnerJoin, master_list_member::Relation::Endorsement.def())
+        .join(JoinType::InnerJoin, verification::Relation::DentalService.def())
+        .filter(verification::Column::StatusId.eq(VerificationStatus::Done.int_code()))
+        .filter(verification::Column::ApprovalCode.is_not_null())
+        .filter(verification::Column::ApprovalDate.is_not_null())
+        .filter(
+            Condition::any()
+                .add(verification::Column::IsReconciled.is_null())
+                .add(verification::Column::IsReconciled.eq(false)),
+        )
+        .filter(verification::Column::LapsedOn.is_null())
+        .filter(verification::Column::LapseOverrideOn.is_null())
+        .select_only()
+        .column(verification::Column::Id)
+        .column(verification::Column::ApprovalDate)
+        .column_as(endorsement::Column::HmoId, "hmo_id")
+        .column_as(dental_service::Column::TypeId, "dental_service_type_id")
+        .into_model::<UnreconciledCodeRow>()
+        .all(db)
+        .await?;
+
+    let ids_to_lapse: Vec<i32> = rows
+        .iter()
+        .filter(|row| {
+            let validity_days = validity_days_for(&windows, row.hmo_id, row.dental_service_type_id);
+            let released_on = business_calendar::date_of(&row.approval_date);
+            lapse_status(released_on, validity_days, false, false, false, today_manila).is_lapsed
+        })
+        .map(|row| row.id)
+        .collect();
+
+    if ids_to_lapse.is_empty() {
+        info!(target: "jobs", "lapse_unreconciled_approval_codes() finished: no codes lapsed");
+        return Ok(());
+    }
+
+    // Update one row at a time so each lapse is written to audit_log. A row lapsed since it was
+    // loaded is left alone.
+    let now = Utc::now().fixed_offset();
+    let txn = db.begin().await?;
+    let mut rows_affected = 0_u64;
+    for id in &ids_to_lapse {
+        let Some(row) = verification::Entity::find_by_id(*id)
+            .filter(verification::Column::LapsedOn.is_null())
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+        else {
+            continue;
+        };
+        let mut am: verification::ActiveModel = row.into();
+        am.lapsed_on = Set(Some(now));
+        am.update(&txn).await?;
+        rows_affected += 1;
+    }
+    txn.commit().await?;
+
+    info!(
+        target: "jobs",
+        "lapse_unreconciled_approval_codes() finished: {} code(s) lapsed; ids={:?}",
+        rows_affected,
+        ids_to_lapse
+    );
+    Ok(())
+}
+// endregion: Daily Lapse
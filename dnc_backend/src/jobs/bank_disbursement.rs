@@ -0,0 +1,755 @@
+//! Bulk-credit upload files for paying dentists through their banks.
+//!
+//! A disbursement run takes the payments due to dentists:
+//! - the retainers of a month, as listed by the dentist retainer payables report, and
+//! - the fee-for-service payout remittances of a batch, at their net amount for the items not
+//!   yet paid.
+//!
+//! The money goes to the dental clinic's account: acct_bank_name, acct_account_number,
+//! acct_account_name and acct_account_type_id. Payments whose account is missing or invalid,
+//! or whose bank has no layout, are not written and are returned as exceptions.
+//!
+//! The rest are grouped by bank. Each bank gets one upload file in the layout configured for
+//! it in bank_disbursement_layout, either CSV or fixed-width. Every payment written is
+//! recorded in bank_disbursement_payment with the file it went into. The unique indexes there
+//! keep a month's retainer or a remittance from going into two files.
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use axum::http::StatusCode;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, Set,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use tracing::info;
+
+use crate::entities::{
+    account_type, bank_disbursement_file, bank_disbursement_layout, bank_disbursement_payment,
+    dental_clinic, dentist_payout_item, dentist_payout_remittance,
+};
+use crate::jobs::billing_amounts::money;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DisbursementError {
+    #[error("Invalid bank layout: {0}")]
+    InvalidLayout(String),
+
+    #[error("Bank layout {0} not found")]
+    LayoutNotFound(i32),
+
+    #[error("Disbursement file {0} not found")]
+    FileNotFound(i32),
+
+    #[error("Could not record the files; another run may have disbursed the same payments: {0}")]
+    Conflict(DbErr),
+
+    #[error("Failed to write the upload file: {0}")]
+    Io(String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+impl From<DisbursementError> for (StatusCode, String) {
+    fn from(e: DisbursementError) -> Self {
+        let status = match e {
+            DisbursementError::InvalidLayout(_) => StatusCode::BAD_REQUEST,
+            DisbursementError::LayoutNotFound(_) | DisbursementError::FileNotFound(_) => StatusCode::NOT_FOUND,
+            DisbursementError::Conflict(_) => StatusCode::CONFLICT,
+            DisbursementError::Io(_) | DisbursementError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DisbursementSource {
+    Retainer,
+    Payout,
+}
+
+impl DisbursementSource {
+    /// Stored in bank_disbursement_payment.source.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            DisbursementSource::Retainer => "retainer",
+            DisbursementSource::Payout => "payout",
+        }
+    }
+}
+
+
+// region: Layouts
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileFormat {
+    Csv,
+    FixedWidth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// What goes into a field. Payment fields are for detail records; the totals, for header and
+/// trailer records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    /// 1, 2, 3... in the file
+    Sequence,
+    AccountNumber,
+    AccountName,
+    /// account_type.name, or its code from account_type_codes
+    AccountType,
+    /// e.g. 1234.50
+    Amount,
+    /// e.g. 123450
+    AmountCentavos,
+    PayeeName,
+    Reference,
+    ValueDate,
+    DebitAccountNumber,
+    PaymentCount,
+    TotalAmount,
+    TotalAmountCentavos,
+    /// the field's value
+    Constant,
+    Blank,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutField {
+    pub field: FieldKind,
+    /// required for fixed-width files
+    #[serde(default)]
+    pub width: Option<usize>,
+    /// defaults to right for numbers, left otherwise
+    #[serde(default)]
+    pub align: Option<Align>,
+    /// defaults to a space
+    #[serde(default)]
+    pub pad: Option<char>,
+    /// the CSV column name
+    #[serde(default)]
+    pub label: Option<String>,
+    /// for constant fields
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// bank_disbursement_layout.spec
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutSpec {
+    pub format: FileFormat,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: String,
+    /// writes the field labels as the first CSV line
+    #[serde(default)]
+    pub include_column_names: bool,
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    /// writes text in upper case
+    #[serde(default)]
+    pub uppercase: bool,
+    /// the number of digits in the bank's account numbers, if fixed
+    #[serde(default)]
+    pub account_number_length: Option<usize>,
+    /// account_type.name -> the bank's code, e.g. {"Savings": "SA"}
+    #[serde(default)]
+    pub account_type_codes: BTreeMap<String, String>,
+    /// the company account the payments are drawn from
+    #[serde(default)]
+    pub debit_account_number: String,
+    #[serde(default)]
+    pub file_extension: Option<String>,
+    #[serde(default = "default_line_ending")]
+    pub line_ending: String,
+    /// a record before the payments
+    #[serde(default)]
+    pub header: Vec<LayoutField>,
+    /// one record per payment
+    pub fields: Vec<LayoutField>,
+    /// a record after the payments
+    #[serde(default)]
+    pub trailer: Vec<LayoutField>,
+}
+
+fn default_delimiter() -> String {
+    ",".to_string()
+}
+
+fn default_date_format() -> String {
+    "%Y%m%d".to_string()
+}
+
+fn default_line_ending() -> String {
+    "\r\n".to_string()
+}
+
+impl LayoutSpec {
+    /// Reads and checks a layout.
+    pub fn parse(spec: &JsonValue) -> Result<Self, DisbursementError> {
+        let layout: LayoutSpec = serde_json::from_value(spec.clone())
+            .map_err(|e| DisbursementError::InvalidLayout(e.to_string()))?;
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    fn validate(&self) -> Result<(), DisbursementError> {
+        let invalid = |message: &str| Err(DisbursementError::InvalidLayout(message.to_string()));
+        if self.fields.is_empty() {
+            return invalid("fields must not be empty");
+        }
+        if self.format == FileFormat::Csv && self.delimiter.is_empty() {
+            return invalid("a CSV layout needs a delimiter");
+        }
+        if StrftimeItems::new(&self.date_format).any(|item| item == Item::Error) {
+            return invalid("date_format is not a valid date format");
+        }
+        for field in self.header.iter().chain(&self.fields).chain(&self.trailer) {
+            if self.format == FileFormat::FixedWidth && field.width.unwrap_or(0) == 0 {
+                return invalid("every field of a fixed-width layout needs a width");
+            }
+            if field.field == FieldKind::Constant && field.value.is_none() {
+                return invalid("constant fields need a value");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn extension(&self) -> &str {
+        match (&self.file_extension, self.format) {
+            (Some(extension), _) => extension.trim_start_matches('.'),
+            (None, FileFormat::Csv) => "csv",
+            (None, FileFormat::FixedWidth) => "txt",
+        }
+    }
+}
+// endregion: Layouts
+
+
+// region: Accounts
+
+/// Why a payment could not be written to an upload file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountProblem {
+    MissingBankName,
+    NoLayoutForBank,
+    MissingAccountNumber,
+    InvalidAccountNumber,
+    MissingAccountName,
+    MissingAccountType,
+}
+
+/// Bank names are matched ignoring case and extra spaces.
+pub fn normalize_bank_name(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// The account number without the spaces and dashes people type into it.
+pub fn clean_account_number(account_number: &str) -> String {
+    account_number
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect()
+}
+
+/// A clinic's bank account as entered: dental_clinic.acct_bank_name, acct_account_number,
+/// acct_account_name, and the account_type.name of acct_account_type_id.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClinicAccount<'a> {
+    pub bank_name: Option<&'a str>,
+    pub account_number: Option<&'a str>,
+    pub account_name: Option<&'a str>,
+    pub account_type: Option<&'a str>,
+}
+
+/// The clinic's bank account, or what is wrong with it. `layout` is the layout for its bank,
+/// if there is one.
+pub fn check_account(
+    clinic: ClinicAccount,
+    layout: Option<&LayoutSpec>,
+) -> Result<PayeeAccount, Vec<AccountProblem>> {
+    let text = |value: Option<&str>| value.map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+    let mut problems = Vec::new();
+
+    let bank_name = text(clinic.bank_name);
+    if bank_name.is_none() {
+        problems.push(AccountProblem::MissingBankName);
+    } else if layout.is_none() {
+        problems.push(AccountProblem::NoLayoutForBank);
+    }
+
+    let account_number = text(clinic.account_number).map(|n| clean_account_number(&n));
+    match &account_number {
+        None => problems.push(AccountProblem::MissingAccountNumber),
+        Some(number) => {
+            let expected_length = layout.and_then(|l| l.account_number_length);
+            if !number.chars().all(|c| c.is_ascii_digit())
+                || expected_length.is_some_and(|length| number.len() != length)
+            {
+                problems.push(AccountProblem::InvalidAccountNumber);
+            }
+        }
+    }
+
+    let account_name = text(clinic.account_name);
+    if account_name.is_none() {
+        problems.push(AccountProblem::MissingAccountName);
+    }
+    let account_type = text(clinic.account_type);
+    if account_type.is_none() {
+        problems.push(AccountProblem::MissingAccountType);
+    }
+
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    Ok(PayeeAccount {
+        bank_name: bank_name.unwrap_or_default(),
+        account_number: account_number.unwrap_or_default(),
+        account_name: account_name.unwrap_or_default(),
+        account_type: account_type.unwrap_or_default(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayeeAccount {
+    pub bank_name: String,
+    pub account_number: String,
+    pub account_name: String,
+    /// account_type.name
+    pub account_type: String,
+}
+// endregion: Accounts
+
+
+// region: Rendering
+
+/// One payment in an upload file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisbursementLine {
+    pub account: PayeeAccount,
+    pub payee_name: String,
+    pub reference: String,
+    pub amount: Decimal,
+}
+
+fn centavos(amount: Decimal) -> String {
+    (money(amount) * Decimal::ONE_HUNDRED).trunc().to_string()
+}
+
+fn is_numeric(kind: FieldKind) -> bool {
+    matches!(
+        kind,
+        FieldKind::Sequence
+            | FieldKind::Amount
+            | FieldKind::AmountCentavos
+            | FieldKind::PaymentCount
+            | FieldKind::TotalAmount
+            | FieldKind::TotalAmountCentavos
+    )
+}
+
+/// Renders an upload file.
+pub fn render_file(layout: &LayoutSpec, lines: &[DisbursementLine], value_date: NaiveDate) -> String {
+    let total: Decimal = lines.iter().map(|l| money(l.amount)).sum();
+    let value_date = value_date.format(&layout.date_format).to_string();
+
+    let value_of = |field: &LayoutField, sequence: usize, line: Option<&DisbursementLine>| -> String {
+        let value = match field.field {
+            FieldKind::Sequence => sequence.to_string(),
+            FieldKind::AccountNumber => line.map(|l| l.account.account_number.clone()).unwrap_or_default(),
+            FieldKind::AccountName => line.map(|l| l.account.account_name.clone()).unwrap_or_default(),
+            FieldKind::AccountType => line
+                .map(|l| {
+                    layout
+                        .account_type_codes
+                        .get(&l.account.account_type)
+                        .cloned()
+                        .unwrap_or_else(|| l.account.account_type.clone())
+                })
+                .unwrap_or_default(),
+            FieldKind::Amount => line.map(|l| format!("{:.2}", money(l.amount))).unwrap_or_default(),
+            FieldKind::AmountCentavos => line.map(|l| centavos(l.amount)).unwrap_or_default(),
+            FieldKind::PayeeName => line.map(|l| l.payee_name.clone()).unwrap_or_default(),
+            FieldKind::Reference => line.map(|l| l.reference.clone()).unwrap_or_default(),
+            FieldKind::ValueDate => value_date.clone(),
+            FieldKind::DebitAccountNumber => layout.debit_account_number.clone(),
+            FieldKind::PaymentCount => lines.len().to_string(),
+            FieldKind::TotalAmount => format!("{:.2}", total),
+            FieldKind::TotalAmountCentavos => centavos(total),
+            FieldKind::Constant => field.value.clone().unwrap_or_default(),
+            FieldKind::Blank => String::new(),
+        };
+        if layout.uppercase { value.to_uppercase() } else { value }
+    };
+
+    let record = |fields: &[LayoutField], sequence: usize, line: Option<&DisbursementLine>| -> String {
+        let values = fields.iter().map(|field| (field, value_of(field, sequence, line)));
+        match layout.format {
+            FileFormat::Csv => values
+                .map(|(_, value)| csv_value(&value, &layout.delimiter))
+                .collect::<Vec<_>>()
+                .join(&layout.delimiter),
+            FileFormat::FixedWidth => values
+                .map(|(field, value)| fixed_width_value(field, &value))
+                .collect(),
+        }
+    };
+
+    let mut records = Vec::new();
+    if layout.format == FileFormat::Csv && layout.include_column_names {
+        records.push(
+            layout
+                .fields
+                .iter()
+                .map(|f| csv_value(f.label.as_deref().unwrap_or(""), &layout.delimiter))
+                .collect::<Vec<_>>()
+                .join(&layout.delimiter),
+        );
+    }
+    if !layout.header.is_empty() {
+        records.push(record(&layout.header, 0, None));
+    }
+    for (index, line) in lines.iter().enumerate() {
+        records.push(record(&layout.fields, index + 1, Some(line)));
+    }
+    if !layout.trailer.is_empty() {
+        records.push(record(&layout.trailer, lines.len() + 1, None));
+    }
+
+    let mut file = records.join(&layout.line_ending);
+    file.push_str(&layout.line_ending);
+    file
+}
+
+fn csv_value(value: &str, delimiter: &str) -> String {
+    if value.contains(delimiter) || value.contains('"') || value.contains('\n') || value.contains('\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Cuts or pads the value to the field's width.
+fn fixed_width_value(field: &LayoutField, value: &str) -> String {
+    let width = field.width.unwrap_or(0);
+    let align = field
+        .align
+        .unwrap_or(if is_numeric(field.field) { Align::Right } else { Align::Left });
+    let pad = field.pad.unwrap_or(' ');
+
+    let length = value.chars().count();
+    if length >= width {
+        // numbers keep their low-order digits, text its beginning
+        return match align {
+            Align::Right => value.chars().skip(length - width).collect(),
+            Align::Left => value.chars().take(width).collect(),
+        };
+    }
+    let padding: String = std::iter::repeat_n(pad, width - length).collect();
+    match align {
+        Align::Right => format!("{padding}{value}"),
+        Align::Left => format!("{value}{padding}"),
+    }
+}
+// endregion: Rendering
+
+
+// region: Disbursement Runs
+
+/// A month's retainer due to a dentist at a clinic, from the retainer payables report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayableRetainer {
+    pub dentist_id: i32,
+    pub dental_clinic_id: i32,
+    pub year: i32,
+    pub month: u32,
+    pub amount: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DisbursementException {
+    pub source: DisbursementSource,
+    pub dentist_id: i32,
+    pub dental_clinic_id: i32,
+    pub dental_clinic_name: String,
+    pub dentist_payout_remittance_id: Option<i32>,
+    pub amount: Decimal,
+    pub problems: Vec<AccountProblem>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DisbursementFileSummary {
+    pub file_id: i32,
+    pub bank_name: String,
+    pub file_name: String,
+    pub payment_count: i32,
+    pub total_amount: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DisbursementSummary {
+    pub files: Vec<DisbursementFileSummary>,
+    pub exceptions: Vec<DisbursementException>,
+    /// payments already in an earlier file
+    pub already_disbursed: usize,
+}
+
+/// A payment due, before its account is checked.
+struct Due {
+    source: DisbursementSource,
+    dentist_id: i32,
+    dental_clinic_id: i32,
+    retainer: Option<(i32, u32)>,
+    remittance_id: Option<i32>,
+    reference: String,
+    amount: Decimal,
+}
+
+/// Writes the upload files for the retainers and for the unpaid remittances of the payout
+/// batch, one file per bank.
+pub async fn create_disbursement_files(
+    db: &DatabaseConnection,
+    retainers: Vec<PayableRetainer>,
+    payout_batch_id: Option<i32>,
+    value_date: NaiveDate,
+    created_by: &str,
+) -> Result<DisbursementSummary, DisbursementError> {
+    // ---1. what is due and not yet in a file
+    let (dues, already_disbursed) = load_dues(db, retainers, payout_batch_id).await?;
+
+    // ---2. check the accounts
+    let clinic_ids: HashSet<i32> = dues.iter().map(|d| d.dental_clinic_id).collect();
+    let clinics: HashMap<i32, dental_clinic::Model> = dental_clinic::Entity::find()
+        .filter(dental_clinic::Column::Id.is_in(clinic_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|c| (c.id, c))
+        .collect();
+    let account_types: HashMap<i32, String> = account_type::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|t| (t.id, t.name))
+        .collect();
+    let mut layouts: HashMap<String, (bank_disbursement_layout::Model, LayoutSpec)> = HashMap::new();
+    for layout in bank_disbursement_layout::Entity::find()
+        .filter(bank_disbursement_layout::Column::Active.eq(true))
+        .all(db)
+        .await?
+    {
+        let spec = LayoutSpec::parse(&layout.spec)?;
+        layouts.insert(normalize_bank_name(&layout.bank_name), (layout, spec));
+    }
+
+    let mut by_bank: BTreeMap<String, Vec<(Due, DisbursementLine)>> = BTreeMap::new();
+    let mut exceptions = Vec::new();
+    for due in dues {
+        let Some(clinic) = clinics.get(&due.dental_clinic_id) else { continue };
+        let bank_key = clinic.acct_bank_name.as_deref().map(normalize_bank_name).unwrap_or_default();
+        let layout = layouts.get(&bank_key).map(|(_, spec)| spec);
+        let account = ClinicAccount {
+            bank_name: clinic.acct_bank_name.as_deref(),
+            account_number: clinic.acct_account_number.as_deref(),
+            account_name: clinic.acct_account_name.as_deref(),
+            account_type: clinic.acct_account_type_id.and_then(|id| account_types.get(&id)).map(String::as_str),
+        };
+        match check_account(account, layout) {
+            Ok(account) => {
+                let line = DisbursementLine {
+                    account,
+                    payee_name: clinic.name.clone(),
+                    reference: due.reference.clone(),
+                    amount: due.amount,
+                };
+                by_bank.entry(bank_key).or_default().push((due, line));
+            }
+            Err(problems) => exceptions.push(DisbursementException {
+                source: due.source,
+                dentist_id: due.dentist_id,
+                dental_clinic_id: due.dental_clinic_id,
+                dental_clinic_name: clinic.name.clone(),
+                dentist_payout_remittance_id: due.remittance_id,
+                amount: due.amount,
+                problems,
+            }),
+        }
+    }
+
+    // ---3. one file per bank. The files are written only after the commit, so a conflict or a
+    // failed commit leaves no file on disk that could be sent to the bank without a record.
+    let now = Utc::now().fixed_offset();
+    let txn = db.begin().await?;
+    let mut files = Vec::new();
+    let mut contents = Vec::new();
+    for (bank_key, entries) in by_bank {
+        let (layout, spec) = &layouts[&bank_key];
+        let lines: Vec<DisbursementLine> = entries.iter().map(|(_, line)| line.clone()).collect();
+        let total_amount: Decimal = lines.iter().map(|l| money(l.amount)).sum();
+        let file_name = format!(
+            "Disbursement_{}_{}_{}.{}",
+            layout.bank_name.split_whitespace().collect::<Vec<_>>().join("_"),
+            value_date.format("%Y%m%d"),
+            Utc::now().format("%H%M%S"),
+            spec.extension()
+        );
+
+        let file = bank_disbursement_file::ActiveModel {
+            layout_id: Set(layout.id),
+            bank_name: Set(layout.bank_name.clone()),
+            file_name: Set(file_name.clone()),
+            value_date: Set(value_date),
+            payment_count: Set(lines.len() as i32),
+            total_amount: Set(total_amount),
+            created_by: Set(created_by.to_string()),
+            created_on: Set(now),
+            ..Default::default()
+        }
+            .insert(&txn)
+            .await?;
+
+        let payments: Vec<bank_disbursement_payment::ActiveModel> = entries
+            .iter()
+            .map(|(due, line)| bank_disbursement_payment::ActiveModel {
+                file_id: Set(file.id),
+                source: Set(due.source.as_str().to_string()),
+                dentist_id: Set(due.dentist_id),
+                dental_clinic_id: Set(due.dental_clinic_id),
+                retainer_year: Set(due.retainer.map(|(year, _)| year)),
+                retainer_month: Set(due.retainer.map(|(_, month)| month as i32)),
+                dentist_payout_remittance_id: Set(due.remittance_id),
+                amount: Set(money(line.amount)),
+                account_number: Set(line.account.account_number.clone()),
+                account_name: Set(line.account.account_name.clone()),
+                ..Default::default()
+            })
+            .collect();
+        bank_disbursement_payment::Entity::insert_many(payments)
+            .exec(&txn)
+            .await
+            .map_err(DisbursementError::Conflict)?;
+
+        contents.push((format!("generated_reports/{}", file_name), render_file(spec, &lines, value_date)));
+        files.push(DisbursementFileSummary {
+            file_id: file.id,
+            bank_name: file.bank_name,
+            file_name,
+            payment_count: file.payment_count,
+            total_amount,
+        });
+    }
+    txn.commit().await?;
+    for (full_filename, content) in contents {
+        std::fs::write(&full_filename, content)
+            .map_err(|e| DisbursementError::Io(format!("{}: {}", full_filename, e)))?;
+    }
+
+    info!(target: "jobs",
+        "create_disbursement_files() wrote {} file(s); {} exception(s), {} already disbursed",
+        files.len(), exceptions.len(), already_disbursed
+    );
+    Ok(DisbursementSummary {
+        files,
+        exceptions,
+        already_disbursed,
+    })
+}
+
+/// The retainers and the batch's unpaid remittances that are not in a file yet, and how many
+/// already were.
+async fn load_dues(
+    db: &DatabaseConnection,
+    retainers: Vec<PayableRetainer>,
+    payout_batch_id: Option<i32>,
+) -> Result<(Vec<Due>, usize), DbErr> {
+    let mut dues = Vec::new();
+    let mut already_disbursed = 0;
+
+    if !retainers.is_empty() {
+        let disbursed: HashSet<(i32, i32, i32, i32)> = bank_disbursement_payment::Entity::find()
+            .filter(bank_disbursement_payment::Column::Source.eq(DisbursementSource::Retainer.as_str()))
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|p| Some((p.dentist_id, p.dental_clinic_id, p.retainer_year?, p.retainer_month?)))
+            .collect();
+        for retainer in retainers {
+            if retainer.amount <= Decimal::ZERO {
+                continue;
+            }
+            let key = (retainer.dentist_id, retainer.dental_clinic_id, retainer.year, retainer.month as i32);
+            if disbursed.contains(&key) {
+                already_disbursed += 1;
+                continue;
+            }
+            dues.push(Due {
+                source: DisbursementSource::Retainer,
+                dentist_id: retainer.dentist_id,
+                dental_clinic_id: retainer.dental_clinic_id,
+                retainer: Some((retainer.year, retainer.month)),
+                remittance_id: None,
+                reference: format!("RET-{}{:02}-{}-{}", retainer.year, retainer.month, retainer.dentist_id, retainer.dental_clinic_id),
+                amount: money(retainer.amount),
+            });
+        }
+    }
+
+    if let Some(batch_id) = payout_batch_id {
+        let remittances = dentist_payout_remittance::Entity::find()
+            .filter(dentist_payout_remittance::Column::BatchId.eq(batch_id))
+            .all(db)
+            .await?;
+        let remittance_ids: Vec<i32> = remittances.iter().map(|r| r.id).collect();
+        let disbursed: HashSet<i32> = bank_disbursement_payment::Entity::find()
+            .filter(bank_disbursement_payment::Column::DentistPayoutRemittanceId.is_in(remittance_ids.clone()))
+            .all(db)
+            .await?
+            .into_iter()
+            .filter_map(|p| p.dentist_payout_remittance_id)
+            .collect();
+        let mut unpaid_net: HashMap<i32, Decimal> = HashMap::new();
+        for item in dentist_payout_item::Entity::find()
+            .filter(dentist_payout_item::Column::RemittanceId.is_in(remittance_ids))
+            .filter(dentist_payout_item::Column::PaidOn.is_null())
+            .all(db)
+            .await?
+        {
+            *unpaid_net.entry(item.remittance_id).or_default() += item.net_amount;
+        }
+
+        for remittance in remittances {
+            if disbursed.contains(&remittance.id) {
+                already_disbursed += 1;
+                continue;
+            }
+            let Some(amount) = unpaid_net.get(&remittance.id).copied().filter(|a| *a > Decimal::ZERO) else {
+                continue;
+            };
+            dues.push(Due {
+                source: DisbursementSource::Payout,
+                dentist_id: remittance.dentist_id,
+                dental_clinic_id: remittance.dental_clinic_id,
+                retainer: None,
+                remittance_id: Some(remittance.id),
+                reference: format!("PO-{}-{}", remittance.batch_id, remittance.id),
+                amount,
+            });
+        }
+    }
+    Ok((dues, already_disbursed))
+}
+// endregion: Disbursement Runs
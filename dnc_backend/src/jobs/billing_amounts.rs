@@ -0,0 +1,108 @@
+//! Per-endorsement HMO billing amounts.
+//!
+//! The member rate comes from the endorsement_billing_rule tier whose min_count..=max_count
+//! holds the billable count, falling back to the endorsement's retainer_fee when no tier
+//! matches. VAT is added per member. Money is rounded to centavos, half away from zero, at
+//! each step that appears on the billing summary so the printed columns add up.
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+
+/// Used when app_config has no hmo_billing_vat_rate.
+pub const DEFAULT_VAT_RATE: Decimal = Decimal::from_parts(12, 0, 0, false, 2);
+
+/// dental_service.type_id of high-end services, whose approved cost is billed and paid on top
+/// of the service rate.
+pub const HIGH_END_SERVICE_TYPE_ID: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateSource {
+    BillingRule,
+    RetainerFee,
+    /// neither a matching tier nor a retainer fee; billed at zero
+    None,
+}
+
+impl RateSource {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            RateSource::BillingRule => "billing_rule",
+            RateSource::RetainerFee => "retainer_fee",
+            RateSource::None => "none",
+        }
+    }
+}
+
+/// One endorsement_billing_rule tier.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BillingTier {
+    pub id: i32,
+    pub min_count: i32,
+    pub max_count: i32,
+    pub rate: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BillingAmounts {
+    pub billable_count: i32,
+    pub rate_source: RateSource,
+    pub billing_rule_id: Option<i32>,
+    /// J: per member, before VAT
+    pub rate: Decimal,
+    pub vat_rate: Decimal,
+    /// K
+    pub vat_per_member: Decimal,
+    /// L = J + K
+    pub rate_with_vat: Decimal,
+    pub amount_before_vat: Decimal,
+    pub vat_amount: Decimal,
+    /// M = L * F
+    pub total_amount: Decimal,
+}
+
+/// Rounds to centavos, half away from zero.
+pub fn money(value: Decimal) -> Decimal {
+    value.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+}
+
+/// The tier `count` falls in. Overlapping tiers are resolved in favour of the narrowest,
+/// then the lowest id, so the choice does not depend on row order.
+pub fn matching_tier(tiers: &[BillingTier], count: i32) -> Option<&BillingTier> {
+    tiers
+        .iter()
+        .filter(|t| t.min_count <= count && count <= t.max_count)
+        .min_by_key(|t| (t.max_count - t.min_count, t.id))
+}
+
+pub fn compute_billing_amounts(
+    billable_count: i32,
+    tiers: &[BillingTier],
+    retainer_fee: Option<Decimal>,
+    vat_rate: Decimal,
+) -> BillingAmounts {
+    let (rate_source, billing_rule_id, rate) = match matching_tier(tiers, billable_count) {
+        Some(tier) => (RateSource::BillingRule, Some(tier.id), tier.rate),
+        None => match retainer_fee {
+            Some(fee) => (RateSource::RetainerFee, None, fee),
+            None => (RateSource::None, None, Decimal::ZERO),
+        },
+    };
+
+    let rate = money(rate);
+    let count = Decimal::from(billable_count.max(0));
+    let vat_per_member = money(rate * vat_rate);
+    let rate_with_vat = rate + vat_per_member;
+
+    BillingAmounts {
+        billable_count,
+        rate_source,
+        billing_rule_id,
+        rate,
+        vat_rate,
+        vat_per_member,
+        rate_with_vat,
+        amount_before_vat: rate * count,
+        vat_amount: vat_per_member * count,
+        total_amount: rate_with_vat * count,
+    }
+}
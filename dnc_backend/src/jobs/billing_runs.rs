@@ -0,0 +1,328 @@
+//! HMO billing runs.
+//!
+//! A billing run is one generation of the HMO billing for a period; its hmo_billing_data and
+//! generated_report rows carry its id. A period has at most one run that is not void.
+//!
+//!   draft ──► finalized ──► void
+//!   draft ──► void
+//!
+//! A draft is regenerated in place: its rows are cleared and generated again under the same
+//! run. A finalized run is locked, and its statements are invoiced (see receivables). Voiding
+//! needs a reason and voids the run's invoices, unless one of them has been paid; the void run
+//! is kept for the record and a new draft for the same period is generated to supersede it.
+use std::collections::HashSet;
+
+use axum::http::StatusCode;
+use chrono::{NaiveDate, Utc};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DatabaseTransaction, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set, SqlErr, TransactionTrait,
+};
+use serde::Serialize;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::entities::{billing_run, generated_report, hmo_billing_data};
+use crate::jobs::hmo_billing::generate_billing_for_run;
+use crate::jobs::receivables;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BillingRunStatus {
+    Draft,
+    Finalized,
+    Void,
+}
+
+impl BillingRunStatus {
+    /// Stored in billing_run.status.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            BillingRunStatus::Draft => "draft",
+            BillingRunStatus::Finalized => "finalized",
+            BillingRunStatus::Void => "void",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "draft" => Some(BillingRunStatus::Draft),
+            "finalized" => Some(BillingRunStatus::Finalized),
+            "void" => Some(BillingRunStatus::Void),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BillingRunError {
+    #[error("Billing run {0} not found")]
+    NotFound(i32),
+
+    #[error("Billing run {id} is {status}; it cannot be {action}")]
+    WrongStatus {
+        id: i32,
+        status: String,
+        action: &'static str,
+    },
+
+    #[error("A reason is required to void a billing run")]
+    ReasonRequired,
+
+    #[error("Billing run {0} has invoices with payments recorded; it cannot be voided")]
+    InvoicesPaid(i32),
+
+    #[error("Another billing run for {0} to {1} was created at the same time; try again")]
+    Conflict(NaiveDate, NaiveDate),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+
+    #[error("Failed to generate the billing: {0}")]
+    Generation(#[from] anyhow::Error),
+}
+
+impl From<BillingRunError> for (StatusCode, String) {
+    fn from(e: BillingRunError) -> Self {
+        let status = match e {
+            BillingRunError::NotFound(_) => StatusCode::NOT_FOUND,
+            BillingRunError::WrongStatus { .. }
+            | BillingRunError::Conflict(..)
+            | BillingRunError::InvoicesPaid(_) => StatusCode::CONFLICT,
+            BillingRunError::ReasonRequired => StatusCode::BAD_REQUEST,
+            BillingRunError::Database(_) | BillingRunError::Generation(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, e.to_string())
+    }
+}
+
+fn status_of(run: &billing_run::Model) -> Option<BillingRunStatus> {
+    BillingRunStatus::parse(&run.status)
+}
+
+fn wrong_status(run: &billing_run::Model, action: &'static str) -> BillingRunError {
+    BillingRunError::WrongStatus {
+        id: run.id,
+        status: run.status.clone(),
+        action,
+    }
+}
+
+/// Generates the billing for the period: a new draft run, or the existing draft regenerated
+/// in place. Fails if the period's run is already finalized.
+pub async fn create_or_regenerate_run(
+    db: &DatabaseConnection,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    created_by: &str,
+) -> Result<billing_run::Model, BillingRunError> {
+    let txn = db.begin().await?;
+
+    let open_run = billing_run::Entity::find()
+        .filter(billing_run::Column::PeriodStart.eq(period_start))
+        .filter(billing_run::Column::PeriodEnd.eq(period_end))
+        .filter(billing_run::Column::Status.ne(BillingRunStatus::Void.as_str()))
+        .lock_exclusive()
+        .one(&txn)
+        .await?;
+
+    let (run, stale_files) = match open_run {
+        Some(run) if status_of(&run) == Some(BillingRunStatus::Draft) => regenerate_locked(&txn, run).await?,
+        Some(run) => return Err(wrong_status(&run, "regenerated")),
+        None => {
+            let superseded = billing_run::Entity::find()
+                .filter(billing_run::Column::PeriodStart.eq(period_start))
+                .filter(billing_run::Column::PeriodEnd.eq(period_end))
+                .filter(billing_run::Column::Status.eq(BillingRunStatus::Void.as_str()))
+                .order_by_desc(billing_run::Column::Id)
+                .one(&txn)
+                .await?;
+            let run = insert_draft(&txn, period_start, period_end, created_by, superseded.map(|r| r.id)).await?;
+            (generate_locked(&txn, run).await?, Vec::new())
+        }
+    };
+
+    txn.commit().await?;
+    remove_stale_files(&current_report_files(db, run.id).await?, stale_files);
+    Ok(run)
+}
+
+/// Regenerates a draft run in place.
+pub async fn regenerate_run(
+    db: &DatabaseConnection,
+    id: i32,
+) -> Result<billing_run::Model, BillingRunError> {
+    let txn = db.begin().await?;
+    let run = find_locked(&txn, id).await?;
+    if status_of(&run) != Some(BillingRunStatus::Draft) {
+        return Err(wrong_status(&run, "regenerated"));
+    }
+    let (run, stale_files) = regenerate_locked(&txn, run).await?;
+    txn.commit().await?;
+    remove_stale_files(&current_report_files(db, run.id).await?, stale_files);
+    Ok(run)
+}
+
+/// Locks a draft run so it can no longer be regenerated, and invoices its statements.
+pub async fn finalize_run(
+    db: &DatabaseConnection,
+    id: i32,
+    finalized_by: &str,
+) -> Result<billing_run::Model, BillingRunError> {
+    let txn = db.begin().await?;
+    let run = find_locked(&txn, id).await?;
+    if status_of(&run) != Some(BillingRunStatus::Draft) {
+        return Err(wrong_status(&run, "finalized"));
+    }
+
+    let mut am: billing_run::ActiveModel = run.into();
+    am.status = Set(BillingRunStatus::Finalized.as_str().to_string());
+    am.finalized_by = Set(Some(finalized_by.to_string()));
+    am.finalized_on = Set(Some(Utc::now().fixed_offset()));
+    let run = am.update(&txn).await?;
+    let invoices = receivables::issue_run_invoices(&txn, &run, finalized_by).await?;
+
+    txn.commit().await?;
+    info!(target: "jobs", "billing run {} finalized by {} with {} invoice(s)", run.id, finalized_by, invoices.len());
+    Ok(run)
+}
+
+/// Voids a run and generates the draft that supersedes it. Returns (voided, replacement).
+pub async fn void_run(
+    db: &DatabaseConnection,
+    id: i32,
+    reason: &str,
+    voided_by: &str,
+) -> Result<(billing_run::Model, billing_run::Model), BillingRunError> {
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err(BillingRunError::ReasonRequired);
+    }
+
+    let txn = db.begin().await?;
+    let run = find_locked(&txn, id).await?;
+    if status_of(&run) == Some(BillingRunStatus::Void) {
+        return Err(wrong_status(&run, "voided"));
+    }
+    if !receivables::paid_run_invoices(&txn, run.id).await?.is_empty() {
+        return Err(BillingRunError::InvoicesPaid(run.id));
+    }
+    receivables::void_run_invoices(&txn, run.id, voided_by).await?;
+
+    let mut am: billing_run::ActiveModel = run.into();
+    am.status = Set(BillingRunStatus::Void.as_str().to_string());
+    am.voided_by = Set(Some(voided_by.to_string()));
+    am.voided_on = Set(Some(Utc::now().fixed_offset()));
+    am.void_reason = Set(Some(reason.to_string()));
+    let voided = am.update(&txn).await?;
+
+    let replacement = insert_draft(&txn, voided.period_start, voided.period_end, voided_by, Some(voided.id)).await?;
+    let replacement = generate_locked(&txn, replacement).await?;
+
+    txn.commit().await?;
+    info!(target: "jobs",
+        "billing run {} voided by {} and superseded by run {}: {}",
+        voided.id, voided_by, replacement.id, reason
+    );
+    Ok((voided, replacement))
+}
+
+async fn find_locked(
+    txn: &DatabaseTransaction,
+    id: i32,
+) -> Result<billing_run::Model, BillingRunError> {
+    billing_run::Entity::find_by_id(id)
+        .lock_exclusive()
+        .one(txn)
+        .await?
+        .ok_or(BillingRunError::NotFound(id))
+}
+
+async fn insert_draft(
+    txn: &DatabaseTransaction,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    created_by: &str,
+    supersedes_run_id: Option<i32>,
+) -> Result<billing_run::Model, BillingRunError> {
+    billing_run::ActiveModel {
+        period_start: Set(period_start),
+        period_end: Set(period_end),
+        status: Set(BillingRunStatus::Draft.as_str().to_string()),
+        request_key: Set(Uuid::new_v4().to_string()),
+        created_by: Set(created_by.to_string()),
+        created_on: Set(Utc::now().fixed_offset()),
+        supersedes_run_id: Set(supersedes_run_id),
+        ..Default::default()
+    }
+        .insert(txn)
+        .await
+        .map_err(|e| match e.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => BillingRunError::Conflict(period_start, period_end),
+            _ => BillingRunError::Database(e),
+        })
+}
+
+/// Clears what a draft generated before and generates it again. Returns the run and the
+/// file names of its old spreadsheets.
+async fn regenerate_locked(
+    txn: &DatabaseTransaction,
+    run: billing_run::Model,
+) -> Result<(billing_run::Model, Vec<String>), BillingRunError> {
+    let old_files: Vec<String> = generated_report::Entity::find()
+        .filter(generated_report::Column::BillingRunId.eq(run.id))
+        .all(txn)
+        .await?
+        .into_iter()
+        .map(|r| r.file_name)
+        .collect();
+
+    generated_report::Entity::delete_many()
+        .filter(generated_report::Column::BillingRunId.eq(run.id))
+        .exec(txn)
+        .await?;
+    hmo_billing_data::Entity::delete_many()
+        .filter(hmo_billing_data::Column::BillingRunId.eq(run.id))
+        .exec(txn)
+        .await?;
+
+    Ok((generate_locked(txn, run).await?, old_files))
+}
+
+async fn generate_locked(
+    txn: &DatabaseTransaction,
+    run: billing_run::Model,
+) -> Result<billing_run::Model, BillingRunError> {
+    let totals = generate_billing_for_run(txn, &run).await?;
+
+    let mut am: billing_run::ActiveModel = run.into();
+    am.endorsement_count = Set(totals.endorsement_count);
+    am.report_count = Set(totals.report_count);
+    am.billable_count = Set(totals.billable_count);
+    am.amount_before_vat = Set(totals.amount_before_vat);
+    am.vat_amount = Set(totals.vat_amount);
+    am.total_amount = Set(totals.total_amount);
+    am.last_generated_on = Set(Some(Utc::now().fixed_offset()));
+    Ok(am.update(txn).await?)
+}
+
+/// The file names of the run's current spreadsheets.
+async fn current_report_files(db: &DatabaseConnection, run_id: i32) -> Result<HashSet<String>, DbErr> {
+    Ok(generated_report::Entity::find()
+        .filter(generated_report::Column::BillingRunId.eq(run_id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|r| r.file_name)
+        .collect())
+}
+
+/// Deletes a regenerated draft's old spreadsheets, except those the new generation rewrote.
+fn remove_stale_files(current: &HashSet<String>, stale_files: Vec<String>) {
+    for file_name in stale_files.into_iter().filter(|f| !current.contains(f)) {
+        let path = format!("generated_reports/{}", file_name);
+        if let Err(err) = std::fs::remove_file(&path) {
+            warn!(target: "jobs", "could not remove stale billing report {}: {}", path, err);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Quarterly BIR Form 2307 certificates for the tax withheld from dentist payouts.
+//!
+//! Tax is withheld when a payout is paid, so a certificate covers the payout items whose
+//! paid_on falls in the quarter (Asia/Manila). It is issued to the dentist clinic, the payee,
+//! with one line per ATC giving the income payments of each month of the quarter and the tax
+//! withheld. The certificate is written from "BIR_2307_Template.xlsx" and recorded in
+//! bir_2307_certificate; generating it again replaces both.
+use std::collections::{BTreeMap, HashMap};
+
+use axum::http::StatusCode;
+use chrono::{Datelike, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use sea_orm::sea_query::OnConflict;
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder, Set};
+use serde::Serialize;
+use tracing::info;
+
+use crate::business_calendar;
+use crate::entities::{
+    app_config, bir_2307_certificate, dental_clinic, dentist_payout_item, dentist_payout_remittance,
+};
+
+/// Template lines for ATCs; more are inserted above the total row when needed.
+const TEMPLATE_LINE_COUNT: u32 = 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CertificateError {
+    #[error("Quarter must be from 1 to 4, not {0}")]
+    InvalidQuarter(u32),
+
+    #[error("BIR 2307 certificate {0} not found")]
+    NotFound(i32),
+
+    #[error("Failed to write the certificate: {0}")]
+    Spreadsheet(String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+impl From<CertificateError> for (StatusCode, String) {
+    fn from(e: CertificateError) -> Self {
+        let status = match e {
+            CertificateError::InvalidQuarter(_) => StatusCode::BAD_REQUEST,
+            CertificateError::NotFound(_) => StatusCode::NOT_FOUND,
+            CertificateError::Spreadsheet(_) | CertificateError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, e.to_string())
+    }
+}
+
+/// The first and last day of a quarter.
+pub fn quarter_bounds(year: i32, quarter: u32) -> Result<(NaiveDate, NaiveDate), CertificateError> {
+    if !(1..=4).contains(&quarter) {
+        return Err(CertificateError::InvalidQuarter(quarter));
+    }
+    let first_month = (quarter - 1) * 3 + 1;
+    let start = NaiveDate::from_ymd_opt(year, first_month, 1).expect("first day of a quarter");
+    let next = if quarter == 4 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, first_month + 3, 1)
+    }
+        .expect("first day of the next quarter");
+    Ok((start, next.pred_opt().expect("last day of a quarter")))
+}
+
+/// Tax withheld from one paid payout item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithholdingEntry {
+    pub paid_date: NaiveDate,
+    pub atc_code: String,
+    pub tax_base: Decimal,
+    pub withheld_amount: Decimal,
+}
+
+/// One ATC line of Part III.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Bir2307Line {
+    pub atc_code: String,
+    /// income payments in the 1st, 2nd and 3rd month of the quarter
+    pub monthly_income: [Decimal; 3],
+    pub income_total: Decimal,
+    pub withheld_amount: Decimal,
+}
+
+/// Totals a payee's entries for the quarter starting on quarter_start by ATC and month.
+/// Entries outside the quarter are ignored.
+pub fn aggregate_quarter(entries: &[WithholdingEntry], quarter_start: NaiveDate) -> Vec<Bir2307Line> {
+    let mut by_atc: BTreeMap<&str, Bir2307Line> = BTreeMap::new();
+    for entry in entries {
+        let month_index = (entry.paid_date.year() - quarter_start.year()) * 12
+            + entry.paid_date.month() as i32
+            - quarter_start.month() as i32;
+        if !(0..3).contains(&month_index) {
+            continue;
+        }
+        let line = by_atc.entry(entry.atc_code.as_str()).or_insert_with(|| Bir2307Line {
+            atc_code: entry.atc_code.clone(),
+            ..Default::default()
+        });
+        line.monthly_income[month_index as usize] += entry.tax_base;
+        line.income_total += entry.tax_base;
+        line.withheld_amount += entry.withheld_amount;
+    }
+    by_atc.into_values().collect()
+}
+
+/// Generates the quarter's certificates for every clinic with tax withheld from its payouts
+/// paid in the quarter, or for one clinic.
+pub async fn generate_bir_2307_certificates(
+    db: &DatabaseConnection,
+    year: i32,
+    quarter: u32,
+    dental_clinic_id: Option<i32>,
+    generated_by: &str,
+) -> Result<Vec<bir_2307_certificate::Model>, CertificateError> {
+    let (start, end) = quarter_bounds(year, quarter)?;
+    let after_end = end.succ_opt().expect("day after the quarter");
+
+    // ---1. the paid items of the quarter, with the ATC of their remittance
+    let mut query = dentist_payout_item::Entity::find()
+        .find_also_related(dentist_payout_remittance::Entity)
+        .filter(dentist_payout_item::Column::PaidOn.gte(business_calendar::start_of_day(start)))
+        .filter(dentist_payout_item::Column::PaidOn.lt(business_calendar::start_of_day(after_end)));
+    if let Some(dental_clinic_id) = dental_clinic_id {
+        query = query.filter(dentist_payout_item::Column::DentalClinicId.eq(dental_clinic_id));
+    }
+    let rows = query
+        .order_by_asc(dentist_payout_item::Column::DentalClinicId)
+        .order_by_asc(dentist_payout_item::Column::PaidOn)
+        .all(db)
+        .await?;
+
+    let mut entries_by_clinic: BTreeMap<i32, Vec<WithholdingEntry>> = BTreeMap::new();
+    for (item, remittance) in rows {
+        let Some(atc_code) = remittance.and_then(|r| r.atc_code) else { continue };
+        let Some(paid_on) = item.paid_on else { continue };
+        entries_by_clinic
+            .entry(item.dental_clinic_id)
+            .or_default()
+            .push(WithholdingEntry {
+                paid_date: business_calendar::date_of(&paid_on),
+                atc_code,
+                tax_base: item.tax_base,
+                withheld_amount: item.withheld_amount,
+            });
+    }
+    if entries_by_clinic.is_empty() {
+        info!(target: "jobs", "generate_bir_2307_certificates() found no tax withheld in {}Q{}", year, quarter);
+        return Ok(Vec::new());
+    }
+
+    // ---2. one certificate per clinic
+    let payor = load_payor(db).await?;
+    let clinics: HashMap<i32, dental_clinic::Model> = dental_clinic::Entity::find()
+        .filter(dental_clinic::Column::Id.is_in(entries_by_clinic.keys().copied()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|c| (c.id, c))
+        .collect();
+
+    let mut certificates = Vec::new();
+    for (clinic_id, entries) in entries_by_clinic {
+        let Some(clinic) = clinics.get(&clinic_id) else { continue };
+        let lines = aggregate_quarter(&entries, start);
+        let income_total: Decimal = lines.iter().map(|l| l.income_total).sum();
+        let withheld_total: Decimal = lines.iter().map(|l| l.withheld_amount).sum();
+        let file_name = format!("BIR_2307_{}_{}Q{}.xlsx", clinic_id, year, quarter);
+        write_certificate_to_spreadsheet(clinic, &payor, &lines, start, end, &file_name)?;
+
+        // one statement, so two generations for the same quarter cannot both insert
+        let certificate = bir_2307_certificate::Entity::insert(bir_2307_certificate::ActiveModel {
+            dental_clinic_id: Set(clinic_id),
+            year: Set(year),
+            quarter: Set(quarter as i32),
+            income_total: Set(income_total),
+            withheld_total: Set(withheld_total),
+            file_name: Set(file_name),
+            generated_by: Set(generated_by.to_string()),
+            generated_on: Set(Utc::now().fixed_offset()),
+            ..Default::default()
+        })
+            .on_conflict(
+                OnConflict::columns([
+                    bir_2307_certificate::Column::DentalClinicId,
+                    bir_2307_certificate::Column::Year,
+                    bir_2307_certificate::Column::Quarter,
+                ])
+                .update_columns([
+                    bir_2307_certificate::Column::IncomeTotal,
+                    bir_2307_certificate::Column::WithheldTotal,
+                    bir_2307_certificate::Column::FileName,
+                    bir_2307_certificate::Column::GeneratedBy,
+                    bir_2307_certificate::Column::GeneratedOn,
+                ])
+                .to_owned(),
+            )
+            .exec_with_returning(db)
+            .await?;
+        certificates.push(certificate);
+    }
+
+    info!(target: "jobs",
+        "generate_bir_2307_certificates() generated {} certificate(s) for {}Q{}",
+        certificates.len(), year, quarter
+    );
+    Ok(certificates)
+}
+
+/// The withholding agent, from app_config's bir_2307_payor_* keys.
+#[derive(Debug, Clone, Default)]
+struct Payor {
+    tin: String,
+    name: String,
+    address: String,
+}
+
+async fn load_payor(db: &DatabaseConnection) -> Result<Payor, DbErr> {
+    let config: HashMap<String, String> = app_config::Entity::find()
+        .filter(app_config::Column::Key.starts_with("bir_2307_payor_"))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|row| (row.key, row.value.trim().to_string()))
+        .collect();
+    let value = |key: &str| config.get(key).cloned().unwrap_or_default();
+    Ok(Payor {
+        tin: value("bir_2307_payor_tin"),
+        name: value("bir_2307_payor_name"),
+        address: value("bir_2307_payor_address"),
+    })
+}
+
+/// write_certificate_to_spreadsheet() fills BIR_2307_Template.xlsx: the period, Part I (payee),
+/// Part II (payor) and a Part III line per ATC with the totals below.
+fn write_certificate_to_spreadsheet(
+    clinic: &dental_clinic::Model,
+    payor: &Payor,
+    lines: &[Bir2307Line],
+    start: NaiveDate,
+    end: NaiveDate,
+    file_name: &str,
+) -> Result<(), CertificateError> {
+    //----1. Load the template
+    let template_path = "billing_templates/BIR_2307_Template.xlsx";
+    let mut book = umya_spreadsheet::reader::xlsx::read(template_path)
+        .map_err(|e| CertificateError::Spreadsheet(format!("Failed to read BIR_2307_Template.xlsx: {}", e)))?;
+    let sheet = book
+        .get_sheet_mut(&0)
+        .ok_or_else(|| CertificateError::Spreadsheet("BIR 2307 template has no worksheet".to_string()))?;
+
+    //----2. Period, payee and payor
+    sheet.get_cell_mut("D4").set_value(start.format("%m/%d/%Y").to_string());
+    sheet.get_cell_mut("F4").set_value(end.format("%m/%d/%Y").to_string());
+
+    let payee_name = clinic
+        .acct_taxpayer_name
+        .clone()
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| clinic.name.clone());
+    sheet.get_cell_mut("D7").set_value(clinic.acct_tin.clone().unwrap_or_default());
+    sheet.get_cell_mut("D8").set_value(payee_name);
+    sheet.get_cell_mut("D9").set_value(clinic.address.as_str());
+    sheet.get_cell_mut("D10").set_value(clinic.zip_code.clone().unwrap_or_default());
+
+    sheet.get_cell_mut("D13").set_value(payor.tin.as_str());
+    sheet.get_cell_mut("D14").set_value(payor.name.as_str());
+    sheet.get_cell_mut("D15").set_value(payor.address.as_str());
+
+    //----3. Part III lines
+    let first_line_row: u32 = 19;
+    let line_count = lines.len() as u32;
+    if line_count > TEMPLATE_LINE_COUNT {
+        let extra = line_count - TEMPLATE_LINE_COUNT;
+        sheet.insert_new_row(&(first_line_row + TEMPLATE_LINE_COUNT), &extra);
+    }
+    for (index, line) in lines.iter().enumerate() {
+        let excel_row = first_line_row + index as u32;
+        sheet.get_cell_mut((1, excel_row)).set_value("Professional fees");
+        sheet.get_cell_mut((2, excel_row)).set_value(line.atc_code.as_str());
+        for (month, income) in line.monthly_income.iter().enumerate() {
+            sheet.get_cell_mut((3 + month as u32, excel_row)).set_value_number(to_f64(*income));
+        }
+        sheet.get_cell_mut((6, excel_row)).set_value_number(to_f64(line.income_total));
+        sheet.get_cell_mut((7, excel_row)).set_value_number(to_f64(line.withheld_amount));
+    }
+
+    //----4. Totals
+    let total_row = first_line_row + line_count.max(TEMPLATE_LINE_COUNT);
+    for month in 0..3 {
+        let total: Decimal = lines.iter().map(|l| l.monthly_income[month]).sum();
+        sheet.get_cell_mut((3 + month as u32, total_row)).set_value_number(to_f64(total));
+    }
+    let income_total: Decimal = lines.iter().map(|l| l.income_total).sum();
+    let withheld_total: Decimal = lines.iter().map(|l| l.withheld_amount).sum();
+    sheet.get_cell_mut((6, total_row)).set_value_number(to_f64(income_total));
+    sheet.get_cell_mut((7, total_row)).set_value_number(to_f64(withheld_total));
+
+    //----5. Save
+    let full_filename = format!("generated_reports/{}", file_name);
+    info!(target: "jobs", " writing BIR 2307 for {} to {}", clinic.name, full_filename);
+    umya_spreadsheet::writer::xlsx::write(&book, &full_filename)
+        .map_err(|e| CertificateError::Spreadsheet(e.to_string()))
+}
+
+fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
@@ -0,0 +1,381 @@
+//! Dated dentist contract rates.
+//!
+//! A contract's rate for a dental service has versions in dentist_contract_service_rates. Each
+//! is in force from effective_from through effective_to, or indefinitely when effective_to is
+//! null. A verification is priced at the version in force on its date_service_performed, so
+//! an old period is always priced at the rates of the time.
+//!
+//! Rates are changed by scheduling a new version from a date. The version in force on that
+//! date is ended the day before, and the new one runs until the next scheduled version, if
+//! any. Past versions are never changed: a change can only be scheduled from today on, except
+//! for a service the contract has no rate for yet. A scheduled version that has not started
+//! can be cancelled, which gives its days back to the version before it.
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use axum::http::StatusCode;
+use chrono::{Days, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseTransaction, DbErr, EntityTrait,
+    QueryFilter, QueryOrder, QuerySelect, Set,
+};
+use serde::Serialize;
+
+use crate::entities::{dentist_contract, dentist_contract_service_rates};
+use crate::jobs::billing_amounts::money;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RateScheduleError {
+    #[error("Dentist contract {0} not found")]
+    ContractNotFound(i32),
+
+    #[error("Contract rate {0} not found")]
+    RateNotFound(i32),
+
+    #[error("Invalid rate for service_id {service_id}: {rate}")]
+    InvalidRate { service_id: i32, rate: Decimal },
+
+    #[error("service_id {0} is listed more than once")]
+    DuplicateService(i32),
+
+    #[error("Rates can only be changed from today ({today}) on, not from {effective_from}")]
+    PastDate { effective_from: NaiveDate, today: NaiveDate },
+
+    #[error("Contract rate {0} is already in force and cannot be cancelled")]
+    NotScheduled(i32),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+impl From<RateScheduleError> for (StatusCode, String) {
+    fn from(e: RateScheduleError) -> Self {
+        let status = match e {
+            RateScheduleError::ContractNotFound(_) | RateScheduleError::RateNotFound(_) => StatusCode::NOT_FOUND,
+            RateScheduleError::InvalidRate { .. }
+            | RateScheduleError::DuplicateService(_)
+            | RateScheduleError::PastDate { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            RateScheduleError::NotScheduled(_) => StatusCode::CONFLICT,
+            RateScheduleError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, e.to_string())
+    }
+}
+
+/// A version of a service's rate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RateVersion {
+    pub id: i32,
+    pub rate: Decimal,
+    pub effective_from: NaiveDate,
+    pub effective_to: Option<NaiveDate>,
+}
+
+impl RateVersion {
+    pub fn covers(&self, date: NaiveDate) -> bool {
+        self.effective_from <= date && self.effective_to.is_none_or(|to| date <= to)
+    }
+}
+
+impl From<&dentist_contract_service_rates::Model> for RateVersion {
+    fn from(model: &dentist_contract_service_rates::Model) -> Self {
+        RateVersion {
+            id: model.id,
+            rate: model.rate,
+            effective_from: model.effective_from,
+            effective_to: model.effective_to,
+        }
+    }
+}
+
+/// The rate in force on the date, from a service's versions.
+pub fn rate_on(versions: &[RateVersion], date: NaiveDate) -> Option<Decimal> {
+    versions.iter().find(|v| v.covers(date)).map(|v| v.rate)
+}
+
+
+// region: Planning
+
+/// A change to a service's versions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RateChange {
+    Insert {
+        rate: Decimal,
+        effective_from: NaiveDate,
+        effective_to: Option<NaiveDate>,
+    },
+    SetRate { id: i32, rate: Decimal },
+    SetEffectiveTo { id: i32, effective_to: Option<NaiveDate> },
+    Delete { id: i32 },
+}
+
+/// The changes that put `rate` in force for a service from `effective_from`, or that end its
+/// rate then when `rate` is None.
+pub fn plan_rate_change(
+    versions: &[RateVersion],
+    effective_from: NaiveDate,
+    rate: Option<Decimal>,
+    today: NaiveDate,
+) -> Result<Vec<RateChange>, RateScheduleError> {
+    if effective_from < today && !versions.is_empty() {
+        return Err(RateScheduleError::PastDate { effective_from, today });
+    }
+    let rate = rate.map(money);
+    let day_before = |date: NaiveDate| date.checked_sub_days(Days::new(1));
+
+    // a version starting that day is replaced
+    if let Some(same_day) = versions.iter().find(|v| v.effective_from == effective_from) {
+        return Ok(match rate {
+            Some(rate) if rate == same_day.rate => Vec::new(),
+            Some(rate) => vec![RateChange::SetRate { id: same_day.id, rate }],
+            None => vec![RateChange::Delete { id: same_day.id }],
+        });
+    }
+
+    let mut changes = Vec::new();
+    if let Some(current) = versions.iter().find(|v| v.covers(effective_from)) {
+        if rate == Some(current.rate) {
+            return Ok(changes);
+        }
+        changes.push(RateChange::SetEffectiveTo {
+            id: current.id,
+            effective_to: day_before(effective_from),
+        });
+    }
+    if let Some(rate) = rate {
+        let next_from = versions
+            .iter()
+            .map(|v| v.effective_from)
+            .filter(|from| *from > effective_from)
+            .min();
+        changes.push(RateChange::Insert {
+            rate,
+            effective_from,
+            effective_to: next_from.and_then(day_before),
+        });
+    }
+    Ok(changes)
+}
+
+/// The changes that end a service's rate from `effective_from` for good, for a service left
+/// out of a full rate list: the version in force then ends the day before, and every version
+/// starting that day or later is deleted.
+pub fn plan_rate_end(
+    versions: &[RateVersion],
+    effective_from: NaiveDate,
+    today: NaiveDate,
+) -> Result<Vec<RateChange>, RateScheduleError> {
+    let current = versions
+        .iter()
+        .find(|v| v.effective_from < effective_from && v.covers(effective_from));
+    let later: Vec<&RateVersion> = versions.iter().filter(|v| v.effective_from >= effective_from).collect();
+    if current.is_none() && later.is_empty() {
+        return Ok(Vec::new());
+    }
+    if effective_from < today {
+        return Err(RateScheduleError::PastDate { effective_from, today });
+    }
+
+    let mut changes = Vec::new();
+    if let Some(current) = current {
+        changes.push(RateChange::SetEffectiveTo {
+            id: current.id,
+            effective_to: effective_from.checked_sub_days(Days::new(1)),
+        });
+    }
+    changes.extend(later.into_iter().map(|v| RateChange::Delete { id: v.id }));
+    Ok(changes)
+}
+// endregion: Planning
+
+
+// region: Scheduling
+
+/// Puts the rates in force for the contract's services from `effective_from`. With
+/// `end_unlisted`, the rates of the services not listed end the day before, scheduled versions
+/// included.
+pub async fn schedule_contract_rates(
+    txn: &DatabaseTransaction,
+    dentist_contract_id: i32,
+    effective_from: NaiveDate,
+    rates: &[(i32, Decimal)],
+    end_unlisted: bool,
+    created_by: &str,
+    today: NaiveDate,
+) -> Result<usize, RateScheduleError> {
+    let mut seen = HashSet::new();
+    for (service_id, rate) in rates {
+        if *rate < Decimal::ZERO {
+            return Err(RateScheduleError::InvalidRate { service_id: *service_id, rate: *rate });
+        }
+        if !seen.insert(*service_id) {
+            return Err(RateScheduleError::DuplicateService(*service_id));
+        }
+    }
+
+    // one schedule at a time per contract
+    dentist_contract::Entity::find_by_id(dentist_contract_id)
+        .lock_exclusive()
+        .one(txn)
+        .await?
+        .ok_or(RateScheduleError::ContractNotFound(dentist_contract_id))?;
+
+    let mut by_service: BTreeMap<i32, Vec<RateVersion>> = BTreeMap::new();
+    for model in dentist_contract_service_rates::Entity::find()
+        .filter(dentist_contract_service_rates::Column::DentistContractId.eq(dentist_contract_id))
+        .all(txn)
+        .await?
+    {
+        by_service.entry(model.service_id).or_default().push(RateVersion::from(&model));
+    }
+
+    let mut planned: Vec<(i32, Vec<RateChange>)> = Vec::new();
+    for (service_id, rate) in rates {
+        let versions = by_service.get(service_id).map(Vec::as_slice).unwrap_or_default();
+        planned.push((*service_id, plan_rate_change(versions, effective_from, Some(*rate), today)?));
+    }
+    if end_unlisted {
+        for (service_id, versions) in by_service.iter().filter(|(id, _)| !seen.contains(*id)) {
+            planned.push((*service_id, plan_rate_end(versions, effective_from, today)?));
+        }
+    }
+
+    let mut change_count = 0;
+    for (service_id, changes) in planned {
+        for change in changes {
+            apply_change(txn, dentist_contract_id, service_id, change, created_by).await?;
+            change_count += 1;
+        }
+    }
+    Ok(change_count)
+}
+
+async fn apply_change(
+    txn: &DatabaseTransaction,
+    dentist_contract_id: i32,
+    service_id: i32,
+    change: RateChange,
+    created_by: &str,
+) -> Result<(), DbErr> {
+    match change {
+        RateChange::Insert { rate, effective_from, effective_to } => {
+            dentist_contract_service_rates::ActiveModel {
+                dentist_contract_id: Set(dentist_contract_id),
+                service_id: Set(service_id),
+                rate: Set(rate),
+                effective_from: Set(effective_from),
+                effective_to: Set(effective_to),
+                created_by: Set(Some(created_by.to_string())),
+                created_on: Set(Utc::now().fixed_offset()),
+                ..Default::default()
+            }
+                .insert(txn)
+                .await?;
+        }
+        RateChange::SetRate { id, rate } => {
+            dentist_contract_service_rates::ActiveModel {
+                id: Set(id),
+                rate: Set(rate),
+                created_by: Set(Some(created_by.to_string())),
+                ..Default::default()
+            }
+                .update(txn)
+                .await?;
+        }
+        RateChange::SetEffectiveTo { id, effective_to } => {
+            dentist_contract_service_rates::ActiveModel {
+                id: Set(id),
+                effective_to: Set(effective_to),
+                ..Default::default()
+            }
+                .update(txn)
+                .await?;
+        }
+        RateChange::Delete { id } => {
+            dentist_contract_service_rates::Entity::delete_by_id(id).exec(txn).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Cancels a version that has not started yet. The version before it runs on in its place.
+pub async fn cancel_scheduled_rate(
+    txn: &DatabaseTransaction,
+    dentist_contract_id: i32,
+    rate_id: i32,
+    today: NaiveDate,
+) -> Result<(), RateScheduleError> {
+    dentist_contract::Entity::find_by_id(dentist_contract_id)
+        .lock_exclusive()
+        .one(txn)
+        .await?
+        .ok_or(RateScheduleError::ContractNotFound(dentist_contract_id))?;
+    let scheduled = dentist_contract_service_rates::Entity::find_by_id(rate_id)
+        .filter(dentist_contract_service_rates::Column::DentistContractId.eq(dentist_contract_id))
+        .one(txn)
+        .await?
+        .ok_or(RateScheduleError::RateNotFound(rate_id))?;
+    if scheduled.effective_from <= today {
+        return Err(RateScheduleError::NotScheduled(rate_id));
+    }
+
+    let previous = dentist_contract_service_rates::Entity::find()
+        .filter(dentist_contract_service_rates::Column::DentistContractId.eq(dentist_contract_id))
+        .filter(dentist_contract_service_rates::Column::ServiceId.eq(scheduled.service_id))
+        .filter(dentist_contract_service_rates::Column::EffectiveFrom.lt(scheduled.effective_from))
+        .order_by_desc(dentist_contract_service_rates::Column::EffectiveFrom)
+        .one(txn)
+        .await?;
+    dentist_contract_service_rates::Entity::delete_by_id(rate_id).exec(txn).await?;
+    if let Some(previous) = previous
+        && previous.effective_to == scheduled.effective_from.checked_sub_days(Days::new(1))
+    {
+        dentist_contract_service_rates::ActiveModel {
+            id: Set(previous.id),
+            effective_to: Set(scheduled.effective_to),
+            ..Default::default()
+        }
+            .update(txn)
+            .await?;
+    }
+    Ok(())
+}
+// endregion: Scheduling
+
+
+// region: Lookup
+
+/// Rate versions by contract and service.
+#[derive(Debug, Clone, Default)]
+pub struct ContractRates {
+    versions: HashMap<(i32, i32), Vec<RateVersion>>,
+}
+
+impl ContractRates {
+    /// The contract's rate for the service on the date.
+    pub fn rate_on(&self, dentist_contract_id: i32, service_id: i32, date: NaiveDate) -> Option<Decimal> {
+        self.versions
+            .get(&(dentist_contract_id, service_id))
+            .and_then(|versions| rate_on(versions, date))
+    }
+}
+
+/// Loads the rate versions of the contracts.
+pub async fn load_contract_rates<C: ConnectionTrait>(
+    db: &C,
+    dentist_contract_ids: impl IntoIterator<Item = i32>,
+) -> Result<ContractRates, DbErr> {
+    let mut versions: HashMap<(i32, i32), Vec<RateVersion>> = HashMap::new();
+    for model in dentist_contract_service_rates::Entity::find()
+        .filter(dentist_contract_service_rates::Column::DentistContractId.is_in(dentist_contract_ids))
+        .all(db)
+        .await?
+    {
+        versions
+            .entry((model.dentist_contract_id, model.service_id))
+            .or_default()
+            .push(RateVersion::from(&model));
+    }
+    Ok(ContractRates { versions })
+}
+// endregion: Lookup
@@ -0,0 +1,653 @@
+//! Fee-for-service payouts to dentists.
+//!
+//! A payout batch collects the reconciled verifications whose service was performed in the
+//! period, done by dentists on a fee-per-service contract (any contract but Flat Fee), that
+//! are not in an earlier batch. Each is priced at the dentist's contract rate for the dental
+//! service in force on the date it was performed (see contract_rates), or at the approved cost
+//! for high-end services. The batch is split into one remittance per dentist and clinic, each
+//! written to its own spreadsheet.
+//!
+//! The clinic is the payee, so the tax withheld from each verification's payout follows the
+//! clinic's tax profile (see withholding_tax). Clinics without a complete tax profile are left
+//! out of the batch and reported, so they are picked up once it is filled in.
+//!
+//! Every verification in a batch gets a dentist_payout_item row, whose unique verification_id
+//! keeps a verification from being paid out twice, even by two batches at once. Items are
+//! then marked paid one at a time or a whole remittance at once; an item is only ever marked
+//! paid once.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use axum::http::StatusCode;
+use chrono::{NaiveDate, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use sea_orm::sea_query::{Expr, Query};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, JoinType, QueryFilter,
+    QueryOrder, QuerySelect, RelationTrait, Set, TransactionTrait,
+};
+use serde::Serialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::entities::{
+    dental_clinic, dental_service, dentist, dentist_payout_batch,
+    dentist_payout_item, dentist_payout_remittance, high_end_verification_information,
+    master_list_member, verification,
+};
+use crate::jobs::billing_amounts::{money, HIGH_END_SERVICE_TYPE_ID};
+use crate::jobs::contract_rates::load_contract_rates;
+use crate::jobs::withholding_tax::{
+    compute_payout_tax, load_payout_tax_rates, PayeeTaxProfile, PayoutTax, TaxConfigError,
+};
+use crate::verification_state::VerificationStatus;
+
+/// dentist_contract.id of the Flat Fee contract. Those dentists are paid retainers instead.
+pub const FLAT_FEE_CONTRACT_ID: i32 = 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PayoutError {
+    #[error("Payout batch {0} not found")]
+    BatchNotFound(i32),
+
+    #[error("Payout remittance {0} not found")]
+    RemittanceNotFound(i32),
+
+    #[error("Payout item {0} not found")]
+    ItemNotFound(i32),
+
+    #[error("Payout item {0} has already been paid")]
+    AlreadyPaid(i32),
+
+    #[error("Every item of remittance {0} has already been paid")]
+    RemittanceAlreadyPaid(i32),
+
+    #[error("Could not record the batch; another batch may have taken the same verifications: {0}")]
+    Conflict(DbErr),
+
+    #[error("Failed to write the remittance: {0}")]
+    Spreadsheet(String),
+
+    #[error(transparent)]
+    TaxConfig(#[from] TaxConfigError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+impl From<PayoutError> for (StatusCode, String) {
+    fn from(e: PayoutError) -> Self {
+        let status = match e {
+            PayoutError::BatchNotFound(_)
+            | PayoutError::RemittanceNotFound(_)
+            | PayoutError::ItemNotFound(_) => StatusCode::NOT_FOUND,
+            PayoutError::AlreadyPaid(_)
+            | PayoutError::RemittanceAlreadyPaid(_)
+            | PayoutError::Conflict(_) => StatusCode::CONFLICT,
+            PayoutError::Spreadsheet(_)
+            | PayoutError::TaxConfig(_)
+            | PayoutError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, e.to_string())
+    }
+}
+
+
+// region: Pricing
+
+/// What a verification is paid out at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PayoutPrice {
+    pub rate: Option<Decimal>,
+    pub approved_cost: Option<Decimal>,
+    pub amount: Decimal,
+}
+
+/// Prices a verification at the approved cost for high-end services that have one, otherwise
+/// at the contract rate. Returns None when there is neither.
+pub fn price_payout(
+    rate: Option<Decimal>,
+    approved_cost: Option<Decimal>,
+    is_high_end: bool,
+) -> Option<PayoutPrice> {
+    let rate = rate.map(money);
+    let approved_cost = approved_cost.filter(|_| is_high_end).map(money);
+    let amount = approved_cost.or(rate)?;
+    Some(PayoutPrice {
+        rate,
+        approved_cost,
+        amount,
+    })
+}
+// endregion: Pricing
+
+
+// region: Batches
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RemittanceSummary {
+    pub remittance_id: i32,
+    pub dentist_id: i32,
+    pub dentist_name: String,
+    pub dental_clinic_id: i32,
+    pub dental_clinic_name: String,
+    pub item_count: i32,
+    pub gross_amount: Decimal,
+    pub atc_code: String,
+    #[serde(flatten)]
+    pub tax: PayoutTax,
+    pub file_name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PayoutBatchSummary {
+    /// None if there was nothing to pay out
+    pub batch_id: Option<i32>,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub item_count: i32,
+    pub gross_amount: Decimal,
+    pub withheld_amount: Decimal,
+    pub net_amount: Decimal,
+    pub remittances: Vec<RemittanceSummary>,
+    /// reconciled in the period but with no contract rate or approved cost; not paid out
+    pub unpriced_verification_ids: Vec<i32>,
+    /// clinics with no tax type, no tax classification, or no withholding rate for it; their
+    /// verifications are not paid out
+    pub clinics_missing_tax_profile: Vec<i32>,
+}
+
+struct PayoutLine {
+    verification: verification::Model,
+    member: Option<master_list_member::Model>,
+    service_name: String,
+    price: PayoutPrice,
+    tax: PayoutTax,
+}
+
+/// One remittance's worth of lines, keyed by (dentist_id, dental_clinic_id).
+struct RemittanceGroup {
+    dentist_name: String,
+    dental_clinic_name: String,
+    profile: PayeeTaxProfile,
+    lines: Vec<PayoutLine>,
+}
+
+impl RemittanceGroup {
+    fn gross_amount(&self) -> Decimal {
+        self.lines.iter().map(|l| l.price.amount).sum()
+    }
+
+    fn tax(&self) -> PayoutTax {
+        self.lines.iter().map(|l| l.tax).sum()
+    }
+}
+
+/// What load_payout_groups found.
+struct PayoutGroups {
+    groups: BTreeMap<(i32, i32), RemittanceGroup>,
+    unpriced_verification_ids: Vec<i32>,
+    clinics_missing_tax_profile: Vec<i32>,
+}
+
+/// Creates a payout batch for the unpaid reconciled verifications of fee-per-service dentists,
+/// or of one dentist, whose service was performed from period_start to period_end.
+pub async fn create_payout_batch(
+    db: &DatabaseConnection,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    dentist_id: Option<i32>,
+    created_by: &str,
+) -> Result<PayoutBatchSummary, PayoutError> {
+    let request_key = Uuid::new_v4().to_string();
+    info!(target: "jobs",
+        "create_payout_batch() started with request_key {} for period {}-{}",
+        request_key,
+        period_start.format("%m/%d/%Y"),
+        period_end.format("%m/%d/%Y")
+    );
+
+    // ---1. price the verifications and compute the tax
+    let PayoutGroups {
+        groups,
+        unpriced_verification_ids,
+        clinics_missing_tax_profile,
+    } = load_payout_groups(db, period_start, period_end, dentist_id).await?;
+    let mut summary = PayoutBatchSummary {
+        batch_id: None,
+        period_start,
+        period_end,
+        item_count: 0,
+        gross_amount: Decimal::ZERO,
+        withheld_amount: Decimal::ZERO,
+        net_amount: Decimal::ZERO,
+        remittances: Vec::new(),
+        unpriced_verification_ids,
+        clinics_missing_tax_profile,
+    };
+    if groups.is_empty() {
+        info!(target: "jobs", "create_payout_batch() found nothing to pay out");
+        return Ok(summary);
+    }
+    let batch_tax: PayoutTax = groups.values().map(|g| g.tax()).sum();
+    summary.item_count = groups.values().map(|g| g.lines.len() as i32).sum();
+    summary.gross_amount = groups.values().map(|g| g.gross_amount()).sum();
+    summary.withheld_amount = batch_tax.withheld_amount;
+    summary.net_amount = batch_tax.net_amount;
+
+    // ---2. record the batch. The files are written before the commit so a failed write
+    // leaves nothing recorded.
+    let now = Utc::now().fixed_offset();
+    let txn = db.begin().await?;
+    let batch = dentist_payout_batch::ActiveModel {
+        period_start: Set(period_start),
+        period_end: Set(period_end),
+        request_key: Set(request_key),
+        item_count: Set(summary.item_count),
+        gross_amount: Set(summary.gross_amount),
+        withheld_amount: Set(summary.withheld_amount),
+        net_amount: Set(summary.net_amount),
+        created_by: Set(created_by.to_string()),
+        created_on: Set(now),
+        ..Default::default()
+    }
+        .insert(&txn)
+        .await?;
+
+    for ((the_dentist_id, dental_clinic_id), group) in &groups {
+        let gross_amount = group.gross_amount();
+        let tax = group.tax();
+        let profile = &group.profile;
+        let file_name = format!(
+            "Remittance_{}_{}_{}_{}.xlsx",
+            batch.id,
+            the_dentist_id,
+            dental_clinic_id,
+            period_end.format("%Y-%m-%d")
+        );
+        let remittance = dentist_payout_remittance::ActiveModel {
+            batch_id: Set(batch.id),
+            dentist_id: Set(*the_dentist_id),
+            dental_clinic_id: Set(*dental_clinic_id),
+            item_count: Set(group.lines.len() as i32),
+            gross_amount: Set(gross_amount),
+            file_name: Set(file_name.clone()),
+            tax_type_id: Set(Some(profile.tax_type_id)),
+            tax_classification_id: Set(Some(profile.tax_classification_id)),
+            vat_registered: Set(profile.vat_registered),
+            business_tax_rate: Set(profile.business_tax_rate),
+            ewt_rate: Set(profile.ewt_rate),
+            atc_code: Set(Some(profile.atc_code.clone())),
+            tax_base: Set(tax.tax_base),
+            business_tax_amount: Set(tax.business_tax_amount),
+            withheld_amount: Set(tax.withheld_amount),
+            net_amount: Set(tax.net_amount),
+            ..Default::default()
+        }
+            .insert(&txn)
+            .await?;
+
+        let items: Vec<dentist_payout_item::ActiveModel> = group
+            .lines
+            .iter()
+            .map(|line| dentist_payout_item::ActiveModel {
+                batch_id: Set(batch.id),
+                remittance_id: Set(remittance.id),
+                verification_id: Set(line.verification.id),
+                dentist_id: Set(*the_dentist_id),
+                dental_clinic_id: Set(*dental_clinic_id),
+                dental_service_id: Set(line.verification.dental_service_id),
+                date_service_performed: Set(line.verification.date_service_performed.unwrap_or(period_end)),
+                rate: Set(line.price.rate),
+                approved_cost: Set(line.price.approved_cost),
+                amount: Set(line.price.amount),
+                tax_base: Set(line.tax.tax_base),
+                business_tax_amount: Set(line.tax.business_tax_amount),
+                withheld_amount: Set(line.tax.withheld_amount),
+                net_amount: Set(line.tax.net_amount),
+                ..Default::default()
+            })
+            .collect();
+        dentist_payout_item::Entity::insert_many(items)
+            .exec(&txn)
+            .await
+            .map_err(PayoutError::Conflict)?;
+
+        write_remittance_to_spreadsheet(group, period_start, period_end, &file_name)?;
+        summary.remittances.push(RemittanceSummary {
+            remittance_id: remittance.id,
+            dentist_id: *the_dentist_id,
+            dentist_name: group.dentist_name.clone(),
+            dental_clinic_id: *dental_clinic_id,
+            dental_clinic_name: group.dental_clinic_name.clone(),
+            item_count: remittance.item_count,
+            gross_amount,
+            atc_code: profile.atc_code.clone(),
+            tax,
+            file_name,
+        });
+    }
+    txn.commit().await?;
+
+    info!(target: "jobs",
+        "create_payout_batch() recorded batch {} with {} verification(s) in {} remittance(s) totalling {} gross, {} withheld",
+        batch.id,
+        summary.item_count,
+        summary.remittances.len(),
+        summary.gross_amount,
+        summary.withheld_amount
+    );
+    summary.batch_id = Some(batch.id);
+    Ok(summary)
+}
+
+/// Loads the period's reconciled verifications of fee-per-service dentists that are not in a
+/// batch yet, prices each, computes its tax and groups them by dentist and clinic.
+async fn load_payout_groups(
+    db: &DatabaseConnection,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    dentist_id: Option<i32>,
+) -> Result<PayoutGroups, PayoutError> {
+    let already_in_batch = Query::select()
+        .column(dentist_payout_item::Column::VerificationId)
+        .from(dentist_payout_item::Entity)
+        .to_owned();
+
+    let mut query = verification::Entity::find()
+        .join(JoinType::InnerJoin, verification::Relation::Dentist.def())
+        .filter(dentist::Column::AccreDentistContractId.is_not_null())
+        .filter(dentist::Column::AccreDentistContractId.ne(FLAT_FEE_CONTRACT_ID))
+        .filter(verification::Column::IsReconciled.eq(true))
+        .filter(verification::Column::StatusId.ne(VerificationStatus::Voided.int_code()))
+        .filter(verification::Column::DateServicePerformed.gte(period_start))
+        .filter(verification::Column::DateServicePerformed.lte(period_end))
+        .filter(verification::Column::Id.not_in_subquery(already_in_batch));
+    if let Some(dentist_id) = dentist_id {
+        query = query.filter(verification::Column::DentistId.eq(dentist_id));
+    }
+    let rows = query
+        .select_also(dentist::Entity)
+        .order_by_asc(verification::Column::DateServicePerformed)
+        .order_by_asc(verification::Column::Id)
+        .all(db)
+        .await?;
+    if rows.is_empty() {
+        return Ok(PayoutGroups {
+            groups: BTreeMap::new(),
+            unpriced_verification_ids: Vec::new(),
+            clinics_missing_tax_profile: Vec::new(),
+        });
+    }
+
+    let verification_ids: Vec<i32> = rows.iter().map(|(v, _)| v.id).collect();
+    let member_ids: Vec<i32> = rows.iter().map(|(v, _)| v.member_id).collect();
+    let clinic_ids: Vec<i32> = rows.iter().map(|(v, _)| v.dental_clinic_id).collect();
+
+    let contract_ids: BTreeSet<i32> = rows
+        .iter()
+        .filter_map(|(_, d)| d.as_ref().and_then(|d| d.accre_dentist_contract_id))
+        .collect();
+    let rates = load_contract_rates(db, contract_ids).await?;
+    let approved_costs: HashMap<i32, Decimal> = high_end_verification_information::Entity::find()
+        .filter(high_end_verification_information::Column::VerificationId.is_in(verification_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|h| h.approved_cost.map(|cost| (h.verification_id, cost)))
+        .collect();
+    let services: HashMap<i32, dental_service::Model> = dental_service::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|s| (s.id, s))
+        .collect();
+    let members: HashMap<i32, master_list_member::Model> = master_list_member::Entity::find()
+        .filter(master_list_member::Column::Id.is_in(member_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|m| (m.id, m))
+        .collect();
+    let clinics: HashMap<i32, dental_clinic::Model> = dental_clinic::Entity::find()
+        .filter(dental_clinic::Column::Id.is_in(clinic_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|c| (c.id, c))
+        .collect();
+    let tax_rates = load_payout_tax_rates(db).await?;
+
+    let mut groups: BTreeMap<(i32, i32), RemittanceGroup> = BTreeMap::new();
+    let mut unpriced = Vec::new();
+    let mut missing_tax_profile = BTreeSet::new();
+    for (the_verification, the_dentist) in rows {
+        let Some(the_dentist) = the_dentist else { continue };
+        let Some(contract_id) = the_dentist.accre_dentist_contract_id else { continue };
+        let service = services.get(&the_verification.dental_service_id);
+        let is_high_end = service.is_some_and(|s| s.type_id == HIGH_END_SERVICE_TYPE_ID);
+        let rate = the_verification
+            .date_service_performed
+            .and_then(|date| rates.rate_on(contract_id, the_verification.dental_service_id, date));
+        let price = price_payout(
+            rate,
+            approved_costs.get(&the_verification.id).copied(),
+            is_high_end,
+        );
+        let Some(price) = price else {
+            unpriced.push(the_verification.id);
+            continue;
+        };
+
+        let clinic_id = the_verification.dental_clinic_id;
+        let clinic = clinics.get(&clinic_id);
+        let Some(profile) = clinic.and_then(|c| tax_rates.profile_for(c.acct_tax_type_id, c.acct_tax_classification_id)) else {
+            missing_tax_profile.insert(clinic_id);
+            continue;
+        };
+        let tax = compute_payout_tax(price.amount, &profile);
+        groups
+            .entry((the_dentist.id, clinic_id))
+            .or_insert_with(|| RemittanceGroup {
+                dentist_name: format_dentist_name(
+                    &the_dentist.last_name,
+                    &the_dentist.given_name,
+                    the_dentist.middle_name.as_deref(),
+                ),
+                dental_clinic_name: clinic.map(|c| c.name.clone()).unwrap_or_default(),
+                profile,
+                lines: Vec::new(),
+            })
+            .lines
+            .push(PayoutLine {
+                member: members.get(&the_verification.member_id).cloned(),
+                service_name: service.map(|s| s.name.clone()).unwrap_or_default(),
+                verification: the_verification,
+                price,
+                tax,
+            });
+    }
+    Ok(PayoutGroups {
+        groups,
+        unpriced_verification_ids: unpriced,
+        clinics_missing_tax_profile: missing_tax_profile.into_iter().collect(),
+    })
+}
+
+fn format_dentist_name(
+    last_name: &str,
+    given_name: &str,
+    middle_name: Option<&str>,
+) -> String {
+    match middle_name {
+        Some(middle) if !middle.trim().is_empty() => {
+            format!("{}, {} {}", last_name, given_name, middle)
+        }
+        _ => format!("{}, {}", last_name, given_name),
+    }
+}
+
+/// Writes one remittance advice: a line per verification, the totals and the tax withheld.
+fn write_remittance_to_spreadsheet(
+    group: &RemittanceGroup,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    file_name: &str,
+) -> Result<(), PayoutError> {
+    let mut book = umya_spreadsheet::new_file();
+    let sheet = book
+        .get_sheet_mut(&0)
+        .ok_or_else(|| PayoutError::Spreadsheet("new workbook has no worksheet".to_string()))?;
+    sheet.set_name("Remittance");
+
+    sheet.get_cell_mut("A1").set_value("REMITTANCE ADVICE");
+    sheet.get_cell_mut("A2").set_value(group.dentist_name.as_str());
+    sheet.get_cell_mut("A3").set_value(group.dental_clinic_name.as_str());
+    sheet.get_cell_mut("A4").set_value(format!(
+        "{} - {}",
+        period_start.format("%B %d"),
+        period_end.format("%B %d, %Y")
+    ));
+
+    let header_row: u32 = 6;
+    let headers = [
+        "DATE OF SERVICE",
+        "MEMBER",
+        "SERVICE",
+        "APPROVAL CODE",
+        "RATE",
+        "APPROVED COST",
+        "AMOUNT",
+        "TAX WITHHELD",
+        "NET AMOUNT",
+    ];
+    for (index, header) in headers.iter().enumerate() {
+        sheet.get_cell_mut((index as u32 + 1, header_row)).set_value(*header);
+    }
+
+    for (index, line) in group.lines.iter().enumerate() {
+        let excel_row = header_row + 1 + index as u32;
+        sheet.get_cell_mut((1, excel_row)).set_value(
+            line.verification
+                .date_service_performed
+                .map(|d| d.format("%m/%d/%Y").to_string())
+                .unwrap_or_default(),
+        );
+        if let Some(member) = &line.member {
+            sheet.get_cell_mut((2, excel_row)).set_value(format!(
+                "{}, {} {} ({})",
+                member.last_name, member.first_name, member.middle_name, member.account_number
+            ));
+        }
+        sheet.get_cell_mut((3, excel_row)).set_value(line.service_name.as_str());
+        sheet.get_cell_mut((4, excel_row))
+            .set_value(line.verification.approval_code.clone().unwrap_or_default());
+        sheet.get_cell_mut((5, excel_row)).set_value_number(to_f64(line.price.rate.unwrap_or_default()));
+        sheet.get_cell_mut((6, excel_row))
+            .set_value_number(to_f64(line.price.approved_cost.unwrap_or_default()));
+        sheet.get_cell_mut((7, excel_row)).set_value_number(to_f64(line.price.amount));
+        sheet.get_cell_mut((8, excel_row)).set_value_number(to_f64(line.tax.withheld_amount));
+        sheet.get_cell_mut((9, excel_row)).set_value_number(to_f64(line.tax.net_amount));
+    }
+
+    let tax = group.tax();
+    let total_row = header_row + 1 + group.lines.len() as u32;
+    sheet.get_cell_mut((6, total_row)).set_value("TOTAL");
+    sheet.get_cell_mut((7, total_row)).set_value_number(to_f64(group.gross_amount()));
+    sheet.get_cell_mut((8, total_row)).set_value_number(to_f64(tax.withheld_amount));
+    sheet.get_cell_mut((9, total_row)).set_value_number(to_f64(tax.net_amount));
+
+    let profile = &group.profile;
+    let (business_tax_label, base_label) = if profile.vat_registered {
+        ("VAT INCLUDED", "GROSS LESS VAT")
+    } else {
+        ("PERCENTAGE TAX DUE", "GROSS")
+    };
+    let summary_row = total_row + 2;
+    let summary = [
+        ("GROSS AMOUNT", to_f64(group.gross_amount())),
+        (business_tax_label, to_f64(tax.business_tax_amount)),
+        (base_label, to_f64(tax.tax_base)),
+        ("TAX WITHHELD", to_f64(tax.withheld_amount)),
+        ("NET AMOUNT", to_f64(tax.net_amount)),
+    ];
+    for (index, (label, value)) in summary.iter().enumerate() {
+        let excel_row = summary_row + index as u32;
+        sheet.get_cell_mut((6, excel_row)).set_value(*label);
+        sheet.get_cell_mut((7, excel_row)).set_value_number(*value);
+    }
+    sheet.get_cell_mut((8, summary_row + 3)).set_value(format!(
+        "{} at {}%",
+        profile.atc_code,
+        (profile.ewt_rate * Decimal::ONE_HUNDRED).normalize()
+    ));
+
+    let full_filename = format!("generated_reports/{}", file_name);
+    info!(target: "jobs", " writing remittance for {} to {}", group.dentist_name, full_filename);
+    umya_spreadsheet::writer::xlsx::write(&book, &full_filename)
+        .map_err(|e| PayoutError::Spreadsheet(e.to_string()))
+}
+
+fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
+// endregion: Batches
+
+
+// region: Paying
+
+/// Marks one item paid. Refused if it already is.
+pub async fn mark_item_paid(
+    db: &DatabaseConnection,
+    item_id: i32,
+    payment_reference: Option<String>,
+    paid_by: &str,
+) -> Result<dentist_payout_item::Model, PayoutError> {
+    let result = dentist_payout_item::Entity::update_many()
+        .col_expr(dentist_payout_item::Column::PaidOn, Expr::value(Utc::now().fixed_offset()))
+        .col_expr(dentist_payout_item::Column::PaidBy, Expr::value(paid_by))
+        .col_expr(dentist_payout_item::Column::PaymentReference, Expr::value(payment_reference))
+        .filter(dentist_payout_item::Column::Id.eq(item_id))
+        .filter(dentist_payout_item::Column::PaidOn.is_null())
+        .exec(db)
+        .await?;
+
+    let item = dentist_payout_item::Entity::find_by_id(item_id)
+        .one(db)
+        .await?
+        .ok_or(PayoutError::ItemNotFound(item_id))?;
+    if result.rows_affected == 0 {
+        return Err(PayoutError::AlreadyPaid(item_id));
+    }
+    info!(target: "jobs", "payout item {} (verification {}) marked paid by {}", item.id, item.verification_id, paid_by);
+    Ok(item)
+}
+
+/// Marks the unpaid items of a remittance paid and returns how many were. Refused if every
+/// item already is.
+pub async fn mark_remittance_paid(
+    db: &DatabaseConnection,
+    remittance_id: i32,
+    payment_reference: Option<String>,
+    paid_by: &str,
+) -> Result<u64, PayoutError> {
+    dentist_payout_remittance::Entity::find_by_id(remittance_id)
+        .one(db)
+        .await?
+        .ok_or(PayoutError::RemittanceNotFound(remittance_id))?;
+
+    let result = dentist_payout_item::Entity::update_many()
+        .col_expr(dentist_payout_item::Column::PaidOn, Expr::value(Utc::now().fixed_offset()))
+        .col_expr(dentist_payout_item::Column::PaidBy, Expr::value(paid_by))
+        .col_expr(dentist_payout_item::Column::PaymentReference, Expr::value(payment_reference))
+        .filter(dentist_payout_item::Column::RemittanceId.eq(remittance_id))
+        .filter(dentist_payout_item::Column::PaidOn.is_null())
+        .exec(db)
+        .await?;
+    if result.rows_affected == 0 {
+        return Err(PayoutError::RemittanceAlreadyPaid(remittance_id));
+    }
+    info!(target: "jobs", "{} item(s) of payout remittance {} marked paid by {}", result.rows_affected, remittance_id, paid_by);
+    Ok(result.rows_affected)
+}
+// endregion: Paying
@@ -3,16 +3,19 @@ use sea_orm::QueryFilter;
 use sea_orm::ColumnTrait;
 use chrono::{NaiveDate, Months, Utc};
 use tracing::info;
-use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, JoinType, QuerySelect, RelationTrait, Set};
+use sea_orm::{ActiveModelTrait, ConnectionTrait, EntityTrait, JoinType, QuerySelect, RelationTrait, Set};
 use crate::AppState;
 
-use crate::entities::{dental_service,
-                      endorsement, endorsement_company,endorsement_counts,
+use crate::entities::{app_config, billing_run, dental_service,
+                      endorsement, endorsement_billing_rule, endorsement_company,endorsement_counts,
                       generated_report,
                       hmo, hmo_billing_data,
                       master_list, master_list_member};
+use crate::jobs::billing_runs;
+use crate::jobs::billing_amounts::{compute_billing_amounts, BillingTier, DEFAULT_VAT_RATE};
+use rust_decimal::Decimal;
+use std::str::FromStr;
 use umya_spreadsheet;
-use uuid::Uuid;
 
 /// Generate HMO Billing Reports for each HMO
 ///
@@ -20,35 +23,51 @@ use uuid::Uuid;
 /// then iterating through all HMOs and generating the Excel report for each HMO
 /// First, it generates billing data per endorsement and saves it to the HMO_Billing_Data table.
 /// Then, it generates the spreadsheets for each hmo.
-/// On the request key: 
 ///
+/// The data and spreadsheets belong to the billing run for the period (see billing_runs). If
+/// the period has a draft run it is regenerated in place; a finalized run is left alone.
 pub async fn generate_hmo_billing_reports(
     state: AppState,
     start_date: Option<NaiveDate>,
     end_date: NaiveDate,
-)-> anyhow::Result<()> {
-
-   let request_key = Uuid::new_v4().to_string();
-
-    let db : &DatabaseConnection = &state.db;
+    created_by: &str,
+)-> anyhow::Result<billing_run::Model> {
     let actual_start_date = match start_date {
         Some(date) => date,
         None => end_date
             .checked_sub_months(Months::new(1)).ok_or_else(|| anyhow::anyhow!("Could not calculate start date"))?,
     };
+    let run = billing_runs::create_or_regenerate_run(&state.db, actual_start_date, end_date, created_by).await?;
+    Ok(run)
+}
+
+/// Generates the billing data and spreadsheets of `run`. billing_runs calls this inside the
+/// transaction that holds the run's lock, after clearing whatever the run had before.
+pub(crate) async fn generate_billing_for_run<C: ConnectionTrait>(
+    db: &C,
+    run: &billing_run::Model,
+)-> anyhow::Result<BillingRunTotals> {
     info!(target: "jobs",
-        "generate_hmo_billing_reports() started with request_key {} for period {}-{}",
-        request_key,
-        actual_start_date.format("%m/%d/%Y"),
-        end_date.format("%m/%d/%Y")
+        "generate_billing_for_run() started for run {} with request_key {} for period {}-{}",
+        run.id,
+        run.request_key,
+        run.period_start.format("%m/%d/%Y"),
+        run.period_end.format("%m/%d/%Y")
     );
+    let mut totals = BillingRunTotals::default();
 
     //---1. Generate Billing Data per endorsement
+    let vat_rate = get_vat_rate(db).await?;
     let endorsements = endorsement::Entity::find()
         .all(db)
         .await?;
     for endorsement in endorsements{
-        generate_billing_data_for_endorsement(state.clone(), request_key.clone(), endorsement.id, actual_start_date, end_date).await?;
+        let row = generate_billing_data_for_endorsement(db, run, endorsement.id, vat_rate).await?;
+        totals.endorsement_count += 1;
+        totals.billable_count += row.billable_count.unwrap_or_default();
+        totals.amount_before_vat += row.amount_before_vat.unwrap_or_default();
+        totals.vat_amount += row.vat_amount.unwrap_or_default();
+        totals.total_amount += row.total_amount.unwrap_or_default();
     }
 
     //---2. Generate Billing Report per HMO
@@ -56,23 +75,50 @@ pub async fn generate_hmo_billing_reports(
         .all(db)
         .await?;
     for hmo in hmos{
-        generate_billing_report_for_hmo(state.clone(),request_key.clone(), hmo.id, actual_start_date, end_date).await?;
+        if generate_billing_report_for_hmo(db, run, hmo.id).await? {
+            totals.report_count += 1;
+        }
     }
-    Ok(())
+    Ok(totals)
+}
+
+/// What a billing run adds up to.
+#[derive(Debug, Clone, Default)]
+pub struct BillingRunTotals {
+    pub endorsement_count: i32,
+    pub report_count: i32,
+    pub billable_count: i32,
+    pub amount_before_vat: Decimal,
+    pub vat_amount: Decimal,
+    pub total_amount: Decimal,
 }
 
 
 
+/// The VAT rate from app_config's hmo_billing_vat_rate, or 12% if the key is not there.
+async fn get_vat_rate<C: ConnectionTrait>(db: &C) -> anyhow::Result<Decimal> {
+    let row = app_config::Entity::find()
+        .filter(app_config::Column::Key.eq("hmo_billing_vat_rate"))
+        .one(db)
+        .await?;
+    match row {
+        Some(row) => Decimal::from_str(row.value.trim())
+            .map_err(|e| anyhow::anyhow!("Invalid hmo_billing_vat_rate '{}': {}", row.value, e)),
+        None => Ok(DEFAULT_VAT_RATE),
+    }
+}
+
 /// For each endorsement, count the master_list_members for the period defined by start_date and end_date.
 /// The method of counting differs depending on whether its endorsement_billing_period_type is annual or monthly.
-async fn generate_billing_data_for_endorsement(
-    state: AppState,
-    request_key: String,
+/// The amounts billed are computed from the count and the endorsement's billing rule tiers (see billing_amounts).
+async fn generate_billing_data_for_endorsement<C: ConnectionTrait>(
+    db: &C,
+    run: &billing_run::Model,
     endorsement_id: i32,
-    start_date: NaiveDate,
-    end_date: NaiveDate,
-)-> anyhow::Result<()> {
-    let db : &DatabaseConnection = &state.db;
+    vat_rate: Decimal,
+)-> anyhow::Result<hmo_billing_data::Model> {
+    let start_date = run.period_start;
+    let end_date = run.period_end;
     let endorsement = endorsement::Entity::find_by_id(endorsement_id)
         .one(db)
         .await?
@@ -80,27 +126,49 @@ async fn generate_billing_data_for_endorsement(
     let billing_period_type_id = endorsement.endorsement_billing_period_type_id;
     let master_list_member_count = match billing_period_type_id {
         1 => {
-            count_annual_master_list_members_for_endorsement(state.clone(), endorsement_id, start_date, end_date).await?
+            count_annual_master_list_members_for_endorsement(db, endorsement_id, start_date, end_date).await?
         },
         2 => {
-            count_monthly_master_list_members_for_endorsement(state.clone(), endorsement_id, start_date, end_date).await?
+            count_monthly_master_list_members_for_endorsement(db, endorsement_id, start_date, end_date).await?
         },
         _ => {
             return Err(anyhow::anyhow!("Invalid billing period type id {}", billing_period_type_id));
         }
     };
+
+    let tiers: Vec<BillingTier> = endorsement_billing_rule::Entity::find()
+        .filter(endorsement_billing_rule::Column::EndorsementId.eq(endorsement_id))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|r| BillingTier { id: r.id, min_count: r.min_count, max_count: r.max_count, rate: r.rate })
+        .collect();
+    let billable_count = (master_list_member_count.master_list_members_count
+        + master_list_member_count.added_counts) as i32;
+    let amounts = compute_billing_amounts(billable_count, &tiers, endorsement.retainer_fee, vat_rate);
+
     let new_billing_data = hmo_billing_data::ActiveModel {
         id: Default::default(),
         date_generated: Set(Utc::now().fixed_offset()),
-        request_key: Set(Some(request_key)),
+        request_key: Set(Some(run.request_key.clone())),
         endorsement_id: Set(endorsement_id),
         master_list_count: Set(Some(master_list_member_count.master_list_members_count as i32)),
-        added_list_count: Set(Some(master_list_member_count.added_counts as i32))
-
+        added_list_count: Set(Some(master_list_member_count.added_counts as i32)),
+        billable_count: Set(Some(amounts.billable_count)),
+        rate_source: Set(Some(amounts.rate_source.as_str().to_string())),
+        billing_rule_id: Set(amounts.billing_rule_id),
+        rate: Set(Some(amounts.rate)),
+        vat_rate: Set(Some(amounts.vat_rate)),
+        vat_per_member: Set(Some(amounts.vat_per_member)),
+        rate_with_vat: Set(Some(amounts.rate_with_vat)),
+        amount_before_vat: Set(Some(amounts.amount_before_vat)),
+        vat_amount: Set(Some(amounts.vat_amount)),
+        total_amount: Set(Some(amounts.total_amount)),
+        billing_run_id: Set(Some(run.id)),
     };
-    let _inserted_billing_data = new_billing_data.insert(db).await?;
+    let inserted_billing_data = new_billing_data.insert(db).await?;
 
-    Ok(())
+    Ok(inserted_billing_data)
 }
 
 
@@ -111,13 +179,12 @@ struct MLMCounts{
 }
 /// Endorsements with Annual Billing Period only count the master_list_members
 /// whose master_list was uploaded within that period, plus members that were added in that period.
-async fn count_annual_master_list_members_for_endorsement(
-    state: AppState,
+async fn count_annual_master_list_members_for_endorsement<C: ConnectionTrait>(
+    db: &C,
     endorsement_id: i32,
     start_date: NaiveDate,
     end_date: NaiveDate,
 )-> anyhow::Result<MLMCounts> {
-    let db : &DatabaseConnection = &state.db;
     let mlm_count = master_list_member::Entity::find()
         .join(
             JoinType::InnerJoin,
@@ -145,13 +212,12 @@ async fn count_annual_master_list_members_for_endorsement(
 
 /// Endorsements with Monthly Billing Period count all total master_list_members
 /// for that period.
-async fn count_monthly_master_list_members_for_endorsement(
-    state: AppState,
+async fn count_monthly_master_list_members_for_endorsement<C: ConnectionTrait>(
+    db: &C,
     endorsement_id: i32,
     _start_date: NaiveDate,
     _end_date: NaiveDate,
 )-> anyhow::Result<MLMCounts> {
-    let db : &DatabaseConnection = &state.db;
     let mlm_count = master_list_member::Entity::find()
         .filter(master_list_member::Column::MasterListId.is_not_null())
         .filter(master_list_member::Column::EndorsementId.eq(endorsement_id))
@@ -176,15 +242,13 @@ async fn count_monthly_master_list_members_for_endorsement(
 /// generate_billing_report_for_hmo() creates the Excel report for the HMO.
 /// Essentially, it reads the data from the hmo_billing_data table for the hmo in question
 /// and writes it to a spreadsheet
-async fn generate_billing_report_for_hmo(
-    state: AppState,
-    request_key: String,
+/// Returns false if the HMO had nothing to bill.
+async fn generate_billing_report_for_hmo<C: ConnectionTrait>(
+    db: &C,
+    run: &billing_run::Model,
     hmo_id: i32, // the HMO id
-    _start_date: NaiveDate,
-    end_date: NaiveDate,
-)-> anyhow::Result<()> {
+)-> anyhow::Result<bool> {
 
-    let db : &DatabaseConnection = &state.db;
     let the_hmo = hmo::Entity::find_by_id(hmo_id)
         .one(db)
         .await?
@@ -197,7 +261,7 @@ async fn generate_billing_report_for_hmo(
         hmo_billing_data::Relation::Endorsement.def(),
     )
         .filter(endorsement::Column::HmoId.eq(hmo_id))
-        .filter(hmo_billing_data::Column::RequestKey.eq(Some(request_key.clone())))
+        .filter(hmo_billing_data::Column::BillingRunId.eq(run.id))
         .all(db)
         .await?;
 
@@ -208,9 +272,9 @@ async fn generate_billing_report_for_hmo(
             hmo_id,
             the_hmo.short_name
         );
-        return Ok(());
+        return Ok(false);
     }
-    let the_filename = write_hmo_billing_to_spreadsheet( state.clone(), &the_hmo.short_name, hmo_billing_data_rows, end_date).await?;
+    let the_filename = write_hmo_billing_to_spreadsheet(db, &the_hmo.short_name, hmo_billing_data_rows, run).await?;
 
 
     let generated_report_record = generated_report::ActiveModel{
@@ -218,22 +282,24 @@ async fn generate_billing_report_for_hmo(
         report_type_id: Set(1),
         file_name: Set(the_filename.clone()),
         date_generated: Set(Some(Utc::now().fixed_offset())),
+        billing_run_id: Set(Some(run.id)),
+        hmo_id: Set(Some(hmo_id)),
     };
     let _ = generated_report_record.insert(db).await?;
-    Ok(())
+    Ok(true)
 }
 
 
 /// write_hmo_billing_to_spreadsheet() does the actual work of writing data to an Excel spreadsheet.
 /// this is called by generate_billing_report_for_hmo
 
-async fn write_hmo_billing_to_spreadsheet(
-    state:AppState,
+async fn write_hmo_billing_to_spreadsheet<C: ConnectionTrait>(
+    db: &C,
     hmo_name:&str,
     billing_data: Vec<hmo_billing_data::Model>,
-    end_date: NaiveDate)
+    run: &billing_run::Model)
     -> anyhow::Result<String> {
-    let db : &DatabaseConnection = &state.db;
+    let end_date = run.period_end;
 
     info!(target: "jobs", "write_hmo_billing_to_spreadsheet() started for HMO {} with {} rows", hmo_name, billing_data.len());
     //----1. Set the path to template XLSX
@@ -283,7 +349,8 @@ async fn write_hmo_billing_to_spreadsheet(
         // E - agreement corp number
         let agreement_corp_number = endorsement.agreement_corp_number;
         // F - total master list members
-        let total_master_list_members = row.master_list_count.unwrap_or_default() + row.added_list_count.unwrap_or_default();
+        let total_master_list_members = row.billable_count
+            .unwrap_or(row.master_list_count.unwrap_or_default() + row.added_list_count.unwrap_or_default());
         // G - billing period type
         let billing_period_str = match endorsement.endorsement_billing_period_type_id {
             1 => "Annual",
@@ -291,13 +358,14 @@ async fn write_hmo_billing_to_spreadsheet(
             _ => "Unknown",
         };
         // H - dental benefits
-        let dental_benefits = get_dental_benefits_string_from_endorsement(state.clone(),endorsement.id).await.unwrap_or_default();
+        let dental_benefits = get_dental_benefits_string_from_endorsement(db, endorsement.id).await.unwrap_or_default();
         // I - effectivity period
         let effectivity_period = format!("{} - {}", endorsement.date_start.format("%m/%d/%Y"),endorsement.date_end.format("%m/%d/%Y") );
-        // J - retainer fee
-        // K- 12% VAT
-        // L= J+K
+        // J - rate per member (billing rule tier, or the retainer fee)
+        // K - VAT per member
+        // L = J+K
         // M = L * F
+        // All four come from hmo_billing_data so the XLSX matches what the API reports.
         sheet
             .get_cell_mut(format!("B{}", excel_row))
             .set_value(index.to_string());
@@ -328,7 +396,19 @@ async fn write_hmo_billing_to_spreadsheet(
 
         sheet
             .get_cell_mut(format!("J{}", excel_row))
-            .set_value(endorsement.retainer_fee.unwrap_or_default().to_string());
+            .set_value(row.rate.or(endorsement.retainer_fee).unwrap_or_default().to_string());
+
+        sheet
+            .get_cell_mut(format!("K{}", excel_row))
+            .set_value(row.vat_per_member.unwrap_or_default().to_string());
+
+        sheet
+            .get_cell_mut(format!("L{}", excel_row))
+            .set_value(row.rate_with_vat.unwrap_or_default().to_string());
+
+        sheet
+            .get_cell_mut(format!("M{}", excel_row))
+            .set_value(row.total_amount.unwrap_or_default().to_string());
 
         info!(target: "jobs",
         "writing row {} for company {} with total_master_list_members {} and dental_benefits '{}'",
@@ -339,7 +419,8 @@ async fn write_hmo_billing_to_spreadsheet(
 
     }
 
-    let the_filename = format!("{}_HMO_Billing_{}.xlsx", hmo_name, Utc::now().format("%Y-%m-%d"));
+    // the run id keeps the files of different runs for the same period apart
+    let the_filename = format!("{}_HMO_Billing_{}_R{}.xlsx", hmo_name, end_date.format("%Y-%m-%d"), run.id);
     let full_filename = format!("generated_reports/{}", the_filename);
 
     info!(target: "jobs", " writing report for {} to {}", hmo_name,full_filename );
@@ -353,11 +434,10 @@ async fn write_hmo_billing_to_spreadsheet(
 /// get_dental_benefits_string_from_endorsement() generates a string to describe the dental benefits
 /// of an endorsement.
 /// this is called by write_hmo_billing_to_spreadsheet
-async fn get_dental_benefits_string_from_endorsement(
-    state: AppState,
+async fn get_dental_benefits_string_from_endorsement<C: ConnectionTrait>(
+    db: &C,
     endorsement_id: i32,
 )  -> anyhow::Result<String> {
-    let db : &DatabaseConnection = &state.db;
 
     let rows = endorsement_counts::Entity::find()
         .find_also_related(dental_service::Entity)
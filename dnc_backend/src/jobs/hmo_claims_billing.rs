@@ -0,0 +1,456 @@
+//! Fee-per-service claims billing to HMOs.
+//!
+//! A claims run collects the reconciled verifications of an HMO whose service was performed
+//! in the period and that have not been billed yet. Each is priced at the endorsement's
+//! endorsement_rates.rate for the dental service, plus the approved cost for high-end
+//! services. The claims statement is written from "Claims Template.xlsx", and every
+//! verification on it gets a hmo_claims_billing_item row. That row's unique verification_id
+//! is what keeps a verification from being billed twice, even by two runs at once.
+//!
+//! Verifications with neither a rate nor an approved cost are covered by the retainer. They
+//! are left unbilled and reported, so they are picked up once a rate is added.
+use std::collections::{HashMap, HashSet};
+
+use chrono::{NaiveDate, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use sea_orm::sea_query::Query;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, JoinType, QueryFilter,
+    QueryOrder, QuerySelect, RelationTrait, Set, TransactionTrait,
+};
+use serde::Serialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::AppState;
+use crate::entities::{
+    dental_service, dentist, endorsement, endorsement_rates, generated_report,
+    high_end_verification_information, hmo, hmo_claims_billing_item, master_list_member,
+    report_type, verification,
+};
+use crate::jobs::billing_amounts::{money, HIGH_END_SERVICE_TYPE_ID};
+use crate::jobs::receivables;
+use crate::verification_state::VerificationStatus;
+
+/// report_type.name of the claims statements in generated_report.
+pub const HMO_CLAIMS_REPORT_TYPE: &str = "HMO Claims";
+
+/// What a verification is billed at.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClaimPrice {
+    pub rate: Decimal,
+    pub approved_cost: Option<Decimal>,
+    pub amount: Decimal,
+}
+
+/// Prices a claim at its service rate plus, for high-end services, the approved cost.
+/// Returns None when there is nothing to bill for it.
+pub fn price_claim(
+    rate: Option<Decimal>,
+    approved_cost: Option<Decimal>,
+    is_high_end: bool,
+) -> Option<ClaimPrice> {
+    let approved_cost = approved_cost.filter(|_| is_high_end).map(money);
+    if rate.is_none() && approved_cost.is_none() {
+        return None;
+    }
+    let rate = money(rate.unwrap_or_default());
+    Some(ClaimPrice {
+        rate,
+        approved_cost,
+        amount: rate + approved_cost.unwrap_or_default(),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HmoClaimsStatement {
+    pub hmo_id: i32,
+    pub hmo_short_name: String,
+    /// None if nothing was billed
+    pub file_name: Option<String>,
+    pub generated_report_id: Option<i32>,
+    pub invoice_number: Option<String>,
+    pub verification_count: usize,
+    pub total_amount: Decimal,
+    /// reconciled in the period but with no rate or approved cost; not billed
+    pub unpriced_verification_ids: Vec<i32>,
+}
+
+struct ClaimLine {
+    verification: verification::Model,
+    member: master_list_member::Model,
+    endorsement_id: i32,
+    dentist_name: String,
+    service_name: String,
+    price: ClaimPrice,
+}
+
+/// The report_type id of HMO claims statements.
+pub async fn hmo_claims_report_type_id(db: &DatabaseConnection) -> anyhow::Result<i32> {
+    report_type::Entity::find()
+        .filter(report_type::Column::Name.eq(HMO_CLAIMS_REPORT_TYPE))
+        .one(db)
+        .await?
+        .map(|row| row.id)
+        .ok_or_else(|| anyhow::anyhow!("report_type '{}' is missing", HMO_CLAIMS_REPORT_TYPE))
+}
+
+/// Bills the unbilled reconciled verifications of one HMO, or of every HMO when `hmo_id` is
+/// None, whose service was performed from start_date to end_date.
+pub async fn generate_hmo_claims_billing(
+    state: AppState,
+    hmo_id: Option<i32>,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    billed_by: &str,
+) -> anyhow::Result<Vec<HmoClaimsStatement>> {
+    let db: &DatabaseConnection = &state.db;
+    let request_key = Uuid::new_v4().to_string();
+    info!(target: "jobs",
+        "generate_hmo_claims_billing() started with request_key {} for period {}-{}",
+        request_key,
+        start_date.format("%m/%d/%Y"),
+        end_date.format("%m/%d/%Y")
+    );
+
+    let mut hmo_query = hmo::Entity::find();
+    if let Some(hmo_id) = hmo_id {
+        hmo_query = hmo_query.filter(hmo::Column::Id.eq(hmo_id));
+    }
+    let hmos = hmo_query.order_by_asc(hmo::Column::ShortName).all(db).await?;
+    if hmo_id.is_some() && hmos.is_empty() {
+        return Err(anyhow::anyhow!("Could not find HMO with id {}", hmo_id.unwrap_or_default()));
+    }
+
+    let mut statements = Vec::new();
+    for the_hmo in hmos {
+        statements.push(
+            generate_claims_statement_for_hmo(db, &request_key, &the_hmo, start_date, end_date, billed_by).await?,
+        );
+    }
+    Ok(statements)
+}
+
+async fn generate_claims_statement_for_hmo(
+    db: &DatabaseConnection,
+    request_key: &str,
+    the_hmo: &hmo::Model,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    billed_by: &str,
+) -> anyhow::Result<HmoClaimsStatement> {
+    // ---1. price the claims
+    let (lines, unpriced_verification_ids) = load_claim_lines(db, the_hmo.id, start_date, end_date).await?;
+    let total_amount: Decimal = lines.iter().map(|l| l.price.amount).sum();
+    let mut statement = HmoClaimsStatement {
+        hmo_id: the_hmo.id,
+        hmo_short_name: the_hmo.short_name.clone(),
+        file_name: None,
+        generated_report_id: None,
+        invoice_number: None,
+        verification_count: lines.len(),
+        total_amount,
+        unpriced_verification_ids,
+    };
+    if lines.is_empty() {
+        info!(target: "jobs",
+            "generate_claims_statement_for_hmo() skipped for HMO id:{}({}) because it has no claims to bill",
+            the_hmo.id,
+            the_hmo.short_name
+        );
+        return Ok(statement);
+    }
+
+    // ---2. mark the verifications billed and record the statement. The file is written
+    // before the commit so a failed write leaves nothing marked.
+    let report_type_id = hmo_claims_report_type_id(db).await?;
+    let now = Utc::now().fixed_offset();
+    let txn = db.begin().await?;
+
+    let the_filename = format!(
+        "{}_HMO_Claims_{}_{}.xlsx",
+        the_hmo.short_name,
+        end_date.format("%Y-%m-%d"),
+        Utc::now().format("%H%M%S")
+    );
+    let report = generated_report::ActiveModel {
+        id: Default::default(),
+        report_type_id: Set(report_type_id),
+        file_name: Set(the_filename.clone()),
+        date_generated: Set(Some(now)),
+        billing_run_id: Set(None),
+        hmo_id: Set(Some(the_hmo.id)),
+    }
+        .insert(&txn)
+        .await?;
+
+    let items: Vec<hmo_claims_billing_item::ActiveModel> = lines
+        .iter()
+        .map(|line| hmo_claims_billing_item::ActiveModel {
+            verification_id: Set(line.verification.id),
+            hmo_id: Set(the_hmo.id),
+            endorsement_id: Set(line.endorsement_id),
+            dental_service_id: Set(line.verification.dental_service_id),
+            dentist_id: Set(line.verification.dentist_id),
+            date_service_performed: Set(line.verification.date_service_performed),
+            period_start: Set(start_date),
+            period_end: Set(end_date),
+            rate: Set(line.price.rate),
+            approved_cost: Set(line.price.approved_cost),
+            amount: Set(line.price.amount),
+            request_key: Set(request_key.to_string()),
+            generated_report_id: Set(Some(report.id)),
+            billed_by: Set(billed_by.to_string()),
+            billed_on: Set(now),
+            ..Default::default()
+        })
+        .collect();
+    hmo_claims_billing_item::Entity::insert_many(items)
+        .exec(&txn)
+        .await
+        .map_err(|e| anyhow::anyhow!(
+            "Could not mark claims billed for HMO {}; another run may have billed them: {}",
+            the_hmo.short_name, e
+        ))?;
+
+    let invoice = receivables::issue_claims_invoice(&txn, &report, the_hmo.id, start_date, end_date, total_amount, billed_by).await?;
+
+    write_hmo_claims_to_spreadsheet(&the_hmo.short_name, &lines, start_date, end_date, &the_filename)?;
+    txn.commit().await?;
+
+    info!(target: "jobs",
+        "generate_claims_statement_for_hmo() billed {} verification(s) for HMO {} totalling {} in {}",
+        lines.len(),
+        the_hmo.short_name,
+        total_amount,
+        the_filename
+    );
+    statement.file_name = Some(the_filename);
+    statement.generated_report_id = Some(report.id);
+    statement.invoice_number = Some(invoice.invoice_number);
+    Ok(statement)
+}
+
+/// Loads the HMO's reconciled, unbilled verifications for the period and prices each.
+/// Returns the billable lines and the ids of the ones with nothing to bill.
+async fn load_claim_lines(
+    db: &DatabaseConnection,
+    hmo_id: i32,
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+) -> anyhow::Result<(Vec<ClaimLine>, Vec<i32>)> {
+    let already_billed = Query::select()
+        .column(hmo_claims_billing_item::Column::VerificationId)
+        .from(hmo_claims_billing_item::Entity)
+        .to_owned();
+
+    let rows = verification::Entity::find()
+        .join(JoinType::InnerJoin, verification::Relation::MasterListMember.def())
+        .join(JoinType::InnerJoin, master_list_member::Relation::Endorsement.def())
+        .filter(endorsement::Column::HmoId.eq(hmo_id))
+        .filter(verification::Column::IsReconciled.eq(true))
+        .filter(verification::Column::StatusId.ne(VerificationStatus::Voided.int_code()))
+        .filter(verification::Column::DateServicePerformed.gte(start_date))
+        .filter(verification::Column::DateServicePerformed.lte(end_date))
+        .filter(verification::Column::Id.not_in_subquery(already_billed))
+        .select_also(master_list_member::Entity)
+        .order_by_asc(verification::Column::DateServicePerformed)
+        .order_by_asc(verification::Column::Id)
+        .all(db)
+        .await?;
+    if rows.is_empty() {
+        return Ok((Vec::new(), Vec::new()));
+    }
+
+    let endorsement_ids: HashSet<i32> = rows
+        .iter()
+        .filter_map(|(_, member)| member.as_ref().map(|m| m.endorsement_id))
+        .collect();
+    let verification_ids: Vec<i32> = rows.iter().map(|(v, _)| v.id).collect();
+    let dentist_ids: HashSet<i32> = rows.iter().map(|(v, _)| v.dentist_id).collect();
+
+    let rates: HashMap<(i32, i32), Decimal> = endorsement_rates::Entity::find()
+        .filter(endorsement_rates::Column::EndorsementId.is_in(endorsement_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|r| ((r.endorsement_id, r.dental_services_id), r.rate))
+        .collect();
+    let approved_costs: HashMap<i32, Decimal> = high_end_verification_information::Entity::find()
+        .filter(high_end_verification_information::Column::VerificationId.is_in(verification_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|h| h.approved_cost.map(|cost| (h.verification_id, cost)))
+        .collect();
+    let services: HashMap<i32, dental_service::Model> = dental_service::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|s| (s.id, s))
+        .collect();
+    let dentist_names: HashMap<i32, String> = dentist::Entity::find()
+        .filter(dentist::Column::Id.is_in(dentist_ids))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|d| (d.id, format_dentist_name(&d.last_name, &d.given_name, d.middle_name.as_deref())))
+        .collect();
+
+    let mut lines = Vec::new();
+    let mut unpriced = Vec::new();
+    for (the_verification, member) in rows {
+        let Some(member) = member else { continue };
+        let service = services.get(&the_verification.dental_service_id);
+        let is_high_end = service.is_some_and(|s| s.type_id == HIGH_END_SERVICE_TYPE_ID);
+        let price = price_claim(
+            rates.get(&(member.endorsement_id, the_verification.dental_service_id)).copied(),
+            approved_costs.get(&the_verification.id).copied(),
+            is_high_end,
+        );
+        match price {
+            Some(price) => lines.push(ClaimLine {
+                endorsement_id: member.endorsement_id,
+                dentist_name: dentist_names.get(&the_verification.dentist_id).cloned().unwrap_or_default(),
+                service_name: service.map(|s| s.name.clone()).unwrap_or_default(),
+                verification: the_verification,
+                member,
+                price,
+            }),
+            None => unpriced.push(the_verification.id),
+        }
+    }
+    Ok((lines, unpriced))
+}
+
+fn format_dentist_name(
+    last_name: &str,
+    given_name: &str,
+    middle_name: Option<&str>,
+) -> String {
+    match middle_name {
+        Some(middle) if !middle.trim().is_empty() => {
+            format!("{}, {} {}", last_name, given_name, middle)
+        }
+        _ => format!("{}, {}", last_name, given_name),
+    }
+}
+
+/// write_hmo_claims_to_spreadsheet() writes one line per billed verification into
+/// Claims Template.xlsx. The template is laid out for the dentist-by-HMO matrix, so the
+/// header row is rewritten and the HMO columns and breakdown are cleared.
+fn write_hmo_claims_to_spreadsheet(
+    hmo_name: &str,
+    lines: &[ClaimLine],
+    start_date: NaiveDate,
+    end_date: NaiveDate,
+    file_name: &str,
+) -> anyhow::Result<()> {
+    //----1. Load the template
+    let template_path = "billing_templates/Claims Template.xlsx";
+    let mut book = umya_spreadsheet::reader::xlsx::read(template_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read Claims Template.xlsx: {}", e))?;
+    let sheet = book
+        .get_sheet_mut(&0)
+        .ok_or_else(|| anyhow::anyhow!("Claims template has no worksheet"))?;
+
+    //----2. Title
+    sheet.get_cell_mut("A2").set_value(format!(
+        "SUMMARY OF CLAIMS - {} SOA {}",
+        hmo_name,
+        end_date.format("%m-%y")
+    ));
+    sheet.get_cell_mut("A3").set_value(format!(
+        "{} - {}",
+        start_date.format("%B %d"),
+        end_date.format("%B %d, %Y")
+    ));
+
+    //----3. Headers; the template's HMO columns (H..T) are not used
+    let header_row: u32 = 6;
+    let first_data_row: u32 = 7;
+    let last_template_col: u32 = 20;
+    let headers = [
+        "DENTIST NAME",
+        "MEMBER",
+        "DATE OF SERVICE",
+        "SERVICE",
+        "APPROVAL CODE",
+        "RATE",
+        "APPROVED COST",
+        "AMOUNT",
+    ];
+    for col in 1..=last_template_col {
+        let header = headers.get(col as usize - 1).copied().unwrap_or("");
+        sheet.get_cell_mut((col, header_row)).set_value(header);
+    }
+
+    //----4. Claim lines, inserted above the template row which is then removed
+    let data_row_count = lines.len() as u32;
+    sheet.insert_new_row(&first_data_row, &data_row_count);
+    for (index, line) in lines.iter().enumerate() {
+        let excel_row = first_data_row + index as u32;
+        let member = &line.member;
+        sheet.get_cell_mut((1, excel_row)).set_value(line.dentist_name.as_str());
+        sheet.get_cell_mut((2, excel_row)).set_value(format!(
+            "{}, {} {} ({})",
+            member.last_name, member.first_name, member.middle_name, member.account_number
+        ));
+        sheet.get_cell_mut((3, excel_row)).set_value(
+            line.verification
+                .date_service_performed
+                .map(|d| d.format("%m/%d/%Y").to_string())
+                .unwrap_or_default(),
+        );
+        sheet.get_cell_mut((4, excel_row)).set_value(line.service_name.as_str());
+        sheet.get_cell_mut((5, excel_row))
+            .set_value(line.verification.approval_code.clone().unwrap_or_default());
+        sheet.get_cell_mut((6, excel_row)).set_value_number(to_f64(line.price.rate));
+        sheet.get_cell_mut((7, excel_row))
+            .set_value_number(to_f64(line.price.approved_cost.unwrap_or_default()));
+        sheet.get_cell_mut((8, excel_row)).set_value_number(to_f64(line.price.amount));
+    }
+    sheet.remove_row(&(first_data_row + data_row_count), &1);
+
+    //----5. Totals. Template rows below the data moved down by data_row_count - 1.
+    let shift = data_row_count - 1;
+    let rate_total: Decimal = lines.iter().map(|l| l.price.rate).sum();
+    let approved_cost_total: Decimal = lines.iter().map(|l| l.price.approved_cost.unwrap_or_default()).sum();
+    let total: Decimal = lines.iter().map(|l| l.price.amount).sum();
+
+    let total_row = 8 + shift;
+    for col in 4..=last_template_col {
+        sheet.get_cell_mut((col, total_row)).set_value("");
+    }
+    sheet.get_cell_mut((3, total_row)).set_value("TOTAL");
+    sheet.get_cell_mut((6, total_row)).set_value_number(to_f64(rate_total));
+    sheet.get_cell_mut((7, total_row)).set_value_number(to_f64(approved_cost_total));
+    sheet.get_cell_mut((8, total_row)).set_value_number(to_f64(total));
+
+    let grand_total_row = 11 + shift;
+    sheet.get_cell_mut((6, grand_total_row)).set_value("");
+    sheet.get_cell_mut((8, grand_total_row)).set_value_number(to_f64(total));
+
+    // per-HMO breakdown (template rows 15-25) becomes this HMO's line only
+    sheet.get_cell_mut((1, 15 + shift)).set_value(hmo_name.to_uppercase());
+    sheet.get_cell_mut((2, 15 + shift)).set_value_number(to_f64(total));
+    for row in 16..=25 {
+        sheet.get_cell_mut((1, row + shift)).set_value("");
+        sheet.get_cell_mut((2, row + shift)).set_value("");
+    }
+    sheet.get_cell_mut((2, 26 + shift)).set_value_number(0.0);
+    sheet.get_cell_mut((2, 27 + shift)).set_value_number(0.0);
+    sheet.get_cell_mut((2, 28 + shift)).set_value_number(to_f64(total));
+
+    //----6. Save
+    let full_filename = format!("generated_reports/{}", file_name);
+    info!(target: "jobs", " writing claims statement for {} to {}", hmo_name, full_filename);
+    umya_spreadsheet::writer::xlsx::write(&book, &full_filename)
+        .map_err(|e| anyhow::anyhow!("Failed to write XLSX: {}", e))?;
+    Ok(())
+}
+
+fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
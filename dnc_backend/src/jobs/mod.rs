@@ -1,17 +1,31 @@
 pub mod report_generation;
 pub mod hmo_billing;
-
-use chrono::{DateTime, Datelike, Days, LocalResult, TimeZone, Utc};
-use chrono_tz::Asia::Manila;
+pub mod billing_amounts;
+pub mod hmo_claims_billing;
+pub mod billing_runs;
+pub mod receivables;
+pub mod queue;
+pub mod dentist_payouts;
+pub mod withholding_tax;
+pub mod bir_2307;
+pub mod bank_disbursement;
+pub mod contract_rates;
+pub mod approval_code_validity;
+
+use chrono::Utc;
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
 use tracing::{error, info, instrument};
 
 use crate::AppState;
 use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
-use sea_orm::sea_query::Expr;
 
-use crate::entities::{verification, verification_status};
+use crate::business_calendar::{self, BusinessCalendar};
+use crate::entities::verification;
+use crate::verification_state::{self, VerificationStatus};
+
+/// How many business days a verification may wait for its approval code before it expires.
+pub const PENDING_VERIFICATION_BUSINESS_DAYS: u32 = 7;
 
 
 /// Starts the in-process background worker.
@@ -35,8 +49,8 @@ pub fn start_daily_worker(state: AppState) -> JoinHandle<()> {
 
         loop {
             let now_utc = Utc::now();
-            let next_run_utc = next_manila_midnight_utc(now_utc);
-            let next_run_manila = next_run_utc.with_timezone(&Manila);
+            let next_run_utc = business_calendar::next_midnight_utc(now_utc);
+            let next_run_manila = next_run_utc.with_timezone(&business_calendar::TIMEZONE);
 
             let sleep_duration = (next_run_utc - now_utc)
                 .to_std()
@@ -60,105 +74,60 @@ pub fn start_daily_worker(state: AppState) -> JoinHandle<()> {
     })
 }
 
-/// Computes the next midnight in Asia/Manila, returned in UTC.
-fn next_manila_midnight_utc(now_utc: DateTime<Utc>) -> DateTime<Utc> {
-    let now_manila = now_utc.with_timezone(&Manila);
-
-    let next_date = now_manila
-        .date_naive()
-        .checked_add_days(Days::new(1))
-        .expect("date overflow while computing next Manila midnight");
-
-    let next_midnight_manila = match Manila.with_ymd_and_hms(
-        next_date.year(),
-        next_date.month(),
-        next_date.day(),
-        0,
-        0,
-        0,
-    ) {
-        LocalResult::Single(dt) => dt,
-        LocalResult::Ambiguous(dt, _) => dt,
-        LocalResult::None => panic!("Could not compute next Manila midnight"),
-    };
-
-    next_midnight_manila.with_timezone(&Utc)
-}
-
 // region: run_daily_job_once()
-// run_daily_job_once() runs once a day to:
-// 1. load all verifications that are still in status_id = 2
+// run_daily_job_once() runs once a day to queue (see queue.rs):
+// 1. expire verifications still waiting for an approval code after 7 business days
+// 2. lapse released approval codes not reconciled within their validity window
+// The dedupe key is the Manila date, so each instance queueing it still makes one job a day.
 #[instrument(skip(state), err)]
 async fn run_daily_job_once(
     state: AppState,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-
-    expire_verifications_older_than_seven_days(state).await?;
+    let today_manila = business_calendar::today();
+
+    queue::enqueue(
+        &state.db,
+        &queue::Job::ExpireVerifications,
+        Some(format!("expire_verifications:{}", today_manila)),
+        "system",
+    )
+        .await?;
+    queue::enqueue(
+        &state.db,
+        &queue::Job::LapseApprovalCodes,
+        Some(format!("lapse_approval_codes:{}", today_manila)),
+        "system",
+    )
+        .await?;
 
     Ok(())
 }
 
-async fn expire_verifications_older_than_seven_days(state: AppState) -> anyhow::Result<()> {
+pub(crate) async fn expire_verifications_older_than_seven_days(state: AppState) -> anyhow::Result<()> {
     // ---- 0. setup variables.
     let db = &state.db;
 
-    let now_manila = Utc::now().with_timezone(&Manila);
+    let now_manila = business_calendar::now();
     let today_manila = now_manila.date_naive();
+    let calendar = BusinessCalendar::load(db).await?;
 
     info!(
         target: "jobs",
         "expire_verifications_older_than_seven_days() started at Manila={}",
         now_manila.format("%Y-%m-%d %H:%M:%S")
     );
-    // ----0. Get the verification_status with name = "Waiting for Approval Code"
-    let waiting_for_approval_code_status = verification_status::Entity::find()
-        .filter(verification_status::Column::Name.eq("Waiting for Approval Code"))
-        .one(db)
-        .await?
-        .ok_or_else(|| {
-            anyhow::anyhow!("verification_status named 'Waiting for Approval Code' was not found")
-        })?;
-    let waiting_for_approval_code = waiting_for_approval_code_status.int_code;
-    info!(
-        target: "jobs",
-        "Waiting for Approval Code Code is: {}",
-        waiting_for_approval_code
-    );
-
-    // --- 0.5 Get the verification_status with name = "Expired"
-    let expired_code_status = verification_status::Entity::find()
-        .filter(verification_status::Column::Name.eq("Expired"))
-        .one(db)
-        .await?
-        .ok_or_else(|| {
-            anyhow::anyhow!("verification_status named 'Expired' was not found")
-        })?;
-    let expired_code = expired_code_status.int_code;
-    info!(
-        target: "jobs",
-        "Waiting for Expired Code is: {}",
-        expired_code
-    );
-
-    // ---- 1. Load only verifications that are still in status_id = int_code for "Waiting for Approval Code"
+    // ---- 1. Load only verifications that are still "Waiting for Approval Code"
     let pending_verifications = verification::Entity::find()
-        .filter(verification::Column::StatusId.eq(waiting_for_approval_code))
+        .filter(verification::Column::StatusId.eq(VerificationStatus::WaitingForApprovalCode.int_code()))
         .all(db)
         .await?;
 
-    // 2. Decide which ones are older than 7 days, excluding Sundays
+    // 2. Decide which ones are older than 7 business days
     let ids_to_expire: Vec<i32> = pending_verifications
-        .into_iter()
+        .iter()
         .filter(|verification| {
-            let created_date_manila = verification
-                .date_created
-                .with_timezone(&Manila)
-                .date_naive();
-
-            let elapsed_non_sunday_days =
-                count_elapsed_days_excluding_sundays(created_date_manila, today_manila);
-
-            elapsed_non_sunday_days > 7
+            let created_date_manila = business_calendar::date_of(&verification.date_created);
+            calendar.business_days_between(created_date_manila, today_manila) > i64::from(PENDING_VERIFICATION_BUSINESS_DAYS)
         })
         .map(|verification| verification.id)
         .collect();
@@ -172,16 +141,28 @@ async fn expire_verifications_older_than_seven_days(state: AppState) -> anyhow::
     }
 
 
-    let update_result = verification::Entity::update_many()
-        .col_expr(verification::Column::StatusId, Expr::value(expired_code))
-        .filter(verification::Column::Id.is_in(ids_to_expire.clone()))
-        .exec(db)
-        .await?;
+    // Update one at a time through the state machine so each expiry is written to
+    // audit_log and verification_status_history.
+    let mut rows_affected = 0_u64;
+    for verification_to_expire in pending_verifications
+        .into_iter()
+        .filter(|verification| ids_to_expire.contains(&verification.id))
+    {
+        verification_state::transition(
+            db,
+            verification_to_expire.into(),
+            VerificationStatus::Expired,
+            "system",
+            Some("no approval code released within 7 business days"),
+        )
+            .await?;
+        rows_affected += 1;
+    }
 
     info!(
         target: "jobs",
-        "run_daily_job_once() finished: updated {} verification(s) to status_id=int_code('Expired'); ids={:?}",
-        update_result.rows_affected,
+        "run_daily_job_once() finished: updated {} verification(s) to Expired; ids={:?}",
+        rows_affected,
         ids_to_expire
     );
 
@@ -191,31 +172,3 @@ async fn expire_verifications_older_than_seven_days(state: AppState) -> anyhow::
 
 // endregion: run_daily_job_once()
 
-
-fn count_elapsed_days_excluding_sundays(
-    start_date: chrono::NaiveDate,
-    end_date: chrono::NaiveDate,
-) -> i64 {
-    use chrono::{Days, Weekday};
-
-    if end_date <= start_date {
-        return 0;
-    }
-
-    let mut count = 0_i64;
-    let mut current = start_date
-        .checked_add_days(Days::new(1))
-        .expect("date overflow while counting elapsed days");
-
-    while current < end_date {
-        if current.weekday() != Weekday::Sun {
-            count += 1;
-        }
-
-        current = current
-            .checked_add_days(Days::new(1))
-            .expect("date overflow while counting elapsed days");
-    }
-
-    count
-}
@@ -0,0 +1,401 @@
+//! DB-backed job queue.
+//!
+//! Background work is queued as a row in `job` and run by `start_job_worker`. Every backend
+//! instance runs a worker; a job is claimed with SELECT ... FOR UPDATE SKIP LOCKED, so it runs
+//! in exactly one of them. A failed attempt is queued again after `retry_delay`, until the
+//! job's max_attempts is used up and it is marked failed. Scheduled jobs are queued with a
+//! dedupe_key, so two instances queueing the same run end up with one row.
+//!
+//!   queued ──► running ──► succeeded
+//!     ▲           │
+//!     └─ retry ◄──┴──► failed ──► (retry by hand) queued
+use std::time::Duration as StdDuration;
+
+use axum::http::StatusCode;
+use chrono::{Duration, NaiveDate, Utc};
+use sea_orm::sea_query::{Expr, LockBehavior, LockType};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, Set, SqlErr, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::AppState;
+use crate::entities::job;
+
+/// How long an idle worker waits before looking for queued jobs again.
+const POLL_INTERVAL: StdDuration = StdDuration::from_secs(15);
+
+/// A job still running after this long is taken to have died with its process.
+const ABANDONED_JOB_AFTER_HOURS: i64 = 6;
+
+const FIRST_RETRY_DELAY_MINUTES: i64 = 1;
+const MAX_RETRY_DELAY_MINUTES: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    /// Stored in job.status.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "queued" => Some(JobStatus::Queued),
+            "running" => Some(JobStatus::Running),
+            "succeeded" => Some(JobStatus::Succeeded),
+            "failed" => Some(JobStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+
+// region: Job Types
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HmoBillingJob {
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+}
+
+/// The kinds of work the queue can run. job.job_type holds `job_type()`, job.payload holds
+/// the variant's data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Job {
+    ExpireVerifications,
+    LapseApprovalCodes,
+    HmoBilling(HmoBillingJob),
+}
+
+impl Job {
+    pub const fn job_type(&self) -> &'static str {
+        match self {
+            Job::ExpireVerifications => "expire_verifications",
+            Job::LapseApprovalCodes => "lapse_approval_codes",
+            Job::HmoBilling(_) => "hmo_billing",
+        }
+    }
+
+    pub fn payload(&self) -> Option<JsonValue> {
+        match self {
+            Job::ExpireVerifications | Job::LapseApprovalCodes => None,
+            Job::HmoBilling(p) => Some(json!(p)),
+        }
+    }
+
+    pub fn parse(job_type: &str, payload: Option<&JsonValue>) -> Result<Self, JobError> {
+        let invalid = |e: serde_json::Error| JobError::InvalidPayload(job_type.to_string(), e.to_string());
+        match job_type {
+            "expire_verifications" => Ok(Job::ExpireVerifications),
+            "lapse_approval_codes" => Ok(Job::LapseApprovalCodes),
+            "hmo_billing" => {
+                let payload = payload.cloned().unwrap_or(JsonValue::Null);
+                Ok(Job::HmoBilling(serde_json::from_value(payload).map_err(invalid)?))
+            }
+            other => Err(JobError::UnknownJobType(other.to_string())),
+        }
+    }
+}
+
+/// Job types accepted by POST /api/jobs.
+pub const JOB_TYPES: [&str; 3] = ["expire_verifications", "lapse_approval_codes", "hmo_billing"];
+
+async fn run_job(state: &AppState, job: &Job, created_by: &str) -> anyhow::Result<()> {
+    match job {
+        Job::ExpireVerifications => super::expire_verifications_older_than_seven_days(state.clone()).await,
+        Job::LapseApprovalCodes => super::approval_code_validity::lapse_unreconciled_approval_codes(state.clone()).await,
+        Job::HmoBilling(p) => {
+            super::hmo_billing::generate_hmo_billing_reports(state.clone(), Some(p.period_start), p.period_end, created_by)
+                .await
+                .map(|_| ())
+        }
+    }
+}
+// endregion: Job Types
+
+
+// region: Errors
+
+#[derive(Debug, thiserror::Error)]
+pub enum JobError {
+    #[error("Job {0} not found")]
+    NotFound(i32),
+
+    #[error("Job {id} is {status}; only failed jobs can be retried")]
+    NotRetryable { id: i32, status: String },
+
+    #[error("Unknown job type '{0}'")]
+    UnknownJobType(String),
+
+    #[error("Invalid payload for job type '{0}': {1}")]
+    InvalidPayload(String, String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+impl From<JobError> for (StatusCode, String) {
+    fn from(e: JobError) -> Self {
+        let status = match e {
+            JobError::NotFound(_) => StatusCode::NOT_FOUND,
+            JobError::NotRetryable { .. } => StatusCode::CONFLICT,
+            JobError::UnknownJobType(_) | JobError::InvalidPayload(..) => StatusCode::BAD_REQUEST,
+            JobError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, e.to_string())
+    }
+}
+// endregion: Errors
+
+
+// region: Queue
+
+/// Wait before the next attempt after `attempts` failed ones: 1, 2, 4, ... minutes, at most an hour.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    let minutes = FIRST_RETRY_DELAY_MINUTES.saturating_mul(1_i64 << doublings);
+    Duration::minutes(minutes.min(MAX_RETRY_DELAY_MINUTES))
+}
+
+/// Queues a job. With a dedupe_key that is already queued (or ran), the existing row is
+/// returned instead.
+pub async fn enqueue(
+    db: &DatabaseConnection,
+    job: &Job,
+    dedupe_key: Option<String>,
+    created_by: &str,
+) -> Result<job::Model, JobError> {
+    let now = Utc::now().fixed_offset();
+    let inserted = job::ActiveModel {
+        job_type: Set(job.job_type().to_string()),
+        payload: Set(job.payload()),
+        status: Set(JobStatus::Queued.as_str().to_string()),
+        run_after: Set(now),
+        dedupe_key: Set(dedupe_key.clone()),
+        created_by: Set(created_by.to_string()),
+        created_on: Set(now),
+        ..Default::default()
+    }
+        .insert(db)
+        .await;
+
+    match (inserted, dedupe_key) {
+        (Ok(row), _) => {
+            info!(target: "jobs", "job {} ({}) queued by {}", row.id, row.job_type, created_by);
+            Ok(row)
+        }
+        (Err(err), Some(key)) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+            job::Entity::find()
+                .filter(job::Column::DedupeKey.eq(key.clone()))
+                .one(db)
+                .await?
+                .ok_or_else(|| JobError::Database(DbErr::Custom(format!("job with dedupe_key {} vanished", key))))
+        }
+        (Err(err), _) => Err(err.into()),
+    }
+}
+
+/// Takes the next queued job that is due, marking it running for `worker_id`.
+async fn claim_next(db: &DatabaseConnection, worker_id: &str) -> Result<Option<job::Model>, DbErr> {
+    let txn = db.begin().await?;
+    let now = Utc::now().fixed_offset();
+
+    let next = job::Entity::find()
+        .filter(job::Column::Status.eq(JobStatus::Queued.as_str()))
+        .filter(job::Column::RunAfter.lte(now))
+        .order_by_asc(job::Column::RunAfter)
+        .order_by_asc(job::Column::Id)
+        .limit(1)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .one(&txn)
+        .await?;
+
+    let Some(next) = next else {
+        txn.commit().await?;
+        return Ok(None);
+    };
+
+    let attempts = next.attempts + 1;
+    let mut am: job::ActiveModel = next.into();
+    am.status = Set(JobStatus::Running.as_str().to_string());
+    am.attempts = Set(attempts);
+    am.locked_by = Set(Some(worker_id.to_string()));
+    am.started_on = Set(Some(now));
+    am.finished_on = Set(None);
+    let claimed = am.update(&txn).await?;
+
+    txn.commit().await?;
+    Ok(Some(claimed))
+}
+
+/// Records the outcome of an attempt: succeeded, queued again after a delay, or failed for good.
+async fn finish(db: &DatabaseConnection, claimed: job::Model, outcome: Result<(), String>) -> Result<job::Model, DbErr> {
+    let now = Utc::now();
+    let attempts = claimed.attempts;
+    let max_attempts = claimed.max_attempts;
+
+    let mut am: job::ActiveModel = claimed.into();
+    am.locked_by = Set(None);
+    match outcome {
+        Ok(()) => {
+            am.status = Set(JobStatus::Succeeded.as_str().to_string());
+            am.finished_on = Set(Some(now.fixed_offset()));
+        }
+        Err(err) if attempts < max_attempts => {
+            am.status = Set(JobStatus::Queued.as_str().to_string());
+            am.run_after = Set((now + retry_delay(attempts)).fixed_offset());
+            am.last_error = Set(Some(err));
+        }
+        Err(err) => {
+            am.status = Set(JobStatus::Failed.as_str().to_string());
+            am.finished_on = Set(Some(now.fixed_offset()));
+            am.last_error = Set(Some(err));
+        }
+    }
+    am.update(db).await
+}
+
+/// Puts jobs whose worker died back in the queue, or fails them if they have no attempts left.
+async fn recover_abandoned(db: &DatabaseConnection) -> Result<u64, DbErr> {
+    let abandoned_before = (Utc::now() - Duration::hours(ABANDONED_JOB_AFTER_HOURS)).fixed_offset();
+    let abandoned = Condition::all()
+        .add(job::Column::Status.eq(JobStatus::Running.as_str()))
+        .add(job::Column::StartedOn.lt(abandoned_before));
+    let message = format!("abandoned: still running after {} hours", ABANDONED_JOB_AFTER_HOURS);
+
+    let failed = job::Entity::update_many()
+        .col_expr(job::Column::Status, Expr::value(JobStatus::Failed.as_str()))
+        .col_expr(job::Column::FinishedOn, Expr::value(Utc::now().fixed_offset()))
+        .col_expr(job::Column::LockedBy, Expr::value(Option::<String>::None))
+        .col_expr(job::Column::LastError, Expr::value(message.clone()))
+        .filter(abandoned.clone())
+        .filter(Expr::col(job::Column::Attempts).gte(Expr::col(job::Column::MaxAttempts)))
+        .exec(db)
+        .await?
+        .rows_affected;
+
+    let requeued = job::Entity::update_many()
+        .col_expr(job::Column::Status, Expr::value(JobStatus::Queued.as_str()))
+        .col_expr(job::Column::RunAfter, Expr::value(Utc::now().fixed_offset()))
+        .col_expr(job::Column::LockedBy, Expr::value(Option::<String>::None))
+        .col_expr(job::Column::LastError, Expr::value(message))
+        .filter(abandoned)
+        .exec(db)
+        .await?
+        .rows_affected;
+
+    Ok(failed + requeued)
+}
+
+/// Queues a failed job again with a fresh set of attempts.
+pub async fn retry_job(db: &DatabaseConnection, id: i32, retried_by: &str) -> Result<job::Model, JobError> {
+    let row = job::Entity::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or(JobError::NotFound(id))?;
+    if JobStatus::parse(&row.status) != Some(JobStatus::Failed) {
+        return Err(JobError::NotRetryable { id, status: row.status });
+    }
+
+    // only a job that is still failed is moved, in case another request retried it first
+    let result = job::Entity::update_many()
+        .col_expr(job::Column::Status, Expr::value(JobStatus::Queued.as_str()))
+        .col_expr(job::Column::Attempts, Expr::value(0))
+        .col_expr(job::Column::RunAfter, Expr::value(Utc::now().fixed_offset()))
+        .col_expr(job::Column::FinishedOn, Expr::value(Option::<chrono::DateTime<chrono::FixedOffset>>::None))
+        .filter(job::Column::Id.eq(id))
+        .filter(job::Column::Status.eq(JobStatus::Failed.as_str()))
+        .exec(db)
+        .await?;
+
+    let row = job::Entity::find_by_id(id)
+        .one(db)
+        .await?
+        .ok_or(JobError::NotFound(id))?;
+    if result.rows_affected == 0 {
+        return Err(JobError::NotRetryable { id, status: row.status });
+    }
+    info!(target: "jobs", "job {} ({}) retried by {}", id, row.job_type, retried_by);
+    Ok(row)
+}
+// endregion: Queue
+
+
+// region: Worker
+
+/// Starts this process's job worker. It runs queued jobs one at a time, back to back, and
+/// polls every POLL_INTERVAL when the queue is empty.
+pub fn start_job_worker(state: AppState) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let worker_id = format!("{}-{}", std::process::id(), Uuid::new_v4());
+        info!(target: "jobs", "Job worker {} started", worker_id);
+
+        loop {
+            match run_next_job(&state, &worker_id).await {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(err) => error!(target: "jobs", "Job worker {} failed to poll the queue: {}", worker_id, err),
+            }
+            sleep(POLL_INTERVAL).await;
+        }
+    })
+}
+
+/// Runs one job if there is one due. Returns whether it did.
+async fn run_next_job(state: &AppState, worker_id: &str) -> Result<bool, DbErr> {
+    let db = &state.db;
+
+    let recovered = recover_abandoned(db).await?;
+    if recovered > 0 {
+        warn!(target: "jobs", "{} abandoned job(s) recovered", recovered);
+    }
+
+    let Some(claimed) = claim_next(db, worker_id).await? else {
+        return Ok(false);
+    };
+    info!(target: "jobs",
+        "job {} ({}) attempt {}/{} started on worker {}",
+        claimed.id, claimed.job_type, claimed.attempts, claimed.max_attempts, worker_id
+    );
+
+    let outcome = match Job::parse(&claimed.job_type, claimed.payload.as_ref()) {
+        Ok(job) => run_job(state, &job, &claimed.created_by).await.map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+
+    let finished = finish(db, claimed, outcome).await?;
+    match JobStatus::parse(&finished.status) {
+        Some(JobStatus::Succeeded) => info!(target: "jobs", "job {} ({}) succeeded", finished.id, finished.job_type),
+        Some(JobStatus::Queued) => warn!(target: "jobs",
+            "job {} ({}) attempt {} failed, retrying at {}: {}",
+            finished.id, finished.job_type, finished.attempts, finished.run_after,
+            finished.last_error.as_deref().unwrap_or_default()
+        ),
+        _ => error!(target: "jobs",
+            "job {} ({}) failed after {} attempt(s): {}",
+            finished.id, finished.job_type, finished.attempts,
+            finished.last_error.as_deref().unwrap_or_default()
+        ),
+    }
+    Ok(true)
+}
+// endregion: Worker
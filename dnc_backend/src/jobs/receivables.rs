@@ -0,0 +1,444 @@
+//! HMO accounts receivable.
+//!
+//! Every billing statement sent to an HMO becomes an invoice: the HMO billing statements of a
+//! billing run when the run is finalized, and an HMO claims statement as soon as it is
+//! generated. Payments (full or partial, each with its OR number) are recorded against an
+//! invoice until its balance reaches zero. Voiding a finalized billing run voids its invoices,
+//! which is refused once any of them has been paid.
+//!
+//!   open ──► paid
+//!   open ──► void
+use std::collections::{BTreeMap, HashMap};
+
+use axum::http::StatusCode;
+use chrono::{Datelike, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr, EntityTrait,
+    JoinType, QueryFilter, QueryOrder, QuerySelect, RelationTrait, Set, TransactionTrait,
+};
+use serde::Serialize;
+use tracing::info;
+
+use crate::business_calendar;
+use crate::entities::{
+    billing_run, endorsement, generated_report, hmo, hmo_billing_data, hmo_invoice, hmo_payment,
+};
+use crate::jobs::billing_amounts::money;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InvoiceStatus {
+    Open,
+    Paid,
+    Void,
+}
+
+impl InvoiceStatus {
+    /// Stored in hmo_invoice.status.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            InvoiceStatus::Open => "open",
+            InvoiceStatus::Paid => "paid",
+            InvoiceStatus::Void => "void",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "open" => Some(InvoiceStatus::Open),
+            "paid" => Some(InvoiceStatus::Paid),
+            "void" => Some(InvoiceStatus::Void),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReceivableError {
+    #[error("Invoice {0} not found")]
+    NotFound(i32),
+
+    #[error("Invoice {invoice_number} is {status}; payments cannot be recorded against it")]
+    NotOpen { invoice_number: String, status: String },
+
+    #[error("The payment amount must be greater than zero")]
+    NonPositiveAmount,
+
+    #[error("The payment of {amount} is more than the {balance} balance of invoice {invoice_number}")]
+    Overpayment {
+        invoice_number: String,
+        amount: Decimal,
+        balance: Decimal,
+    },
+
+    #[error("An OR number is required")]
+    OrNumberRequired,
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+impl From<ReceivableError> for (StatusCode, String) {
+    fn from(e: ReceivableError) -> Self {
+        let status = match e {
+            ReceivableError::NotFound(_) => StatusCode::NOT_FOUND,
+            ReceivableError::NotOpen { .. } => StatusCode::CONFLICT,
+            ReceivableError::NonPositiveAmount
+            | ReceivableError::Overpayment { .. }
+            | ReceivableError::OrNumberRequired => StatusCode::BAD_REQUEST,
+            ReceivableError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, e.to_string())
+    }
+}
+
+
+// region: Issuing Invoices
+
+/// e.g. INV-2026-000123 for generated_report 123.
+pub fn invoice_number(invoice_date: NaiveDate, generated_report_id: i32) -> String {
+    format!("INV-{}-{:06}", invoice_date.year(), generated_report_id)
+}
+
+async fn insert_invoice<C: ConnectionTrait>(
+    db: &C,
+    report: &generated_report::Model,
+    hmo_id: i32,
+    billing_run_id: Option<i32>,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    total_amount: Decimal,
+    created_by: &str,
+) -> Result<hmo_invoice::Model, DbErr> {
+    let now = Utc::now().fixed_offset();
+    let invoice_date = business_calendar::date_of(&now);
+    let status = if total_amount > Decimal::ZERO { InvoiceStatus::Open } else { InvoiceStatus::Paid };
+    let invoice = hmo_invoice::ActiveModel {
+        invoice_number: Set(invoice_number(invoice_date, report.id)),
+        hmo_id: Set(hmo_id),
+        generated_report_id: Set(report.id),
+        billing_run_id: Set(billing_run_id),
+        period_start: Set(period_start),
+        period_end: Set(period_end),
+        invoice_date: Set(invoice_date),
+        total_amount: Set(money(total_amount)),
+        amount_paid: Set(Decimal::ZERO),
+        status: Set(status.as_str().to_string()),
+        created_by: Set(created_by.to_string()),
+        created_on: Set(now),
+        ..Default::default()
+    }
+        .insert(db)
+        .await?;
+
+    info!(target: "jobs",
+        "invoice {} issued to HMO {} for {} ({})",
+        invoice.invoice_number, hmo_id, invoice.total_amount, report.file_name
+    );
+    Ok(invoice)
+}
+
+/// Issues an invoice for an HMO claims statement. Called in the transaction that bills it.
+pub(crate) async fn issue_claims_invoice<C: ConnectionTrait>(
+    db: &C,
+    report: &generated_report::Model,
+    hmo_id: i32,
+    period_start: NaiveDate,
+    period_end: NaiveDate,
+    total_amount: Decimal,
+    created_by: &str,
+) -> Result<hmo_invoice::Model, DbErr> {
+    insert_invoice(db, report, hmo_id, None, period_start, period_end, total_amount, created_by).await
+}
+
+/// Issues an invoice for each HMO billing statement of a run. Called in the transaction that
+/// finalizes it.
+pub(crate) async fn issue_run_invoices<C: ConnectionTrait>(
+    db: &C,
+    run: &billing_run::Model,
+    created_by: &str,
+) -> Result<Vec<hmo_invoice::Model>, DbErr> {
+    let reports = generated_report::Entity::find()
+        .filter(generated_report::Column::BillingRunId.eq(run.id))
+        .order_by_asc(generated_report::Column::Id)
+        .all(db)
+        .await?;
+
+    let mut invoices = Vec::new();
+    for report in reports {
+        let Some(hmo_id) = report.hmo_id else {
+            continue;
+        };
+        let total_amount: Decimal = hmo_billing_data::Entity::find()
+            .join(JoinType::InnerJoin, hmo_billing_data::Relation::Endorsement.def())
+            .filter(endorsement::Column::HmoId.eq(hmo_id))
+            .filter(hmo_billing_data::Column::BillingRunId.eq(run.id))
+            .all(db)
+            .await?
+            .iter()
+            .filter_map(|row| row.total_amount)
+            .sum();
+        invoices.push(
+            insert_invoice(db, &report, hmo_id, Some(run.id), run.period_start, run.period_end, total_amount, created_by)
+                .await?,
+        );
+    }
+    Ok(invoices)
+}
+
+/// The run's invoices that have payments recorded against them.
+pub(crate) async fn paid_run_invoices<C: ConnectionTrait>(
+    db: &C,
+    run_id: i32,
+) -> Result<Vec<hmo_invoice::Model>, DbErr> {
+    hmo_invoice::Entity::find()
+        .filter(hmo_invoice::Column::BillingRunId.eq(run_id))
+        .filter(hmo_invoice::Column::AmountPaid.gt(Decimal::ZERO))
+        .all(db)
+        .await
+}
+
+/// Voids the run's invoices. Callers check `paid_run_invoices` first.
+pub(crate) async fn void_run_invoices<C: ConnectionTrait>(
+    db: &C,
+    run_id: i32,
+    voided_by: &str,
+) -> Result<u64, DbErr> {
+    let result = hmo_invoice::Entity::update_many()
+        .col_expr(hmo_invoice::Column::Status, Expr::value(InvoiceStatus::Void.as_str()))
+        .col_expr(hmo_invoice::Column::VoidedBy, Expr::value(voided_by))
+        .col_expr(hmo_invoice::Column::VoidedOn, Expr::value(Utc::now().fixed_offset()))
+        .filter(hmo_invoice::Column::BillingRunId.eq(run_id))
+        .filter(hmo_invoice::Column::Status.ne(InvoiceStatus::Void.as_str()))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected)
+}
+// endregion: Issuing Invoices
+
+
+// region: Payments
+
+/// Records a payment against an open invoice and marks the invoice paid once its balance
+/// reaches zero. Payments larger than the balance are refused.
+pub async fn record_payment(
+    db: &DatabaseConnection,
+    invoice_id: i32,
+    payment_date: NaiveDate,
+    amount: Decimal,
+    or_number: &str,
+    remarks: Option<String>,
+    recorded_by: &str,
+) -> Result<(hmo_invoice::Model, hmo_payment::Model), ReceivableError> {
+    let amount = money(amount);
+    if amount <= Decimal::ZERO {
+        return Err(ReceivableError::NonPositiveAmount);
+    }
+    let or_number = or_number.trim();
+    if or_number.is_empty() {
+        return Err(ReceivableError::OrNumberRequired);
+    }
+
+    let txn = db.begin().await?;
+    let invoice = hmo_invoice::Entity::find_by_id(invoice_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+        .ok_or(ReceivableError::NotFound(invoice_id))?;
+    if InvoiceStatus::parse(&invoice.status) != Some(InvoiceStatus::Open) {
+        return Err(ReceivableError::NotOpen {
+            invoice_number: invoice.invoice_number,
+            status: invoice.status,
+        });
+    }
+
+    let balance = invoice.total_amount - invoice.amount_paid;
+    if amount > balance {
+        return Err(ReceivableError::Overpayment {
+            invoice_number: invoice.invoice_number,
+            amount,
+            balance,
+        });
+    }
+
+    let payment = hmo_payment::ActiveModel {
+        hmo_invoice_id: Set(invoice.id),
+        payment_date: Set(payment_date),
+        amount: Set(amount),
+        or_number: Set(or_number.to_string()),
+        remarks: Set(remarks),
+        recorded_by: Set(recorded_by.to_string()),
+        recorded_on: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    }
+        .insert(&txn)
+        .await?;
+
+    let amount_paid = invoice.amount_paid + amount;
+    let total_amount = invoice.total_amount;
+    let mut am: hmo_invoice::ActiveModel = invoice.into();
+    am.amount_paid = Set(amount_paid);
+    if amount_paid >= total_amount {
+        am.status = Set(InvoiceStatus::Paid.as_str().to_string());
+    }
+    let invoice = am.update(&txn).await?;
+
+    txn.commit().await?;
+    info!(target: "jobs",
+        "payment of {} (OR {}) recorded against invoice {} by {}",
+        payment.amount, payment.or_number, invoice.invoice_number, recorded_by
+    );
+    Ok((invoice, payment))
+}
+// endregion: Payments
+
+
+// region: Aging
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgingBucket {
+    /// 0 to 30 days since the invoice date
+    Current,
+    Days31To60,
+    Days61To90,
+    Over90,
+}
+
+pub fn aging_bucket(invoice_date: NaiveDate, as_of: NaiveDate) -> AgingBucket {
+    match (as_of - invoice_date).num_days() {
+        ..=30 => AgingBucket::Current,
+        31..=60 => AgingBucket::Days31To60,
+        61..=90 => AgingBucket::Days61To90,
+        _ => AgingBucket::Over90,
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HmoAging {
+    pub hmo_id: i32,
+    pub hmo_short_name: String,
+    pub invoice_count: usize,
+    pub current: Decimal,
+    pub days_31_to_60: Decimal,
+    pub days_61_to_90: Decimal,
+    pub over_90: Decimal,
+    pub total_outstanding: Decimal,
+}
+
+impl HmoAging {
+    fn add(&mut self, bucket: AgingBucket, balance: Decimal) {
+        match bucket {
+            AgingBucket::Current => self.current += balance,
+            AgingBucket::Days31To60 => self.days_31_to_60 += balance,
+            AgingBucket::Days61To90 => self.days_61_to_90 += balance,
+            AgingBucket::Over90 => self.over_90 += balance,
+        }
+        self.total_outstanding += balance;
+        self.invoice_count += 1;
+    }
+}
+
+/// What each HMO still owed as of the date: invoices issued on or before it, less the payments
+/// made on or before it, by age of invoice. HMOs that owe nothing are left out.
+pub async fn aging_report(db: &DatabaseConnection, as_of: NaiveDate) -> Result<Vec<HmoAging>, DbErr> {
+    let invoices = hmo_invoice::Entity::find()
+        .filter(hmo_invoice::Column::Status.ne(InvoiceStatus::Void.as_str()))
+        .filter(hmo_invoice::Column::InvoiceDate.lte(as_of))
+        .all(db)
+        .await?;
+    if invoices.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let invoice_ids: Vec<i32> = invoices.iter().map(|i| i.id).collect();
+    let mut paid_by_invoice: HashMap<i32, Decimal> = HashMap::new();
+    for payment in hmo_payment::Entity::find()
+        .filter(hmo_payment::Column::HmoInvoiceId.is_in(invoice_ids))
+        .filter(hmo_payment::Column::PaymentDate.lte(as_of))
+        .all(db)
+        .await?
+    {
+        *paid_by_invoice.entry(payment.hmo_invoice_id).or_default() += payment.amount;
+    }
+
+    let hmo_names: HashMap<i32, String> = hmo::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|h| (h.id, h.short_name))
+        .collect();
+
+    let mut by_hmo: BTreeMap<String, HmoAging> = BTreeMap::new();
+    for invoice in invoices {
+        let paid = paid_by_invoice.get(&invoice.id).copied().unwrap_or_default();
+        let balance = invoice.total_amount - paid;
+        if balance <= Decimal::ZERO {
+            continue;
+        }
+        let name = hmo_names.get(&invoice.hmo_id).cloned().unwrap_or_default();
+        by_hmo
+            .entry(name.clone())
+            .or_insert_with(|| HmoAging {
+                hmo_id: invoice.hmo_id,
+                hmo_short_name: name,
+                ..Default::default()
+            })
+            .add(aging_bucket(invoice.invoice_date, as_of), balance);
+    }
+
+    Ok(by_hmo.into_values().collect())
+}
+
+/// Writes the aging report to a new workbook.
+pub fn write_aging_to_workbook(rows: &[HmoAging], as_of: NaiveDate) -> umya_spreadsheet::Spreadsheet {
+    let mut workbook = umya_spreadsheet::new_file();
+    let sheet = workbook.get_sheet_mut(&0).expect("new workbook has a sheet");
+    sheet.set_name("Aging");
+
+    sheet.get_cell_mut("A1").set_value("HMO RECEIVABLES AGING");
+    sheet.get_cell_mut("A2").set_value(format!("As of {}", as_of.format("%B %-d, %Y")));
+
+    let header_row: u32 = 4;
+    let headers = ["HMO", "INVOICES", "CURRENT", "31-60 DAYS", "61-90 DAYS", "OVER 90 DAYS", "TOTAL"];
+    for (index, header) in headers.iter().enumerate() {
+        sheet.get_cell_mut((index as u32 + 1, header_row)).set_value(*header);
+    }
+
+    let mut totals = HmoAging::default();
+    for (index, row) in rows.iter().enumerate() {
+        let excel_row = header_row + 1 + index as u32;
+        sheet.get_cell_mut((1, excel_row)).set_value(row.hmo_short_name.as_str());
+        sheet.get_cell_mut((2, excel_row)).set_value_number(row.invoice_count as f64);
+        sheet.get_cell_mut((3, excel_row)).set_value_number(to_f64(row.current));
+        sheet.get_cell_mut((4, excel_row)).set_value_number(to_f64(row.days_31_to_60));
+        sheet.get_cell_mut((5, excel_row)).set_value_number(to_f64(row.days_61_to_90));
+        sheet.get_cell_mut((6, excel_row)).set_value_number(to_f64(row.over_90));
+        sheet.get_cell_mut((7, excel_row)).set_value_number(to_f64(row.total_outstanding));
+
+        totals.invoice_count += row.invoice_count;
+        totals.current += row.current;
+        totals.days_31_to_60 += row.days_31_to_60;
+        totals.days_61_to_90 += row.days_61_to_90;
+        totals.over_90 += row.over_90;
+        totals.total_outstanding += row.total_outstanding;
+    }
+
+    let total_row = header_row + 1 + rows.len() as u32;
+    sheet.get_cell_mut((1, total_row)).set_value("TOTAL");
+    sheet.get_cell_mut((2, total_row)).set_value_number(totals.invoice_count as f64);
+    sheet.get_cell_mut((3, total_row)).set_value_number(to_f64(totals.current));
+    sheet.get_cell_mut((4, total_row)).set_value_number(to_f64(totals.days_31_to_60));
+    sheet.get_cell_mut((5, total_row)).set_value_number(to_f64(totals.days_61_to_90));
+    sheet.get_cell_mut((6, total_row)).set_value_number(to_f64(totals.over_90));
+    sheet.get_cell_mut((7, total_row)).set_value_number(to_f64(totals.total_outstanding));
+
+    workbook
+}
+fn to_f64(value: Decimal) -> f64 {
+    value.to_f64().unwrap_or_default()
+}
+// endregion: Aging
@@ -1,13 +1,154 @@
-use anyhow::Result;
-use chrono::Utc;
+//! Monthly report workers.
+//!
+//! Each report type with a config_key_prefix is scheduled from app_config:
+//!   <prefix>_day            day of the month (clamped to the month's last day)
+//!   <prefix>_time           Manila time of day, HH:MM
+//!   <prefix>_period_months  how many whole calendar months before the run's month it covers
+//!
+//! The worker keeps its place in report_schedule_state. A run is claimed by moving
+//! last_scheduled_for forward with a conditional update, so only one process runs a slot,
+//! a restart picks up any slot that came due while the server was down, and a slot that
+//! already ran is never run again. A failed run is tried again an hour later, until it succeeds
+//! or the next slot comes due.
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Utc};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter, Set,
+    SqlErr,
+};
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::AppState;
+use crate::business_calendar;
+use crate::entities::{app_config, report_schedule_state, report_type};
+
+/// Longest the worker sleeps before reloading its schedule, so app_config changes take effect.
+const MAX_SLEEP: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// A run still marked running after this long is taken to have died with the process, and
+/// its slot is run again.
+const ABANDONED_RUN_AFTER_HOURS: i64 = 6;
+
+/// How long after a failed run its slot is run again.
+pub const RETRY_FAILED_RUN_AFTER_MINUTES: i64 = 60;
+
+// report_schedule_state.last_run_status
+pub const RUN_STATUS_RUNNING: &str = "running";
+pub const RUN_STATUS_SUCCEEDED: &str = "succeeded";
+pub const RUN_STATUS_FAILED: &str = "failed";
+
+
+// region: Schedule
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MonthlySchedule {
+    pub day_of_month: u32,
+    /// Manila time
+    pub time_of_day: NaiveTime,
+    pub period_months: u32,
+}
+
+/// The dates a report covers, both inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportPeriod {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl MonthlySchedule {
+    /// The run time in the given month, in UTC.
+    pub fn slot_in_month(&self, year: i32, month: u32) -> DateTime<Utc> {
+        let first = NaiveDate::from_ymd_opt(year, month, 1).expect("valid month");
+        let last_day = last_day_of_month(first).day();
+        let date = first
+            .with_day(self.day_of_month.clamp(1, last_day))
+            .expect("day clamped to the month");
+
+        business_calendar::local_datetime(date, self.time_of_day).with_timezone(&Utc)
+    }
+
+    /// The first run time strictly after `after`.
+    pub fn next_slot_after(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        let after_manila = after.with_timezone(&business_calendar::TIMEZONE);
+        let slot = self.slot_in_month(after_manila.year(), after_manila.month());
+        if slot > after {
+            return slot;
+        }
+        let next_month = after_manila
+            .date_naive()
+            .with_day(1)
+            .and_then(|d| d.checked_add_months(Months::new(1)))
+            .expect("date overflow while computing the next monthly run");
+        self.slot_in_month(next_month.year(), next_month.month())
+    }
+
+    /// The whole calendar months before the slot's month, e.g. a run on 10 Oct with
+    /// period_months 1 covers 1 Sep to 30 Sep.
+    pub fn period_for(&self, slot: DateTime<Utc>) -> ReportPeriod {
+        let run_month = business_calendar::date_of(&slot)
+            .with_day(1)
+            .expect("first of month");
+        let start = run_month
+            .checked_sub_months(Months::new(self.period_months.max(1)))
+            .expect("date overflow while computing the report period");
+        let end = run_month.pred_opt().expect("date overflow while computing the report period");
+        ReportPeriod { start, end }
+    }
+}
+
+fn last_day_of_month(first: NaiveDate) -> NaiveDate {
+    first
+        .checked_add_months(Months::new(1))
+        .and_then(|d| d.pred_opt())
+        .expect("date overflow while computing the end of the month")
+}
+
+async fn app_config_value(db: &DatabaseConnection, key: &str) -> Result<String> {
+    app_config::Entity::find()
+        .filter(app_config::Column::Key.eq(key))
+        .one(db)
+        .await?
+        .map(|row| row.value.trim().to_string())
+        .ok_or_else(|| anyhow!("App config key not found: {}", key))
+}
+
+/// Reads the report type's schedule from app_config.
+pub async fn load_monthly_schedule(db: &DatabaseConnection, report: &report_type::Model) -> Result<MonthlySchedule> {
+    let prefix = report
+        .config_key_prefix
+        .as_deref()
+        .ok_or_else(|| anyhow!("Report type '{}' has no config_key_prefix; it cannot be scheduled", report.name))?;
+
+    let day_key = format!("{prefix}_day");
+    let day_of_month = app_config_value(db, &day_key).await?
+        .parse::<u32>()
+        .ok()
+        .filter(|d| (1..=31).contains(d))
+        .ok_or_else(|| anyhow!("App config '{}' must be a day of the month from 1 to 31", day_key))?;
+
+    let time_key = format!("{prefix}_time");
+    let time_of_day = NaiveTime::parse_from_str(&app_config_value(db, &time_key).await?, "%H:%M")
+        .map_err(|err| anyhow!("App config '{}' must be a time as HH:MM: {}", time_key, err))?;
+
+    let period_key = format!("{prefix}_period_months");
+    let period_months = app_config_value(db, &period_key).await?
+        .parse::<u32>()
+        .ok()
+        .filter(|m| *m >= 1)
+        .ok_or_else(|| anyhow!("App config '{}' must be a whole number of months, at least 1", period_key))?;
+
+    Ok(MonthlySchedule { day_of_month, time_of_day, period_months })
+}
+// endregion: Schedule
+
+
+// region: Worker
 
 /*
-F is a function that takes an AppState and returns a Fut.
+F is a function that takes an AppState and the period to report on, and returns a Fut.
 Fut is an async operation that eventually returns an anyhow::Result<()>
 Send - can be safely moved across threads.
 Sync - can be safely referenced across threads.
@@ -20,60 +161,242 @@ pub fn start_monthly_report_worker<F, Fut>(
     run_once: F,
 ) -> JoinHandle<()>
 where
-    F: Fn(AppState) -> Fut + Send + Sync + Copy + 'static,
+    F: Fn(AppState, ReportPeriod) -> Fut + Send + Sync + Copy + 'static,
     Fut: std::future::Future<Output = anyhow::Result<()>> + Send + 'static,
 {
     // every thing inside {} after async move is a new tokio task.
     tokio::spawn(async move {
         info!(target: "jobs", "{} monthly worker started", report_name);
 
-        // Run once on startup.
-        match run_once(state.clone()).await {
-            Ok(()) => info!(target: "jobs", "{} startup check completed", report_name),
-            Err(err) => error!(target: "jobs", "{} startup check failed: {}", report_name, err),
-        }
-
         loop {
-            // Set the time now as now_utc.
-            let now_utc = Utc::now();
-
-            // Determine the next run, given the report_name, and the now_utc.
-            let next_run_utc = match next_monthly_report_run_utc(state.clone(), report_name, now_utc).await {
-                Ok(dt) => dt,
-                Err(err) => {
-                    error!(
+            let sleep_duration = match run_due_slot(&state, report_name, run_once).await {
+                Ok(Some(next_run_utc)) => {
+                    let until_next = (next_run_utc - Utc::now())
+                        .to_std()
+                        .unwrap_or(std::time::Duration::ZERO);
+                    info!(
                         target: "jobs",
-                        "{} failed to calculate next monthly run: {}",
+                        "{} next run at Manila={} UTC={}",
                         report_name,
-                        err
+                        next_run_utc.with_timezone(&business_calendar::TIMEZONE).format("%Y-%m-%d %H:%M:%S"),
+                        next_run_utc.format("%Y-%m-%d %H:%M:%S UTC"),
                     );
-
-                    sleep(std::time::Duration::from_secs(60 * 60)).await;
-                    continue;
+                    until_next.min(MAX_SLEEP)
+                }
+                // a slot was just run; check straight away for another missed one
+                Ok(None) => std::time::Duration::ZERO,
+                Err(err) => {
+                    error!(target: "jobs", "{} monthly worker failed: {}", report_name, err);
+                    MAX_SLEEP
                 }
             };
 
-            let sleep_duration = (next_run_utc - now_utc)
-                .to_std()
-                .unwrap_or(std::time::Duration::from_secs(60));
+            sleep(sleep_duration).await;
+        }
+    })
+}
+
+/// Runs the report if a slot is due. Returns None after running one, or the next run time
+/// if nothing was due.
+async fn run_due_slot<F, Fut>(
+    state: &AppState,
+    report_name: &str,
+    run_once: F,
+) -> Result<Option<DateTime<Utc>>>
+where
+    F: Fn(AppState, ReportPeriod) -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<()>>,
+{
+    let db = &state.db;
 
-            info!(
-                target: "jobs",
-                "{} sleeping until UTC={} sleep_for={:?}",
-                report_name,
-                next_run_utc.format("%Y-%m-%d %H:%M:%S UTC"),
-                sleep_duration
-            );
+    // ---1 load the schedule and where the worker is up to
+    let report = report_type::Entity::find()
+        .filter(report_type::Column::Name.eq(report_name))
+        .one(db)
+        .await?
+        .ok_or_else(|| anyhow!("Report type '{}' not found", report_name))?;
+    let schedule = load_monthly_schedule(db, &report).await?;
+    let schedule_state = find_or_create_state(db, report.id).await?;
 
-            sleep(sleep_duration).await;
+    // ---2 the slot after the last one run, or after the worker was first set up
+    let now = Utc::now();
+    let DueRun { slot, run_at } = due_run(&schedule, &ScheduleProgress::from(&schedule_state), now);
+    if run_at > now {
+        if schedule_state.next_run_on.map(|d| d.with_timezone(&Utc)) != Some(run_at) {
+            let mut am: report_schedule_state::ActiveModel = schedule_state.into();
+            am.next_run_on = Set(Some(run_at.fixed_offset()));
+            am.update(db).await?;
+        }
+        return Ok(Some(run_at));
+    }
+
+    // ---3 claim the slot; if another process got there first, leave it to them
+    let period = schedule.period_for(slot);
+    if !claim_slot(db, &schedule_state, slot, period, now).await? {
+        info!(target: "jobs", "{} run for {} was claimed by another worker", report_name, slot);
+        return Ok(Some(Utc::now() + Duration::minutes(1)));
+    }
+
+    info!(
+        target: "jobs",
+        "{} run for slot {} started, period {} to {}",
+        report_name, slot, period.start, period.end
+    );
+
+    // ---4 run it and record the outcome
+    let outcome = run_once(state.clone(), period).await;
+    let (status, last_error) = match &outcome {
+        Ok(()) => {
+            info!(target: "jobs", "{} run for slot {} completed", report_name, slot);
+            (RUN_STATUS_SUCCEEDED, None)
+        }
+        Err(err) => {
+            error!(target: "jobs", "{} run for slot {} failed: {}", report_name, slot, err);
+            (RUN_STATUS_FAILED, Some(err.to_string()))
+        }
+    };
+
+    report_schedule_state::Entity::update_many()
+        .col_expr(report_schedule_state::Column::LastRunStatus, Expr::value(status))
+        .col_expr(report_schedule_state::Column::LastRunError, Expr::value(last_error))
+        .col_expr(report_schedule_state::Column::LastRunFinishedOn, Expr::value(Utc::now().fixed_offset()))
+        .col_expr(report_schedule_state::Column::NextRunOn, Expr::value(schedule.next_slot_after(slot).fixed_offset()))
+        .filter(report_schedule_state::Column::Id.eq(schedule_state.id))
+        .filter(report_schedule_state::Column::LastScheduledFor.eq(slot.fixed_offset()))
+        .exec(db)
+        .await?;
+
+    Ok(None)
+}
+
+/// report_schedule_state, as far as choosing the next run goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleProgress {
+    pub created_on: DateTime<Utc>,
+    pub last_scheduled_for: Option<DateTime<Utc>>,
+    pub last_run_status: Option<String>,
+    pub last_run_started_on: Option<DateTime<Utc>>,
+    pub last_run_finished_on: Option<DateTime<Utc>>,
+}
 
-            match run_once(state.clone()).await {
-                Ok(()) => info!(target: "jobs", "{} scheduled check completed", report_name),
-                Err(err) => error!(target: "jobs", "{} scheduled check failed: {}", report_name, err),
+impl From<&report_schedule_state::Model> for ScheduleProgress {
+    fn from(row: &report_schedule_state::Model) -> Self {
+        let utc = |d: DateTime<chrono::FixedOffset>| d.with_timezone(&Utc);
+        Self {
+            created_on: utc(row.created_on),
+            last_scheduled_for: row.last_scheduled_for.map(utc),
+            last_run_status: row.last_run_status.clone(),
+            last_run_started_on: row.last_run_started_on.map(utc),
+            last_run_finished_on: row.last_run_finished_on.map(utc),
+        }
+    }
+}
+
+/// The slot to run next and when to run it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DueRun {
+    pub slot: DateTime<Utc>,
+    pub run_at: DateTime<Utc>,
+}
+
+/// An abandoned run's slot again, straight away; a failed run's slot again once
+/// RETRY_FAILED_RUN_AFTER_MINUTES have passed, unless the next slot is due by then; otherwise
+/// the first slot after the last one run (or after the state row was created, for a worker
+/// that never ran).
+pub fn due_run(schedule: &MonthlySchedule, progress: &ScheduleProgress, now: DateTime<Utc>) -> DueRun {
+    let anchor = progress.last_scheduled_for.unwrap_or(progress.created_on);
+    let next_slot = schedule.next_slot_after(anchor);
+
+    if let (Some(last_slot), Some(started)) = (progress.last_scheduled_for, progress.last_run_started_on) {
+        match progress.last_run_status.as_deref() {
+            Some(RUN_STATUS_RUNNING) if started < now - Duration::hours(ABANDONED_RUN_AFTER_HOURS) => {
+                warn!(target: "jobs", "run for slot {} was left running since {}; running it again", last_slot, started);
+                return DueRun { slot: last_slot, run_at: last_slot };
+            }
+            Some(RUN_STATUS_FAILED) => {
+                let failed_on = progress.last_run_finished_on.unwrap_or(started);
+                let retry_at = failed_on + Duration::minutes(RETRY_FAILED_RUN_AFTER_MINUTES);
+                if retry_at < next_slot {
+                    return DueRun { slot: last_slot, run_at: retry_at };
+                }
             }
+            _ => {}
         }
-    })
+    }
+
+    DueRun { slot: next_slot, run_at: next_slot }
+}
+
+/// Moves last_scheduled_for to `slot` unless it is already there (or past it) with a run that
+/// is still alive, succeeded, or failed too recently to retry. True if this process now owns
+/// the run.
+async fn claim_slot(
+    db: &DatabaseConnection,
+    schedule_state: &report_schedule_state::Model,
+    slot: DateTime<Utc>,
+    period: ReportPeriod,
+    now: DateTime<Utc>,
+) -> Result<bool> {
+    let slot = slot.fixed_offset();
+    let abandoned_before = (now - Duration::hours(ABANDONED_RUN_AFTER_HOURS)).fixed_offset();
+    let failed_before = (now - Duration::minutes(RETRY_FAILED_RUN_AFTER_MINUTES)).fixed_offset();
+
+    let result = report_schedule_state::Entity::update_many()
+        .col_expr(report_schedule_state::Column::LastScheduledFor, Expr::value(slot))
+        .col_expr(report_schedule_state::Column::LastRunStartedOn, Expr::value(now.fixed_offset()))
+        .col_expr(report_schedule_state::Column::LastRunFinishedOn, Expr::value(Option::<DateTime<chrono::FixedOffset>>::None))
+        .col_expr(report_schedule_state::Column::LastRunStatus, Expr::value(RUN_STATUS_RUNNING))
+        .col_expr(report_schedule_state::Column::LastRunError, Expr::value(Option::<String>::None))
+        .col_expr(report_schedule_state::Column::LastPeriodStart, Expr::value(period.start))
+        .col_expr(report_schedule_state::Column::LastPeriodEnd, Expr::value(period.end))
+        .filter(report_schedule_state::Column::Id.eq(schedule_state.id))
+        .filter(
+            Condition::any()
+                .add(report_schedule_state::Column::LastScheduledFor.is_null())
+                .add(report_schedule_state::Column::LastScheduledFor.lt(slot))
+                .add(
+                    Condition::all()
+                        .add(report_schedule_state::Column::LastScheduledFor.eq(slot))
+                        .add(report_schedule_state::Column::LastRunStatus.eq(RUN_STATUS_RUNNING))
+                        .add(report_schedule_state::Column::LastRunStartedOn.lt(abandoned_before)),
+                )
+                .add(
+                    Condition::all()
+                        .add(report_schedule_state::Column::LastScheduledFor.eq(slot))
+                        .add(report_schedule_state::Column::LastRunStatus.eq(RUN_STATUS_FAILED))
+                        .add(report_schedule_state::Column::LastRunFinishedOn.lte(failed_before)),
+                ),
+        )
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected == 1)
+}
+
+async fn find_or_create_state(db: &DatabaseConnection, report_type_id: i32) -> Result<report_schedule_state::Model> {
+    let find = || {
+        report_schedule_state::Entity::find()
+            .filter(report_schedule_state::Column::ReportTypeId.eq(report_type_id))
+            .one(db)
+    };
+    if let Some(row) = find().await? {
+        return Ok(row);
+    }
+
+    let inserted = report_schedule_state::ActiveModel {
+        report_type_id: Set(report_type_id),
+        created_on: Set(Utc::now().fixed_offset()),
+        ..Default::default()
+    }
+        .insert(db)
+        .await;
+    match inserted {
+        Ok(row) => Ok(row),
+        // another process created it at the same time
+        Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => find()
+            .await?
+            .ok_or_else(|| anyhow!("report_schedule_state for report type {} vanished", report_type_id)),
+        Err(err) => Err(err.into()),
+    }
 }
-async fn next_monthly_report_run_utc(_state: AppState, _report_name: &str, _now_utc: chrono::DateTime<Utc>) -> Result<chrono::DateTime<Utc>> {
-    Ok(Utc::now())
-}
\ No newline at end of file
+// endregion: Worker
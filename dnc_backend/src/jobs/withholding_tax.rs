@@ -0,0 +1,197 @@
+//! Taxes on dentist payouts.
+//!
+//! The payee of a payout is the dentist clinic, and its tax profile is its acct_tax_type_id
+//! and acct_tax_classification_id:
+//! - VAT-Reg clinics' fees include VAT. The income subject to withholding is the fee less VAT.
+//! - Non-VAT-Reg clinics owe percentage tax on the fee. It is not withheld; it is shown so the
+//!   clinic can reconcile it. The whole fee is subject to withholding.
+//! - Expanded withholding tax is withheld at the classification's rate (Individual,
+//!   Corporation, GPP), and is reported under the classification's ATC on BIR Form 2307.
+//!
+//! The rates are in app_config: payout_vat_rate, payout_percentage_tax_rate, and
+//! payout_ewt_rate_<classification> with payout_ewt_atc_<classification>, where the
+//! classification is its name in lower case.
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+use sea_orm::{ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter};
+use serde::Serialize;
+
+use crate::entities::{app_config, tax_classification, tax_type};
+use crate::jobs::billing_amounts::money;
+
+/// tax_type.name of VAT-registered payees.
+pub const VAT_REGISTERED_TAX_TYPE: &str = "VAT-Reg";
+
+#[derive(Debug, thiserror::Error)]
+pub enum TaxConfigError {
+    #[error("App config '{key}' must be a rate from 0 to 1, not '{value}'")]
+    InvalidRate { key: String, value: String },
+
+    #[error("App config '{0}' is missing")]
+    Missing(String),
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+/// How a payee is taxed, as of when its payout was computed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PayeeTaxProfile {
+    pub tax_type_id: i32,
+    pub tax_classification_id: i32,
+    pub vat_registered: bool,
+    /// the VAT rate for VAT-registered payees, the percentage tax rate otherwise
+    pub business_tax_rate: Decimal,
+    pub ewt_rate: Decimal,
+    pub atc_code: String,
+}
+
+/// The taxes on one payout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+pub struct PayoutTax {
+    /// the income payment subject to withholding
+    pub tax_base: Decimal,
+    /// VAT included in the gross, or percentage tax due on it
+    pub business_tax_amount: Decimal,
+    pub withheld_amount: Decimal,
+    /// gross less the tax withheld
+    pub net_amount: Decimal,
+}
+
+/// Computes the taxes on a gross payout.
+pub fn compute_payout_tax(gross: Decimal, profile: &PayeeTaxProfile) -> PayoutTax {
+    let gross = money(gross);
+    let (tax_base, business_tax_amount) = if profile.vat_registered {
+        let tax_base = money(gross / (Decimal::ONE + profile.business_tax_rate));
+        (tax_base, gross - tax_base)
+    } else {
+        (gross, money(gross * profile.business_tax_rate))
+    };
+    let withheld_amount = money(tax_base * profile.ewt_rate);
+    PayoutTax {
+        tax_base,
+        business_tax_amount,
+        withheld_amount,
+        net_amount: gross - withheld_amount,
+    }
+}
+
+impl std::ops::Add for PayoutTax {
+    type Output = PayoutTax;
+
+    fn add(self, other: PayoutTax) -> PayoutTax {
+        PayoutTax {
+            tax_base: self.tax_base + other.tax_base,
+            business_tax_amount: self.business_tax_amount + other.business_tax_amount,
+            withheld_amount: self.withheld_amount + other.withheld_amount,
+            net_amount: self.net_amount + other.net_amount,
+        }
+    }
+}
+
+impl std::iter::Sum for PayoutTax {
+    fn sum<I: Iterator<Item = PayoutTax>>(iter: I) -> PayoutTax {
+        iter.fold(PayoutTax::default(), |total, tax| total + tax)
+    }
+}
+
+/// The app_config key suffix of a tax classification, e.g. "gpp" for GPP.
+pub fn classification_key(name: &str) -> String {
+    name.trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// The payout tax rates from app_config, with the tax types and classifications they apply to.
+#[derive(Debug, Clone)]
+pub struct PayoutTaxRates {
+    pub vat_rate: Decimal,
+    pub percentage_tax_rate: Decimal,
+    /// classification key -> (rate, ATC)
+    pub ewt: HashMap<String, (Decimal, String)>,
+    tax_types: HashMap<i32, String>,
+    classifications: HashMap<i32, String>,
+}
+
+impl PayoutTaxRates {
+    /// The clinic's tax profile, or None when it has no tax type or classification, or the
+    /// classification has no withholding rate.
+    pub fn profile_for(
+        &self,
+        tax_type_id: Option<i32>,
+        tax_classification_id: Option<i32>,
+    ) -> Option<PayeeTaxProfile> {
+        let tax_type_id = tax_type_id?;
+        let tax_classification_id = tax_classification_id?;
+        let tax_type_name = self.tax_types.get(&tax_type_id)?;
+        let classification = self.classifications.get(&tax_classification_id)?;
+        let (ewt_rate, atc_code) = self.ewt.get(&classification_key(classification))?;
+
+        let vat_registered = tax_type_name.eq_ignore_ascii_case(VAT_REGISTERED_TAX_TYPE);
+        Some(PayeeTaxProfile {
+            tax_type_id,
+            tax_classification_id,
+            vat_registered,
+            business_tax_rate: if vat_registered { self.vat_rate } else { self.percentage_tax_rate },
+            ewt_rate: *ewt_rate,
+            atc_code: atc_code.clone(),
+        })
+    }
+}
+
+/// Loads the payout tax rates from app_config.
+pub async fn load_payout_tax_rates<C: ConnectionTrait>(db: &C) -> Result<PayoutTaxRates, TaxConfigError> {
+    let config: HashMap<String, String> = app_config::Entity::find()
+        .filter(app_config::Column::Key.starts_with("payout_"))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|row| (row.key, row.value.trim().to_string()))
+        .collect();
+    let rate = |key: &str| -> Result<Decimal, TaxConfigError> {
+        let value = config.get(key).ok_or_else(|| TaxConfigError::Missing(key.to_string()))?;
+        parse_rate(key, value)
+    };
+
+    let classifications: HashMap<i32, String> = tax_classification::Entity::find()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|c| (c.id, c.name))
+        .collect();
+    let mut ewt = HashMap::new();
+    for name in classifications.values() {
+        let key = classification_key(name);
+        let rate_key = format!("payout_ewt_rate_{key}");
+        let Some(value) = config.get(&rate_key) else { continue };
+        let atc = config.get(&format!("payout_ewt_atc_{key}")).cloned().unwrap_or_default();
+        ewt.insert(key, (parse_rate(&rate_key, value)?, atc));
+    }
+
+    Ok(PayoutTaxRates {
+        vat_rate: rate("payout_vat_rate")?,
+        percentage_tax_rate: rate("payout_percentage_tax_rate")?,
+        ewt,
+        tax_types: tax_type::Entity::find()
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|t| (t.id, t.name))
+            .collect(),
+        classifications,
+    })
+}
+
+fn parse_rate(key: &str, value: &str) -> Result<Decimal, TaxConfigError> {
+    Decimal::from_str(value)
+        .ok()
+        .filter(|rate| *rate >= Decimal::ZERO && *rate <= Decimal::ONE)
+        .ok_or_else(|| TaxConfigError::InvalidRate {
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+}
@@ -1,6 +1,9 @@
 pub mod handlers;
 pub use handlers::{LoginRequest, LoginResponse, Claims};
+mod audit;
 mod db;
+pub mod verification_state;
+pub mod business_calendar;
 mod entities;
 pub mod jobs;
 #[derive(Clone)]
@@ -13,6 +16,7 @@ use axum::{Router, routing::{get,post,put,patch}, middleware};
 use sea_orm::DatabaseConnection;
 use handlers::boiler::{hello_world, healthcheck, test_posting_json, whoami};
 use handlers::login::{ login_handler};
+use handlers::sessions::{refresh_handler, logout_handler, revoke_user_sessions_handler};
 
 use http::{HeaderValue, HeaderName, Method,};
 use http::request::Parts;
@@ -38,7 +42,7 @@ use jsonwebtoken::{Validation, Algorithm, DecodingKey};
 use handlers::JwtConfig;
 use std::sync::Arc;
 use axum::routing::delete;
-use handlers::{require_jwt};
+use handlers::{require_jwt, require_permission};
 use crate::handlers::{get_data_objects, get_dental_service_types, post_dental_service, patch_dental_service, check_approval_code};
 use crate::handlers::{get_companies_for_hmo_id, get_utilization_report, download_utilization_report, get_master_lists_with_members_for_endorsement};
 use crate::handlers::{get_generated_hmo_billing_reports, download_generated_report, get_csr_verification_activity_counts};
@@ -50,12 +54,32 @@ use crate::handlers::{update_dentist_application_status_handler, get_contact_us_
 use crate::handlers::{get_billing_rules_for_endorsement_id, post_billing_rule, patch_billing_rule, delete_billing_rule};
 use crate::handlers::{get_used_service_counts_for_member_id, get_service_counts_for_endorsement_id};
 use crate::handlers::{get_service_counts_for_member_id, create_verification, cancel_verification, create_master_list_member};
+use crate::handlers::{get_verification_status_history, dry_run_approval_code_for_verification_id, validate_verification};
+use crate::handlers::{get_void_reasons, void_approval_code};
+use crate::handlers::{get_approval_code_validity, put_approval_code_validity, delete_approval_code_validity, post_lapse_override};
+use crate::handlers::{get_approval_code_rules, get_approval_code_rule_configs, post_approval_code_rule_config,
+                      patch_approval_code_rule_config, delete_approval_code_rule_config};
+use crate::handlers::get_member_eligibility;
+use crate::handlers::{get_master_list_column_mappings, post_master_list_column_mapping, patch_master_list_column_mapping,
+                      delete_master_list_column_mapping, preview_endorsement_master_list, commit_endorsement_master_list_preview,
+                      preview_endorsement_master_list_replacement};
+use crate::handlers::{get_generated_hmo_claims_reports, post_hmo_claims_billing};
+use crate::handlers::{get_job, get_jobs, post_job, post_retry_job};
+use crate::handlers::{download_receivables_aging, get_hmo_invoice, get_hmo_invoices, get_receivables_aging, post_hmo_payment};
+use crate::handlers::{download_dentist_payout_remittance, get_dentist_payout_batch, get_dentist_payout_batches,
+                      post_dentist_payout_batch, post_dentist_payout_item_paid, post_dentist_payout_remittance_paid};
+use crate::handlers::{download_bir_2307_certificate, get_bir_2307_certificates, post_bir_2307_certificates};
+use crate::handlers::{download_bank_disbursement, get_bank_disbursement, get_bank_disbursement_layouts, get_bank_disbursements,
+                      post_bank_disbursement, post_bank_disbursement_layout, put_bank_disbursement_layout};
+use crate::handlers::{get_business_calendar, post_holiday, patch_holiday, delete_holiday, import_holidays};
+use crate::handlers::{post_hmo_billing_run, post_regenerate_hmo_billing_run, post_finalize_hmo_billing_run, post_void_hmo_billing_run};
 use crate::handlers::{patch_master_list_member, get_master_list_member, get_approval_code_for_verification_id, get_high_end_verifications};
 use crate::handlers::{post_high_end_verification_approval};
 use crate::handlers::{get_done_verifications, reconcile_verification, unreconcile_verification, get_all_member_names_from_company};
 use crate::handlers::{create_acc_reconciliation, get_acc_recons};
 use crate::handlers::{get_hmos, post_hmo, patch_hmo, get_hmo_by_id, post_dentist_contract, patch_dentist_contract};
 use crate::handlers::{patch_dentist_contract_rates, get_regions, get_provinces, get_cities_by_province, get_cities};
+use crate::handlers::{get_dentist_contract_rate_history, post_dentist_contract_rate_schedule, delete_dentist_contract_rate_schedule};
 use crate::handlers::{get_dental_clinics, get_dental_clinic_by_id, create_dental_clinic, patch_dental_clinic};
 use crate::handlers::{get_dental_clinic_names_for_dentist};
 use crate::handlers::{get_clinic_capabilities_for_clinic, add_clinic_capability_to_clinic, remove_clinic_capability_from_clinic};
@@ -84,6 +108,7 @@ use crate::handlers::{upload_high_end_file, list_uploaded_high_end_files, downlo
 use crate::handlers::{save_member_name_for_company};
 use crate::handlers::{test_generate_hmo_billing_reports};
 use crate::handlers::{get_dentist_hmo_service_audit_matrix_handler};
+use crate::handlers::{get_audit_log};
 use crate::handlers::public::contact_us::submit_contact_us_message_handler;
 use crate::handlers::public::dentist_applications::submit_dentist_application_handler;
 use crate::handlers::public::find_dentist::search_public_dentists_handler;
@@ -102,6 +127,8 @@ fn protected_routes() ->Router<AppState>{
         .route("/users", get(get_users))
         .route("/users/", post(post_user))
         .route("/users/{:id}", patch(patch_user))
+        .route("/users/{:id}/revoke_sessions", post(revoke_user_sessions_handler))
+        .route("/logout", post(logout_handler))
         .route("/roles", get(get_roles))
         .route("/roles/", post(create_role))
         .route("/roles/{:id}", patch(patch_role))
@@ -118,6 +145,9 @@ fn protected_routes() ->Router<AppState>{
         .route("/dentist_contracts/",post(post_dentist_contract))
         .route("/dentist_contracts/{:id}",patch(patch_dentist_contract))
         .route("/dentist_contracts/{:id}/rates",patch(patch_dentist_contract_rates))
+        .route("/dentist_contracts/{:id}/rate_history",get(get_dentist_contract_rate_history))
+        .route("/dentist_contracts/{:id}/rate_schedules",post(post_dentist_contract_rate_schedule))
+        .route("/dentist_contracts/{:id}/rate_schedules/{:rate_id}",delete(delete_dentist_contract_rate_schedule))
         .route("/cities", get(get_cities))
         .route("/provinces", get(get_provinces))
         .route("/provinces/{:province_id}/cities", get(get_cities_by_province))
@@ -197,8 +227,13 @@ fn protected_routes() ->Router<AppState>{
         .route("/master_list_members/{id}", patch(patch_master_list_member))
         .route("/verifications", get(get_all_verifications))
         .route("/verifications", post(create_verification))
+        .route("/verifications/validate", post(validate_verification))
         .route("/verifications/{verification_id}/cancel", post(cancel_verification))
+        .route("/verifications/{verification_id}/void", post(void_approval_code))
+        .route("/verification_void_reasons", get(get_void_reasons))
+        .route("/verifications/{verification_id}/status_history", get(get_verification_status_history))
         .route("/verifications/{verification_id}/approval_code", post(get_approval_code_for_verification_id))
+        .route("/verifications/{verification_id}/approval_code/dry_run", post(dry_run_approval_code_for_verification_id))
         .route("/tooth_service_types", get(get_tooth_service_types))
         .route("/tooth_surfaces", get(get_tooth_surfaces))
         .route("/verifications/{verification_id}/high_end_files", post(upload_high_end_file).get(list_uploaded_high_end_files))
@@ -219,6 +254,10 @@ fn protected_routes() ->Router<AppState>{
         .route("/hmo_billing/", get(get_generated_hmo_billing_reports))
         .route("/hmo_billing/download/{file_name}", get(download_generated_report))
         .route("/generate_hmo_billings", get(test_generate_hmo_billing_reports))
+        .route("/hmo_billing/runs", post(post_hmo_billing_run))
+        .route("/hmo_billing/runs/{id}/regenerate", post(post_regenerate_hmo_billing_run))
+        .route("/hmo_billing/runs/{id}/finalize", post(post_finalize_hmo_billing_run))
+        .route("/hmo_billing/runs/{id}/void", post(post_void_hmo_billing_run))
         /*
         Dashboard
         */
@@ -252,6 +291,75 @@ fn protected_routes() ->Router<AppState>{
          */
         .route("/csr/dentists", get(get_all_dentists_for_csr))
         .route("/csr/endorsements", get(get_endorsements_for_csr))
+        /*
+        Audit Trail
+         */
+        .route("/audit", get(get_audit_log))
+        /*
+        Approval Code Release Rules
+         */
+        .route("/approval_code_rules", get(get_approval_code_rules))
+        .route("/approval_code_rules/configs", get(get_approval_code_rule_configs).post(post_approval_code_rule_config))
+        .route("/approval_code_rules/configs/{id}", patch(patch_approval_code_rule_config).delete(delete_approval_code_rule_config))
+        .route("/approval_code_rules/validity", get(get_approval_code_validity).put(put_approval_code_validity))
+        .route("/approval_code_rules/validity/{id}", delete(delete_approval_code_validity))
+        .route("/verifications/{verification_id}/lapse_override", post(post_lapse_override))
+        /*
+        Member Eligibility
+         */
+        .route("/eligibility", get(get_member_eligibility))
+        /*
+        Master List Previews and Column Mappings
+         */
+        .route("/hmos/{hmo_id}/master_list_mappings", get(get_master_list_column_mappings).post(post_master_list_column_mapping))
+        .route("/master_list_mappings/{id}", patch(patch_master_list_column_mapping).delete(delete_master_list_column_mapping))
+        .route("/endorsements/{endorsement_id}/master_list/preview", post(preview_endorsement_master_list))
+        .route("/endorsements/{endorsement_id}/master_list/replacement_preview", post(preview_endorsement_master_list_replacement))
+        .route("/endorsements/{endorsement_id}/master_list/previews/{token}/commit", post(commit_endorsement_master_list_preview))
+        /*
+        HMO Claims Billing
+         */
+        .route("/hmo_claims_billing", post(post_hmo_claims_billing))
+        .route("/hmo_claims_billing/", get(get_generated_hmo_claims_reports))
+        /*
+        Background Jobs
+         */
+        .route("/jobs", get(get_jobs).post(post_job))
+        .route("/jobs/{id}", get(get_job))
+        .route("/jobs/{id}/retry", post(post_retry_job))
+        /*
+        HMO Receivables
+         */
+        .route("/receivables/invoices", get(get_hmo_invoices))
+        .route("/receivables/invoices/{id}", get(get_hmo_invoice))
+        .route("/receivables/invoices/{id}/payments", post(post_hmo_payment))
+        .route("/receivables/aging", get(get_receivables_aging))
+        .route("/receivables/aging/download", get(download_receivables_aging))
+        /*
+        Dentist Fee-for-Service Payouts
+         */
+        .route("/dentist_payouts/batches", get(get_dentist_payout_batches).post(post_dentist_payout_batch))
+        .route("/dentist_payouts/batches/{id}", get(get_dentist_payout_batch))
+        .route("/dentist_payouts/remittances/{id}/download", get(download_dentist_payout_remittance))
+        .route("/dentist_payouts/remittances/{id}/pay", post(post_dentist_payout_remittance_paid))
+        .route("/dentist_payouts/items/{id}/pay", post(post_dentist_payout_item_paid))
+        .route("/dentist_payouts/bir_2307", get(get_bir_2307_certificates).post(post_bir_2307_certificates))
+        .route("/dentist_payouts/bir_2307/{id}/download", get(download_bir_2307_certificate))
+        /*
+        Bank Disbursements
+         */
+        .route("/disbursements/layouts", get(get_bank_disbursement_layouts).post(post_bank_disbursement_layout))
+        .route("/disbursements/layouts/{id}", put(put_bank_disbursement_layout))
+        .route("/disbursements", get(get_bank_disbursements).post(post_bank_disbursement))
+        .route("/disbursements/{id}", get(get_bank_disbursement))
+        .route("/disbursements/{id}/download", get(download_bank_disbursement))
+        /*
+        Business Calendar
+         */
+        .route("/business_calendar", get(get_business_calendar))
+        .route("/business_calendar/holidays", post(post_holiday))
+        .route("/business_calendar/holidays/import", post(import_holidays))
+        .route("/business_calendar/holidays/{id}", patch(patch_holiday).delete(delete_holiday))
 
 
 }
@@ -306,16 +414,23 @@ pub fn build_app(my_state:AppState) ->Router{
         decoding_key: DecodingKey::from_secret(std::env::var("JWT_SECRET").unwrap().as_bytes()),
         validation,
     });
-    let protected:Router<AppState> = protected_routes().layer(middleware::from_fn_with_state(
-        jwt_cfg.clone(),
-        require_jwt,
-    ));
+    // require_permission is added first, so it runs after require_jwt has set the AuthUser.
+    let protected:Router<AppState> = protected_routes()
+        .layer(middleware::from_fn_with_state(
+            my_state.clone(),
+            require_permission,
+        ))
+        .layer(middleware::from_fn_with_state(
+            (jwt_cfg.clone(), my_state.clone()),
+            require_jwt,
+        ));
 
     Router::new()
         .nest("/api", protected)
         .route("/hello", get( hello_world))
         .route("/healthcheck", get( healthcheck))
         .route("/login", post(login_handler))
+        .route("/refresh", post(refresh_handler))
         .route("/public/dentist_applications", post(submit_dentist_application_handler))
         .route("/public/dentists/search", get(search_public_dentists_handler))
         .route("/public/contact_messages", post(submit_contact_us_message_handler))
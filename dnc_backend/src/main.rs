@@ -1,7 +1,5 @@
 #[allow(unused_imports, dead_code)]
 mod db;
-mod entities;
-pub mod jobs;
 use std::error::Error;
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
@@ -9,7 +7,7 @@ use dotenvy::dotenv;
 use tracing_subscriber::{EnvFilter, };
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
-use dnc_backend::{build_app, AppState};
+use dnc_backend::{build_app, jobs, AppState};
 use db::check_db;
 use opentelemetry::{global, KeyValue,trace::TracerProvider};
 use opentelemetry_otlp::{SpanExporter, WithExportConfig};
@@ -129,6 +127,20 @@ async fn main()-> Result<(), Box<dyn Error>> {
     check_db(&db).await;
 
     let _daily_worker = jobs::start_daily_worker(the_state.clone());
+    let _hmo_billing_worker = jobs::report_generation::start_monthly_report_worker(
+        the_state.clone(),
+        "HMO Billing",
+        |state, period| async move {
+            let job = jobs::queue::Job::HmoBilling(jobs::queue::HmoBillingJob {
+                period_start: period.start,
+                period_end: period.end,
+            });
+            let dedupe_key = format!("hmo_billing:{}:{}", period.start, period.end);
+            jobs::queue::enqueue(&state.db, &job, Some(dedupe_key), "system").await?;
+            Ok(())
+        },
+    );
+    let _job_worker = jobs::queue::start_job_worker(the_state.clone());
 
     let app=build_app(the_state);
     let addr= SocketAddr::from(([0,0,0,0], port));
@@ -0,0 +1,223 @@
+//! Verification status state machine.
+//!
+//! `verification.status_id` holds a `verification_status.int_code`. Code should use
+//! `VerificationStatus` instead of the raw integers, and every status change has to go
+//! through `transition()`, which rejects illegal moves and writes a
+//! `verification_status_history` row on the same connection (or transaction).
+//!
+//!   Waiting for Files ──► Waiting for Dentist Approval ──► Dentist-quoted ──► Done
+//!   Waiting for Approval Code ─────────────────────────────────────────────► Done
+//!   any non-final status ──► Cancelled;  Waiting for Approval Code ──► Expired
+//!   Done ──► Voided (only through the void operation, which checks reconciliation and payment)
+//!
+//! Cancelled, Expired and Voided are final. Done is final except for voiding.
+use std::fmt;
+
+use axum::http::StatusCode;
+use chrono::Utc;
+use sea_orm::{ActiveModelTrait, ActiveValue, ConnectionTrait, DbErr, Set};
+use serde::Serialize;
+
+use crate::entities::{verification, verification_status_history};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+pub enum VerificationStatus {
+    Cancelled,
+    WaitingForApprovalCode,
+    WaitingForFiles,
+    DentistQuoted,
+    WaitingForDentistApproval,
+    Done,
+    Voided,
+    Expired,
+}
+
+impl VerificationStatus {
+    pub const ALL: [VerificationStatus; 8] = [
+        VerificationStatus::Cancelled,
+        VerificationStatus::WaitingForApprovalCode,
+        VerificationStatus::WaitingForFiles,
+        VerificationStatus::DentistQuoted,
+        VerificationStatus::WaitingForDentistApproval,
+        VerificationStatus::Done,
+        VerificationStatus::Voided,
+        VerificationStatus::Expired,
+    ];
+
+    /// The `verification_status.int_code` stored in `verification.status_id`.
+    pub const fn int_code(self) -> i32 {
+        match self {
+            VerificationStatus::Cancelled => 0,
+            VerificationStatus::WaitingForApprovalCode => 1,
+            VerificationStatus::WaitingForFiles => 2,
+            VerificationStatus::DentistQuoted => 3,
+            VerificationStatus::WaitingForDentistApproval => 21,
+            VerificationStatus::Done => 99,
+            VerificationStatus::Voided => 98,
+            VerificationStatus::Expired => 999,
+        }
+    }
+
+    pub fn from_int_code(int_code: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.int_code() == int_code)
+    }
+
+    /// Same text as `verification_status.name`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            VerificationStatus::Cancelled => "Cancelled",
+            VerificationStatus::WaitingForApprovalCode => "Waiting for Approval Code",
+            VerificationStatus::WaitingForFiles => "Waiting for Files",
+            VerificationStatus::DentistQuoted => "Dentist-quoted; waiting for approval code",
+            VerificationStatus::WaitingForDentistApproval => "Waiting for Dentist Approval",
+            VerificationStatus::Done => "Done",
+            VerificationStatus::Voided => "Voided",
+            VerificationStatus::Expired => "Expired",
+        }
+    }
+
+    pub const fn is_final(self) -> bool {
+        matches!(
+            self,
+            VerificationStatus::Done
+                | VerificationStatus::Voided
+                | VerificationStatus::Cancelled
+                | VerificationStatus::Expired
+        )
+    }
+
+    /// The status a new verification starts in.
+    pub const fn initial(is_high_end: bool) -> Self {
+        if is_high_end {
+            VerificationStatus::WaitingForFiles
+        } else {
+            VerificationStatus::WaitingForApprovalCode
+        }
+    }
+
+    pub fn can_transition_to(self, to: VerificationStatus) -> bool {
+        use VerificationStatus::*;
+        matches!(
+            (self, to),
+            (WaitingForFiles, WaitingForDentistApproval)
+                | (WaitingForDentistApproval, DentistQuoted)
+                | (WaitingForApprovalCode, Done)
+                | (DentistQuoted, Done)
+                | (WaitingForApprovalCode, Expired)
+                | (WaitingForApprovalCode, Cancelled)
+                | (WaitingForFiles, Cancelled)
+                | (WaitingForDentistApproval, Cancelled)
+                | (DentistQuoted, Cancelled)
+                | (Done, Voided)
+        )
+    }
+}
+
+impl fmt::Display for VerificationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransitionError {
+    #[error("Unknown verification status_id {0}")]
+    UnknownStatus(i32),
+
+    #[error("Verification cannot go from '{from}' to '{to}'")]
+    Illegal {
+        from: VerificationStatus,
+        to: VerificationStatus,
+    },
+
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+}
+
+impl From<TransitionError> for (StatusCode, String) {
+    fn from(e: TransitionError) -> Self {
+        match e {
+            TransitionError::Illegal { .. } => (StatusCode::CONFLICT, e.to_string()),
+            TransitionError::UnknownStatus(_) | TransitionError::Database(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+            }
+        }
+    }
+}
+
+/// Status of a stored verification.
+pub fn status_of(model: &verification::Model) -> Result<VerificationStatus, TransitionError> {
+    VerificationStatus::from_int_code(model.status_id)
+        .ok_or(TransitionError::UnknownStatus(model.status_id))
+}
+
+/// Moves `am` to `to`, saves it (together with any other fields the caller has set) and
+/// records the change in verification_status_history.
+///
+/// A move to the status the verification is already in is not an error; it saves the
+/// other fields without writing a history row.
+pub async fn transition<C: ConnectionTrait>(
+    db: &C,
+    mut am: verification::ActiveModel,
+    to: VerificationStatus,
+    changed_by: &str,
+    note: Option<&str>,
+) -> Result<verification::Model, TransitionError> {
+    let current = match &am.status_id {
+        ActiveValue::Set(v) | ActiveValue::Unchanged(v) => *v,
+        ActiveValue::NotSet => return Err(TransitionError::UnknownStatus(-1)),
+    };
+    let from = VerificationStatus::from_int_code(current)
+        .ok_or(TransitionError::UnknownStatus(current))?;
+
+    if from == to {
+        return Ok(am.update(db).await?);
+    }
+    if !from.can_transition_to(to) {
+        return Err(TransitionError::Illegal { from, to });
+    }
+
+    am.status_id = Set(to.int_code());
+    let updated = am.update(db).await?;
+
+    record(db, updated.id, Some(from), to, changed_by, note).await?;
+
+    Ok(updated)
+}
+
+/// Inserts a new verification in its initial status and records it in the history.
+pub async fn create<C: ConnectionTrait>(
+    db: &C,
+    mut am: verification::ActiveModel,
+    initial: VerificationStatus,
+    changed_by: &str,
+) -> Result<verification::Model, TransitionError> {
+    am.status_id = Set(initial.int_code());
+    let inserted = am.insert(db).await?;
+
+    record(db, inserted.id, None, initial, changed_by, None).await?;
+
+    Ok(inserted)
+}
+
+async fn record<C: ConnectionTrait>(
+    db: &C,
+    verification_id: i32,
+    from: Option<VerificationStatus>,
+    to: VerificationStatus,
+    changed_by: &str,
+    note: Option<&str>,
+) -> Result<(), DbErr> {
+    verification_status_history::ActiveModel {
+        verification_id: Set(verification_id),
+        from_status_id: Set(from.map(VerificationStatus::int_code)),
+        to_status_id: Set(to.int_code()),
+        changed_on: Set(Utc::now().fixed_offset()),
+        changed_by: Set(changed_by.to_string()),
+        note: Set(note.map(str::to_string)),
+        ..Default::default()
+    }
+        .insert(db)
+        .await?;
+    Ok(())
+}
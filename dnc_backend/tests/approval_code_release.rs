@@ -0,0 +1,130 @@
+mod common;
+use common::{login, setup_server};
+
+use chrono::{Duration, Utc};
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, QueryResult, Statement, Value};
+use serde_json::{json, Value as JsonValue};
+use tokio::task::JoinSet;
+
+/// reject_code of the endorsement_limit rule
+const REJECT_ENDORSEMENT_LIMIT: i64 = 6;
+/// releases fired at once, beyond the endorsement limit
+const EXTRA_RELEASES: i64 = 3;
+
+async fn query_one(db: &DatabaseConnection, sql: &str, values: Vec<Value>) -> Option<QueryResult> {
+    db.query_one_raw(Statement::from_sql_and_values(DbBackend::Postgres, sql, values))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn parallel_releases_do_not_exceed_the_endorsement_limit(){
+    let addr = setup_server().await;
+    let token = login(addr, "admin@dnc.com.ph", "password").await;
+    let db = dnc_backend::AppState::new().await.db;
+
+    // 1. A limited, non-high-end service of an active endorsement, and a dentist at a clinic.
+    let limit_row = query_one(
+        &db,
+        r#"
+        SELECT ec.endorsement_id, ec.dental_services_id, ec.count
+        FROM endorsement_counts ec
+        JOIN dental_service ds ON ds.id = ec.dental_services_id
+        JOIN endorsement e ON e.id = ec.endorsement_id
+        WHERE ec.count BETWEEN 1 AND 5
+          AND ds.type_id <> 3
+          AND ds.is_unlimited IS NOT TRUE
+          AND e.is_active
+        ORDER BY ec.id
+        LIMIT 1
+        "#,
+        vec![],
+    ).await.expect("an endorsement with a limited dental service");
+    let endorsement_id: i32 = limit_row.try_get("", "endorsement_id").unwrap();
+    let dental_service_id: i32 = limit_row.try_get("", "dental_services_id").unwrap();
+    let limit: i32 = limit_row.try_get("", "count").unwrap();
+
+    let dentist_row = query_one(
+        &db,
+        "SELECT dentist_id, clinic_id FROM dentist_clinic WHERE clinic_id IS NOT NULL ORDER BY id LIMIT 1",
+        vec![],
+    ).await.expect("a dentist at a clinic");
+    let dentist_id: i32 = dentist_row.try_get("", "dentist_id").unwrap();
+    let clinic_id: i32 = dentist_row.try_get("", "clinic_id").unwrap();
+
+    // 2. A new member, so no codes were released for them yet, with more waiting
+    // verifications than the limit allows.
+    let member_id: i32 = query_one(
+        &db,
+        r#"
+        INSERT INTO master_list_member (endorsement_id, account_number, last_name, first_name, middle_name)
+        VALUES ($1, 'TEST-PARALLEL-RELEASE', 'Release', 'Parallel', '')
+        RETURNING id
+        "#,
+        vec![endorsement_id.into()],
+    ).await.unwrap().try_get("", "id").unwrap();
+
+    let mut verification_ids = Vec::new();
+    for _ in 0..(limit as i64 + EXTRA_RELEASES) {
+        let id: i32 = query_one(
+            &db,
+            r#"
+            INSERT INTO verification (date_created, created_by, dentist_id, member_id, dental_service_id, status_id, dental_clinic_id)
+            VALUES (now(), 'parallel release test', $1, $2, $3, 1, $4)
+            RETURNING id
+            "#,
+            vec![dentist_id.into(), member_id.into(), dental_service_id.into(), clinic_id.into()],
+        ).await.unwrap().try_get("", "id").unwrap();
+        verification_ids.push(id);
+    }
+
+    // 3. Release them all at once, each on its own service date so the daily limit stays out of it.
+    let client = reqwest::Client::new();
+    let today = Utc::now().date_naive();
+    let mut releases = JoinSet::new();
+    for (i, verification_id) in verification_ids.iter().copied().enumerate() {
+        let client = client.clone();
+        let token = token.clone();
+        let date_service_performed = (today - Duration::days(i as i64)).to_string();
+        releases.spawn(async move {
+            let response = client
+                .post(format!("http://{}/api/verifications/{}/approval_code", addr, verification_id))
+                .bearer_auth(&token)
+                .json(&json!({
+                    "date_service_performed": date_service_performed,
+                    "tooth_id": null,
+                    "tooth_service_type_id": null,
+                    "tooth_surface_ids": [],
+                }))
+                .send()
+                .await
+                .unwrap();
+            assert!(response.status().is_success(), "release failed: {}", response.status());
+            response.json::<JsonValue>().await.unwrap()
+        });
+    }
+    let responses = releases.join_all().await;
+
+    let released_count = query_one(
+        &db,
+        "SELECT count(*)::int AS released FROM verification WHERE member_id = $1 AND approval_code IS NOT NULL",
+        vec![member_id.into()],
+    ).await.unwrap().try_get::<i32>("", "released").unwrap();
+
+    // 4. Clean up before asserting; status history and tooth surfaces cascade.
+    db.execute_raw(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "DELETE FROM verification WHERE member_id = $1",
+        [member_id.into()],
+    )).await.unwrap();
+    db.execute_raw(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "DELETE FROM master_list_member WHERE id = $1",
+        [member_id.into()],
+    )).await.unwrap();
+
+    let reject_codes: Vec<i64> = responses.iter().map(|r| r["reject_code"].as_i64().unwrap()).collect();
+    assert_eq!(released_count, limit, "reject codes: {reject_codes:?}");
+    assert_eq!(reject_codes.iter().filter(|c| **c == 0).count(), limit as usize);
+    assert!(reject_codes.iter().all(|c| *c == 0 || *c == REJECT_ENDORSEMENT_LIMIT), "reject codes: {reject_codes:?}");
+}
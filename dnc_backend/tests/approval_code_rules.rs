@@ -0,0 +1,91 @@
+mod common;
+use common::date;
+
+use serde_json::json;
+
+use dnc_backend::handlers::{age_on, available_on, RuleConfigSource, RuleParams};
+
+#[test]
+fn age_goes_up_on_the_birthday(){
+    let born = date(2014, 10, 18);
+    assert_eq!(age_on(born, date(2026, 10, 17)), 11);
+    assert_eq!(age_on(born, date(2026, 10, 18)), 12);
+    assert_eq!(age_on(born, date(2026, 12, 31)), 12);
+    assert_eq!(age_on(born, date(2027, 1, 1)), 12);
+}
+
+#[test]
+fn leap_day_birthdays_count_from_the_first_of_march(){
+    let born = date(2012, 2, 29);
+    assert_eq!(age_on(born, date(2026, 2, 28)), 13);
+    assert_eq!(age_on(born, date(2026, 3, 1)), 14);
+    assert_eq!(age_on(born, date(2028, 2, 29)), 16);
+}
+
+#[test]
+fn services_are_available_the_given_days_after_enrollment(){
+    assert_eq!(available_on(date(2026, 10, 1), 30), date(2026, 10, 31));
+    assert_eq!(available_on(date(2026, 12, 15), 30), date(2027, 1, 14));
+}
+
+#[test]
+fn config_params_replace_the_defaults_key_by_key(){
+    let defaults = json!({ "days": 180, "dental_service_ids": [] });
+
+    let merged = RuleParams::merged_over(defaults.clone(), Some(&json!({ "days": 90 })));
+    assert_eq!(merged.0, json!({ "days": 90, "dental_service_ids": [] }));
+
+    // params that are not an object leave the defaults alone
+    assert_eq!(RuleParams::merged_over(defaults.clone(), Some(&json!([1, 2]))).0, defaults);
+    assert_eq!(RuleParams::merged_over(defaults.clone(), None).0, defaults);
+}
+
+#[test]
+fn each_service_gets_its_own_entry_or_the_top_level_settings(){
+    let params = RuleParams(json!({
+        "min_age": null,
+        "max_age": 60,
+        "dental_service_ids": [],
+        "services": [
+            { "dental_service_ids": [5, 6], "max_age": 12 },
+            { "dental_service_ids": [7], "min_age": 18, "max_age": null },
+        ],
+    }));
+
+    let children_only = params.for_dental_service(6).unwrap();
+    assert_eq!(children_only.int("max_age"), Some(12));
+    assert_eq!(children_only.int("min_age"), None);
+
+    let adults_only = params.for_dental_service(7).unwrap();
+    assert_eq!(adults_only.int("min_age"), Some(18));
+    assert_eq!(adults_only.int("max_age"), None);
+
+    assert_eq!(params.for_dental_service(9).unwrap().int("max_age"), Some(60));
+}
+
+#[test]
+fn top_level_service_ids_limit_the_services_no_entry_lists(){
+    let params = RuleParams(json!({
+        "days": 180,
+        "dental_service_ids": [1],
+        "services": [{ "dental_service_ids": [2], "days": 365 }],
+    }));
+
+    assert_eq!(params.for_dental_service(1).unwrap().int("days"), Some(180));
+    assert_eq!(params.for_dental_service(2).unwrap().int("days"), Some(365));
+    assert!(params.for_dental_service(3).is_none());
+}
+
+#[test]
+fn endorsement_configs_beat_hmo_configs_beat_global_ones(){
+    let endorsement = RuleConfigSource::of_scope(None, Some(3));
+    let hmo = RuleConfigSource::of_scope(Some(2), None);
+    let global = RuleConfigSource::of_scope(None, None);
+
+    assert_eq!(endorsement, RuleConfigSource::Endorsement);
+    assert_eq!(hmo, RuleConfigSource::Hmo);
+    assert_eq!(global, RuleConfigSource::Global);
+    assert!(endorsement > hmo);
+    assert!(hmo > global);
+    assert!(global > RuleConfigSource::Default);
+}
@@ -0,0 +1,58 @@
+mod common;
+use common::date;
+
+use dnc_backend::jobs::approval_code_validity::{lapse_status, valid_until, validity_days_for, ValidityWindow};
+use dnc_backend::jobs::queue::{Job, JOB_TYPES};
+
+fn window(hmo_id: Option<i32>, dental_service_type_id: Option<i32>, validity_days: i32) -> ValidityWindow {
+    ValidityWindow { hmo_id, dental_service_type_id, validity_days }
+}
+
+#[test]
+fn the_most_specific_window_applies(){
+    let windows = vec![
+        window(None, None, 90),
+        window(None, Some(3), 30),
+        window(Some(7), None, 60),
+        window(Some(7), Some(3), 14),
+    ];
+    assert_eq!(validity_days_for(&windows, 7, 3), Some(14));
+    assert_eq!(validity_days_for(&windows, 7, 1), Some(60));
+    assert_eq!(validity_days_for(&windows, 8, 3), Some(30));
+    assert_eq!(validity_days_for(&windows, 8, 1), Some(90));
+    assert_eq!(validity_days_for(&windows[1..], 8, 1), None);
+}
+
+#[test]
+fn codes_lapse_the_day_after_their_window(){
+    let released_on = date(2026, 10, 1);
+    assert_eq!(valid_until(released_on, 30), date(2026, 10, 30));
+    assert!(!lapse_status(released_on, Some(30), false, false, false, date(2026, 10, 30)).is_lapsed);
+    assert!(lapse_status(released_on, Some(30), false, false, false, date(2026, 10, 31)).is_lapsed);
+
+    // without a window a code never lapses, unless the daily job already marked it
+    assert!(!lapse_status(released_on, None, false, false, false, date(2030, 1, 1)).is_lapsed);
+    assert!(lapse_status(released_on, None, true, false, false, date(2026, 10, 2)).is_lapsed);
+}
+
+#[test]
+fn overridden_codes_are_not_lapsed(){
+    let status = lapse_status(date(2026, 1, 1), Some(30), true, true, false, date(2026, 10, 18));
+    assert!(!status.is_lapsed);
+    assert!(status.is_overridden);
+    assert_eq!(status.valid_until, Some(date(2026, 1, 30)));
+}
+
+#[test]
+fn reconciled_and_voided_codes_are_not_lapsed(){
+    let status = lapse_status(date(2026, 1, 1), Some(30), true, false, true, date(2026, 10, 18));
+    assert!(!status.is_lapsed);
+    assert!(!status.is_overridden);
+    assert_eq!(status.valid_until, Some(date(2026, 1, 30)));
+}
+
+#[test]
+fn lapsing_is_a_queued_job(){
+    assert!(JOB_TYPES.contains(&Job::LapseApprovalCodes.job_type()));
+    assert_eq!(Job::parse("lapse_approval_codes", None).unwrap(), Job::LapseApprovalCodes);
+}
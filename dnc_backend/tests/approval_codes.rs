@@ -0,0 +1,47 @@
+use dnc_backend::handlers::approval_codes::{
+    decode_approval_code, generate_approval_code, ApprovalCodeKeyError, ApprovalCodeKeyRing,
+};
+
+#[test]
+fn v1_codes_keep_the_original_format(){
+    let keys = ApprovalCodeKeyRing::parse("v1=first secret", None).unwrap();
+    let code = generate_approval_code(&keys, 1234);
+
+    assert_eq!(code.len(), 11);
+    let decoded = decode_approval_code(&keys, &code).unwrap();
+    assert_eq!(decoded.verification_id, 1234);
+    assert_eq!(decoded.key_id, "v1");
+}
+
+#[test]
+fn rotated_key_still_accepts_old_codes(){
+    let old_keys = ApprovalCodeKeyRing::parse("v1=first secret", None).unwrap();
+    let old_code = generate_approval_code(&old_keys, 77);
+
+    let keys = ApprovalCodeKeyRing::parse("v1=first secret,v2=second secret", None).unwrap();
+    assert_eq!(keys.signing_key_id(), "v2");
+
+    let new_code = generate_approval_code(&keys, 78);
+    assert!(new_code.starts_with("V2-"));
+
+    assert_eq!(decode_approval_code(&keys, &old_code).unwrap().verification_id, 77);
+    assert_eq!(decode_approval_code(&keys, &new_code).unwrap().verification_id, 78);
+}
+
+#[test]
+fn retired_key_codes_are_rejected(){
+    let old_keys = ApprovalCodeKeyRing::parse("v1=first secret", None).unwrap();
+    let old_code = generate_approval_code(&old_keys, 77);
+
+    let keys = ApprovalCodeKeyRing::parse("v2=second secret", None).unwrap();
+    assert_eq!(decode_approval_code(&keys, &old_code), None);
+}
+
+#[test]
+fn signing_key_must_be_in_the_ring(){
+    assert_eq!(
+        ApprovalCodeKeyRing::parse("v1=a,v2=b", Some("v3")).err(),
+        Some(ApprovalCodeKeyError::UnknownSigningKey("v3".to_string()))
+    );
+    assert_eq!(ApprovalCodeKeyRing::parse("v1=a", Some("v1")).unwrap().signing_key_id(), "v1");
+}
@@ -0,0 +1,127 @@
+mod common;
+use common::dec;
+
+use chrono::NaiveDate;
+use serde_json::json;
+
+use dnc_backend::jobs::bank_disbursement::{
+    check_account, normalize_bank_name, render_file, AccountProblem, ClinicAccount, DisbursementLine,
+    LayoutSpec, PayeeAccount,
+};
+
+fn line(account_number: &str, account_name: &str, amount: &str) -> DisbursementLine {
+    DisbursementLine {
+        account: PayeeAccount {
+            bank_name: "BDO".to_string(),
+            account_number: account_number.to_string(),
+            account_name: account_name.to_string(),
+            account_type: "Savings".to_string(),
+        },
+        payee_name: account_name.to_string(),
+        reference: "RET-202610-1-2".to_string(),
+        amount: dec(amount),
+    }
+}
+
+#[test]
+fn fixed_width_files_pad_and_cut_fields_and_add_a_trailer(){
+    let layout = LayoutSpec::parse(&json!({
+        "format": "fixed_width",
+        "uppercase": true,
+        "account_type_codes": {"Savings": "SA"},
+        "fields": [
+            {"field": "account_type", "width": 2},
+            {"field": "account_number", "width": 12, "pad": "0", "align": "right"},
+            {"field": "account_name", "width": 10},
+            {"field": "amount_centavos", "width": 10, "pad": "0"}
+        ],
+        "trailer": [
+            {"field": "constant", "value": "T", "width": 1},
+            {"field": "payment_count", "width": 4, "pad": "0"},
+            {"field": "total_amount_centavos", "width": 12, "pad": "0"}
+        ]
+    })).unwrap();
+
+    let file = render_file(
+        &layout,
+        &[line("1234567890", "Smile Dental Clinic", "1500.5"), line("987", "Ana", "20")],
+        NaiveDate::from_ymd_opt(2026, 10, 30).unwrap(),
+    );
+    assert_eq!(
+        file,
+        "SA001234567890SMILE DENT0000150050\r\n\
+         SA000000000987ANA       0000002000\r\n\
+         T0002000000152050\r\n"
+    );
+}
+
+#[test]
+fn csv_files_quote_values_with_the_delimiter(){
+    let layout = LayoutSpec::parse(&json!({
+        "format": "csv",
+        "include_column_names": true,
+        "date_format": "%m/%d/%Y",
+        "fields": [
+            {"field": "sequence", "label": "No"},
+            {"field": "account_number", "label": "Account"},
+            {"field": "account_name", "label": "Name"},
+            {"field": "amount", "label": "Amount"},
+            {"field": "value_date", "label": "Date"}
+        ]
+    })).unwrap();
+
+    let file = render_file(
+        &layout,
+        &[line("1234", "Cruz, Juan \"JC\"", "100")],
+        NaiveDate::from_ymd_opt(2026, 10, 30).unwrap(),
+    );
+    assert_eq!(file, "No,Account,Name,Amount,Date\r\n1,1234,\"Cruz, Juan \"\"JC\"\"\",100.00,10/30/2026\r\n");
+}
+
+#[test]
+fn fixed_width_layouts_need_widths(){
+    let layout = LayoutSpec::parse(&json!({
+        "format": "fixed_width",
+        "fields": [{"field": "account_number"}]
+    }));
+    assert!(layout.is_err());
+}
+
+#[test]
+fn accounts_with_missing_or_invalid_data_are_exceptions(){
+    let layout = LayoutSpec::parse(&json!({
+        "format": "csv",
+        "account_number_length": 10,
+        "fields": [{"field": "account_number"}]
+    })).unwrap();
+
+    let problems = check_account(
+        ClinicAccount {
+            bank_name: Some("BDO"),
+            account_number: Some("12-34"),
+            account_name: Some("  "),
+            account_type: None,
+        },
+        Some(&layout),
+    ).unwrap_err();
+    assert_eq!(problems, vec![
+        AccountProblem::InvalidAccountNumber,
+        AccountProblem::MissingAccountName,
+        AccountProblem::MissingAccountType,
+    ]);
+
+    let problems = check_account(ClinicAccount { bank_name: Some("Unknown Bank"), ..Default::default() }, None).unwrap_err();
+    assert_eq!(problems[0], AccountProblem::NoLayoutForBank);
+
+    let account = check_account(
+        ClinicAccount {
+            bank_name: Some(" BDO "),
+            account_number: Some("1234-567 890"),
+            account_name: Some("Smile Dental Clinic"),
+            account_type: Some("Savings"),
+        },
+        Some(&layout),
+    ).unwrap();
+    assert_eq!(account.account_number, "1234567890");
+    assert_eq!(normalize_bank_name("  Bank of   the Philippine Islands "), "bank of the philippine islands");
+}
@@ -0,0 +1,101 @@
+mod common;
+use common::date;
+
+use chrono::{NaiveTime, TimeZone, Utc, Weekday};
+
+use dnc_backend::business_calendar::{
+    self, parse_holiday_csv, parse_weekdays, BusinessCalendar, HolidayType,
+};
+
+#[test]
+fn the_default_calendar_only_skips_sundays(){
+    let calendar = BusinessCalendar::default();
+    assert!(calendar.is_business_day(date(2026, 10, 17)));
+    assert!(!calendar.is_business_day(date(2026, 10, 18)));
+
+    // 6 to 18 October, less Sundays the 11th and 18th
+    assert_eq!(calendar.business_days_between(date(2026, 10, 5), date(2026, 10, 19)), 11);
+    assert_eq!(calendar.business_days_between(date(2026, 10, 5), date(2026, 10, 6)), 0);
+    assert_eq!(calendar.business_days_between(date(2026, 10, 19), date(2026, 10, 5)), 0);
+}
+
+#[test]
+fn holidays_are_not_business_days(){
+    let all_saints = date(2026, 11, 2);
+    let calendar = BusinessCalendar::new([Weekday::Sat, Weekday::Sun], [all_saints]).unwrap();
+    assert!(calendar.is_holiday(all_saints));
+    assert!(!calendar.is_business_day(all_saints));
+    assert!(!calendar.is_business_day(date(2026, 10, 31)));
+
+    // Friday 30 October to Thursday 5 November: the 3rd and 4th only
+    assert_eq!(calendar.business_days_between(date(2026, 10, 30), date(2026, 11, 5)), 2);
+}
+
+#[test]
+fn counting_back_business_days(){
+    let calendar = BusinessCalendar::default();
+    assert_eq!(calendar.start_of_last_business_days(date(2026, 10, 19), 7), date(2026, 10, 12));
+    // a Sunday does not count itself
+    assert_eq!(calendar.start_of_last_business_days(date(2026, 10, 18), 7), date(2026, 10, 10));
+    assert_eq!(calendar.start_of_last_business_days(date(2026, 10, 18), 0), date(2026, 10, 18));
+
+    let with_holiday = BusinessCalendar::new([Weekday::Sun], [date(2026, 10, 14)]).unwrap();
+    assert_eq!(with_holiday.start_of_last_business_days(date(2026, 10, 19), 7), date(2026, 10, 10));
+}
+
+#[test]
+fn non_working_weekdays_come_from_a_list_of_names(){
+    assert_eq!(parse_weekdays("Sat, sunday").unwrap(), vec![Weekday::Sat, Weekday::Sun]);
+    assert_eq!(parse_weekdays("").unwrap(), vec![]);
+    assert!(parse_weekdays("Sat,Funday").is_err());
+
+    let every_day = parse_weekdays("Mon,Tue,Wed,Thu,Fri,Sat,Sun").unwrap();
+    assert!(BusinessCalendar::new(every_day, []).is_err());
+}
+
+#[test]
+fn manila_days_start_at_1600_utc(){
+    let day = date(2026, 10, 18);
+    assert_eq!(
+        business_calendar::start_of_day(day).to_utc(),
+        Utc.with_ymd_and_hms(2026, 10, 17, 16, 0, 0).unwrap()
+    );
+    assert_eq!(
+        business_calendar::next_midnight_utc(Utc.with_ymd_and_hms(2026, 10, 17, 15, 59, 0).unwrap()),
+        Utc.with_ymd_and_hms(2026, 10, 17, 16, 0, 0).unwrap()
+    );
+    assert_eq!(business_calendar::date_of(&Utc.with_ymd_and_hms(2026, 10, 17, 16, 0, 0).unwrap()), day);
+    assert_eq!(
+        business_calendar::local_datetime(day, NaiveTime::from_hms_opt(9, 30, 0).unwrap()).to_utc(),
+        Utc.with_ymd_and_hms(2026, 10, 18, 1, 30, 0).unwrap()
+    );
+}
+
+#[test]
+fn holiday_files_are_read_with_their_types(){
+    let file = b"holiday_date,name,holiday_type\n\
+        2026-12-25,Christmas Day,\n\
+        2026-12-24,Christmas Eve,special_non_working\n\
+        \n\
+        2026-12-30,Rizal Day,Regular\n";
+    let entries = parse_holiday_csv(file).unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].holiday_date, date(2026, 12, 25));
+    assert_eq!(entries[0].holiday_type, HolidayType::Regular);
+    assert_eq!(entries[1].holiday_type, HolidayType::SpecialNonWorking);
+    assert_eq!(entries[2].name, "Rizal Day");
+}
+
+#[test]
+fn every_bad_holiday_row_is_reported(){
+    let file = b"holiday_date,name,holiday_type\n\
+        12/25/2026,Christmas Day,regular\n\
+        2026-12-30,,regular\n\
+        2026-12-31,New Year's Eve,half_day\n\
+        2027-01-01,New Year's Day,regular\n\
+        2027-01-01,New Year's Day,regular\n";
+    let errors = parse_holiday_csv(file).unwrap_err();
+    assert_eq!(errors.len(), 4, "{errors:?}");
+    assert!(errors[0].starts_with("line 2:"));
+    assert!(errors[3].starts_with("line 6:"));
+}
@@ -1,9 +1,13 @@
 // tests/common/mod.rs
 use std::net::SocketAddr;
+use std::str::FromStr;
+use chrono::NaiveDate;
 use http::StatusCode;
+use rust_decimal::Decimal;
 use tokio::net::TcpListener;
 use dnc_backend::{LoginRequest, LoginResponse};
 
+#[allow(dead_code)]
 pub async fn setup_server() -> SocketAddr {
     let app = dnc_backend::build_app(dnc_backend::AppState::new().await);
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -67,3 +71,31 @@ pub async fn test_api(email: &str, password: &str, api: &str, expected_contents:
     }
 }
 
+
+#[allow(dead_code)]
+pub async fn login(addr: SocketAddr, email: &str, password: &str) -> String {
+    let request = LoginRequest {
+        email: email.to_string(),
+        password: password.to_string()
+    };
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/login", addr))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let login: LoginResponse = response.json().await.unwrap();
+    login.token
+}
+
+
+#[allow(dead_code)]
+pub fn dec(value: &str) -> Decimal {
+    Decimal::from_str(value).unwrap()
+}
+
+#[allow(dead_code)]
+pub fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(y, m, d).unwrap()
+}
@@ -0,0 +1,105 @@
+mod common;
+use common::{date, dec};
+
+use chrono::NaiveDate;
+
+use dnc_backend::jobs::contract_rates::{
+    plan_rate_change, plan_rate_end, rate_on, RateChange, RateScheduleError, RateVersion,
+};
+
+fn version(id: i32, rate: &str, from: NaiveDate, to: Option<NaiveDate>) -> RateVersion {
+    RateVersion {
+        id,
+        rate: dec(rate),
+        effective_from: from,
+        effective_to: to,
+    }
+}
+
+#[test]
+fn services_are_priced_at_the_rate_in_force_on_the_day(){
+    let versions = vec![
+        version(1, "500", date(2000, 1, 1), Some(date(2026, 10, 31))),
+        version(2, "550", date(2026, 11, 1), None),
+    ];
+    assert_eq!(rate_on(&versions, date(2026, 10, 31)), Some(dec("500")));
+    assert_eq!(rate_on(&versions, date(2026, 11, 1)), Some(dec("550")));
+    assert_eq!(rate_on(&versions, date(1999, 12, 31)), None);
+}
+
+#[test]
+fn scheduling_a_change_ends_the_current_rate_the_day_before(){
+    let today = date(2026, 10, 18);
+    let versions = vec![
+        version(1, "500", date(2000, 1, 1), Some(date(2026, 12, 31))),
+        version(2, "600", date(2027, 1, 1), None),
+    ];
+    let changes = plan_rate_change(&versions, date(2026, 11, 1), Some(dec("550.004")), today).unwrap();
+    assert_eq!(changes, vec![
+        RateChange::SetEffectiveTo { id: 1, effective_to: Some(date(2026, 10, 31)) },
+        RateChange::Insert { rate: dec("550.00"), effective_from: date(2026, 11, 1), effective_to: Some(date(2026, 12, 31)) },
+    ]);
+
+    // the same rate again changes nothing
+    assert!(plan_rate_change(&versions, date(2026, 11, 1), Some(dec("500")), today).unwrap().is_empty());
+    // a version starting that day gets the new rate
+    assert_eq!(
+        plan_rate_change(&versions, date(2027, 1, 1), Some(dec("650")), today).unwrap(),
+        vec![RateChange::SetRate { id: 2, rate: dec("650.00") }]
+    );
+}
+
+#[test]
+fn past_rates_cannot_be_changed(){
+    let today = date(2026, 10, 18);
+    let versions = vec![version(1, "500", date(2000, 1, 1), None)];
+    assert!(matches!(
+        plan_rate_change(&versions, date(2026, 10, 1), Some(dec("550")), today),
+        Err(RateScheduleError::PastDate { .. })
+    ));
+
+    // a service without rates can be given one from any date
+    assert_eq!(
+        plan_rate_change(&[], date(2026, 1, 1), Some(dec("300")), today).unwrap(),
+        vec![RateChange::Insert { rate: dec("300.00"), effective_from: date(2026, 1, 1), effective_to: None }]
+    );
+}
+
+#[test]
+fn ending_a_rate_closes_the_current_version(){
+    let today = date(2026, 10, 18);
+    let versions = vec![version(1, "500", date(2000, 1, 1), None)];
+    assert_eq!(
+        plan_rate_change(&versions, today, None, today).unwrap(),
+        vec![RateChange::SetEffectiveTo { id: 1, effective_to: Some(date(2026, 10, 17)) }]
+    );
+}
+
+#[test]
+fn services_left_out_of_a_full_list_lose_their_scheduled_rates_too(){
+    let today = date(2026, 10, 18);
+    let versions = vec![
+        version(1, "500", date(2000, 1, 1), Some(date(2026, 11, 30))),
+        version(2, "550", date(2026, 12, 1), Some(date(2026, 12, 31))),
+        version(3, "600", date(2027, 1, 1), None),
+    ];
+    assert_eq!(
+        plan_rate_end(&versions, date(2026, 11, 1), today).unwrap(),
+        vec![
+            RateChange::SetEffectiveTo { id: 1, effective_to: Some(date(2026, 10, 31)) },
+            RateChange::Delete { id: 2 },
+            RateChange::Delete { id: 3 },
+        ]
+    );
+
+    // a service whose only rate has not started yet
+    let scheduled_only = vec![version(4, "300", date(2027, 1, 1), None)];
+    assert_eq!(
+        plan_rate_end(&scheduled_only, date(2026, 11, 1), today).unwrap(),
+        vec![RateChange::Delete { id: 4 }]
+    );
+
+    // a service whose rates already ended
+    let ended = vec![version(5, "300", date(2000, 1, 1), Some(date(2026, 9, 30)))];
+    assert!(plan_rate_end(&ended, date(2026, 11, 1), today).unwrap().is_empty());
+}
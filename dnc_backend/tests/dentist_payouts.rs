@@ -0,0 +1,30 @@
+mod common;
+use common::dec;
+
+use dnc_backend::jobs::dentist_payouts::{price_payout, PayoutPrice};
+
+#[test]
+fn payouts_are_priced_at_the_contract_rate(){
+    assert_eq!(
+        price_payout(Some(dec("350")), None, false),
+        Some(PayoutPrice { rate: Some(dec("350.00")), approved_cost: None, amount: dec("350.00") })
+    );
+    // an approved cost only counts for high-end services
+    assert_eq!(price_payout(Some(dec("350")), Some(dec("9000")), false).unwrap().amount, dec("350.00"));
+}
+
+#[test]
+fn high_end_payouts_are_priced_at_the_approved_cost(){
+    let price = price_payout(Some(dec("500")), Some(dec("12500.456")), true).unwrap();
+    assert_eq!(price.amount, dec("12500.46"));
+    assert_eq!(price.rate, Some(dec("500.00")));
+
+    // without an approved cost the contract rate still applies
+    assert_eq!(price_payout(Some(dec("500")), None, true).unwrap().amount, dec("500.00"));
+}
+
+#[test]
+fn payouts_with_no_rate_or_approved_cost_are_unpriced(){
+    assert_eq!(price_payout(None, None, false), None);
+    assert_eq!(price_payout(None, Some(dec("9000")), false), None);
+}
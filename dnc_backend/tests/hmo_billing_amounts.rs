@@ -0,0 +1,49 @@
+mod common;
+use common::dec;
+
+use dnc_backend::jobs::billing_amounts::{compute_billing_amounts, BillingTier, RateSource, DEFAULT_VAT_RATE};
+use rust_decimal::Decimal;
+
+fn tiers() -> Vec<BillingTier> {
+    vec![
+        BillingTier { id: 1, min_count: 1, max_count: 100, rate: dec("150.00") },
+        BillingTier { id: 2, min_count: 101, max_count: 500, rate: dec("125.00") },
+    ]
+}
+
+#[test]
+fn rate_comes_from_the_tier_the_count_falls_in(){
+    let amounts = compute_billing_amounts(250, &tiers(), Some(dec("200.00")), DEFAULT_VAT_RATE);
+    assert_eq!(amounts.rate_source, RateSource::BillingRule);
+    assert_eq!(amounts.billing_rule_id, Some(2));
+    assert_eq!(amounts.rate, dec("125.00"));
+    assert_eq!(amounts.vat_per_member, dec("15.00"));
+    assert_eq!(amounts.rate_with_vat, dec("140.00"));
+    assert_eq!(amounts.total_amount, dec("35000.00"));
+}
+
+#[test]
+fn retainer_fee_is_used_when_no_tier_matches(){
+    let amounts = compute_billing_amounts(600, &tiers(), Some(dec("99.99")), DEFAULT_VAT_RATE);
+    assert_eq!(amounts.rate_source, RateSource::RetainerFee);
+    assert_eq!(amounts.billing_rule_id, None);
+    // 99.99 * 0.12 = 11.9988
+    assert_eq!(amounts.vat_per_member, dec("12.00"));
+    assert_eq!(amounts.rate_with_vat, dec("111.99"));
+    assert_eq!(amounts.amount_before_vat + amounts.vat_amount, amounts.total_amount);
+}
+
+#[test]
+fn nothing_to_bill_without_a_tier_or_retainer_fee(){
+    let amounts = compute_billing_amounts(10, &[], None, DEFAULT_VAT_RATE);
+    assert_eq!(amounts.rate_source, RateSource::None);
+    assert_eq!(amounts.total_amount, Decimal::ZERO);
+}
+
+#[test]
+fn narrowest_overlapping_tier_wins(){
+    let mut tiers = tiers();
+    tiers.push(BillingTier { id: 3, min_count: 90, max_count: 110, rate: dec("140.00") });
+    let amounts = compute_billing_amounts(100, &tiers, None, DEFAULT_VAT_RATE);
+    assert_eq!(amounts.billing_rule_id, Some(3));
+}
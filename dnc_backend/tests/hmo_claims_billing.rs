@@ -0,0 +1,28 @@
+mod common;
+use common::dec;
+
+use dnc_backend::jobs::hmo_claims_billing::price_claim;
+use rust_decimal::Decimal;
+
+#[test]
+fn claims_are_billed_at_the_service_rate(){
+    let price = price_claim(Some(dec("350")), None, false).unwrap();
+    assert_eq!(price.amount, dec("350.00"));
+    assert_eq!(price.approved_cost, None);
+}
+
+#[test]
+fn high_end_claims_add_the_approved_cost(){
+    let price = price_claim(Some(dec("500.00")), Some(dec("12000.005")), true).unwrap();
+    assert_eq!(price.approved_cost, Some(dec("12000.01")));
+    assert_eq!(price.amount, dec("12500.01"));
+
+    let price = price_claim(None, Some(dec("8000")), true).unwrap();
+    assert_eq!(price.rate, Decimal::ZERO);
+    assert_eq!(price.amount, dec("8000"));
+}
+
+#[test]
+fn approved_cost_is_ignored_for_other_services(){
+    assert_eq!(price_claim(None, Some(dec("8000")), false), None);
+}
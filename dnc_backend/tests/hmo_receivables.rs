@@ -0,0 +1,39 @@
+mod common;
+use common::{date, dec};
+
+use dnc_backend::jobs::receivables::{aging_bucket, invoice_number, write_aging_to_workbook, AgingBucket, HmoAging};
+
+#[test]
+fn invoices_age_into_thirty_day_buckets(){
+    let invoiced = date(2026, 6, 30);
+    assert_eq!(aging_bucket(invoiced, date(2026, 6, 30)), AgingBucket::Current);
+    assert_eq!(aging_bucket(invoiced, date(2026, 7, 30)), AgingBucket::Current);
+    assert_eq!(aging_bucket(invoiced, date(2026, 7, 31)), AgingBucket::Days31To60);
+    assert_eq!(aging_bucket(invoiced, date(2026, 8, 29)), AgingBucket::Days31To60);
+    assert_eq!(aging_bucket(invoiced, date(2026, 8, 30)), AgingBucket::Days61To90);
+    assert_eq!(aging_bucket(invoiced, date(2026, 9, 28)), AgingBucket::Days61To90);
+    assert_eq!(aging_bucket(invoiced, date(2026, 9, 29)), AgingBucket::Over90);
+}
+
+#[test]
+fn invoice_number_is_year_and_statement_id(){
+    assert_eq!(invoice_number(date(2026, 10, 18), 123), "INV-2026-000123");
+}
+
+#[test]
+fn aging_workbook_ends_with_a_total_row(){
+    let row = |name: &str, current: &str, over_90: &str| HmoAging {
+        hmo_id: 1,
+        hmo_short_name: name.to_string(),
+        invoice_count: 2,
+        current: dec(current),
+        over_90: dec(over_90),
+        total_outstanding: dec(current) + dec(over_90),
+        ..Default::default()
+    };
+    let workbook = write_aging_to_workbook(&[row("ABC", "100.50", "0"), row("XYZ", "0", "200.25")], date(2026, 10, 18));
+    let sheet = workbook.get_sheet(&0).unwrap();
+    assert_eq!(sheet.get_value((1, 5)), "ABC");
+    assert_eq!(sheet.get_value((1, 7)), "TOTAL");
+    assert_eq!(sheet.get_value((7, 7)), "300.75");
+}
@@ -0,0 +1,32 @@
+use chrono::{Duration, NaiveDate};
+use serde_json::json;
+
+use dnc_backend::jobs::queue::{retry_delay, HmoBillingJob, Job, JOB_TYPES};
+
+#[test]
+fn retry_delay_doubles_up_to_an_hour(){
+    assert_eq!(retry_delay(1), Duration::minutes(1));
+    assert_eq!(retry_delay(2), Duration::minutes(2));
+    assert_eq!(retry_delay(4), Duration::minutes(8));
+    assert_eq!(retry_delay(7), Duration::minutes(60));
+    assert_eq!(retry_delay(100), Duration::minutes(60));
+}
+
+#[test]
+fn job_round_trips_through_type_and_payload(){
+    let job = Job::HmoBilling(HmoBillingJob {
+        period_start: NaiveDate::from_ymd_opt(2026, 9, 1).unwrap(),
+        period_end: NaiveDate::from_ymd_opt(2026, 9, 30).unwrap(),
+    });
+    assert_eq!(job.payload(), Some(json!({"period_start": "2026-09-01", "period_end": "2026-09-30"})));
+    assert_eq!(Job::parse(job.job_type(), job.payload().as_ref()).unwrap(), job);
+    assert!(JOB_TYPES.contains(&job.job_type()));
+    assert!(JOB_TYPES.contains(&Job::ExpireVerifications.job_type()));
+}
+
+#[test]
+fn bad_job_type_or_payload_is_rejected(){
+    assert!(Job::parse("send_newsletter", None).is_err());
+    assert!(Job::parse("hmo_billing", None).is_err());
+    assert!(Job::parse("hmo_billing", Some(&json!({"period_start": "soon"}))).is_err());
+}
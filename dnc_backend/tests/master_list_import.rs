@@ -0,0 +1,252 @@
+mod common;
+use common::date;
+
+use std::collections::BTreeMap;
+
+use dnc_backend::handlers::{
+    decode_text, guess_delimiter, map_rows, parse_date, read_json, ColumnMapping, ColumnRef, FileFormat,
+    MasterListField, RawTable,
+};
+
+fn table(rows: &[&[&str]]) -> RawTable {
+    RawTable {
+        rows: rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect(),
+    }
+}
+
+fn header(name: &str) -> ColumnRef {
+    ColumnRef::Header(name.to_string())
+}
+
+const HEADERS: &[&str] = &["Member ID", "Surname", "Given Name", "M.I.", "Date of Birth", "E-mail", "Mobile No."];
+
+#[test]
+fn headers_are_recognised_whatever_their_spelling(){
+    let mapping = ColumnMapping::detect(&table(&[HEADERS]), 1).unwrap();
+
+    assert_eq!(mapping.header_row, 1);
+    assert_eq!(mapping.columns.get(&MasterListField::AccountNumber), Some(&header("Member ID")));
+    assert_eq!(mapping.columns.get(&MasterListField::LastName), Some(&header("Surname")));
+    assert_eq!(mapping.columns.get(&MasterListField::FirstName), Some(&header("Given Name")));
+    assert_eq!(mapping.columns.get(&MasterListField::MiddleName), Some(&header("M.I.")));
+    assert_eq!(mapping.columns.get(&MasterListField::BirthDate), Some(&header("Date of Birth")));
+    assert_eq!(mapping.columns.get(&MasterListField::EmailAddress), Some(&header("E-mail")));
+    assert_eq!(mapping.columns.get(&MasterListField::MobileNumber), Some(&header("Mobile No.")));
+    assert_eq!(mapping.columns.get(&MasterListField::CorporateNumber), None);
+}
+
+#[test]
+fn headers_can_be_below_a_title_row(){
+    let file = table(&[&["October master list"], HEADERS, &["A-1", "Cruz", "Juan", "", "", "", ""]]);
+    assert!(ColumnMapping::detect(&file, 1).is_none());
+
+    let mapping = ColumnMapping::detect(&file, 2).unwrap();
+    let sheet = map_rows(&file, &mapping).unwrap();
+    assert_eq!(sheet.rows.len(), 1);
+    assert_eq!(sheet.rows[0].row_number, 3);
+}
+
+#[test]
+fn detection_fails_without_a_required_column(){
+    let file = table(&[&["Surname", "Given Name", "Date of Birth"]]);
+    assert!(ColumnMapping::detect(&file, 1).is_none());
+}
+
+#[test]
+fn files_without_known_headers_are_read_from_columns_f_to_j(){
+    let file = table(&[
+        &["No.", "Plan", "Branch", "Remarks", "Status", "Corp", "Acct", "Last", "First", "Middle"],
+        &["1", "Gold", "Makati", "", "Active", "C-100", "A-1", "Cruz", "Juan", "Santos"],
+    ]);
+    assert!(ColumnMapping::detect(&file, 1).is_none());
+
+    let sheet = map_rows(&file, &ColumnMapping::legacy()).unwrap();
+    assert!(sheet.errors.is_empty());
+    let row = &sheet.rows[0];
+    assert_eq!(row.corporate_number.as_deref(), Some("C-100"));
+    assert_eq!(row.account_number, "A-1");
+    assert_eq!(row.last_name, "Cruz");
+    assert_eq!(row.first_name, "Juan");
+    assert_eq!(row.middle_name, "Santos");
+}
+
+#[test]
+fn rows_with_nothing_in_the_mapped_columns_are_counted_as_blank(){
+    let file = table(&[
+        HEADERS,
+        &["A-1", "Cruz", "Juan", "", "", "", ""],
+        &["", "", "", "", "", "", ""],
+        &[],
+        // text only in a column that is not mapped
+        &["", "", "", "", "", "", "", "note"],
+        &["A-2", "Reyes", "Ana", "", "", "", ""],
+    ]);
+    let mapping = ColumnMapping::detect(&file, 1).unwrap();
+    let sheet = map_rows(&file, &mapping).unwrap();
+
+    assert_eq!(sheet.blank_row_count, 3);
+    assert_eq!(sheet.rows.iter().map(|r| r.row_number).collect::<Vec<_>>(), vec![2, 6]);
+    assert!(sheet.errors.is_empty());
+}
+
+#[test]
+fn rows_missing_a_required_field_are_left_out(){
+    let file = table(&[
+        HEADERS,
+        &["A-1", "Cruz", "", "", "", "", ""],
+        &["", "Reyes", "", "", "", "", ""],
+        &["A-3", "Santos", "Maria", "", "", "", ""],
+    ]);
+    let mapping = ColumnMapping::detect(&file, 1).unwrap();
+    let sheet = map_rows(&file, &mapping).unwrap();
+
+    assert_eq!(sheet.rows.len(), 1);
+    assert_eq!(sheet.rows[0].account_number, "A-3");
+    let errors: Vec<(usize, Option<&str>)> = sheet.errors.iter().map(|e| (e.row_number, e.field)).collect();
+    assert_eq!(
+        errors,
+        vec![(2, Some("first_name")), (3, Some("account_number")), (3, Some("first_name"))]
+    );
+}
+
+#[test]
+fn emails_and_mobile_numbers_are_checked(){
+    let file = table(&[
+        HEADERS,
+        &["A-1", "Cruz", "Juan", "", "", "juan@example.com", "+63 917-123-4567"],
+        &["A-2", "Cruz", "Ana", "", "", "ana@example", ""],
+        &["A-3", "Cruz", "Jose", "", "", "jose @example.com", ""],
+        &["A-4", "Cruz", "Rosa", "", "", "", "12345"],
+        &["A-5", "Cruz", "Luz", "", "", "", "0917abc4567"],
+    ]);
+    let mapping = ColumnMapping::detect(&file, 1).unwrap();
+    let sheet = map_rows(&file, &mapping).unwrap();
+
+    assert_eq!(sheet.rows.len(), 1);
+    assert_eq!(sheet.rows[0].email_address.as_deref(), Some("juan@example.com"));
+    assert_eq!(sheet.rows[0].mobile_number.as_deref(), Some("+63 917-123-4567"));
+    let errors: Vec<(usize, Option<&str>)> = sheet.errors.iter().map(|e| (e.row_number, e.field)).collect();
+    assert_eq!(
+        errors,
+        vec![
+            (3, Some("email_address")),
+            (4, Some("email_address")),
+            (5, Some("mobile_number")),
+            (6, Some("mobile_number")),
+        ]
+    );
+}
+
+#[test]
+fn mapped_headers_must_be_in_the_file(){
+    let mapping = ColumnMapping {
+        header_row: 1,
+        columns: BTreeMap::from([
+            (MasterListField::AccountNumber, header("member id")),
+            (MasterListField::LastName, ColumnRef::Index(1)),
+            (MasterListField::FirstName, header("First Name")),
+        ]),
+    };
+
+    // header references ignore case and punctuation
+    let sheet = map_rows(&table(&[&["MEMBER-ID", "Surname", "first name"], &["A-1", "Cruz", "Juan"]]), &mapping).unwrap();
+    assert_eq!(sheet.columns[&MasterListField::FirstName].index, 2);
+    assert_eq!(sheet.rows[0].first_name, "Juan");
+
+    let err = map_rows(&table(&[&["Member ID", "Surname", "Given Name"]]), &mapping).unwrap_err();
+    assert!(err.contains("First Name"), "{err}");
+}
+
+#[test]
+fn file_format_comes_from_the_content_before_the_extension(){
+    let ole = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1, 0x00];
+    assert_eq!(FileFormat::detect("members.xlsx", &ole), Some(FileFormat::Xls));
+
+    let ods = b"PK\x03\x04\x14\x00\x00\x00mimetypeapplication/vnd.oasis.opendocument.spreadsheet";
+    assert_eq!(FileFormat::detect("members.xlsx", ods), Some(FileFormat::Ods));
+    assert_eq!(FileFormat::detect("members", b"PK\x03\x04rest of the zip"), Some(FileFormat::Xlsx));
+    assert_eq!(FileFormat::detect("members.ods", b"PK\x03\x04rest of the zip"), Some(FileFormat::Ods));
+
+    assert_eq!(FileFormat::detect("members", b"  [{\"account_number\": \"A-1\"}]"), Some(FileFormat::Json));
+    assert_eq!(FileFormat::detect("members", b"\xEF\xBB\xBF{\"members\": []}"), Some(FileFormat::Json));
+    // a CSV whose first cell starts with a bracket
+    assert_eq!(FileFormat::detect("members.csv", b"[note],Acct"), Some(FileFormat::Csv));
+    // a CSV export saved with a spreadsheet extension
+    assert_eq!(FileFormat::detect("members.xlsx", b"Acct,Surname"), Some(FileFormat::Csv));
+    assert_eq!(FileFormat::detect("members", b"Acct,Surname"), Some(FileFormat::Csv));
+
+    assert_eq!(FileFormat::detect("members.json", b""), Some(FileFormat::Json));
+    assert_eq!(FileFormat::detect("members", b""), None);
+}
+
+#[test]
+fn the_delimiter_is_the_most_frequent_one_on_the_first_line(){
+    assert_eq!(guess_delimiter("Acct;Surname;Given Name\nA-1,B;Cruz;Juan"), b';');
+    assert_eq!(guess_delimiter("Acct\tSurname\tGiven Name"), b'\t');
+    assert_eq!(guess_delimiter("Acct|Surname|Given Name, Jr."), b'|');
+    assert_eq!(guess_delimiter("Acct"), b',');
+    assert_eq!(guess_delimiter(""), b',');
+}
+
+#[test]
+fn text_encoding_comes_from_the_bom_or_falls_back_to_windows_1252(){
+    assert_eq!(decode_text(b"\xEF\xBB\xBFPe\xC3\xB1a", None).unwrap(), "Pe\u{f1}a");
+    assert_eq!(decode_text(b"\xFF\xFEP\x00e\x00", None).unwrap(), "Pe");
+    assert_eq!(decode_text(b"Pe\xC3\xB1a", None).unwrap(), "Pe\u{f1}a");
+    // not UTF-8: what Excel on Windows writes
+    assert_eq!(decode_text(b"Pe\xF1a", None).unwrap(), "Pe\u{f1}a");
+
+    assert_eq!(decode_text(b"Pe\xF1a", Some("latin1")).unwrap(), "Pe\u{f1}a");
+    assert!(decode_text(b"Pe\xF1a", Some("utf-8")).is_err());
+    assert!(decode_text(b"Pena", Some("klingon")).is_err());
+}
+
+#[test]
+fn json_objects_become_a_header_row_and_data_rows(){
+    let file = br#"{"count": 2, "members": [{"a": "A-1", "b": 2}, {"b": 3, "c": null}]}"#;
+    let rows = read_json(file).unwrap().rows;
+    assert_eq!(rows, vec![vec!["a", "b", "c"], vec!["A-1", "2", ""], vec!["", "3", ""]]);
+
+    assert!(read_json(br#"{"members": [], "dependents": []}"#).is_err());
+    assert!(read_json(br#"[{"a": 1}, 2]"#).is_err());
+    assert!(read_json(br#""members""#).is_err());
+}
+
+#[test]
+fn json_keys_keep_the_order_of_the_file(){
+    let file = br#"[{"Surname": "Cruz", "Acct": "A-1", "Given": "Juan"}, {"Acct": "A-2", "Middle": "Santos"}]"#;
+    let rows = read_json(file).unwrap().rows;
+    assert_eq!(rows[0], vec!["Surname", "Acct", "Given", "Middle"]);
+    assert_eq!(rows[2], vec!["", "A-2", "", "Santos"]);
+}
+
+#[test]
+fn json_arrays_of_arrays_are_read_as_rows(){
+    let rows = read_json(br#"[["Acct", "Surname"], ["A-1", " Cruz "], [1, true]]"#).unwrap().rows;
+    assert_eq!(rows, vec![vec!["Acct", "Surname"], vec!["A-1", "Cruz"], vec!["1", "true"]]);
+}
+
+#[test]
+fn dates_are_read_in_the_formats_hmos_send(){
+    let expected = Some(date(2014, 10, 18));
+    for text in [
+        "2014-10-18",
+        " 2014-10-18T00:00:00 ",
+        "10/18/2014",
+        "2014/10/18",
+        "18-Oct-2014",
+        "Oct 18, 2014",
+        "October 18, 2014",
+        // Excel serial number
+        "41930",
+    ] {
+        assert_eq!(parse_date(text), expected, "{text}");
+    }
+
+    assert_eq!(parse_date("18/10/2014"), None);
+    assert_eq!(parse_date("not a date"), None);
+    assert_eq!(parse_date("0"), None);
+}
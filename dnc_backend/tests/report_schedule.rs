@@ -0,0 +1,125 @@
+mod common;
+use common::date;
+
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc};
+
+use dnc_backend::jobs::report_generation::{
+    due_run, DueRun, MonthlySchedule, ReportPeriod, ScheduleProgress, RUN_STATUS_FAILED, RUN_STATUS_RUNNING,
+    RUN_STATUS_SUCCEEDED,
+};
+
+fn schedule(day_of_month: u32) -> MonthlySchedule {
+    MonthlySchedule {
+        day_of_month,
+        time_of_day: NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+        period_months: 1,
+    }
+}
+
+#[test]
+fn slot_is_manila_time_on_the_configured_day(){
+    // 06:00 in Manila is 22:00 UTC the day before
+    let slot = schedule(10).slot_in_month(2026, 10);
+    assert_eq!(slot, Utc.with_ymd_and_hms(2026, 10, 9, 22, 0, 0).unwrap());
+}
+
+#[test]
+fn day_past_the_end_of_the_month_runs_on_its_last_day(){
+    let slot = schedule(31).slot_in_month(2026, 2);
+    assert_eq!(slot, Utc.with_ymd_and_hms(2026, 2, 27, 22, 0, 0).unwrap());
+}
+
+#[test]
+fn next_slot_is_strictly_after_the_last_one(){
+    let s = schedule(10);
+    let october = s.slot_in_month(2026, 10);
+    assert_eq!(s.next_slot_after(october - chrono::Duration::seconds(1)), october);
+    assert_eq!(s.next_slot_after(october), s.slot_in_month(2026, 11));
+    let december = s.slot_in_month(2026, 12);
+    assert_eq!(s.next_slot_after(december), s.slot_in_month(2027, 1));
+}
+
+#[test]
+fn period_covers_the_calendar_months_before_the_run(){
+    let mut s = schedule(10);
+    let slot = s.slot_in_month(2026, 10);
+    assert_eq!(s.period_for(slot), ReportPeriod { start: date(2026, 9, 1), end: date(2026, 9, 30) });
+
+    s.period_months = 3;
+    let slot = s.slot_in_month(2027, 1);
+    assert_eq!(s.period_for(slot), ReportPeriod { start: date(2026, 10, 1), end: date(2026, 12, 31) });
+}
+
+/// Progress after a run of `slot` that started a minute late and ended with `status`
+/// `minutes` later.
+fn ran(slot: DateTime<Utc>, status: &str, minutes: i64) -> ScheduleProgress {
+    let started = slot + Duration::minutes(1);
+    ScheduleProgress {
+        created_on: slot - Duration::days(40),
+        last_scheduled_for: Some(slot),
+        last_run_status: Some(status.to_string()),
+        last_run_started_on: Some(started),
+        last_run_finished_on: (status != RUN_STATUS_RUNNING).then(|| started + Duration::minutes(minutes)),
+    }
+}
+
+#[test]
+fn after_a_successful_run_the_next_slot_is_due(){
+    let s = schedule(10);
+    let october = s.slot_in_month(2026, 10);
+    let november = s.slot_in_month(2026, 11);
+
+    let due = due_run(&s, &ran(october, RUN_STATUS_SUCCEEDED, 5), october + Duration::hours(3));
+    assert_eq!(due, DueRun { slot: november, run_at: november });
+}
+
+#[test]
+fn a_failed_slot_is_run_again_after_the_backoff(){
+    let s = schedule(10);
+    let october = s.slot_in_month(2026, 10);
+    let progress = ran(october, RUN_STATUS_FAILED, 5);
+    let failed_on = progress.last_run_finished_on.unwrap();
+
+    let due = due_run(&s, &progress, failed_on + Duration::minutes(1));
+    assert_eq!(due, DueRun { slot: october, run_at: failed_on + Duration::hours(1) });
+}
+
+#[test]
+fn a_failed_slot_gives_way_to_the_next_slot(){
+    let s = schedule(10);
+    let october = s.slot_in_month(2026, 10);
+    let november = s.slot_in_month(2026, 11);
+    // failed half an hour before November's run
+    let mut progress = ran(october, RUN_STATUS_FAILED, 5);
+    progress.last_run_finished_on = Some(november - Duration::minutes(30));
+
+    let due = due_run(&s, &progress, november - Duration::minutes(29));
+    assert_eq!(due, DueRun { slot: november, run_at: november });
+}
+
+#[test]
+fn a_running_slot_is_run_again_only_once_abandoned(){
+    let s = schedule(10);
+    let october = s.slot_in_month(2026, 10);
+    let november = s.slot_in_month(2026, 11);
+    let progress = ran(october, RUN_STATUS_RUNNING, 0);
+
+    assert_eq!(due_run(&s, &progress, october + Duration::hours(2)).slot, november);
+    assert_eq!(due_run(&s, &progress, october + Duration::hours(7)), DueRun { slot: october, run_at: october });
+}
+
+#[test]
+fn a_new_worker_waits_for_the_first_slot_after_it_was_set_up(){
+    let s = schedule(10);
+    let created_on = s.slot_in_month(2026, 10) + Duration::days(1);
+    let progress = ScheduleProgress {
+        created_on,
+        last_scheduled_for: None,
+        last_run_status: None,
+        last_run_started_on: None,
+        last_run_finished_on: None,
+    };
+
+    let november = s.slot_in_month(2026, 11);
+    assert_eq!(due_run(&s, &progress, created_on), DueRun { slot: november, run_at: november });
+}
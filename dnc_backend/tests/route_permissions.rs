@@ -0,0 +1,70 @@
+mod common;
+use common::test_api;
+use dnc_backend::handlers::route_permissions::{route_access, PermissionActionEnum, RouteAccess};
+
+#[test]
+fn lookups_only_need_a_login(){
+    assert_eq!(route_access("GET", "/api/whoami"), Some(RouteAccess::Authenticated));
+    assert_eq!(route_access("GET", "/tooth_surfaces"), Some(RouteAccess::Authenticated));
+}
+
+#[test]
+fn setup_routes_map_to_their_data_objects(){
+    assert_eq!(
+        route_access("GET", "/api/users"),
+        Some(RouteAccess::Requires(&[("user", PermissionActionEnum::Read)]))
+    );
+    assert_eq!(
+        route_access("POST", "/api/roles/"),
+        Some(RouteAccess::Requires(&[("role", PermissionActionEnum::Create)]))
+    );
+}
+
+#[test]
+fn payments_need_accounting_rights(){
+    assert_eq!(
+        route_access("GET", "/api/dentists/payments/make_payment"),
+        Some(RouteAccess::Requires(&[("acc_reconciliation", PermissionActionEnum::Create)]))
+    );
+}
+
+#[test]
+fn closing_a_billing_run_needs_update_rights(){
+    for action in ["finalize", "void"] {
+        assert_eq!(
+            route_access("POST", &format!("/api/hmo_billing/runs/{{id}}/{action}")),
+            Some(RouteAccess::Requires(&[("acc_reconciliation", PermissionActionEnum::Update)]))
+        );
+    }
+}
+
+#[test]
+fn unmapped_routes_are_denied(){
+    assert_eq!(route_access("GET", "/api/not_a_route"), None);
+    assert_eq!(route_access("DELETE", "/api/users"), None);
+}
+
+#[tokio::test]
+async fn get_verifications_noperms(){
+    test_api("noperms@dnc.com.ph", "noperms", "verifications", false).await;
+}
+
+#[test]
+fn voiding_an_approval_code_needs_delete_rights(){
+    assert_eq!(
+        route_access("POST", "/api/verifications/{verification_id}/void"),
+        Some(RouteAccess::Requires(&[("verifications", PermissionActionEnum::Delete)]))
+    );
+}
+
+#[test]
+fn holidays_are_kept_under_the_business_calendar(){
+    assert_eq!(
+        route_access("POST", "/api/business_calendar/holidays/import"),
+        Some(RouteAccess::Requires(&[("business_calendar", PermissionActionEnum::Create)]))
+    );
+    assert_eq!(
+        route_access("DELETE", "/api/business_calendar/holidays/{id}"),
+        Some(RouteAccess::Requires(&[("business_calendar", PermissionActionEnum::Delete)]))
+    );
+}
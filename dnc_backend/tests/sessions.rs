@@ -0,0 +1,94 @@
+mod common;
+
+use dnc_backend::{LoginRequest, LoginResponse};
+use dnc_backend::handlers::sessions::{RefreshRequest, RefreshResponse};
+
+async fn login(client: &reqwest::Client, addr: std::net::SocketAddr) -> LoginResponse {
+    let request = LoginRequest{
+        email: "admin@dnc.com.ph".to_string(),
+        password: "password".to_string()
+    };
+    let response = client
+        .post(format!("http://{}/login", addr))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_refresh_rotates_token(){
+    let addr = common::setup_server().await;
+    let client = reqwest::Client::new();
+    let login = login(&client, addr).await;
+
+    // 1. First refresh succeeds and returns a different refresh token
+    let response = client
+        .post(format!("http://{}/refresh", addr))
+        .json(&RefreshRequest{ refresh_token: login.refresh_token.clone() })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let refreshed: RefreshResponse = response.json().await.unwrap();
+    assert_ne!(refreshed.refresh_token, login.refresh_token);
+
+    // 2. The new access token works
+    let whoami = client
+        .get(format!("http://{}/api/whoami", addr))
+        .bearer_auth(&refreshed.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(whoami.status(), 200);
+
+    // 3. Replaying the old refresh token is rejected and kills the session
+    let replay = client
+        .post(format!("http://{}/refresh", addr))
+        .json(&RefreshRequest{ refresh_token: login.refresh_token.clone() })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(replay.status(), 401);
+
+    let whoami = client
+        .get(format!("http://{}/api/whoami", addr))
+        .bearer_auth(&refreshed.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(whoami.status(), 401);
+}
+
+#[tokio::test]
+async fn test_logout_revokes_token(){
+    let addr = common::setup_server().await;
+    let client = reqwest::Client::new();
+    let login = login(&client, addr).await;
+
+    let response = client
+        .post(format!("http://{}/api/logout", addr))
+        .bearer_auth(&login.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+
+    let whoami = client
+        .get(format!("http://{}/api/whoami", addr))
+        .bearer_auth(&login.token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(whoami.status(), 401);
+
+    let refresh = client
+        .post(format!("http://{}/refresh", addr))
+        .json(&RefreshRequest{ refresh_token: login.refresh_token.clone() })
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(refresh.status(), 401);
+}
@@ -0,0 +1,48 @@
+use dnc_backend::verification_state::VerificationStatus;
+
+#[test]
+fn int_codes_round_trip(){
+    for status in VerificationStatus::ALL {
+        assert_eq!(VerificationStatus::from_int_code(status.int_code()), Some(status));
+    }
+    assert_eq!(VerificationStatus::from_int_code(42), None);
+}
+
+#[test]
+fn done_verifications_cannot_be_cancelled(){
+    assert!(!VerificationStatus::Done.can_transition_to(VerificationStatus::Cancelled));
+}
+
+#[test]
+fn expired_verifications_cannot_get_an_approval_code(){
+    assert!(!VerificationStatus::Expired.can_transition_to(VerificationStatus::Done));
+}
+
+#[test]
+fn high_end_verifications_need_a_dentist_quote_before_done(){
+    assert!(!VerificationStatus::WaitingForFiles.can_transition_to(VerificationStatus::Done));
+    assert!(VerificationStatus::WaitingForFiles.can_transition_to(VerificationStatus::WaitingForDentistApproval));
+    assert!(VerificationStatus::WaitingForDentistApproval.can_transition_to(VerificationStatus::DentistQuoted));
+    assert!(VerificationStatus::DentistQuoted.can_transition_to(VerificationStatus::Done));
+}
+
+#[test]
+fn final_statuses_go_nowhere(){
+    for from in VerificationStatus::ALL.into_iter().filter(|s| s.is_final()) {
+        for to in VerificationStatus::ALL {
+            // a released approval code can still be voided
+            if (from, to) == (VerificationStatus::Done, VerificationStatus::Voided) {
+                continue;
+            }
+            assert!(!from.can_transition_to(to), "{from} -> {to}");
+        }
+    }
+}
+
+#[test]
+fn only_released_codes_can_be_voided(){
+    for from in VerificationStatus::ALL {
+        assert_eq!(from.can_transition_to(VerificationStatus::Voided), from == VerificationStatus::Done, "{from}");
+    }
+    assert!(VerificationStatus::Voided.is_final());
+}
@@ -0,0 +1,165 @@
+mod common;
+use common::{login, setup_server};
+
+use reqwest::StatusCode;
+use sea_orm::{ConnectionTrait, DatabaseConnection, DbBackend, QueryResult, Statement, Value};
+use serde_json::{json, Value as JsonValue};
+use tokio::task::JoinSet;
+
+// reason codes of verification_validation
+const INVALID_DENTAL_SERVICE: i64 = 101;
+const INVALID_DENTAL_CLINIC: i64 = 103;
+const INVALID_DENTIST: i64 = 105;
+const INVALID_MEMBER: i64 = 107;
+const PENDING_DUPLICATE: i64 = 112;
+/// creates fired at once for the same member, service, dentist and clinic
+const PARALLEL_CREATES: usize = 5;
+
+async fn query_one(db: &DatabaseConnection, sql: &str, values: Vec<Value>) -> Option<QueryResult> {
+    db.query_one_raw(Statement::from_sql_and_values(DbBackend::Postgres, sql, values))
+        .await
+        .unwrap()
+}
+
+fn reason_codes(validation: &JsonValue) -> Vec<i64> {
+    validation["reasons"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["code"].as_i64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn every_reason_comes_back_at_once(){
+    let addr = setup_server().await;
+    let token = login(addr, "admin@dnc.com.ph", "password").await;
+
+    let response = reqwest::Client::new()
+        .post(format!("http://{}/api/verifications/validate", addr))
+        .bearer_auth(&token)
+        .json(&json!({
+            "dentist_id": i32::MAX,
+            "member_id": i32::MAX,
+            "dental_service_id": i32::MAX,
+            "dental_clinic_id": i32::MAX,
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let validation: JsonValue = response.json().await.unwrap();
+    assert_eq!(validation["is_valid"], json!(false));
+    assert_eq!(
+        reason_codes(&validation),
+        vec![INVALID_DENTAL_SERVICE, INVALID_DENTAL_CLINIC, INVALID_DENTIST, INVALID_MEMBER]
+    );
+}
+
+#[tokio::test]
+async fn parallel_creates_make_one_verification(){
+    let addr = setup_server().await;
+    let token = login(addr, "admin@dnc.com.ph", "password").await;
+    let db = dnc_backend::AppState::new().await.db;
+
+    // 1. An active service, an endorsement in force, and a dentist at an active clinic with no
+    // HMO or company relations, so every endorsement is open to them.
+    let dental_service_id: i32 = query_one(
+        &db,
+        "SELECT id FROM dental_service WHERE active AND verification_limit >= 1 ORDER BY id LIMIT 1",
+        vec![],
+    ).await.expect("an active dental service").try_get("", "id").unwrap();
+
+    let endorsement_id: i32 = query_one(
+        &db,
+        r#"
+        SELECT id FROM endorsement
+        WHERE is_active
+          AND (now() AT TIME ZONE 'Asia/Manila')::date BETWEEN date_start AND date_end
+        ORDER BY id
+        LIMIT 1
+        "#,
+        vec![],
+    ).await.expect("an endorsement in force").try_get("", "id").unwrap();
+
+    let dentist_row = query_one(
+        &db,
+        r#"
+        SELECT dc.dentist_id, dc.clinic_id
+        FROM dentist_clinic dc
+        JOIN dental_clinic c ON c.id = dc.clinic_id
+        WHERE c.active IS NOT FALSE
+          AND NOT EXISTS (SELECT 1 FROM dentist_hmo_relations r WHERE r.dentist_id = dc.dentist_id)
+          AND NOT EXISTS (SELECT 1 FROM dentist_company_relations r WHERE r.dentist_id = dc.dentist_id)
+        ORDER BY dc.id
+        LIMIT 1
+        "#,
+        vec![],
+    ).await.expect("a dentist without relations at an active clinic");
+    let dentist_id: i32 = dentist_row.try_get("", "dentist_id").unwrap();
+    let clinic_id: i32 = dentist_row.try_get("", "clinic_id").unwrap();
+
+    // 2. A new member, so they have no open verifications yet.
+    let member_id: i32 = query_one(
+        &db,
+        r#"
+        INSERT INTO master_list_member (endorsement_id, account_number, last_name, first_name, middle_name)
+        VALUES ($1, 'TEST-PARALLEL-CREATE', 'Create', 'Parallel', '')
+        RETURNING id
+        "#,
+        vec![endorsement_id.into()],
+    ).await.unwrap().try_get("", "id").unwrap();
+
+    // 3. Create the same verification several times at once.
+    let client = reqwest::Client::new();
+    let mut creates = JoinSet::new();
+    for _ in 0..PARALLEL_CREATES {
+        let client = client.clone();
+        let token = token.clone();
+        creates.spawn(async move {
+            let response = client
+                .post(format!("http://{}/api/verifications", addr))
+                .bearer_auth(&token)
+                .json(&json!({
+                    "dentist_id": dentist_id,
+                    "member_id": member_id,
+                    "dental_service_id": dental_service_id,
+                    "dental_clinic_id": clinic_id,
+                }))
+                .send()
+                .await
+                .unwrap();
+            let status = response.status();
+            (status, response.text().await.unwrap())
+        });
+    }
+    let responses = creates.join_all().await;
+
+    let created_count = query_one(
+        &db,
+        "SELECT count(*)::int AS created FROM verification WHERE member_id = $1",
+        vec![member_id.into()],
+    ).await.unwrap().try_get::<i32>("", "created").unwrap();
+
+    // 4. Clean up before asserting; status history cascades.
+    db.execute_raw(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "DELETE FROM verification WHERE member_id = $1",
+        [member_id.into()],
+    )).await.unwrap();
+    db.execute_raw(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "DELETE FROM master_list_member WHERE id = $1",
+        [member_id.into()],
+    )).await.unwrap();
+
+    let statuses: Vec<StatusCode> = responses.iter().map(|(status, _)| *status).collect();
+    assert_eq!(created_count, 1, "statuses: {statuses:?}");
+    assert_eq!(statuses.iter().filter(|s| **s == StatusCode::CREATED).count(), 1, "statuses: {statuses:?}");
+    for (status, body) in responses.iter().filter(|(status, _)| *status != StatusCode::CREATED) {
+        assert_eq!(*status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+        let validation: JsonValue = serde_json::from_str(body).unwrap();
+        assert_eq!(reason_codes(&validation), vec![PENDING_DUPLICATE], "{body}");
+    }
+}
@@ -0,0 +1,80 @@
+mod common;
+use common::{date, dec};
+
+use rust_decimal::Decimal;
+
+use dnc_backend::jobs::bir_2307::{aggregate_quarter, quarter_bounds, WithholdingEntry};
+use dnc_backend::jobs::withholding_tax::{classification_key, compute_payout_tax, PayeeTaxProfile, PayoutTax};
+
+fn profile(vat_registered: bool, business_tax_rate: &str, ewt_rate: &str) -> PayeeTaxProfile {
+    PayeeTaxProfile {
+        tax_type_id: if vat_registered { 1 } else { 2 },
+        tax_classification_id: 1,
+        vat_registered,
+        business_tax_rate: dec(business_tax_rate),
+        ewt_rate: dec(ewt_rate),
+        atc_code: "WC010".to_string(),
+    }
+}
+
+#[test]
+fn vat_registered_payees_are_withheld_on_the_fee_less_vat(){
+    let tax = compute_payout_tax(dec("1120"), &profile(true, "0.12", "0.10"));
+    assert_eq!(tax, PayoutTax {
+        tax_base: dec("1000.00"),
+        business_tax_amount: dec("120.00"),
+        withheld_amount: dec("100.00"),
+        net_amount: dec("1020.00"),
+    });
+}
+
+#[test]
+fn non_vat_payees_are_withheld_on_the_whole_fee(){
+    let tax = compute_payout_tax(dec("1500"), &profile(false, "0.03", "0.05"));
+    assert_eq!(tax, PayoutTax {
+        tax_base: dec("1500.00"),
+        business_tax_amount: dec("45.00"),
+        withheld_amount: dec("75.00"),
+        net_amount: dec("1425.00"),
+    });
+}
+
+#[test]
+fn classification_names_become_config_key_suffixes(){
+    assert_eq!(classification_key("GPP"), "gpp");
+    assert_eq!(classification_key(" Individual "), "individual");
+}
+
+#[test]
+fn quarters_run_from_the_first_to_the_last_day_of_three_months(){
+    assert_eq!(quarter_bounds(2026, 1).unwrap(), (date(2026, 1, 1), date(2026, 3, 31)));
+    assert_eq!(quarter_bounds(2026, 4).unwrap(), (date(2026, 10, 1), date(2026, 12, 31)));
+    assert!(quarter_bounds(2026, 5).is_err());
+}
+
+#[test]
+fn withholding_is_totalled_per_atc_and_month_of_the_quarter(){
+    let entry = |paid_date, atc: &str, base: &str, withheld: &str| WithholdingEntry {
+        paid_date,
+        atc_code: atc.to_string(),
+        tax_base: dec(base),
+        withheld_amount: dec(withheld),
+    };
+    let entries = vec![
+        entry(date(2026, 7, 5), "WC010", "1000", "100"),
+        entry(date(2026, 7, 20), "WC010", "500", "50"),
+        entry(date(2026, 9, 30), "WC010", "2000", "200"),
+        entry(date(2026, 8, 1), "WI010", "400", "20"),
+        // outside the quarter
+        entry(date(2026, 10, 1), "WC010", "9999", "999"),
+    ];
+
+    let lines = aggregate_quarter(&entries, date(2026, 7, 1));
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].atc_code, "WC010");
+    assert_eq!(lines[0].monthly_income, [dec("1500"), Decimal::ZERO, dec("2000")]);
+    assert_eq!(lines[0].income_total, dec("3500"));
+    assert_eq!(lines[0].withheld_amount, dec("350"));
+    assert_eq!(lines[1].atc_code, "WI010");
+    assert_eq!(lines[1].monthly_income, [Decimal::ZERO, dec("400"), Decimal::ZERO]);
+}